@@ -0,0 +1,292 @@
+//! Alert rule metrics and evaluation.
+//!
+//! Every metric maps to a SQL expression evaluated against the latest record
+//! (`r`) and the client row (`c`) of the rule's client, so computed metrics
+//! such as percentages are derived in the database rather than stored.
+
+pub mod status;
+
+use tracing::{error, info};
+
+use crate::api::AppState;
+use crate::db::AlertEvaluation;
+use crate::error::AppResult;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing::{self, EventType};
+use crate::tasks::digest::format_bytes;
+
+/// Metrics that alert rules can target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertMetric {
+    Cpu,
+    Gpu,
+    RamPct,
+    SwapPct,
+    DiskPct,
+    Load,
+    Load5,
+    Load15,
+    /// 15-minute load divided by CPU cores.
+    Load15PerCore,
+    Temp,
+    NetIn,
+    NetOut,
+    Process,
+    Connections,
+    FdPct,
+    InodePct,
+    /// 1 when the client is stale, 0 otherwise; use threshold 0.
+    Stale,
+    /// Absolute agent clock offset in milliseconds.
+    ClockOffsetMs,
+    /// TCP connections by state, from the latest `tcp_states` snapshot.
+    TcpEstablished,
+    TcpSynRecv,
+    TcpTimeWait,
+    TcpCloseWait,
+    /// Latest traffic totals as a percentage of the client's traffic limit,
+    /// counted as its `traffic_limit_type` says.
+    TrafficPct,
+}
+
+impl AlertMetric {
+    /// All supported metrics.
+    pub const ALL: &'static [AlertMetric] = &[
+        AlertMetric::Cpu,
+        AlertMetric::Gpu,
+        AlertMetric::RamPct,
+        AlertMetric::SwapPct,
+        AlertMetric::DiskPct,
+        AlertMetric::Load,
+        AlertMetric::Load5,
+        AlertMetric::Load15,
+        AlertMetric::Load15PerCore,
+        AlertMetric::Temp,
+        AlertMetric::NetIn,
+        AlertMetric::NetOut,
+        AlertMetric::Process,
+        AlertMetric::Connections,
+        AlertMetric::FdPct,
+        AlertMetric::InodePct,
+        AlertMetric::Stale,
+        AlertMetric::ClockOffsetMs,
+        AlertMetric::TcpEstablished,
+        AlertMetric::TcpSynRecv,
+        AlertMetric::TcpTimeWait,
+        AlertMetric::TcpCloseWait,
+        AlertMetric::TrafficPct,
+    ];
+
+    /// Metric name as stored in `alert_rules.metric`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertMetric::Cpu => "cpu",
+            AlertMetric::Gpu => "gpu",
+            AlertMetric::RamPct => "ram_pct",
+            AlertMetric::SwapPct => "swap_pct",
+            AlertMetric::DiskPct => "disk_pct",
+            AlertMetric::Load => "load",
+            AlertMetric::Load5 => "load5",
+            AlertMetric::Load15 => "load15",
+            AlertMetric::Load15PerCore => "load15_per_core",
+            AlertMetric::Temp => "temp",
+            AlertMetric::NetIn => "net_in",
+            AlertMetric::NetOut => "net_out",
+            AlertMetric::Process => "process",
+            AlertMetric::Connections => "connections",
+            AlertMetric::FdPct => "fd_pct",
+            AlertMetric::InodePct => "inode_pct",
+            AlertMetric::Stale => "stale",
+            AlertMetric::ClockOffsetMs => "clock_offset_ms",
+            AlertMetric::TcpEstablished => "tcp_established",
+            AlertMetric::TcpSynRecv => "tcp_syn_recv",
+            AlertMetric::TcpTimeWait => "tcp_time_wait",
+            AlertMetric::TcpCloseWait => "tcp_close_wait",
+            AlertMetric::TrafficPct => "traffic_pct",
+        }
+    }
+
+    /// Parse a metric from its stored name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|m| m.as_str() == name)
+    }
+
+    /// SQL expression computing the current value of this metric.
+    pub fn sql_expr(&self) -> &'static str {
+        match self {
+            AlertMetric::Cpu => "r.cpu",
+            AlertMetric::Gpu => "r.gpu",
+            AlertMetric::RamPct => "r.ram * 100.0 / NULLIF(r.ram_total, 0)",
+            AlertMetric::SwapPct => "r.swap * 100.0 / NULLIF(r.swap_total, 0)",
+            AlertMetric::DiskPct => "r.disk * 100.0 / NULLIF(r.disk_total, 0)",
+            AlertMetric::Load => "r.load",
+            AlertMetric::Load5 => "r.load5",
+            AlertMetric::Load15 => "r.load15",
+            AlertMetric::Load15PerCore => "r.load15 / NULLIF(c.cpu_cores, 0)",
+            AlertMetric::Temp => "r.temp",
+            AlertMetric::NetIn => "r.net_in",
+            AlertMetric::NetOut => "r.net_out",
+            AlertMetric::Process => "r.process",
+            AlertMetric::Connections => "r.connections",
+            AlertMetric::FdPct => "r.fd_used * 100.0 / NULLIF(r.fd_total, 0)",
+            AlertMetric::InodePct => "r.inode_used * 100.0 / NULLIF(r.inode_total, 0)",
+            // Matches OnlineStatus::Stale; $1 is the `stale_after_secs` setting
+            AlertMetric::Stale => {
+                "CASE WHEN NOT c.online AND c.last_seen_at <= NOW() - make_interval(secs => $1::float8) THEN 1 ELSE 0 END"
+            }
+            AlertMetric::ClockOffsetMs => "ABS(c.clock_offset_ms)",
+            AlertMetric::TcpEstablished => "(c.tcp_states->>'established')::float8",
+            AlertMetric::TcpSynRecv => "(c.tcp_states->>'syn_recv')::float8",
+            AlertMetric::TcpTimeWait => "(c.tcp_states->>'time_wait')::float8",
+            AlertMetric::TcpCloseWait => "(c.tcp_states->>'close_wait')::float8",
+            AlertMetric::TrafficPct => {
+                "CASE c.traffic_limit_type \
+                    WHEN 'sum' THEN r.net_total_up + r.net_total_down \
+                    WHEN 'min' THEN LEAST(r.net_total_up, r.net_total_down) \
+                    WHEN 'up' THEN r.net_total_up \
+                    WHEN 'down' THEN r.net_total_down \
+                    ELSE GREATEST(r.net_total_up, r.net_total_down) \
+                 END * 100.0 / NULLIF(c.traffic_limit, 0)"
+            }
+        }
+    }
+
+    /// Build a `CASE` expression mapping `ar.metric` to its current value.
+    pub fn case_expr() -> String {
+        let mut expr = String::from("CASE ar.metric");
+        for metric in Self::ALL {
+            expr.push_str(&format!(
+                " WHEN '{}' THEN ({})::float8",
+                metric.as_str(),
+                metric.sql_expr()
+            ));
+        }
+        expr.push_str(" END");
+        expr
+    }
+}
+
+/// Default thresholds as `(metric, warning, critical)`.
+pub const DEFAULT_THRESHOLDS: &[(AlertMetric, f32, f32)] = &[
+    (AlertMetric::Cpu, 80.0, 95.0),
+    (AlertMetric::RamPct, 80.0, 95.0),
+    (AlertMetric::SwapPct, 80.0, 95.0),
+    (AlertMetric::DiskPct, 80.0, 95.0),
+    (AlertMetric::FdPct, 80.0, 95.0),
+    (AlertMetric::InodePct, 80.0, 95.0),
+];
+
+/// Evaluate all enabled alert rules once, firing and resolving alerts.
+pub async fn evaluate_rules(state: &AppState) -> AppResult<()> {
+    let evaluations = state
+        .db
+        .get_alert_evaluations(&AlertMetric::case_expr(), state.runtime().stale_after_secs)
+        .await?;
+
+    let locale = state.runtime().locale.clone();
+
+    for eval in evaluations {
+        let Some(value) = eval.value else {
+            continue;
+        };
+
+        let breached = value > eval.threshold as f64;
+
+        match (breached, eval.open_alert_id) {
+            (true, None) => {
+                state
+                    .db
+                    .insert_alert_history(
+                        eval.rule_id,
+                        eval.client_id,
+                        &eval.metric,
+                        value as f32,
+                        eval.threshold,
+                        &eval.severity,
+                    )
+                    .await?;
+
+                info!(
+                    "Alert fired: {} {} = {:.2} on {}",
+                    eval.severity, eval.metric, value, eval.client_name
+                );
+
+                notify(state, &eval, &locale, MessageKey::ThresholdFired, value).await;
+            }
+            (false, Some(alert_id)) => {
+                state.db.resolve_alert(alert_id).await?;
+
+                info!(
+                    "Alert resolved: {} {} = {:.2} on {}",
+                    eval.severity, eval.metric, value, eval.client_name
+                );
+
+                notify(state, &eval, &locale, MessageKey::ThresholdRecovered, value).await;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Send an alert notification to the providers routed for the client.
+///
+/// A `traffic_pct` rule firing is sent as a traffic warning.
+async fn notify(
+    state: &AppState,
+    eval: &AlertEvaluation,
+    locale: &str,
+    key: MessageKey,
+    value: f64,
+) {
+    if eval.in_maintenance {
+        return;
+    }
+    let client = match state.db.find_client_by_id(eval.client_id).await {
+        Ok(Some(client)) => client,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to load client for alert notification: {}", e);
+            return;
+        }
+    };
+
+    let mut params = vec![
+        ("client", eval.client_name.clone()),
+        ("metric", eval.metric.clone()),
+        ("severity", eval.severity.clone()),
+        ("value", format!("{:.2}", value)),
+        ("threshold", format!("{:.2}", eval.threshold)),
+    ];
+    let (event, key) = if eval.metric == AlertMetric::TrafficPct.as_str() {
+        let limit = client.traffic_limit as f64;
+        params.extend([
+            ("used", format_bytes(limit * value / 100.0)),
+            ("limit", format_bytes(limit)),
+            ("percent", format!("{:.1}", value)),
+        ]);
+        let key = match key {
+            MessageKey::ThresholdFired => MessageKey::TrafficWarning,
+            key => key,
+        };
+        (EventType::Traffic, key)
+    } else {
+        (EventType::Threshold, key)
+    };
+    let rule_targets: Vec<_> = eval.notification_id.into_iter().collect();
+
+    if let Err(e) = routing::dispatch(
+        &state.db,
+        &client,
+        event,
+        &rule_targets,
+        locale,
+        key,
+        &params,
+    )
+    .await
+    {
+        error!("Failed to send alert notification: {}", e);
+    }
+}
@@ -0,0 +1,77 @@
+//! Online and offline notifications.
+//!
+//! A client going offline or coming back online is sent as an
+//! [`EventType::Offline`] event, whichever way it changed. Without a
+//! matching route it goes to the client's offline notifications, then to
+//! the global default.
+
+use chrono::Utc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::error::AppResult;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing::{self, EventType};
+
+/// Mark clients offline that stopped reporting without disconnecting, and
+/// send each change.
+pub async fn check_report_timeouts(state: &AppState) -> AppResult<()> {
+    let connected = state.ws_agents.connected_ids();
+    let stale = state
+        .db
+        .mark_stale_clients_offline(state.runtime().report_timeout_secs, &connected)
+        .await?;
+    for client_id in stale {
+        info!(client_id = %client_id, "Client missed its report timeout");
+        notify(state, client_id, false);
+    }
+    Ok(())
+}
+
+/// Send a status change of a client in the background.
+pub fn notify(state: &AppState, client_id: Uuid, online: bool) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = send(&state, client_id, online).await {
+            error!(
+                client_id = %client_id,
+                error = %e,
+                "Failed to send status notification"
+            );
+        }
+    });
+}
+
+/// Send a status change of a client, unless it is in maintenance.
+///
+/// The client is reloaded so maintenance set after it connected applies.
+async fn send(state: &AppState, client_id: Uuid, online: bool) -> AppResult<()> {
+    let Some(client) = state.db.find_client_by_id(client_id).await? else {
+        return Ok(());
+    };
+    if client
+        .maintenance_until
+        .is_some_and(|until| until > Utc::now())
+    {
+        return Ok(());
+    }
+
+    let key = if online {
+        MessageKey::ClientOnline
+    } else {
+        MessageKey::ClientOffline
+    };
+    let params = [("client", client.name.clone())];
+    let offline_targets = state.db.get_offline_notification_ids(client.id).await?;
+    routing::dispatch(
+        &state.db,
+        &client,
+        EventType::Offline,
+        &offline_targets,
+        &state.runtime().locale,
+        key,
+        &params,
+    )
+    .await
+}
@@ -0,0 +1,509 @@
+//! Anomaly detection against per-client baselines.
+//!
+//! Off by default; enabled through the `anomaly` setting:
+//!
+//! ```json
+//! {"enabled": true, "sigmas": 3.0, "window_minutes": 60, "min_samples": 30, "notify": false}
+//! ```
+//!
+//! A baseline is the mean and standard deviation of a client metric in one
+//! hour of the week (UTC), kept in `client_baselines` and updated daily from
+//! the records of the previous day (see [`crate::tasks::baselines`]). Older
+//! weeks decay, so the baseline follows gradual changes.
+//!
+//! Incoming reports are compared with the baselines cached in memory, so
+//! detection costs no queries. A metric more than `sigmas` standard
+//! deviations from its mean for `window_minutes` is recorded in
+//! `alert_history` with kind `anomaly`, and resolved by the next report
+//! within range. With `notify`, both are sent through notification routing
+//! as `anomaly` events.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::alerts::AlertMetric;
+use crate::api::AppState;
+use crate::db::{Client, ClientBaseline, RecordInput};
+use crate::error::AppResult;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing::{self, EventType};
+
+/// Metrics checked for anomalies, with the smallest standard deviation
+/// assumed for each so a flat baseline does not flag every small change.
+const METRICS: &[(AlertMetric, f64)] = &[
+    (AlertMetric::Cpu, 2.0),
+    (AlertMetric::RamPct, 2.0),
+    (AlertMetric::Load, 0.1),
+    (AlertMetric::NetIn, 10_240.0),
+    (AlertMetric::NetOut, 10_240.0),
+];
+
+/// Weight kept by a baseline each time a new day is merged into it.
+pub const BASELINE_DECAY: f64 = 0.8;
+
+/// Anomaly detection configuration stored in the `anomaly` setting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalySettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Standard deviations from the baseline mean that count as anomalous.
+    #[serde(default = "default_sigmas")]
+    pub sigmas: f64,
+    /// Minutes a deviation must last before it is recorded.
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: u32,
+    /// Samples a baseline needs before it is used.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: u32,
+    /// Send anomalies through notification routing.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+fn default_sigmas() -> f64 {
+    3.0
+}
+
+fn default_window_minutes() -> u32 {
+    60
+}
+
+fn default_min_samples() -> u32 {
+    30
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sigmas: default_sigmas(),
+            window_minutes: default_window_minutes(),
+            min_samples: default_min_samples(),
+            notify: false,
+        }
+    }
+}
+
+impl AnomalySettings {
+    /// Check that the settings are in range.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1.0..=10.0).contains(&self.sigmas) {
+            return Err("anomaly.sigmas must be 1-10".into());
+        }
+        if !(1..=1440).contains(&self.window_minutes) {
+            return Err("anomaly.window_minutes must be 1-1440".into());
+        }
+        if self.min_samples < 1 {
+            return Err("anomaly.min_samples must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+/// Weighted mean and standard deviation of a metric.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub weight: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Stats {
+    /// Combine a baseline with a batch of new samples. The baseline's weight
+    /// is first multiplied by `decay`, so older samples count less.
+    pub fn merge(self, batch: Stats, decay: f64) -> Stats {
+        let old_weight = self.weight * decay;
+        let weight = old_weight + batch.weight;
+        if weight <= 0.0 {
+            return batch;
+        }
+        let delta = batch.mean - self.mean;
+        let mean = self.mean + delta * batch.weight / weight;
+        // Pooled variance plus the spread between the two means
+        let m2 = self.stddev.powi(2) * old_weight
+            + batch.stddev.powi(2) * batch.weight
+            + delta.powi(2) * old_weight * batch.weight / weight;
+        Stats {
+            weight,
+            mean,
+            stddev: (m2 / weight).max(0.0).sqrt(),
+        }
+    }
+}
+
+impl From<&ClientBaseline> for Stats {
+    fn from(b: &ClientBaseline) -> Self {
+        Self {
+            weight: b.weight,
+            mean: b.mean,
+            stddev: b.stddev,
+        }
+    }
+}
+
+/// Ongoing deviation of a client metric.
+#[derive(Debug, Default)]
+struct Streak {
+    /// First report of the current deviation.
+    breach_since: Option<DateTime<Utc>>,
+    /// An anomaly has been recorded and not resolved.
+    open: bool,
+    /// Its `alert_history` ID, once stored.
+    alert_id: Option<Uuid>,
+}
+
+/// Change of a streak caused by one report.
+enum Transition {
+    Detected,
+    Resolved(Option<Uuid>),
+}
+
+/// Cached baselines and ongoing deviations.
+#[derive(Default)]
+pub struct AnomalyDetector {
+    baselines: ArcSwap<HashMap<(Uuid, AlertMetric, i16), Stats>>,
+    streaks: DashMap<(Uuid, AlertMetric), Streak>,
+    loaded: AtomicBool,
+}
+
+impl AnomalyDetector {
+    /// Whether the baselines have been loaded since startup.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::Relaxed)
+    }
+
+    /// Update the streak of a metric with one report. `breached` is `None`
+    /// without a usable baseline, which ends a deviation without resolving
+    /// a recorded anomaly.
+    fn track(
+        &self,
+        key: (Uuid, AlertMetric),
+        breached: Option<bool>,
+        now: DateTime<Utc>,
+        window: Duration,
+    ) -> Option<Transition> {
+        let mut streak = self.streaks.entry(key).or_default();
+        match breached {
+            Some(true) => {
+                let since = *streak.breach_since.get_or_insert(now);
+                if !streak.open && now - since >= window {
+                    streak.open = true;
+                    return Some(Transition::Detected);
+                }
+                None
+            }
+            Some(false) => {
+                streak.breach_since = None;
+                if streak.open {
+                    streak.open = false;
+                    return Some(Transition::Resolved(streak.alert_id.take()));
+                }
+                None
+            }
+            None => {
+                streak.breach_since = None;
+                None
+            }
+        }
+    }
+}
+
+/// Hour of the week in UTC, 0 being Monday 00:00-01:00.
+fn hour_of_week(at: DateTime<Utc>) -> i16 {
+    (at.weekday().num_days_from_monday() * 24 + at.hour()) as i16
+}
+
+/// Value of a metric in a report; mirrors [`AlertMetric::sql_expr`].
+fn record_value(metric: AlertMetric, r: &RecordInput) -> Option<f64> {
+    match metric {
+        AlertMetric::Cpu => Some(r.cpu.into()),
+        AlertMetric::RamPct => (r.ram_total > 0).then(|| r.ram as f64 * 100.0 / r.ram_total as f64),
+        AlertMetric::Load => Some(r.load.into()),
+        AlertMetric::NetIn => Some(r.net_in as f64),
+        AlertMetric::NetOut => Some(r.net_out as f64),
+        _ => None,
+    }
+}
+
+/// Check a client's report against its baselines, recording and resolving
+/// anomalies. Failures are logged; they never reject the report.
+pub async fn observe(state: &AppState, client: &Client, record: &RecordInput) {
+    let runtime = state.runtime();
+    let settings = &runtime.anomaly;
+    if !settings.enabled {
+        return;
+    }
+
+    let detector = &state.anomalies;
+    let now = Utc::now();
+    let hour = hour_of_week(now);
+    let window = Duration::minutes(settings.window_minutes.into());
+    let baselines = detector.baselines.load();
+
+    for &(metric, min_stddev) in METRICS {
+        let Some(value) = record_value(metric, record) else {
+            continue;
+        };
+        let baseline = baselines
+            .get(&(client.id, metric, hour))
+            .filter(|b| b.weight >= settings.min_samples as f64);
+        let deviation = baseline.map(|b| (value - b.mean) / b.stddev.max(min_stddev));
+        let breached = deviation.map(|d| d.abs() > settings.sigmas);
+
+        match detector.track((client.id, metric), breached, now, window) {
+            Some(Transition::Detected) => {
+                let (Some(b), Some(deviation)) = (baseline, deviation) else {
+                    continue;
+                };
+                let bound =
+                    b.mean + settings.sigmas * b.stddev.max(min_stddev) * deviation.signum();
+                match state
+                    .db
+                    .insert_anomaly(client.id, metric.as_str(), value as f32, bound as f32)
+                    .await
+                {
+                    Ok(alert) => {
+                        if let Some(mut streak) = detector.streaks.get_mut(&(client.id, metric)) {
+                            streak.alert_id = Some(alert.id);
+                        }
+                    }
+                    Err(e) => error!("Failed to record anomaly: {}", e),
+                }
+                info!(
+                    "Anomaly detected: {} = {:.2} on {} (usual {:.2}, {:.1} sigma)",
+                    metric.as_str(),
+                    value,
+                    client.name,
+                    b.mean,
+                    deviation
+                );
+                if settings.notify {
+                    let params = [
+                        ("client", client.name.clone()),
+                        ("metric", metric.as_str().to_string()),
+                        ("value", format!("{:.2}", value)),
+                        ("mean", format!("{:.2}", b.mean)),
+                        ("sigmas", format!("{:.1}", deviation.abs())),
+                    ];
+                    notify(
+                        state,
+                        client,
+                        &runtime.locale,
+                        MessageKey::AnomalyDetected,
+                        &params,
+                    )
+                    .await;
+                }
+            }
+            Some(Transition::Resolved(alert_id)) => {
+                if let Some(alert_id) = alert_id
+                    && let Err(e) = state.db.resolve_alert(alert_id).await
+                {
+                    error!("Failed to resolve anomaly: {}", e);
+                }
+                info!(
+                    "Anomaly resolved: {} = {:.2} on {}",
+                    metric.as_str(),
+                    value,
+                    client.name
+                );
+                if settings.notify {
+                    let params = [
+                        ("client", client.name.clone()),
+                        ("metric", metric.as_str().to_string()),
+                        ("value", format!("{:.2}", value)),
+                    ];
+                    notify(
+                        state,
+                        client,
+                        &runtime.locale,
+                        MessageKey::AnomalyRecovered,
+                        &params,
+                    )
+                    .await;
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+async fn notify(
+    state: &AppState,
+    client: &Client,
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) {
+    if client
+        .maintenance_until
+        .is_some_and(|until| until > Utc::now())
+    {
+        return;
+    }
+    if let Err(e) = routing::dispatch(
+        &state.db,
+        client,
+        EventType::Anomaly,
+        &[],
+        locale,
+        key,
+        params,
+    )
+    .await
+    {
+        error!("Failed to send anomaly notification: {}", e);
+    }
+}
+
+/// Load the baselines into the detector. On the first load, anomalies left
+/// open by a previous run are picked up so they can be resolved.
+pub async fn load(state: &AppState) -> AppResult<()> {
+    let baselines = state.db.get_client_baselines().await?;
+    let map: HashMap<_, _> = baselines
+        .iter()
+        .filter_map(|b| {
+            let metric = AlertMetric::from_name(&b.metric)?;
+            Some(((b.client_id, metric, b.hour_of_week), Stats::from(b)))
+        })
+        .collect();
+    state.anomalies.baselines.store(map.into());
+
+    if !state.anomalies.loaded.swap(true, Ordering::Relaxed) {
+        for alert in state.db.get_open_anomalies().await? {
+            if let Some(metric) = AlertMetric::from_name(&alert.metric) {
+                let mut streak = state
+                    .anomalies
+                    .streaks
+                    .entry((alert.client_id, metric))
+                    .or_default();
+                streak.open = true;
+                streak.alert_id = Some(alert.id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merge the records between `since` and `until` into the stored baselines.
+/// Returns the number of baselines written.
+pub async fn update_baselines(
+    state: &AppState,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> AppResult<usize> {
+    let metric_values = METRICS
+        .iter()
+        .map(|(metric, _)| format!("('{}', ({})::float8)", metric.as_str(), metric.sql_expr()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let samples = state
+        .db
+        .get_baseline_samples(&metric_values, since, until)
+        .await?;
+    if samples.is_empty() {
+        return Ok(0);
+    }
+
+    let existing: HashMap<_, _> = state
+        .db
+        .get_client_baselines()
+        .await?
+        .into_iter()
+        .map(|b| ((b.client_id, b.metric.clone(), b.hour_of_week), b))
+        .collect();
+    let merged: Vec<ClientBaseline> = samples
+        .into_iter()
+        .map(|sample| {
+            let key = (sample.client_id, sample.metric.clone(), sample.hour_of_week);
+            let Some(baseline) = existing.get(&key) else {
+                return sample;
+            };
+            let stats = Stats::from(baseline).merge(Stats::from(&sample), BASELINE_DECAY);
+            ClientBaseline {
+                weight: stats.weight,
+                mean: stats.mean,
+                stddev: stats.stddev,
+                ..sample
+            }
+        })
+        .collect();
+
+    state.db.upsert_client_baselines(&merged).await?;
+    Ok(merged.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unweighted stats of `samples`, one unit of weight each.
+    fn stats(samples: &[f64]) -> Stats {
+        let weight = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / weight;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / weight;
+        Stats {
+            weight,
+            mean,
+            stddev: variance.sqrt(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn merge_pools_variance() {
+        let merged = stats(&[1.0, 2.0, 3.0]).merge(stats(&[4.0, 5.0]), 1.0);
+        let all = stats(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_close(merged.weight, all.weight);
+        assert_close(merged.mean, all.mean);
+        assert_close(merged.stddev, all.stddev);
+    }
+
+    #[test]
+    fn merge_decays_baseline() {
+        let baseline = Stats {
+            weight: 10.0,
+            mean: 0.0,
+            stddev: 0.0,
+        };
+        let batch = Stats {
+            weight: 10.0,
+            mean: 10.0,
+            stddev: 0.0,
+        };
+        let merged = baseline.merge(batch, 0.5);
+        assert_close(merged.weight, 15.0);
+        assert_close(merged.mean, 100.0 / 15.0);
+        // Spread between the means weighted 5:10
+        assert_close(merged.stddev, (100.0_f64 * 5.0 * 10.0 / 15.0 / 15.0).sqrt());
+
+        // A fully decayed baseline is replaced by the batch
+        assert_eq!(baseline.merge(batch, 0.0), batch);
+    }
+
+    #[test]
+    fn merge_empty_batch() {
+        let baseline = stats(&[1.0, 2.0, 3.0]);
+        let empty = Stats {
+            weight: 0.0,
+            mean: 0.0,
+            stddev: 0.0,
+        };
+        assert_eq!(baseline.merge(empty, 1.0), baseline);
+        let decayed = baseline.merge(empty, 0.5);
+        assert_close(decayed.weight, 1.5);
+        assert_close(decayed.mean, baseline.mean);
+        assert_close(decayed.stddev, baseline.stddev);
+        assert_eq!(empty.merge(empty, 1.0), empty);
+    }
+}
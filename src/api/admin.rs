@@ -2,26 +2,79 @@
 //!
 //! These endpoints require authentication and are used for server management.
 
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
 use argon2::{
     Argon2,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
 use axum::{
     Json,
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
 };
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use ipnet::Ipv6Net;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::AppState;
-use crate::db::{Client, Notification, PingTask, Session, User};
-use crate::error::{AppError, AppResult};
+use crate::alerts::{AlertMetric, DEFAULT_THRESHOLDS};
+use crate::anomaly::AnomalySettings;
+use crate::api::agent_connections::DisconnectReason;
+use crate::api::auth::UserInfo;
+use crate::api::public::{ClientStatus, ClientWithStatus};
+use crate::api::{
+    AppState, CursorPage, PageQuery, PagedResponse, RuntimeSettings, compare, decode_cursor,
+    secrets,
+};
+use crate::build_info;
+use crate::config::Config;
+use crate::db::{
+    AlertHistory, AlertRule, AuditLog, Client, ClientAlertRule, ClientLogLine, ClientPublic,
+    ClientsFilter, Heartbeat, IncidentEvent, MonitorGroup, Notification, NotificationRoute,
+    PingRecord, PingTask, SecurityEvent, Session, ShareLink, Silence, SilenceSchedule,
+    StatusTransition, TimelineEvent, User, VACUUM_TABLES,
+};
+use crate::error::{AppError, AppResult, with_timeout};
+use crate::monitors::MonitorLogic;
+use crate::notifier::i18n;
+use crate::notifier::routing::EventType;
+use crate::notifier::validation::{self, ConfigProblem};
+use crate::security::{self, SecurityAlertSettings, SecurityEventType};
+use crate::silences;
+use crate::storage::{self, ObjectStorage, ObjectStorageSettings, RemoteObject, storage_error};
+use crate::tasks::archive::{self, ArchiveSettings, ArchiveStatus};
+use crate::tasks::digest::{self, DigestSettings};
+use crate::tasks::retention;
+use crate::tasks::{backup, ping};
 
 // ==================== Client Management ====================
 
-/// GET /api/admin/clients - List all clients.
-pub async fn list_clients(State(state): State<AppState>) -> AppResult<Json<Vec<Client>>> {
-    let clients = state.db.get_all_clients().await?;
+/// GET /api/admin/clients - List clients, filtered by `?q=` when given.
+pub async fn list_clients(
+    State(state): State<AppState>,
+    Query(filter): Query<ClientsFilter>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<Client>>> {
+    let db = state.db.pinned();
+    let clients = db
+        .get_clients_paged(&filter, page.limit(), page.offset())
+        .await?;
+    let total = db.count_clients(&filter).await?;
+    Ok(Json(PagedResponse::new(clients, total, page)))
+}
+
+/// GET /api/admin/clients/search - Full-text search clients.
+pub async fn search_clients(
+    State(state): State<AppState>,
+    Query(filter): Query<ClientsFilter>,
+) -> AppResult<Json<Vec<Client>>> {
+    let q = filter.q.unwrap_or_default();
+    if q.trim().is_empty() {
+        return Err(AppError::BadRequest("Search query is required".into()));
+    }
+    let clients = state.db.search_clients(&q).await?;
     Ok(Json(clients))
 }
 
@@ -54,7 +107,7 @@ pub async fn get_client(
 }
 
 /// Edit client request.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct EditClientRequest {
     pub name: Option<String>,
     pub group_name: Option<String>,
@@ -62,14 +115,58 @@ pub struct EditClientRequest {
     pub public_remark: Option<String>,
     pub hidden: Option<bool>,
     pub weight: Option<i32>,
+    pub tags: Option<Vec<String>>,
+    /// `#rrggbb`, or empty to clear.
+    pub display_color: Option<String>,
+    /// Letters, digits and hyphens, or empty to clear.
+    pub display_icon: Option<String>,
+    pub alert_on_ip_change: Option<bool>,
+    /// `null` falls back to the `report_interval_seconds` setting.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub report_interval_seconds: Option<Option<i32>>,
+    /// Traffic limit in bytes, or 0 for none.
+    pub traffic_limit: Option<i64>,
+    /// How `traffic_limit` counts traffic; see [`TRAFFIC_LIMIT_TYPES`].
+    pub traffic_limit_type: Option<String>,
 }
 
+/// Ways a traffic limit counts the upload and download totals.
+pub const TRAFFIC_LIMIT_TYPES: &[&str] = &["sum", "max", "min", "up", "down"];
+
 /// POST /api/admin/clients/:id - Edit client.
 pub async fn edit_client(
     State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
     Path(id): Path<Uuid>,
     Json(req): Json<EditClientRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
+    if let Some(tags) = &req.tags {
+        validate_tags(tags)?;
+    }
+    if let Some(color) = &req.display_color {
+        validate_display_color(color)?;
+    }
+    if let Some(icon) = &req.display_icon {
+        validate_display_icon(icon)?;
+    }
+    if let Some(Some(secs)) = req.report_interval_seconds {
+        validate_report_interval(secs.into())?;
+    }
+    if req.traffic_limit.is_some_and(|limit| limit < 0) {
+        return Err(AppError::BadRequest(
+            "traffic_limit must not be negative".into(),
+        ));
+    }
+    if let Some(kind) = &req.traffic_limit_type
+        && !TRAFFIC_LIMIT_TYPES.contains(&kind.as_str())
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid traffic_limit_type: {}",
+            kind
+        )));
+    }
+
     state
         .db
         .update_client(
@@ -80,214 +177,2331 @@ pub async fn edit_client(
             req.public_remark.as_deref(),
             req.hidden,
             req.weight,
+            req.tags.as_deref(),
+            req.display_color.as_deref(),
+            req.display_icon.as_deref(),
+            req.alert_on_ip_change,
+            req.report_interval_seconds,
+            req.traffic_limit,
+            req.traffic_limit_type.as_deref(),
+        )
+        .await?;
+    if req.report_interval_seconds.is_some() {
+        state.ws_agents.settings_changed(Some(id));
+    }
+
+    // Record the fields that were sent
+    let mut changes = serde_json::json!(req);
+    if let Some(fields) = changes.as_object_mut() {
+        fields.retain(|_, v| !v.is_null());
+    }
+    state
+        .db
+        .insert_audit_log(
+            Some(user.id),
+            "client.updated",
+            serde_json::json!({"client_id": id, "changes": changes}),
+            session.ip_address.as_deref(),
         )
         .await?;
 
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
-/// DELETE /api/admin/clients/:id - Delete client.
+/// Modify tags request.
+#[derive(Debug, Deserialize)]
+pub struct ModifyTagsRequest {
+    pub add: Option<Vec<String>>,
+    pub remove: Option<Vec<String>>,
+}
+
+/// PATCH /api/admin/clients/:id/tags - Add and remove client tags.
+pub async fn modify_client_tags(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<ModifyTagsRequest>,
+) -> AppResult<Json<Vec<String>>> {
+    let add = req.add.unwrap_or_default();
+    let remove = req.remove.unwrap_or_default();
+    validate_tags(&add)?;
+    validate_tags(&remove)?;
+
+    let tags = state
+        .db
+        .modify_client_tags(id, &add, &remove)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    state
+        .db
+        .insert_audit_log(
+            Some(user.id),
+            "client.tags_changed",
+            serde_json::json!({"client_id": id, "add": add, "remove": remove}),
+            session.ip_address.as_deref(),
+        )
+        .await?;
+
+    Ok(Json(tags))
+}
+
+/// Validate tags: 1-50 characters of ASCII letters, digits, `-` or `_`.
+fn validate_tags(tags: &[String]) -> AppResult<()> {
+    for tag in tags {
+        let valid = !tag.is_empty()
+            && tag.len() <= 50
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AppError::BadRequest(format!("Invalid tag: {}", tag)));
+        }
+    }
+    Ok(())
+}
+
+/// Longest report interval advised to agents, in seconds.
+const MAX_REPORT_INTERVAL_SECS: i64 = 3600;
+
+/// Report intervals are 1 to [`MAX_REPORT_INTERVAL_SECS`] seconds.
+fn validate_report_interval(secs: i64) -> AppResult<()> {
+    if !(1..=MAX_REPORT_INTERVAL_SECS).contains(&secs) {
+        return Err(AppError::BadRequest(format!(
+            "report_interval_seconds must be 1-{}",
+            MAX_REPORT_INTERVAL_SECS
+        )));
+    }
+    Ok(())
+}
+
+/// Display colors are empty or `#` followed by six hex digits.
+fn validate_display_color(color: &str) -> AppResult<()> {
+    let valid = color.is_empty()
+        || (color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit()));
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid display color: {}",
+            color
+        )));
+    }
+    Ok(())
+}
+
+/// Display icons are letters, digits and hyphens, up to 50 characters.
+fn validate_display_icon(icon: &str) -> AppResult<()> {
+    let valid = icon.len() <= 50 && icon.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid display icon: {}",
+            icon
+        )));
+    }
+    Ok(())
+}
+
+/// DELETE /api/admin/clients/:id - Delete client. Its live agent
+/// WebSockets are closed with the `client_deleted` reason.
 pub async fn delete_client(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
+    if let Some(client) = state.db.find_client_by_id(id).await?
+        && client.builtin
+    {
+        return Err(AppError::BadRequest(
+            "The built-in client cannot be deleted; disable self_monitor instead".into(),
+        ));
+    }
     state.db.delete_client(id).await?;
+    state.ws_agents.disconnect(id, DisconnectReason::Deleted);
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
-/// GET /api/admin/clients/:id/token - Get client token.
-pub async fn get_client_token(
+/// POST /api/admin/clients/:id/archive - Archive a decommissioned client.
+/// Its agent is disconnected and its reports are rejected from now on.
+pub async fn archive_client(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<Client>> {
     let client = state
         .db
         .find_client_by_id(id)
         .await?
         .ok_or(AppError::NotFound("Client not found".into()))?;
+    if client.builtin {
+        return Err(AppError::BadRequest(
+            "The built-in client cannot be archived; disable self_monitor instead".into(),
+        ));
+    }
 
-    Ok(Json(serde_json::json!({
-        "uuid": client.id.to_string(),
-        "token": client.token
-    })))
+    state.db.set_client_archived(id, true).await?;
+    state.db.update_client_online(id, false).await?;
+    state.ws_agents.disconnect(id, DisconnectReason::Archived);
+    client_response(&state, id).await
 }
 
-// ==================== Settings ====================
-
-/// GET /api/admin/settings - Get all settings.
-pub async fn get_settings(State(state): State<AppState>) -> AppResult<Json<serde_json::Value>> {
-    let site_name = state
-        .db
-        .get_setting("site_name")
-        .await?
-        .unwrap_or(serde_json::json!("Vanmoi"));
-    let site_description = state
+/// POST /api/admin/clients/:id/unarchive - Return an archived client to
+/// service.
+pub async fn unarchive_client(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Client>> {
+    state
         .db
-        .get_setting("site_description")
+        .find_client_by_id(id)
         .await?
-        .unwrap_or(serde_json::json!("Server Monitoring"));
+        .ok_or(AppError::NotFound("Client not found".into()))?;
 
-    Ok(Json(serde_json::json!({
-        "site_name": site_name,
-        "site_description": site_description
-    })))
+    state.db.set_client_archived(id, false).await?;
+    client_response(&state, id).await
 }
 
-/// Update settings request.
+/// Online override request.
 #[derive(Debug, Deserialize)]
-pub struct UpdateSettingsRequest {
-    pub site_name: Option<String>,
-    pub site_description: Option<String>,
+pub struct OnlineOverrideRequest {
+    pub online: bool,
+    pub reason: Option<String>,
 }
 
-/// POST /api/admin/settings - Update settings.
-pub async fn update_settings(
+/// PATCH /api/admin/clients/:id/online - Override the online status, e.g.
+/// while the network path an agent reports through is down but the server
+/// is fine. Connections and reports leave the status alone until the
+/// override is cleared.
+pub async fn set_online_override(
     State(state): State<AppState>,
-    Json(req): Json<UpdateSettingsRequest>,
-) -> AppResult<Json<serde_json::Value>> {
-    if let Some(name) = req.site_name {
-        state
-            .db
-            .set_setting("site_name", serde_json::json!(name))
-            .await?;
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<OnlineOverrideRequest>,
+) -> AppResult<Json<Client>> {
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    if client.archived {
+        return Err(AppError::BadRequest(
+            "Archived clients cannot be overridden".into(),
+        ));
     }
-    if let Some(desc) = req.site_description {
-        state
-            .db
-            .set_setting("site_description", serde_json::json!(desc))
-            .await?;
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.len() > 500) {
+        return Err(AppError::BadRequest(
+            "Reason must be at most 500 characters".into(),
+        ));
     }
 
-    Ok(Json(serde_json::json!({"status": "ok"})))
-}
+    state
+        .db
+        .set_client_online_override(id, req.online, reason)
+        .await?;
+    state
+        .db
+        .insert_audit_log(
+            Some(user.id),
+            "client.online_overridden",
+            serde_json::json!({"client_id": id, "online": req.online, "reason": reason}),
+            session.ip_address.as_deref(),
+        )
+        .await?;
 
-// ==================== Notifications ====================
+    client_response(&state, id).await
+}
 
-/// GET /api/admin/notifications - List all notifications.
-pub async fn list_notifications(
+/// POST /api/admin/clients/:id/online/clear-override - Clear a manual
+/// online override. The client is online again if its agent is connected or
+/// reported within `report_timeout_secs`.
+pub async fn clear_online_override(
     State(state): State<AppState>,
-) -> AppResult<Json<Vec<Notification>>> {
-    let notifications = state.db.get_all_notifications().await?;
-    Ok(Json(notifications))
-}
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Client>> {
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    if !client.manually_overridden {
+        return Ok(Json(client));
+    }
 
-/// Add notification request.
-#[derive(Debug, Deserialize)]
-pub struct AddNotificationRequest {
-    pub name: String,
-    pub provider: String,
-    pub config: serde_json::Value,
+    state.db.clear_client_online_override(id).await?;
+    let cleared = Client {
+        manually_overridden: false,
+        ..client
+    };
+    let online = state.ws_agents.is_connected(id)
+        || cleared.effective_online(Utc::now(), state.runtime().report_timeout_secs);
+    if online != cleared.online {
+        state.db.update_client_online(id, online).await?;
+    }
+    state
+        .db
+        .insert_audit_log(
+            Some(user.id),
+            "client.online_override_cleared",
+            serde_json::json!({"client_id": id, "online": online}),
+            session.ip_address.as_deref(),
+        )
+        .await?;
+
+    client_response(&state, id).await
 }
 
-/// POST /api/admin/notifications - Add notification.
-pub async fn add_notification(
-    State(state): State<AppState>,
-    Json(req): Json<AddNotificationRequest>,
-) -> AppResult<Json<Notification>> {
-    let notification = state
+async fn client_response(state: &AppState, id: Uuid) -> AppResult<Json<Client>> {
+    state
         .db
-        .create_notification(&req.name, &req.provider, req.config)
-        .await?;
-    Ok(Json(notification))
+        .find_client_by_id(id)
+        .await?
+        .map(Json)
+        .ok_or(AppError::NotFound("Client not found".into()))
 }
 
-/// DELETE /api/admin/notifications/:id - Delete notification.
-pub async fn delete_notification(
+/// GET /api/admin/clients/:id/token - Get client token.
+pub async fn get_client_token(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
-    state.db.delete_notification(id).await?;
-    Ok(Json(serde_json::json!({"status": "ok"})))
-}
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    if client.builtin {
+        return Err(AppError::BadRequest(
+            "The built-in client has no token".into(),
+        ));
+    }
 
-/// Test notification request.
-#[derive(Debug, Deserialize)]
-pub struct TestNotificationRequest {
-    pub provider: String,
-    pub config: serde_json::Value,
-    #[serde(default = "default_title")]
-    pub title: String,
-    #[serde(default = "default_message")]
-    pub message: String,
+    Ok(Json(serde_json::json!({
+        "uuid": client.id.to_string(),
+        "token": state.db.client_token(&client)?
+    })))
 }
 
-fn default_title() -> String {
-    "Vanmoi Test".to_string()
+/// Duplicate record purge query params.
+#[derive(Debug, Deserialize)]
+pub struct DuplicateRecordsQuery {
+    /// Records this close to an identical earlier one are duplicates.
+    #[serde(default = "default_duplicate_window_ms")]
+    pub window_ms: i32,
+    /// Only count the duplicates.
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
-fn default_message() -> String {
-    "This is a test notification from Vanmoi.".to_string()
+fn default_duplicate_window_ms() -> i32 {
+    1000
 }
 
-/// POST /api/admin/notifications/test - Test notification.
-pub async fn test_notification(
-    Json(req): Json<TestNotificationRequest>,
+/// DELETE /api/admin/clients/:id/records/duplicate - Delete records that
+/// repeat an earlier one within `window_ms`, e.g. from a reconnecting agent.
+pub async fn delete_duplicate_records(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DuplicateRecordsQuery>,
 ) -> AppResult<Json<serde_json::Value>> {
-    crate::notifier::send_notification(&req.provider, &req.config, &req.title, &req.message)
-        .await
-        .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))?;
-
-    Ok(Json(
-        serde_json::json!({"status": "ok", "message": "Notification sent"}),
-    ))
-}
-
-// ==================== Ping Tasks ====================
+    if !(1..=60_000).contains(&query.window_ms) {
+        return Err(AppError::BadRequest("window_ms must be 1-60000".into()));
+    }
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
 
-/// GET /api/admin/ping - List all ping tasks.
-pub async fn list_ping_tasks(State(state): State<AppState>) -> AppResult<Json<Vec<PingTask>>> {
-    let tasks = state.db.get_all_ping_tasks().await?;
-    Ok(Json(tasks))
+    if query.dry_run {
+        let would_delete = state
+            .db
+            .count_duplicate_records(id, query.window_ms)
+            .await?;
+        return Ok(Json(serde_json::json!({"would_delete": would_delete})));
+    }
+    let deleted = state
+        .db
+        .delete_duplicate_records(id, query.window_ms)
+        .await?;
+    Ok(Json(serde_json::json!({"deleted": deleted})))
 }
 
-/// Add ping task request.
+/// Client log query params.
 #[derive(Debug, Deserialize)]
-pub struct AddPingTaskRequest {
-    pub name: String,
-    pub target: String,
-    #[serde(default = "default_interval")]
-    pub interval_seconds: i32,
-    #[serde(default = "default_timeout")]
-    pub timeout_seconds: i32,
+pub struct ClientLogsQuery {
+    /// Lines to return (default 100, max 1000).
+    pub limit: Option<i32>,
+    /// Case-insensitive substring the lines must contain.
+    pub search: Option<String>,
 }
 
-fn default_interval() -> i32 {
-    60
+/// GET /api/admin/clients/:id/logs - Latest agent log lines, newest first.
+pub async fn get_client_logs(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ClientLogsQuery>,
+) -> AppResult<Json<Vec<ClientLogLine>>> {
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let search = query.search.as_deref().filter(|s| !s.is_empty());
+    let lines = state.db.get_log_lines(id, limit, search).await?;
+    Ok(Json(lines))
 }
 
-fn default_timeout() -> i32 {
-    5
+/// Status history query params.
+#[derive(Debug, Deserialize)]
+pub struct StatusHistoryQuery {
+    /// Transitions to return (default 100, max 1000).
+    pub limit: Option<i32>,
 }
 
-/// POST /api/admin/ping - Add ping task.
-pub async fn add_ping_task(
+/// GET /api/admin/clients/:id/status-history - Online/offline transitions, newest first.
+pub async fn get_status_history(
     State(state): State<AppState>,
-    Json(req): Json<AddPingTaskRequest>,
-) -> AppResult<Json<PingTask>> {
-    let task = state
+    Path(id): Path<Uuid>,
+    Query(query): Query<StatusHistoryQuery>,
+) -> AppResult<Json<Vec<StatusTransition>>> {
+    state
         .db
-        .create_ping_task(
-            &req.name,
-            &req.target,
-            req.interval_seconds,
-            req.timeout_seconds,
-        )
-        .await?;
-    Ok(Json(task))
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let transitions = state.db.get_status_history(id, limit).await?;
+    Ok(Json(transitions))
 }
 
-/// DELETE /api/admin/ping/:id - Delete ping task.
-pub async fn delete_ping_task(
+/// Most peers returned for a client.
+const MAX_PEERS: i32 = 5;
+
+/// GET /api/admin/clients/:id/peers - Clients in the same group running the
+/// same OS, with their latest status, for comparison.
+pub async fn get_client_peers(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<ClientWithStatus>>> {
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let peers = state
+        .db
+        .find_peer_clients(id, &client.group_name, &client.os, MAX_PEERS)
+        .await?;
+    let ids: Vec<Uuid> = peers.iter().map(|c| c.id).collect();
+    let mut latest: HashMap<Uuid, _> = state
+        .db
+        .get_latest_records(&ids)
+        .await?
+        .into_iter()
+        .map(|r| (r.client_id, r))
+        .collect();
+    let active_silences = silences::active(&state.db).await?;
+
+    let stale_after_secs = state.runtime().stale_after_secs;
+    let peers = peers
+        .into_iter()
+        .map(|peer| ClientWithStatus {
+            status: latest.remove(&peer.id).map(ClientStatus::from),
+            silenced: silences::client_silenced(&active_silences, &peer),
+            client: ClientPublic::new(peer, stale_after_secs),
+        })
+        .collect();
+    Ok(Json(peers))
+}
+
+/// GET /api/admin/clients/:id/neighbors - Clients sharing the client's IPv4
+/// /24 or IPv6 /64 subnet.
+pub async fn get_client_neighbors(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<ClientPublic>>> {
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let mut neighbors = Vec::new();
+    if let Some(ipv4) = parse_address::<Ipv4Addr>(&client.ipv4) {
+        let [a, b, c, _] = ipv4.octets();
+        neighbors = state.db.find_ipv4_neighbors(id, [a, b, c]).await?;
+    }
+    if let Some(ipv6) = parse_address::<Ipv6Addr>(&client.ipv6) {
+        let subnet = Ipv6Net::new_assert(ipv6, 64).trunc();
+        for other in state.db.get_ipv6_clients(id).await? {
+            let in_subnet =
+                parse_address::<Ipv6Addr>(&other.ipv6).is_some_and(|ip| subnet.contains(&ip));
+            if in_subnet && !neighbors.iter().any(|n| n.id == other.id) {
+                neighbors.push(other);
+            }
+        }
+    }
+
+    let stale_after_secs = state.runtime().stale_after_secs;
+    Ok(Json(
+        neighbors
+            .into_iter()
+            .map(|c| ClientPublic::new(c, stale_after_secs))
+            .collect(),
+    ))
+}
+
+/// Parse a stored client address, ignoring blank and invalid ones.
+fn parse_address<T: std::str::FromStr>(address: &Option<String>) -> Option<T> {
+    address.as_deref()?.trim().parse().ok()
+}
+
+/// Client timeline query params.
+#[derive(Debug, Deserialize)]
+pub struct TimelineQuery {
+    /// Start of the window (default: 7 days before `to`).
+    pub from: Option<DateTime<Utc>>,
+    /// End of the window (default: now).
+    pub to: Option<DateTime<Utc>>,
+    /// Events to return (default and max 500).
+    pub limit: Option<i64>,
+}
+
+/// Most events returned by the client timeline.
+const MAX_TIMELINE_EVENTS: i64 = 500;
+
+/// GET /api/admin/clients/:id/timeline - Alerts, address changes and
+/// configuration changes of a client, newest first.
+pub async fn get_client_timeline(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<TimelineQuery>,
+) -> AppResult<Json<Vec<TimelineEvent>>> {
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(7));
+    if from > to {
+        return Err(AppError::BadRequest("from must not be after to".into()));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(MAX_TIMELINE_EVENTS)
+        .clamp(1, MAX_TIMELINE_EVENTS);
+    let events = state.db.get_client_timeline(id, from, to, limit).await?;
+    Ok(Json(events))
+}
+
+/// Incident timeline query params.
+#[derive(Debug, Deserialize)]
+pub struct IncidentTimelineQuery {
+    /// Start of the window (default: 7 days before `to`).
+    pub from: Option<DateTime<Utc>>,
+    /// End of the window (default: now).
+    pub to: Option<DateTime<Utc>>,
+    /// Only events of this client.
+    pub client_id: Option<Uuid>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    /// Events per page (default 100, max 500).
+    pub limit: Option<i64>,
+}
+
+/// GET /api/admin/timeline - Client status changes, alerts, ping failures
+/// and audit entries, newest first.
+pub async fn get_incident_timeline(
+    State(state): State<AppState>,
+    Query(query): Query<IncidentTimelineQuery>,
+) -> AppResult<Json<CursorPage<IncidentEvent>>> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(7));
+    if from > to {
+        return Err(AppError::BadRequest("from must not be after to".into()));
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_TIMELINE_EVENTS);
+    let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let events = state
+        .db
+        .get_incident_timeline(from, to, query.client_id, cursor.as_ref(), limit + 1)
+        .await?;
+    Ok(Json(CursorPage::new(events, limit, IncidentEvent::cursor)))
+}
+
+// ==================== Share Links ====================
+
+/// GET /api/admin/share-links - List all share links.
+pub async fn list_share_links(State(state): State<AppState>) -> AppResult<Json<Vec<ShareLink>>> {
+    let links = state.db.get_all_share_links().await?;
+    Ok(Json(links))
+}
+
+/// Add share link request.
+#[derive(Debug, Deserialize)]
+pub struct AddShareLinkRequest {
+    pub client_id: Uuid,
+    /// Omit for a link that never expires.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// POST /api/admin/share-links - Create a read-only share link for a client.
+pub async fn add_share_link(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Json(req): Json<AddShareLinkRequest>,
+) -> AppResult<Json<ShareLink>> {
+    state
+        .db
+        .find_client_by_id(req.client_id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    if req.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err(AppError::BadRequest(
+            "expires_at must be in the future".into(),
+        ));
+    }
+
+    let link = state
+        .db
+        .create_share_link(req.client_id, req.expires_at, user.id)
+        .await?;
+    Ok(Json(link))
+}
+
+/// DELETE /api/admin/share-links/:id - Revoke share link.
+pub async fn revoke_share_link(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    if !state.db.revoke_share_link(id).await? {
+        return Err(AppError::NotFound("Share link not found".into()));
+    }
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Settings ====================
+
+/// GET /api/admin/settings - Get all settings.
+pub async fn get_settings(State(state): State<AppState>) -> AppResult<Json<serde_json::Value>> {
+    let site_name = state
+        .db
+        .get_setting("site_name")
+        .await?
+        .unwrap_or(serde_json::json!("Vanmoi"));
+    let site_description = state
+        .db
+        .get_setting("site_description")
+        .await?
+        .unwrap_or(serde_json::json!("Server Monitoring"));
+    let logo_url = state
+        .db
+        .get_setting("logo_url")
+        .await?
+        .unwrap_or(serde_json::json!(""));
+    let custom_css = state
+        .db
+        .get_setting("custom_css")
+        .await?
+        .unwrap_or(serde_json::json!(""));
+    let custom_js_url = state
+        .db
+        .get_setting("custom_js_url")
+        .await?
+        .unwrap_or(serde_json::json!(""));
+    let runtime = RuntimeSettings::load(&state.db, &state.config).await?;
+    let telegram_bot = state
+        .db
+        .get_setting("telegram_bot")
+        .await?
+        .map(|mut bot| {
+            secrets::mask(&mut bot, &["bot_token"]);
+            bot
+        })
+        .unwrap_or(serde_json::json!({"bot_token": "", "allowed_chat_ids": []}));
+    let announcement_text = state
+        .db
+        .get_setting("announcement_text")
+        .await?
+        .unwrap_or(serde_json::json!(""));
+    let announcement_color = state
+        .db
+        .get_setting("announcement_color")
+        .await?
+        .unwrap_or(serde_json::json!("info"));
+    let announcement_until = state
+        .db
+        .get_setting("announcement_until")
+        .await?
+        .unwrap_or(serde_json::Value::Null);
+    let default_notification_id = state
+        .db
+        .get_setting("default_notification_id")
+        .await?
+        .unwrap_or(serde_json::Value::Null);
+    let record_retention_days = retention::retention_days(&state).await?;
+    let timezone = compare::load_timezone(&state).await?;
+    let ping_retention_days = retention::ping_retention_days(&state).await?;
+    let retain_archived_records = retention::retain_archived_records(&state).await?;
+    let password_login_enabled = crate::api::auth::password_login_enabled(&state).await?;
+    let digest = digest::load_settings(&state).await?;
+    let archive = archive::load_settings(&state).await?;
+    let object_storage = storage::load_settings(&state).await?;
+    let security_alerts = security::load_settings(&state).await?;
+
+    Ok(Json(serde_json::json!({
+        "site_name": site_name,
+        "site_description": site_description,
+        "logo_url": logo_url,
+        "custom_css": custom_css,
+        "custom_js_url": custom_js_url,
+        "locale": runtime.locale,
+        "timezone": timezone.name(),
+        "telegram_bot": telegram_bot,
+        "announcement_text": announcement_text,
+        "announcement_color": announcement_color,
+        "announcement_until": announcement_until,
+        "public_max_records": runtime.public_max_records,
+        "admin_max_records": runtime.admin_max_records,
+        "max_reports_per_minute": runtime.max_reports_per_minute,
+        "stale_after_secs": runtime.stale_after_secs,
+        "report_timeout_secs": runtime.report_timeout_secs,
+        "report_interval_seconds": runtime.report_interval_seconds,
+        "anomaly": runtime.anomaly,
+        "self_monitor": runtime.self_monitor,
+        "record_retention_days": record_retention_days,
+        "ping_retention_days": ping_retention_days,
+        "retain_archived_records": retain_archived_records,
+        "default_notification_id": default_notification_id,
+        "password_login_enabled": password_login_enabled,
+        "digest": digest,
+        "archive": archive,
+        "object_storage": object_storage.masked(),
+        "security_alerts": security_alerts
+    })))
+}
+
+/// Update settings request.
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub site_name: Option<String>,
+    pub site_description: Option<String>,
+    /// HTTPS URL of the site logo, or empty for none.
+    pub logo_url: Option<String>,
+    pub custom_css: Option<String>,
+    /// HTTPS URL of a script loaded by the frontend, or empty for none.
+    pub custom_js_url: Option<String>,
+    pub locale: Option<String>,
+    /// IANA timezone of period boundaries, e.g. `Asia/Shanghai`.
+    pub timezone: Option<String>,
+    pub telegram_bot: Option<TelegramBotSettings>,
+    pub announcement_text: Option<String>,
+    pub announcement_color: Option<String>,
+    pub announcement_until: Option<DateTime<Utc>>,
+    pub public_max_records: Option<i32>,
+    pub admin_max_records: Option<i32>,
+    pub max_reports_per_minute: Option<u32>,
+    pub stale_after_secs: Option<i64>,
+    pub report_timeout_secs: Option<i64>,
+    /// `null` leaves the report interval to the agents.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub report_interval_seconds: Option<Option<u32>>,
+    pub anomaly: Option<AnomalySettings>,
+    /// Report the server's own host as the built-in client.
+    pub self_monitor: Option<bool>,
+    /// Days records are kept; 0 keeps them forever.
+    pub record_retention_days: Option<i32>,
+    pub ping_retention_days: Option<i32>,
+    /// Keep the records of archived clients past the retention period.
+    pub retain_archived_records: Option<bool>,
+    /// `null` clears the default.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub default_notification_id: Option<Option<Uuid>>,
+    pub password_login_enabled: Option<bool>,
+    pub digest: Option<DigestSettings>,
+    pub archive: Option<ArchiveSettings>,
+    pub object_storage: Option<ObjectStorageSettings>,
+    pub security_alerts: Option<SecurityAlertSettings>,
+}
+
+/// Longest accepted `custom_css`, in characters.
+const MAX_CUSTOM_CSS_CHARS: usize = 10_000;
+
+/// Check that a URL setting is empty or an absolute HTTPS URL.
+fn validate_https_url(field: &str, value: &str) -> AppResult<()> {
+    if value.is_empty() || reqwest::Url::parse(value).is_ok_and(|url| url.scheme() == "https") {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "{} must be an HTTPS URL",
+            field
+        )))
+    }
+}
+
+/// Distinguish an explicit `null` (`Some(None)`) from a missing field (`None`).
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Telegram bot command interface settings.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TelegramBotSettings {
+    pub bot_token: String,
+    #[serde(default)]
+    pub allowed_chat_ids: Vec<String>,
+}
+
+/// POST /api/admin/settings - Update settings.
+pub async fn update_settings(
+    State(state): State<AppState>,
+    Json(req): Json<UpdateSettingsRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    if let Some(name) = req.site_name {
+        state
+            .db
+            .set_setting("site_name", serde_json::json!(name))
+            .await?;
+    }
+    if let Some(desc) = req.site_description {
+        state
+            .db
+            .set_setting("site_description", serde_json::json!(desc))
+            .await?;
+    }
+    if let Some(url) = req.logo_url {
+        validate_https_url("logo_url", &url)?;
+        state
+            .db
+            .set_setting("logo_url", serde_json::json!(url))
+            .await?;
+    }
+    if let Some(css) = req.custom_css {
+        if css.chars().count() > MAX_CUSTOM_CSS_CHARS {
+            return Err(AppError::BadRequest(format!(
+                "custom_css must be at most {} characters",
+                MAX_CUSTOM_CSS_CHARS
+            )));
+        }
+        state
+            .db
+            .set_setting("custom_css", serde_json::json!(css))
+            .await?;
+    }
+    if let Some(url) = req.custom_js_url {
+        validate_https_url("custom_js_url", &url)?;
+        state
+            .db
+            .set_setting("custom_js_url", serde_json::json!(url))
+            .await?;
+    }
+    if let Some(locale) = req.locale {
+        state
+            .db
+            .set_setting("locale", serde_json::json!(locale))
+            .await?;
+    }
+    if let Some(timezone) = req.timezone {
+        if timezone.parse::<chrono_tz::Tz>().is_err() {
+            return Err(AppError::BadRequest(format!(
+                "Invalid timezone: {}",
+                timezone
+            )));
+        }
+        state
+            .db
+            .set_setting("timezone", serde_json::json!(timezone))
+            .await?;
+    }
+    if let Some(mut bot) = req.telegram_bot {
+        let stored: Option<TelegramBotSettings> = state
+            .db
+            .get_setting("telegram_bot")
+            .await?
+            .and_then(|v| serde_json::from_value(v).ok());
+        secrets::restore_str(
+            &mut bot.bot_token,
+            stored.as_ref().map_or("", |s| s.bot_token.as_str()),
+            "bot_token",
+        )?;
+        state
+            .db
+            .set_setting("telegram_bot", serde_json::json!(bot))
+            .await?;
+    }
+    if let Some(text) = req.announcement_text {
+        state
+            .db
+            .set_setting("announcement_text", serde_json::json!(text))
+            .await?;
+    }
+    if let Some(color) = req.announcement_color {
+        if !["info", "warning", "error"].contains(&color.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid announcement color: {}",
+                color
+            )));
+        }
+        state
+            .db
+            .set_setting("announcement_color", serde_json::json!(color))
+            .await?;
+    }
+    if let Some(until) = req.announcement_until {
+        state
+            .db
+            .set_setting("announcement_until", serde_json::json!(until))
+            .await?;
+    }
+    if let Some(max) = req.public_max_records {
+        if max < 1 {
+            return Err(AppError::BadRequest(
+                "public_max_records must be at least 1".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("public_max_records", serde_json::json!(max))
+            .await?;
+    }
+    if let Some(max) = req.admin_max_records {
+        if max < 1 {
+            return Err(AppError::BadRequest(
+                "admin_max_records must be at least 1".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("admin_max_records", serde_json::json!(max))
+            .await?;
+    }
+    if let Some(max) = req.max_reports_per_minute {
+        if max < 1 {
+            return Err(AppError::BadRequest(
+                "max_reports_per_minute must be at least 1".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("max_reports_per_minute", serde_json::json!(max))
+            .await?;
+    }
+    if let Some(secs) = req.stale_after_secs {
+        if secs < 60 {
+            return Err(AppError::BadRequest(
+                "stale_after_secs must be at least 60".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("stale_after_secs", serde_json::json!(secs))
+            .await?;
+    }
+    if let Some(secs) = req.report_timeout_secs {
+        if secs < 10 {
+            return Err(AppError::BadRequest(
+                "report_timeout_secs must be at least 10".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("report_timeout_secs", serde_json::json!(secs))
+            .await?;
+    }
+    if let Some(interval) = req.report_interval_seconds {
+        if let Some(secs) = interval {
+            validate_report_interval(secs.into())?;
+        }
+        state
+            .db
+            .set_setting("report_interval_seconds", serde_json::json!(interval))
+            .await?;
+    }
+    if let Some(anomaly) = req.anomaly {
+        anomaly.validate().map_err(AppError::BadRequest)?;
+        state
+            .db
+            .set_setting("anomaly", serde_json::json!(anomaly))
+            .await?;
+    }
+    if let Some(enabled) = req.self_monitor {
+        state
+            .db
+            .set_setting("self_monitor", serde_json::json!(enabled))
+            .await?;
+    }
+    if let Some(days) = req.record_retention_days {
+        if days < 0 {
+            return Err(AppError::BadRequest(
+                "record_retention_days must not be negative".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("record_retention_days", serde_json::json!(days))
+            .await?;
+    }
+    if let Some(days) = req.ping_retention_days {
+        if days < 0 {
+            return Err(AppError::BadRequest(
+                "ping_retention_days must not be negative".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("ping_retention_days", serde_json::json!(days))
+            .await?;
+    }
+    if let Some(retain) = req.retain_archived_records {
+        state
+            .db
+            .set_setting("retain_archived_records", serde_json::json!(retain))
+            .await?;
+    }
+    if let Some(default_notification_id) = req.default_notification_id {
+        if let Some(id) = default_notification_id {
+            state
+                .db
+                .find_notification_by_id(id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+        }
+        state
+            .db
+            .set_setting(
+                "default_notification_id",
+                serde_json::json!(default_notification_id),
+            )
+            .await?;
+    }
+    if let Some(enabled) = req.password_login_enabled {
+        if !enabled && state.config.oidc.is_none() {
+            return Err(AppError::BadRequest(
+                "Cannot disable password login without OIDC configured".into(),
+            ));
+        }
+        state
+            .db
+            .set_setting("password_login_enabled", serde_json::json!(enabled))
+            .await?;
+    }
+    if let Some(digest) = req.digest {
+        digest.validate().map_err(AppError::BadRequest)?;
+        if let Some(id) = digest.notification_id {
+            state
+                .db
+                .find_notification_by_id(id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+        }
+        state
+            .db
+            .set_setting("digest", serde_json::json!(digest))
+            .await?;
+    }
+    if let Some(archive) = req.archive {
+        archive.validate().map_err(AppError::BadRequest)?;
+        if let Some(id) = archive.notification_id {
+            state
+                .db
+                .find_notification_by_id(id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+        }
+        state
+            .db
+            .set_setting("archive", serde_json::json!(archive))
+            .await?;
+    }
+    if let Some(security_alerts) = req.security_alerts {
+        security_alerts.validate().map_err(AppError::BadRequest)?;
+        if let Some(id) = security_alerts.notification_id {
+            state
+                .db
+                .find_notification_by_id(id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+        }
+        state
+            .db
+            .set_setting("security_alerts", serde_json::json!(security_alerts))
+            .await?;
+    }
+    if let Some(mut object_storage) = req.object_storage {
+        object_storage.restore_secrets(&storage::load_settings(&state).await?)?;
+        object_storage.validate().map_err(AppError::BadRequest)?;
+        state
+            .db
+            .set_setting("object_storage", serde_json::json!(object_storage))
+            .await?;
+    }
+    state.reload_runtime_settings().await?;
+
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+/// POST /api/admin/settings/reload - Reload the runtime settings from the
+/// database, e.g. after editing the settings table directly.
+pub async fn reload_settings(State(state): State<AppState>) -> AppResult<Json<RuntimeSettings>> {
+    let settings = state.reload_runtime_settings().await?;
+    Ok(Json(RuntimeSettings::clone(&settings)))
+}
+
+// ==================== Notifications ====================
+
+/// Notification as returned by the API, with secret config fields masked.
+fn masked_notification(mut notification: Notification) -> Notification {
+    secrets::mask(
+        &mut notification.config,
+        secrets::notification_secret_fields(&notification.provider),
+    );
+    notification
+}
+
+/// GET /api/admin/notifications - List all notifications.
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<Notification>>> {
+    let db = state.db.pinned();
+    let notifications = db
+        .get_notifications_paged(page.limit(), page.offset())
+        .await?
+        .into_iter()
+        .map(masked_notification)
+        .collect();
+    let total = db.count_notifications().await?;
+    Ok(Json(PagedResponse::new(notifications, total, page)))
+}
+
+/// A notification with how many offline notification links and alert rules
+/// use it.
+#[derive(Debug, Serialize)]
+pub struct NotificationDetail {
+    #[serde(flatten)]
+    pub notification: Notification,
+    pub offline_notification_count: i64,
+    pub alert_rule_count: i64,
+}
+
+/// GET /api/admin/notifications/:id - Get a notification, with secret
+/// config fields masked, and its usage counts.
+pub async fn get_notification(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<NotificationDetail>> {
+    let notification = state
+        .db
+        .find_notification_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+    let (offline_notification_count, alert_rule_count) =
+        state.db.count_notification_usage(id).await?;
+
+    Ok(Json(NotificationDetail {
+        notification: masked_notification(notification),
+        offline_notification_count,
+        alert_rule_count,
+    }))
+}
+
+/// Add notification request.
+#[derive(Debug, Deserialize)]
+pub struct AddNotificationRequest {
+    pub name: String,
+    pub provider: String,
+    pub config: serde_json::Value,
+    /// Unset to use the global locale.
+    pub notification_locale: Option<String>,
+}
+
+/// Check that a notification locale has a message catalog.
+fn validate_notification_locale(locale: Option<&str>) -> AppResult<()> {
+    match locale {
+        Some(locale) if !i18n::is_supported(locale) => Err(AppError::BadRequest(format!(
+            "Unsupported notification locale: {} (supported: {})",
+            locale,
+            i18n::SUPPORTED_LOCALES.join(", ")
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reject a notification config with problems.
+fn check_notification_config(provider: &str, config: &serde_json::Value) -> AppResult<()> {
+    let problems = validation::validate_config(provider, config);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+    Err(AppError::BadRequest(format!(
+        "Invalid notification config: {}",
+        problems.join("; ")
+    )))
+}
+
+/// POST /api/admin/notifications - Add notification.
+pub async fn add_notification(
+    State(state): State<AppState>,
+    Json(mut req): Json<AddNotificationRequest>,
+) -> AppResult<Json<Notification>> {
+    validate_notification_locale(req.notification_locale.as_deref())?;
+    // Nothing is stored yet, so a sentinel here is rejected
+    secrets::restore(
+        &mut req.config,
+        None,
+        secrets::notification_secret_fields(&req.provider),
+    )?;
+    check_notification_config(&req.provider, &req.config)?;
+
+    let notification = state
+        .db
+        .create_notification(
+            &req.name,
+            &req.provider,
+            req.config,
+            req.notification_locale.as_deref(),
+        )
+        .await?;
+    Ok(Json(masked_notification(notification)))
+}
+
+/// Edit notification request.
+///
+/// Secret config fields sent back as the sentinel keep their stored value.
+#[derive(Debug, Deserialize)]
+pub struct EditNotificationRequest {
+    pub name: String,
+    pub provider: String,
+    pub config: serde_json::Value,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Unset to use the global locale.
+    pub notification_locale: Option<String>,
+}
+
+/// POST /api/admin/notifications/:id - Update notification.
+pub async fn edit_notification(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(mut req): Json<EditNotificationRequest>,
+) -> AppResult<Json<Notification>> {
+    validate_notification_locale(req.notification_locale.as_deref())?;
+    let stored = state
+        .db
+        .find_notification_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+    secrets::restore(
+        &mut req.config,
+        Some(&stored.config),
+        secrets::notification_secret_fields(&req.provider),
+    )?;
+    check_notification_config(&req.provider, &req.config)?;
+
+    let notification = state
+        .db
+        .update_notification(
+            id,
+            &req.name,
+            &req.provider,
+            req.config,
+            req.enabled,
+            req.notification_locale.as_deref(),
+        )
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+    Ok(Json(masked_notification(notification)))
+}
+
+/// POST /api/admin/notifications/:id/duplicate - Copy a notification.
+pub async fn duplicate_notification(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Notification>> {
+    let notification = state
+        .db
+        .duplicate_notification(id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+    Ok(Json(masked_notification(notification)))
+}
+
+/// DELETE /api/admin/notifications/:id - Delete notification.
+pub async fn delete_notification(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    state.db.delete_notification(id).await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+/// Validate notification request.
+#[derive(Debug, Deserialize)]
+pub struct ValidateNotificationRequest {
+    pub provider: String,
+    pub config: serde_json::Value,
+    /// Stored notification whose secrets a sentinel in `config` stands for.
+    pub id: Option<Uuid>,
+}
+
+/// Notification config validation result.
+#[derive(Debug, Serialize)]
+pub struct NotificationValidation {
+    pub valid: bool,
+    pub problems: Vec<ConfigProblem>,
+}
+
+/// POST /api/admin/notifications/validate - Check a notification config
+/// without saving it or sending anything.
+pub async fn validate_notification(
+    State(state): State<AppState>,
+    Json(mut req): Json<ValidateNotificationRequest>,
+) -> AppResult<Json<NotificationValidation>> {
+    let stored = match req.id {
+        Some(id) => Some(
+            state
+                .db
+                .find_notification_by_id(id)
+                .await?
+                .ok_or(AppError::NotFound("Notification not found".into()))?,
+        ),
+        None => None,
+    };
+    secrets::restore(
+        &mut req.config,
+        stored.as_ref().map(|n| &n.config),
+        secrets::notification_secret_fields(&req.provider),
+    )?;
+
+    let problems = validation::validate_config(&req.provider, &req.config);
+    Ok(Json(NotificationValidation {
+        valid: problems.is_empty(),
+        problems,
+    }))
+}
+
+/// Test notification request.
+#[derive(Debug, Deserialize)]
+pub struct TestNotificationRequest {
+    pub provider: String,
+    pub config: serde_json::Value,
+    #[serde(default = "default_title")]
+    pub title: String,
+    #[serde(default = "default_message")]
+    pub message: String,
+}
+
+fn default_title() -> String {
+    "Vanmoi Test".to_string()
+}
+
+fn default_message() -> String {
+    "This is a test notification from Vanmoi.".to_string()
+}
+
+/// Limit on sending a test notification.
+const NOTIFICATION_TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// POST /api/admin/notifications/test - Test notification.
+pub async fn test_notification(
+    Json(req): Json<TestNotificationRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    with_timeout(
+        NOTIFICATION_TEST_TIMEOUT,
+        crate::notifier::send_notification(&req.provider, &req.config, &req.title, &req.message),
+    )
+    .await?
+    .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))?;
+
+    Ok(Json(
+        serde_json::json!({"status": "ok", "message": "Notification sent"}),
+    ))
+}
+
+/// POST /api/admin/notifications/:id/test - Send a test message through a
+/// saved notification, using its stored config.
+pub async fn test_saved_notification(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let notification = state
+        .db
+        .find_notification_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+
+    with_timeout(
+        NOTIFICATION_TEST_TIMEOUT,
+        crate::notifier::send_notification(
+            &notification.provider,
+            &notification.config,
+            &default_title(),
+            &default_message(),
+        ),
+    )
+    .await?
+    .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))?;
+
+    Ok(Json(
+        serde_json::json!({"status": "ok", "message": "Notification sent"}),
+    ))
+}
+
+/// POST /api/admin/notifications/digest/test - Send a digest report now.
+pub async fn test_digest(State(state): State<AppState>) -> AppResult<Json<serde_json::Value>> {
+    let settings = digest::load_settings(&state).await?;
+    settings.validate().map_err(AppError::BadRequest)?;
+    digest::send_digest(&state, &settings, Utc::now(), false).await?;
+
+    Ok(Json(
+        serde_json::json!({"status": "ok", "message": "Digest sent"}),
+    ))
+}
+
+// ==================== Notification Routes ====================
+
+/// GET /api/admin/notification-routes - List all notification routes.
+pub async fn list_notification_routes(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<NotificationRoute>>> {
+    let routes = state.db.get_all_notification_routes().await?;
+    Ok(Json(routes))
+}
+
+/// Notification route request.
+#[derive(Debug, Deserialize)]
+pub struct NotificationRouteRequest {
+    pub name: String,
+    pub group_name: Option<String>,
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    pub notification_id: Uuid,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Validate a notification route request.
+async fn validate_route(state: &AppState, req: &NotificationRouteRequest) -> AppResult<()> {
+    if req.group_name.is_none() && req.tag.is_none() {
+        return Err(AppError::BadRequest(
+            "A route needs a group_name or tag matcher".into(),
+        ));
+    }
+    for event in &req.event_types {
+        if EventType::from_name(event).is_none() {
+            return Err(AppError::BadRequest(format!(
+                "Unknown event type: {}",
+                event
+            )));
+        }
+    }
+    state
+        .db
+        .find_notification_by_id(req.notification_id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+    Ok(())
+}
+
+/// POST /api/admin/notification-routes - Add notification route.
+pub async fn add_notification_route(
+    State(state): State<AppState>,
+    Json(req): Json<NotificationRouteRequest>,
+) -> AppResult<Json<NotificationRoute>> {
+    validate_route(&state, &req).await?;
+
+    let route = state
+        .db
+        .create_notification_route(
+            &req.name,
+            req.group_name.as_deref(),
+            req.tag.as_deref(),
+            &req.event_types,
+            req.notification_id,
+            req.enabled,
+        )
+        .await?;
+    Ok(Json(route))
+}
+
+/// POST /api/admin/notification-routes/:id - Update notification route.
+pub async fn edit_notification_route(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<NotificationRouteRequest>,
+) -> AppResult<Json<NotificationRoute>> {
+    validate_route(&state, &req).await?;
+
+    let route = state
+        .db
+        .update_notification_route(
+            id,
+            &req.name,
+            req.group_name.as_deref(),
+            req.tag.as_deref(),
+            &req.event_types,
+            req.notification_id,
+            req.enabled,
+        )
+        .await?
+        .ok_or(AppError::NotFound("Notification route not found".into()))?;
+    Ok(Json(route))
+}
+
+/// DELETE /api/admin/notification-routes/:id - Delete notification route.
+pub async fn delete_notification_route(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    state.db.delete_notification_route(id).await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Ping Tasks ====================
+
+/// GET /api/admin/ping - List all ping tasks.
+pub async fn list_ping_tasks(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<PingTask>>> {
+    let db = state.db.pinned();
+    let tasks = db.get_ping_tasks_paged(page.limit(), page.offset()).await?;
+    let total = db.count_ping_tasks().await?;
+    Ok(Json(PagedResponse::new(tasks, total, page)))
+}
+
+/// Records included in a ping task detail.
+const PING_DETAIL_RECORDS: i32 = 10;
+
+/// Uptime and latency of a ping task over the last 24 hours.
+#[derive(Debug, Serialize)]
+pub struct PingStats {
+    /// Share of successful checks; 0 without checks.
+    pub uptime_pct: f64,
+    /// Average latency of successful checks.
+    pub avg_latency_ms: Option<f64>,
+}
+
+/// A ping task with its latest results, for the admin detail panel.
+#[derive(Debug, Serialize)]
+pub struct PingTaskDetail {
+    #[serde(flatten)]
+    pub task: PingTask,
+    /// Latest records, newest first.
+    pub recent_records: Vec<PingRecord>,
+    /// Whether the latest check succeeded; `None` before the first check.
+    pub last_status: Option<bool>,
+    pub last_check_at: Option<DateTime<Utc>>,
+    /// When the scheduler next checks an enabled task.
+    pub next_check_at: Option<DateTime<Utc>>,
+    pub stats_24h: PingStats,
+}
+
+/// GET /api/admin/ping/:id - Get a ping task with its recent records and
+/// 24-hour statistics.
+pub async fn get_ping_task(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PingTaskDetail>> {
+    let task = state
+        .db
+        .find_ping_task_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Ping task not found".into()))?;
+    let raw_retention_days = retention::ping_retention_days(&state).await?;
+    let summary = state
+        .db
+        .get_ping_task_summary(id, raw_retention_days)
+        .await?;
+    let recent_records = state
+        .db
+        .get_recent_ping_records(id, PING_DETAIL_RECORDS, None)
+        .await?;
+
+    let last_check_at = summary.last_check_at;
+    let next_check_at = last_check_at
+        .filter(|_| task.enabled)
+        .map(|at| at + chrono::Duration::seconds(task.interval_seconds.into()));
+
+    Ok(Json(PingTaskDetail {
+        last_status: last_check_at.map(|_| summary.last_success),
+        last_check_at,
+        next_check_at,
+        stats_24h: PingStats {
+            uptime_pct: summary.uptime_24h_pct,
+            avg_latency_ms: summary.avg_latency_ms,
+        },
+        recent_records,
+        task,
+    }))
+}
+
+/// Add ping task request.
+#[derive(Debug, Deserialize)]
+pub struct AddPingTaskRequest {
+    pub name: String,
+    pub target: String,
+    #[serde(default = "default_interval")]
+    pub interval_seconds: i32,
+    #[serde(default = "default_timeout")]
+    pub timeout_seconds: i32,
+    /// HTTP targets only: the response body must contain this.
+    pub expected_body_contains: Option<String>,
+    /// HTTP targets only: the response body must not contain this.
+    pub expected_body_not_contains: Option<String>,
+    /// HTTP targets only: header names mapped to values.
+    #[serde(default)]
+    pub request_headers: HashMap<String, String>,
+}
+
+fn default_interval() -> i32 {
+    60
+}
+
+fn default_timeout() -> i32 {
+    5
+}
+
+/// POST /api/admin/ping - Add ping task.
+pub async fn add_ping_task(
+    State(state): State<AppState>,
+    Json(req): Json<AddPingTaskRequest>,
+) -> AppResult<Json<PingTask>> {
+    // Empty strings would match every body
+    let expected_body_contains = req.expected_body_contains.filter(|s| !s.is_empty());
+    let expected_body_not_contains = req.expected_body_not_contains.filter(|s| !s.is_empty());
+    let http_options = expected_body_contains.is_some()
+        || expected_body_not_contains.is_some()
+        || !req.request_headers.is_empty();
+    if http_options && !ping::is_http_target(&req.target) {
+        return Err(AppError::BadRequest(
+            "Body matching and request headers need an http:// or https:// target".into(),
+        ));
+    }
+    for (name, value) in &req.request_headers {
+        if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+            || reqwest::header::HeaderValue::from_str(value).is_err()
+        {
+            return Err(AppError::BadRequest(format!(
+                "Invalid request header: {}",
+                name
+            )));
+        }
+    }
+
+    let task = state
+        .db
+        .create_ping_task(
+            &req.name,
+            &req.target,
+            req.interval_seconds,
+            req.timeout_seconds,
+            expected_body_contains.as_deref(),
+            expected_body_not_contains.as_deref(),
+            &serde_json::json!(req.request_headers),
+        )
+        .await?;
+    Ok(Json(task))
+}
+
+/// Most ping tasks created by one bulk request.
+const MAX_BULK_PING_TASKS: usize = 100;
+
+/// Bulk add ping tasks request.
+#[derive(Debug, Deserialize)]
+pub struct BulkAddPingTasksRequest {
+    pub targets: Vec<String>,
+    #[serde(default = "default_interval")]
+    pub interval_seconds: i32,
+    #[serde(default = "default_timeout")]
+    pub timeout_seconds: i32,
+}
+
+/// A bulk item that was not created.
+#[derive(Debug, Serialize)]
+pub struct BulkItemError {
+    /// Position of the item in the request.
+    pub index: usize,
+    pub target: String,
+    pub error: String,
+}
+
+/// Bulk add ping tasks response.
+#[derive(Debug, Serialize)]
+pub struct BulkAddPingTasksResponse {
+    pub created: Vec<Uuid>,
+    pub errors: Vec<BulkItemError>,
+}
+
+/// POST /api/admin/ping/bulk - Add ping tasks for several targets with the
+/// same settings, each named after its target.
+pub async fn bulk_add_ping_tasks(
+    State(state): State<AppState>,
+    Json(req): Json<BulkAddPingTasksRequest>,
+) -> AppResult<Json<BulkAddPingTasksResponse>> {
+    if req.targets.is_empty() || req.targets.len() > MAX_BULK_PING_TASKS {
+        return Err(AppError::BadRequest(format!(
+            "targets must have 1-{} entries",
+            MAX_BULK_PING_TASKS
+        )));
+    }
+
+    let mut errors = Vec::new();
+    let mut valid = Vec::new();
+    for (index, target) in req.targets.iter().enumerate() {
+        let trimmed = target.trim();
+        if trimmed.is_empty() || trimmed.len() > 255 {
+            errors.push(BulkItemError {
+                index,
+                target: target.clone(),
+                error: "Target must be 1-255 characters".into(),
+            });
+        } else {
+            valid.push((index, trimmed));
+        }
+    }
+
+    let targets: Vec<&str> = valid.iter().map(|(_, target)| *target).collect();
+    let results = state
+        .db
+        .create_ping_tasks(&targets, req.interval_seconds, req.timeout_seconds)
+        .await?;
+
+    let mut created = Vec::new();
+    for ((index, target), result) in valid.into_iter().zip(results) {
+        match result {
+            Ok(task) => created.push(task.id),
+            Err(e) => errors.push(BulkItemError {
+                index,
+                target: target.to_string(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    errors.sort_by_key(|e| e.index);
+
+    Ok(Json(BulkAddPingTasksResponse { created, errors }))
+}
+
+/// POST /api/admin/ping/:id/duplicate - Copy a ping task.
+pub async fn duplicate_ping_task(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PingTask>> {
+    let task = state
+        .db
+        .duplicate_ping_task(id)
+        .await?
+        .ok_or(AppError::NotFound("Ping task not found".into()))?;
+    Ok(Json(task))
+}
+
+/// Hard limit on a manually triggered ping.
+const PING_RUN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// POST /api/admin/ping/:id/run - Ping a task's target now and return the
+/// stored record.
+pub async fn run_ping_task(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PingRecord>> {
+    let task = state
+        .db
+        .find_ping_task_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Ping task not found".into()))?;
+
+    let record = with_timeout(PING_RUN_TIMEOUT, ping::execute_ping(&task, &state.db))
+        .await
+        .map_err(|_| {
+            AppError::Timeout(format!(
+                "{} did not respond within {} seconds",
+                task.target,
+                PING_RUN_TIMEOUT.as_secs()
+            ))
+        })??;
+    Ok(Json(record))
+}
+
+/// DELETE /api/admin/ping/:id - Delete ping task.
+pub async fn delete_ping_task(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    sqlx::query("DELETE FROM ping_tasks WHERE id = $1")
+        .bind(id)
+        .execute(state.db.primary()?)
+        .await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Monitor Groups ====================
+
+/// GET /api/admin/monitors - List all monitor groups.
+pub async fn list_monitor_groups(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<MonitorGroup>>> {
+    let groups = state.db.get_all_monitor_groups().await?;
+    Ok(Json(groups))
+}
+
+/// Monitor group request.
+#[derive(Debug, Deserialize)]
+pub struct MonitorGroupRequest {
+    pub name: String,
+    /// `all` (default) or `any`.
+    #[serde(default = "default_monitor_logic")]
+    pub logic: String,
+    pub task_ids: Vec<Uuid>,
+    pub notification_id: Option<Uuid>,
+}
+
+fn default_monitor_logic() -> String {
+    MonitorLogic::All.as_str().to_string()
+}
+
+/// Validate a monitor group request, returning its deduplicated task IDs.
+async fn validate_monitor_group(
+    state: &AppState,
+    req: &MonitorGroupRequest,
+) -> AppResult<Vec<Uuid>> {
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "Monitor name must be 1-100 characters".into(),
+        ));
+    }
+    if MonitorLogic::from_name(&req.logic).is_none() {
+        return Err(AppError::BadRequest(format!(
+            "Unknown monitor logic: {}",
+            req.logic
+        )));
+    }
+
+    let mut task_ids = req.task_ids.clone();
+    task_ids.sort();
+    task_ids.dedup();
+    if state.db.count_ping_tasks_by_ids(&task_ids).await? != task_ids.len() as i64 {
+        return Err(AppError::NotFound("Ping task not found".into()));
+    }
+    if let Some(id) = req.notification_id {
+        state
+            .db
+            .find_notification_by_id(id)
+            .await?
+            .ok_or(AppError::NotFound("Notification not found".into()))?;
+    }
+    Ok(task_ids)
+}
+
+/// POST /api/admin/monitors - Add monitor group.
+pub async fn add_monitor_group(
+    State(state): State<AppState>,
+    Json(req): Json<MonitorGroupRequest>,
+) -> AppResult<Json<MonitorGroup>> {
+    let task_ids = validate_monitor_group(&state, &req).await?;
+
+    let group = state
+        .db
+        .create_monitor_group(req.name.trim(), &req.logic, &task_ids, req.notification_id)
+        .await?;
+    Ok(Json(group))
+}
+
+/// POST /api/admin/monitors/:id - Update monitor group.
+pub async fn edit_monitor_group(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<MonitorGroupRequest>,
+) -> AppResult<Json<MonitorGroup>> {
+    let task_ids = validate_monitor_group(&state, &req).await?;
+
+    let group = state
+        .db
+        .update_monitor_group(
+            id,
+            req.name.trim(),
+            &req.logic,
+            &task_ids,
+            req.notification_id,
+        )
+        .await?
+        .ok_or(AppError::NotFound("Monitor group not found".into()))?;
+    Ok(Json(group))
+}
+
+/// DELETE /api/admin/monitors/:id - Delete monitor group.
+pub async fn delete_monitor_group(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    state.db.delete_monitor_group(id).await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Heartbeats ====================
+
+/// GET /api/admin/heartbeats - List heartbeats with their current state.
+pub async fn list_heartbeats(State(state): State<AppState>) -> AppResult<Json<Vec<Heartbeat>>> {
+    let heartbeats = state.db.get_all_heartbeats().await?;
+    Ok(Json(heartbeats))
+}
+
+/// Heartbeat request.
+#[derive(Debug, Deserialize)]
+pub struct HeartbeatRequest {
+    pub name: String,
+    /// Expected seconds between beats.
+    pub interval_secs: i32,
+    /// Extra seconds to wait before the heartbeat counts as missed.
+    #[serde(default)]
+    pub grace_secs: i32,
+    pub notification_id: Option<Uuid>,
+    #[serde(default)]
+    pub public: bool,
+}
+
+/// Validate a heartbeat request.
+async fn validate_heartbeat(state: &AppState, req: &HeartbeatRequest) -> AppResult<()> {
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "Heartbeat name must be 1-100 characters".into(),
+        ));
+    }
+    // Heartbeats are checked once a minute
+    if !(60..=30 * 86400).contains(&req.interval_secs) {
+        return Err(AppError::BadRequest(
+            "interval_secs must be between 60 and 2592000".into(),
+        ));
+    }
+    if !(0..=7 * 86400).contains(&req.grace_secs) {
+        return Err(AppError::BadRequest(
+            "grace_secs must be between 0 and 604800".into(),
+        ));
+    }
+    if let Some(id) = req.notification_id {
+        state
+            .db
+            .find_notification_by_id(id)
+            .await?
+            .ok_or(AppError::NotFound("Notification not found".into()))?;
+    }
+    Ok(())
+}
+
+/// POST /api/admin/heartbeats - Add heartbeat.
+pub async fn add_heartbeat(
+    State(state): State<AppState>,
+    Json(req): Json<HeartbeatRequest>,
+) -> AppResult<Json<Heartbeat>> {
+    validate_heartbeat(&state, &req).await?;
+
+    let heartbeat = state
+        .db
+        .create_heartbeat(
+            req.name.trim(),
+            req.interval_secs,
+            req.grace_secs,
+            req.notification_id,
+            req.public,
+        )
+        .await?;
+    Ok(Json(heartbeat))
+}
+
+/// POST /api/admin/heartbeats/:id - Update heartbeat.
+pub async fn edit_heartbeat(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<HeartbeatRequest>,
+) -> AppResult<Json<Heartbeat>> {
+    validate_heartbeat(&state, &req).await?;
+
+    let heartbeat = state
+        .db
+        .update_heartbeat(
+            id,
+            req.name.trim(),
+            req.interval_secs,
+            req.grace_secs,
+            req.notification_id,
+            req.public,
+        )
+        .await?
+        .ok_or(AppError::NotFound("Heartbeat not found".into()))?;
+    Ok(Json(heartbeat))
+}
+
+/// DELETE /api/admin/heartbeats/:id - Delete heartbeat.
+pub async fn delete_heartbeat(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
-    sqlx::query("DELETE FROM ping_tasks WHERE id = $1")
-        .bind(id)
-        .execute(state.db.pool())
+    state.db.delete_heartbeat(id).await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Silences ====================
+
+/// GET /api/admin/silences - List all silences.
+pub async fn list_silences(State(state): State<AppState>) -> AppResult<Json<Vec<Silence>>> {
+    let silences = state.db.get_all_silences().await?;
+    Ok(Json(silences))
+}
+
+/// Silence request.
+#[derive(Debug, Deserialize)]
+pub struct SilenceRequest {
+    pub client_id: Option<Uuid>,
+    pub group_name: Option<String>,
+    pub task_id: Option<Uuid>,
+    #[serde(flatten)]
+    pub schedule: SilenceSchedule,
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// Validate a silence request's matchers and schedule.
+async fn validate_silence(state: &AppState, req: &SilenceRequest) -> AppResult<()> {
+    let matches_client = req.client_id.is_some() || req.group_name.is_some();
+    if matches_client == req.task_id.is_some() {
+        return Err(AppError::BadRequest(
+            "Silence must match a client_id and/or group_name, or a task_id".into(),
+        ));
+    }
+    if let Some(id) = req.client_id {
+        state
+            .db
+            .find_client_by_id(id)
+            .await?
+            .ok_or(AppError::NotFound("Client not found".into()))?;
+    }
+    if let Some(id) = req.task_id
+        && state.db.count_ping_tasks_by_ids(&[id]).await? == 0
+    {
+        return Err(AppError::NotFound("Ping task not found".into()));
+    }
+    req.schedule.validate().map_err(AppError::BadRequest)
+}
+
+/// POST /api/admin/silences - Add silence.
+pub async fn add_silence(
+    State(state): State<AppState>,
+    Json(req): Json<SilenceRequest>,
+) -> AppResult<Json<Silence>> {
+    validate_silence(&state, &req).await?;
+
+    let silence = state
+        .db
+        .create_silence(
+            req.client_id,
+            req.group_name.as_deref(),
+            req.task_id,
+            &req.schedule,
+            &req.comment,
+        )
+        .await?;
+    Ok(Json(silence))
+}
+
+/// POST /api/admin/silences/:id - Update silence.
+pub async fn edit_silence(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SilenceRequest>,
+) -> AppResult<Json<Silence>> {
+    validate_silence(&state, &req).await?;
+
+    let silence = state
+        .db
+        .update_silence(
+            id,
+            req.client_id,
+            req.group_name.as_deref(),
+            req.task_id,
+            &req.schedule,
+            &req.comment,
+        )
+        .await?
+        .ok_or(AppError::NotFound("Silence not found".into()))?;
+    Ok(Json(silence))
+}
+
+/// DELETE /api/admin/silences/:id - Delete silence.
+pub async fn delete_silence(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    state.db.delete_silence(id).await?;
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+// ==================== Alert Rules ====================
+
+/// GET /api/admin/alert-rules - List all alert rules.
+pub async fn list_alert_rules(State(state): State<AppState>) -> AppResult<Json<Vec<AlertRule>>> {
+    let rules = state.db.get_all_alert_rules().await?;
+    Ok(Json(rules))
+}
+
+/// Add alert rule request.
+#[derive(Debug, Deserialize)]
+pub struct AddAlertRuleRequest {
+    pub client_id: Uuid,
+    pub notification_id: Option<Uuid>,
+    pub metric: String,
+    pub threshold: f32,
+    #[serde(default = "default_severity")]
+    pub severity: String,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+/// POST /api/admin/alert-rules - Add alert rule.
+pub async fn add_alert_rule(
+    State(state): State<AppState>,
+    Json(req): Json<AddAlertRuleRequest>,
+) -> AppResult<Json<AlertRule>> {
+    if AlertMetric::from_name(&req.metric).is_none() {
+        return Err(AppError::BadRequest(format!(
+            "Unknown metric: {}",
+            req.metric
+        )));
+    }
+    if req.severity != "warning" && req.severity != "critical" {
+        return Err(AppError::BadRequest(format!(
+            "Unknown severity: {}",
+            req.severity
+        )));
+    }
+
+    state
+        .db
+        .find_client_by_id(req.client_id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let rule = state
+        .db
+        .create_alert_rule(
+            req.client_id,
+            req.notification_id,
+            &req.metric,
+            req.threshold,
+            &req.severity,
+        )
         .await?;
+    Ok(Json(rule))
+}
+
+/// Default alert rules request.
+#[derive(Debug, Deserialize)]
+pub struct DefaultAlertRulesRequest {
+    pub client_id: Uuid,
+    pub notification_id: Option<Uuid>,
+}
+
+/// POST /api/admin/alert-rules/defaults - Create standard warning and
+/// critical rules for a client.
+pub async fn add_default_alert_rules(
+    State(state): State<AppState>,
+    Json(req): Json<DefaultAlertRulesRequest>,
+) -> AppResult<Json<Vec<AlertRule>>> {
+    state
+        .db
+        .find_client_by_id(req.client_id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    let mut rules = Vec::new();
+    for (metric, warning, critical) in DEFAULT_THRESHOLDS {
+        for (threshold, severity) in [(*warning, "warning"), (*critical, "critical")] {
+            let rule = state
+                .db
+                .create_alert_rule(
+                    req.client_id,
+                    req.notification_id,
+                    metric.as_str(),
+                    threshold,
+                    severity,
+                )
+                .await?;
+            rules.push(rule);
+        }
+    }
+
+    Ok(Json(rules))
+}
+
+/// Thresholds of the rules created by the bootstrap endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct DefaultThresholds {
+    pub cpu_warn: f32,
+    pub cpu_crit: f32,
+    pub ram_warn_pct: f32,
+    pub disk_warn_pct: f32,
+    /// °C.
+    pub temp_warn: f32,
+}
+
+impl Default for DefaultThresholds {
+    fn default() -> Self {
+        Self {
+            cpu_warn: 80.0,
+            cpu_crit: 95.0,
+            ram_warn_pct: 85.0,
+            disk_warn_pct: 85.0,
+            temp_warn: 80.0,
+        }
+    }
+}
+
+impl DefaultThresholds {
+    /// Rules to create as `(metric, threshold, severity)`.
+    fn rules(&self) -> [(AlertMetric, f32, &'static str); 5] {
+        [
+            (AlertMetric::Cpu, self.cpu_warn, "warning"),
+            (AlertMetric::Cpu, self.cpu_crit, "critical"),
+            (AlertMetric::RamPct, self.ram_warn_pct, "warning"),
+            (AlertMetric::DiskPct, self.disk_warn_pct, "warning"),
+            (AlertMetric::Temp, self.temp_warn, "warning"),
+        ]
+    }
+}
+
+/// Bootstrap alert rules request.
+#[derive(Debug, Deserialize)]
+pub struct BootstrapRulesRequest {
+    pub notification_id: Uuid,
+    #[serde(default)]
+    pub thresholds: Option<DefaultThresholds>,
+}
+
+/// POST /api/admin/clients/:id/alert-rules/bootstrap - Create CPU, RAM, disk
+/// and temperature rules for a client.
+///
+/// Rules whose metric and severity the client already has are skipped, so
+/// repeating the request creates nothing. Returns the created rules.
+pub async fn bootstrap_alert_rules(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<BootstrapRulesRequest>,
+) -> AppResult<Json<Vec<AlertRule>>> {
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    state
+        .db
+        .find_notification_by_id(req.notification_id)
+        .await?
+        .ok_or(AppError::NotFound("Notification not found".into()))?;
+
+    let existing = state.db.get_alert_rules_by_client(id).await?;
+    let mut rules = Vec::new();
+    for (metric, threshold, severity) in req.thresholds.unwrap_or_default().rules() {
+        if existing
+            .iter()
+            .any(|r| r.metric == metric.as_str() && r.severity == severity)
+        {
+            continue;
+        }
+        let rule = state
+            .db
+            .create_alert_rule(
+                id,
+                Some(req.notification_id),
+                metric.as_str(),
+                threshold,
+                severity,
+            )
+            .await?;
+        rules.push(rule);
+    }
+
+    Ok(Json(rules))
+}
+
+/// GET /api/admin/clients/:id/alert-rules - List a client's alert rules with
+/// their notification's name and provider.
+pub async fn list_client_alert_rules(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<ClientAlertRule>>> {
+    client_alert_rules(&state, id, false).await
+}
+
+/// GET /api/admin/clients/:id/alert-rules/active - List a client's alert rules
+/// that fired in the last 24 hours.
+pub async fn list_active_client_alert_rules(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Vec<ClientAlertRule>>> {
+    client_alert_rules(&state, id, true).await
+}
+
+async fn client_alert_rules(
+    state: &AppState,
+    id: Uuid,
+    active_only: bool,
+) -> AppResult<Json<Vec<ClientAlertRule>>> {
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+    let rules = state.db.get_client_alert_rules(id, active_only).await?;
+    Ok(Json(rules))
+}
+
+/// DELETE /api/admin/alert-rules/:id - Delete alert rule.
+pub async fn delete_alert_rule(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    state.db.delete_alert_rule(id).await?;
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
+/// GET /api/admin/alert-history - List recent alert firings.
+pub async fn list_alert_history(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<AlertHistory>>> {
+    let db = state.db.pinned();
+    let alerts = db.get_alert_history(page.limit(), page.offset()).await?;
+    let total = db.count_alert_history().await?;
+    Ok(Json(PagedResponse::new(alerts, total, page)))
+}
+
 // ==================== User Management ====================
 
 /// Change password request.
@@ -301,6 +2515,7 @@ pub struct ChangePasswordRequest {
 pub async fn change_password(
     State(state): State<AppState>,
     Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
     Json(req): Json<ChangePasswordRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     // Verify old password
@@ -325,25 +2540,449 @@ pub async fn change_password(
 
     // Update password
     state.db.update_user_password(user.id, &new_hash).await?;
+    security::record(
+        &state,
+        SecurityEventType::PasswordChanged,
+        Some(&user),
+        None,
+        session.ip_address.as_deref(),
+        session.user_agent.as_deref(),
+    )
+    .await;
 
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
+/// Change username request.
+#[derive(Debug, Deserialize)]
+pub struct ChangeUsernameRequest {
+    pub new_username: String,
+    pub password: String,
+}
+
+/// POST /api/admin/user/username - Change username (also PATCH).
+///
+/// Requires the current password. Usernames are unique regardless of case;
+/// changing only the casing of one's own name is allowed. Sessions belong
+/// to the user, not the name, so they stay signed in.
+pub async fn change_username(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(session): Extension<Session>,
+    Json(req): Json<ChangeUsernameRequest>,
+) -> AppResult<Json<UserInfo>> {
+    if user.external {
+        return Err(AppError::BadRequest(
+            "Single sign-on users cannot change their username".into(),
+        ));
+    }
+
+    let new_username = req.new_username.trim();
+    if new_username.is_empty() || new_username.len() > 255 {
+        return Err(AppError::BadRequest(
+            "Username must be 1-255 characters".into(),
+        ));
+    }
+
+    // Verify password
+    let parsed_hash = PasswordHash::new(&user.password_hash)
+        .map_err(|_| AppError::Internal("Invalid password hash".into()))?;
+    if Argon2::default()
+        .verify_password(req.password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Err(AppError::BadRequest("Invalid password".into()));
+    }
+
+    if let Some(existing) = state.db.find_user_by_username(new_username).await?
+        && existing.id != user.id
+    {
+        return Err(AppError::Conflict("Username is already taken".into()));
+    }
+
+    state.db.update_username(user.id, new_username).await?;
+    state
+        .db
+        .insert_audit_log(
+            Some(user.id),
+            "user.username_changed",
+            serde_json::json!({"old": user.username, "new": new_username}),
+            session.ip_address.as_deref(),
+        )
+        .await?;
+
+    let user = User {
+        username: new_username.to_string(),
+        ..user
+    };
+    Ok(Json(UserInfo::from(&user)))
+}
+
+/// GET /api/admin/user/jwks - Public keys for verifying session JWTs.
+///
+/// Session JWTs are signed with HS256, a shared secret that must not be
+/// published, so the set is always empty; verifiers outside the server
+/// need `JWT_SECRET` itself. The endpoint is in place for a switch to an
+/// asymmetric algorithm.
+pub async fn get_jwks() -> Json<serde_json::Value> {
+    Json(serde_json::json!({"keys": []}))
+}
+
+// ==================== Audit Log ====================
+
+/// GET /api/admin/audit-logs - List audit log entries.
+pub async fn list_audit_logs(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<AuditLog>>> {
+    let db = state.db.pinned();
+    let logs = db.get_audit_logs_paged(page.limit(), page.offset()).await?;
+    let total = db.count_audit_logs().await?;
+    Ok(Json(PagedResponse::new(logs, total, page)))
+}
+
+// ==================== Security Events ====================
+
+/// GET /api/admin/security/events - List security events, newest first.
+pub async fn list_security_events(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<SecurityEvent>>> {
+    let db = state.db.pinned();
+    let events = db
+        .get_security_events_paged(page.limit(), page.offset())
+        .await?;
+    let total = db.count_security_events().await?;
+    Ok(Json(PagedResponse::new(events, total, page)))
+}
+
+// ==================== Database Maintenance ====================
+
+/// Vacuum request.
+#[derive(Debug, Deserialize)]
+pub struct VacuumRequest {
+    /// Table to vacuum; all vacuumable tables when omitted.
+    pub table: Option<String>,
+    #[serde(default)]
+    pub analyze: bool,
+}
+
+/// Vacuum response.
+#[derive(Debug, Serialize)]
+pub struct VacuumResponse {
+    pub duration_ms: u64,
+    pub tables_vacuumed: Vec<String>,
+}
+
+/// POST /api/admin/db/vacuum - Vacuum one or all high-churn tables.
+pub async fn vacuum(
+    State(state): State<AppState>,
+    Json(req): Json<VacuumRequest>,
+) -> AppResult<Json<VacuumResponse>> {
+    let tables: Vec<String> = match req.table {
+        Some(table) => vec![table],
+        None => VACUUM_TABLES.iter().map(|t| t.to_string()).collect(),
+    };
+
+    let started = std::time::Instant::now();
+    for table in &tables {
+        state.db.vacuum_table(table, req.analyze).await?;
+    }
+
+    Ok(Json(VacuumResponse {
+        duration_ms: started.elapsed().as_millis() as u64,
+        tables_vacuumed: tables,
+    }))
+}
+
+/// Rows removed by a manual retention cleanup.
+#[derive(Debug, Serialize)]
+pub struct DeleteOldRecordsResponse {
+    pub records_deleted: u64,
+    pub ping_records_deleted: u64,
+    pub logs_deleted: u64,
+    pub duration_ms: u64,
+}
+
+/// DELETE /api/admin/records/old - Run the retention cleanup now.
+pub async fn delete_old_records(
+    State(state): State<AppState>,
+) -> AppResult<Json<DeleteOldRecordsResponse>> {
+    let started = tokio::time::Instant::now();
+    let stats = retention::cleanup_now(&state).await?;
+
+    Ok(Json(DeleteOldRecordsResponse {
+        records_deleted: stats.records_deleted,
+        ping_records_deleted: stats.ping_records_deleted,
+        logs_deleted: stats.log_lines_deleted,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }))
+}
+
+/// GET /api/admin/archive/status - Outcome of the last record archival run.
+pub async fn archive_status(
+    State(state): State<AppState>,
+) -> AppResult<Json<Option<ArchiveStatus>>> {
+    Ok(Json(archive::load_status(&state).await?))
+}
+
+/// HTTP stats query params.
+#[derive(Debug, Deserialize)]
+pub struct DebugHttpQuery {
+    #[serde(default = "default_debug_http_limit")]
+    pub limit: usize,
+}
+
+fn default_debug_http_limit() -> usize {
+    20
+}
+
+/// GET /api/admin/debug/http - Busiest routes since startup, with p95 latency.
+pub async fn debug_http(
+    State(state): State<AppState>,
+    Query(query): Query<DebugHttpQuery>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "uptime_secs": state.http_metrics.uptime_secs(),
+        "routes": state.http_metrics.top_routes(query.limit.min(200)),
+    }))
+}
+
+/// GET /api/admin/debug/agent-connections - Live agent WebSocket connections.
+pub async fn debug_agent_connections(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "connections": state.ws_agents.list(),
+    }))
+}
+
+/// GET /api/admin/debug/buildinfo - Build metadata, enabled features and a
+/// summary of the configuration with secrets masked.
+pub async fn get_build_info(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "version": build_info::VERSION,
+        "commit": build_info::GIT_COMMIT,
+        "build_timestamp": build_info::build_timestamp(),
+        "rustc_version": build_info::RUSTC_VERSION,
+        "features": build_info::features(),
+        "config": config_summary(&state.config),
+    }))
+}
+
+/// Configuration for the build info endpoint. Secrets are replaced with the
+/// sentinel when set, and the database password is removed from its URL.
+fn config_summary(config: &Config) -> serde_json::Value {
+    let database_url = |url: &str| match reqwest::Url::parse(url) {
+        Ok(mut url) => {
+            if url.password().is_some() {
+                let _ = url.set_password(Some(secrets::SENTINEL));
+            }
+            url.to_string()
+        }
+        Err(_) => secrets::mask_str(url),
+    };
+    serde_json::json!({
+        "database_url": database_url(&config.database_url),
+        "database_replica_urls": config
+            .database_replica_urls
+            .iter()
+            .map(|url| database_url(url))
+            .collect::<Vec<_>>(),
+        "db_query_timeout_secs": config.db_query_timeout_secs,
+        "listen_addr": config.listen_addr,
+        "jwt_secret": secrets::mask_str(&config.jwt_secret),
+        "jwt_expires_secs": config.jwt_expires_secs,
+        "use_jwt": config.use_jwt,
+        "admin_username": config.admin_username,
+        "admin_password": secrets::mask_str(&config.admin_password),
+        "trust_proxy": config.trust_proxy,
+        "allow_mixed_transport": config.allow_mixed_transport,
+        "max_reports_per_minute": config.max_reports_per_minute,
+        "registration_token": config.registration_token.as_deref().map(secrets::mask_str),
+        "secret_key": config.secret_key.as_deref().map(secrets::mask_str),
+        "metrics_token": config.metrics_token.as_deref().map(secrets::mask_str),
+        "compression_algorithms": config.compression_algorithms,
+        "compression_min_size": config.compression_min_size,
+        "notification_locale": config.notification_locale,
+        "widget_frame_ancestors": config.widget_frame_ancestors,
+        "cookie_secure": config.cookie_secure,
+        "cookie_same_site": config.cookie_same_site,
+        "cookie_domain": config.cookie_domain,
+        "warn_on_query_param_auth": config.warn_on_query_param_auth,
+        "ws_max_message_bytes": config.ws_max_message_bytes,
+        "ws_max_frame_bytes": config.ws_max_frame_bytes,
+        "ws_max_malformed_per_minute": config.ws_max_malformed_per_minute,
+        "max_clock_drift_secs": config.max_clock_drift_secs,
+        "startup_db_fix": config.startup_db_fix,
+        "oidc": config.oidc.as_ref().map(|oidc| serde_json::json!({
+            "provider": oidc.provider,
+            "issuer_url": oidc.issuer_url,
+            "client_id": oidc.client_id,
+            "client_secret": secrets::mask_str(&oidc.client_secret),
+            "redirect_url": oidc.redirect_url,
+            "allowed_emails": oidc.allowed_emails,
+            "allowed_orgs": oidc.allowed_orgs,
+            "default_role": oidc.default_role,
+        })),
+    })
+}
+
+/// POST /api/admin/debug/agent-connections/:client_id/disconnect - Force-close
+/// a client's agent WebSockets.
+pub async fn disconnect_agent(
+    State(state): State<AppState>,
+    Path(client_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let closed = state
+        .ws_agents
+        .disconnect(client_id, DisconnectReason::Requested);
+    if closed == 0 {
+        return Err(AppError::NotFound("No agent connection for client".into()));
+    }
+    Ok(Json(
+        serde_json::json!({"status": "ok", "disconnected": closed}),
+    ))
+}
+
+// ==================== Backups ====================
+
+async fn object_storage(state: &AppState) -> AppResult<ObjectStorage> {
+    ObjectStorage::from_state(state)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Object storage is not enabled".into()))
+}
+
+/// POST /api/admin/backup/upload-now - Upload a configuration backup now.
+pub async fn upload_backup_now(
+    State(state): State<AppState>,
+) -> AppResult<Json<serde_json::Value>> {
+    let storage = object_storage(&state).await?;
+    let key = backup::upload_backup(&state, &storage).await?;
+    Ok(Json(serde_json::json!({"status": "ok", "key": key})))
+}
+
+/// GET /api/admin/backup/remote - List the backups in object storage.
+pub async fn list_remote_backups(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<RemoteObject>>> {
+    let storage = object_storage(&state).await?;
+    let mut backups = storage
+        .list_objects(backup::BACKUP_PREFIX)
+        .await
+        .map_err(storage_error)?;
+    backups.sort_by(|a, b| b.key.cmp(&a.key));
+    Ok(Json(backups))
+}
+
 // ==================== Session Management ====================
 
-/// GET /api/admin/sessions - List user sessions.
+/// Session with its user agent parsed.
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: Session,
+    /// Browser name and version, or the raw user agent if it cannot be parsed.
+    pub browser: Option<String>,
+    pub os: Option<String>,
+    /// Device category, e.g. `pc`, `smartphone` or `crawler`.
+    pub device: Option<String>,
+    /// The session of this request.
+    pub is_current: bool,
+}
+
+impl SessionInfo {
+    fn new(session: Session, current: &Session) -> Self {
+        let parsed = session
+            .user_agent
+            .as_deref()
+            .and_then(|ua| woothee::parser::Parser::new().parse(ua))
+            .filter(|ua| ua.name != woothee::woothee::VALUE_UNKNOWN);
+        let known =
+            |value: &str| (value != woothee::woothee::VALUE_UNKNOWN).then(|| value.to_string());
+
+        let (browser, os, device) = match parsed {
+            Some(ua) => (
+                Some(match known(ua.version) {
+                    Some(version) => format!("{} {}", ua.name, version),
+                    None => ua.name.to_string(),
+                }),
+                known(ua.os).map(|os| match known(&ua.os_version) {
+                    Some(version) => format!("{} {}", os, version),
+                    None => os,
+                }),
+                known(ua.category),
+            ),
+            None => (session.user_agent.clone(), None, None),
+        };
+        Self {
+            is_current: session.id == current.id,
+            session,
+            browser,
+            os,
+            device,
+        }
+    }
+}
+
+/// GET /api/admin/sessions - List the user's active sessions, newest first.
 pub async fn list_sessions(
     State(state): State<AppState>,
     Extension(user): Extension<User>,
-) -> AppResult<Json<Vec<Session>>> {
-    let sessions = state.db.get_user_sessions(user.id).await?;
-    Ok(Json(sessions))
+    Extension(current): Extension<Session>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<PagedResponse<SessionInfo>>> {
+    let (sessions, total) = tokio::try_join!(
+        state
+            .db
+            .get_user_sessions_paged(user.id, page.limit(), page.offset()),
+        state.db.count_user_sessions(user.id),
+    )?;
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionInfo::new(session, &current))
+        .collect();
+    Ok(Json(PagedResponse::new(sessions, total, page)))
+}
+
+/// Revoke sessions request.
+#[derive(Debug, Deserialize)]
+pub struct RevokeSessionsRequest {
+    /// Revoke every session from this IP address.
+    pub ip: String,
+}
+
+/// POST /api/admin/sessions/revoke - Revoke the user's sessions from an IP
+/// address. The session of this request is kept.
+pub async fn revoke_sessions(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(current): Extension<Session>,
+    Json(req): Json<RevokeSessionsRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let revoked = state
+        .db
+        .delete_user_sessions_by_ip(user.id, req.ip.trim(), &current.token)
+        .await?;
+    if revoked > 0 {
+        security::record(
+            &state,
+            SecurityEventType::SessionRevoked,
+            Some(&user),
+            None,
+            current.ip_address.as_deref(),
+            current.user_agent.as_deref(),
+        )
+        .await;
+    }
+    Ok(Json(
+        serde_json::json!({"status": "ok", "revoked": revoked}),
+    ))
 }
 
 /// DELETE /api/admin/sessions/:id - Delete a session.
 pub async fn delete_session(
     State(state): State<AppState>,
     Extension(user): Extension<User>,
+    Extension(current): Extension<Session>,
     Path(id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
     // Verify session belongs to user
@@ -354,6 +2993,94 @@ pub async fn delete_session(
         .ok_or(AppError::NotFound("Session not found".into()))?;
 
     state.db.delete_session(&session.token).await?;
+    security::record(
+        &state,
+        SecurityEventType::SessionRevoked,
+        Some(&user),
+        None,
+        current.ip_address.as_deref(),
+        current.user_agent.as_deref(),
+    )
+    .await;
 
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
+
+// ==================== Dashboard ====================
+
+/// How long a dashboard payload is reused.
+pub const DASHBOARD_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Ping task with its most recent check.
+#[derive(Debug, Serialize)]
+pub struct PingTaskWithLastStatus {
+    #[serde(flatten)]
+    pub task: PingTask,
+    pub last_record: Option<PingRecord>,
+}
+
+/// Everything the admin dashboard shows on load.
+#[derive(Debug, Serialize)]
+pub struct DashboardPayload {
+    pub clients: Vec<Client>,
+    pub online_count: i64,
+    pub notifications: Vec<Notification>,
+    pub ping_tasks: Vec<PingTaskWithLastStatus>,
+    pub active_alerts: Vec<AlertHistory>,
+    pub sessions: Vec<SessionInfo>,
+    pub settings: serde_json::Value,
+}
+
+/// GET /api/admin/dashboard - Clients, notifications, ping tasks, open
+/// alerts, sessions and settings in one response. Cached per session for
+/// [`DASHBOARD_CACHE_TTL`].
+pub async fn get_dashboard(
+    State(state): State<AppState>,
+    Extension(user): Extension<User>,
+    Extension(current): Extension<Session>,
+) -> AppResult<Json<serde_json::Value>> {
+    if let Some(cached) = state.dashboard_cache.get(&current.id)
+        && cached.0.elapsed() < DASHBOARD_CACHE_TTL
+    {
+        return Ok(Json(cached.1.as_ref().clone()));
+    }
+
+    let (clients, notifications, tasks, latest_pings, active_alerts, sessions, settings) = tokio::try_join!(
+        state.db.get_active_clients(),
+        state.db.get_all_notifications(),
+        state.db.get_all_ping_tasks(),
+        state.db.get_latest_ping_records(),
+        state.db.get_open_alerts(),
+        state.db.get_user_sessions(user.id),
+        get_settings(State(state.clone())),
+    )?;
+
+    let mut latest_pings: HashMap<Uuid, PingRecord> =
+        latest_pings.into_iter().map(|r| (r.task_id, r)).collect();
+    let payload = DashboardPayload {
+        online_count: clients.iter().filter(|c| c.online).count() as i64,
+        clients,
+        notifications: notifications.into_iter().map(masked_notification).collect(),
+        ping_tasks: tasks
+            .into_iter()
+            .map(|task| PingTaskWithLastStatus {
+                last_record: latest_pings.remove(&task.id),
+                task,
+            })
+            .collect(),
+        active_alerts,
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionInfo::new(session, &current))
+            .collect(),
+        settings: settings.0,
+    };
+
+    let value = serde_json::to_value(payload)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize dashboard: {}", e)))?;
+    state.dashboard_cache.insert(
+        current.id,
+        (std::time::Instant::now(), Arc::new(value.clone())),
+    );
+    Ok(Json(value))
+}
@@ -0,0 +1,243 @@
+//! Registry of live agent WebSocket connections.
+//!
+//! Each connection registers itself for its lifetime. Per-connection message
+//! counters are atomics updated by the WebSocket loop, so the hot path never
+//! takes a lock; the maps are only touched on connect and disconnect.
+//! Connections are also how admin changes reach live agents: a connection
+//! told that its settings changed rereads them and pushes them to the agent,
+//! and one closed by an admin action tells the agent why in its close frame.
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Wire format of an agent connection. Agents only speak JSON today.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WsProtocol {
+    Json,
+}
+
+/// Why the server closed an agent connection, sent in the close frame so
+/// agents can tell a removed client from a transient disconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Closed from the debug endpoint; the agent may reconnect.
+    Requested,
+    /// The client was archived; reports are rejected.
+    Archived,
+    /// The client was deleted; its token no longer authenticates.
+    Deleted,
+}
+
+impl DisconnectReason {
+    /// WebSocket close code, in the range reserved for applications.
+    pub fn close_code(self) -> u16 {
+        match self {
+            DisconnectReason::Requested => 4000,
+            DisconnectReason::Archived => 4001,
+            DisconnectReason::Deleted => 4002,
+        }
+    }
+
+    /// Machine-readable close reason.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisconnectReason::Requested => "disconnected",
+            DisconnectReason::Archived => "client_archived",
+            DisconnectReason::Deleted => "client_deleted",
+        }
+    }
+}
+
+/// One live agent WebSocket.
+#[derive(Debug)]
+pub struct AgentConnection {
+    pub id: u64,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub connected_at: DateTime<Utc>,
+    pub remote_ip: Option<IpAddr>,
+    pub protocol: WsProtocol,
+    messages_received: AtomicU64,
+    /// Unix milliseconds of the latest message, 0 before the first one.
+    last_message_at_ms: AtomicI64,
+    /// Cancelled to force the connection closed.
+    close: CancellationToken,
+    /// Why the connection was closed, set before `close` is cancelled.
+    close_reason: OnceLock<DisconnectReason>,
+    /// Advised seconds between reports, 0 when the agent chooses.
+    report_interval_secs: AtomicU32,
+    /// Signalled when the client's settings may have changed.
+    settings_changed: Notify,
+}
+
+impl AgentConnection {
+    /// Count a received message.
+    pub fn record_message(&self) {
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+        self.last_message_at_ms
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// Resolves with the reason when the connection is asked to close.
+    pub async fn closed(&self) -> DisconnectReason {
+        self.close.cancelled().await;
+        self.close_reason
+            .get()
+            .copied()
+            .unwrap_or(DisconnectReason::Requested)
+    }
+
+    /// Resolves when the client's settings may have changed.
+    pub async fn settings_changed(&self) {
+        self.settings_changed.notified().await
+    }
+
+    /// Advised seconds between reports, if any.
+    pub fn report_interval(&self) -> Option<u32> {
+        Some(self.report_interval_secs.load(Ordering::Relaxed)).filter(|&secs| secs > 0)
+    }
+
+    /// Set the advised seconds between reports. Returns whether it changed.
+    pub fn set_report_interval(&self, secs: Option<u32>) -> bool {
+        let secs = secs.unwrap_or(0);
+        self.report_interval_secs.swap(secs, Ordering::Relaxed) != secs
+    }
+}
+
+/// Snapshot of a connection for the debug endpoint.
+#[derive(Debug, Serialize)]
+pub struct AgentConnectionInfo {
+    pub connection_id: u64,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub connected_at: DateTime<Utc>,
+    pub remote_ip: Option<IpAddr>,
+    pub protocol: WsProtocol,
+    pub messages_received: u64,
+    /// Seconds since the latest message, or `None` before the first one.
+    pub last_message_age_secs: Option<f64>,
+}
+
+/// Live agent WebSocket connections.
+#[derive(Debug, Default)]
+pub struct AgentConnections {
+    next_id: AtomicU64,
+    connections: DashMap<u64, Arc<AgentConnection>>,
+    /// Open connections per client.
+    per_client: DashMap<Uuid, usize>,
+}
+
+impl AgentConnections {
+    /// Register a new connection.
+    pub fn register(
+        &self,
+        client_id: Uuid,
+        client_name: String,
+        remote_ip: Option<IpAddr>,
+    ) -> Arc<AgentConnection> {
+        let connection = Arc::new(AgentConnection {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            client_id,
+            client_name,
+            connected_at: Utc::now(),
+            remote_ip,
+            protocol: WsProtocol::Json,
+            messages_received: AtomicU64::new(0),
+            last_message_at_ms: AtomicI64::new(0),
+            close: CancellationToken::new(),
+            close_reason: OnceLock::new(),
+            report_interval_secs: AtomicU32::new(0),
+            settings_changed: Notify::new(),
+        });
+        self.connections
+            .insert(connection.id, Arc::clone(&connection));
+        *self.per_client.entry(client_id).or_insert(0) += 1;
+        connection
+    }
+
+    /// Remove a connection. Returns whether it was the client's last one.
+    pub fn unregister(&self, connection: &AgentConnection) -> bool {
+        self.connections.remove(&connection.id);
+        let last_connection = match self.per_client.get_mut(&connection.client_id) {
+            Some(mut count) => {
+                *count -= 1;
+                *count == 0
+            }
+            None => true,
+        };
+        if last_connection {
+            self.per_client
+                .remove_if(&connection.client_id, |_, count| *count == 0);
+        }
+        last_connection
+    }
+
+    /// Whether the client has an open WebSocket.
+    pub fn is_connected(&self, client_id: Uuid) -> bool {
+        self.per_client.contains_key(&client_id)
+    }
+
+    /// Clients with an open WebSocket.
+    pub fn connected_ids(&self) -> Vec<Uuid> {
+        self.per_client.iter().map(|entry| *entry.key()).collect()
+    }
+
+    /// Snapshot of every connection, oldest first.
+    pub fn list(&self) -> Vec<AgentConnectionInfo> {
+        let now_ms = Utc::now().timestamp_millis();
+        let mut connections: Vec<AgentConnectionInfo> = self
+            .connections
+            .iter()
+            .map(|entry| {
+                let c = entry.value();
+                let last_ms = c.last_message_at_ms.load(Ordering::Relaxed);
+                AgentConnectionInfo {
+                    connection_id: c.id,
+                    client_id: c.client_id,
+                    client_name: c.client_name.clone(),
+                    connected_at: c.connected_at,
+                    remote_ip: c.remote_ip,
+                    protocol: c.protocol,
+                    messages_received: c.messages_received.load(Ordering::Relaxed),
+                    last_message_age_secs: (last_ms > 0)
+                        .then(|| (now_ms - last_ms).max(0) as f64 / 1000.0),
+                }
+            })
+            .collect();
+        connections.sort_by_key(|c| c.connection_id);
+        connections
+    }
+
+    /// Tell the connections of a client, or of every client, that their
+    /// settings may have changed.
+    pub fn settings_changed(&self, client_id: Option<Uuid>) {
+        for entry in self.connections.iter() {
+            if client_id.is_none_or(|id| id == entry.client_id) {
+                entry.settings_changed.notify_one();
+            }
+        }
+    }
+
+    /// Ask every connection of a client to close, giving `reason` to the
+    /// agent. Returns how many there were.
+    pub fn disconnect(&self, client_id: Uuid, reason: DisconnectReason) -> usize {
+        let mut closed = 0;
+        for entry in self.connections.iter() {
+            if entry.client_id == client_id {
+                let _ = entry.close_reason.set(reason);
+                entry.close.cancel();
+                closed += 1;
+            }
+        }
+        closed
+    }
+}
@@ -13,8 +13,12 @@ use axum::{
 use serde::{Deserialize, Serialize};
 
 use crate::api::AppState;
+use crate::config::Config;
 use crate::db::User;
 use crate::error::{AppError, AppResult};
+use crate::jwt;
+use crate::middleware::RealIp;
+use crate::security::{self, SecurityEventType};
 
 /// Login request body.
 #[derive(Debug, Deserialize)]
@@ -35,6 +39,7 @@ pub struct LoginResponse {
 pub struct UserInfo {
     pub id: String,
     pub username: String,
+    pub role: String,
 }
 
 impl From<&User> for UserInfo {
@@ -42,6 +47,7 @@ impl From<&User> for UserInfo {
         Self {
             id: user.id.to_string(),
             username: user.username.clone(),
+            role: user.role.clone(),
         }
     }
 }
@@ -49,51 +55,143 @@ impl From<&User> for UserInfo {
 /// POST /api/login - User login.
 pub async fn login(
     State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    headers: axum::http::HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> AppResult<impl IntoResponse> {
+    if !password_login_enabled(&state).await? {
+        return Err(AppError::Forbidden);
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    let ip_address = real_ip.map(|Extension(RealIp(ip))| ip.to_string());
+
     // Find user
     let user = state
         .db
         .find_user_by_username(&req.username)
         .await?
-        .ok_or(AppError::BadRequest("Invalid username or password".into()))?;
+        .filter(|user| !user.external);
 
     // Verify password using argon2
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|_| AppError::Internal("Invalid password hash".into()))?;
-
-    let valid = Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed_hash)
-        .is_ok();
-
-    if !valid {
-        return Err(AppError::BadRequest("Invalid username or password".into()));
-    }
+    let valid = match &user {
+        Some(user) => {
+            let parsed_hash = PasswordHash::new(&user.password_hash)
+                .map_err(|_| AppError::Internal("Invalid password hash".into()))?;
+            Argon2::default()
+                .verify_password(req.password.as_bytes(), &parsed_hash)
+                .is_ok()
+        }
+        None => false,
+    };
 
-    // Generate session token
-    let token = format!(
-        "vmses_{}",
-        uuid::Uuid::new_v4().to_string().replace("-", "")
-    );
+    let user = match user {
+        Some(user) if valid => user,
+        user => {
+            security::record(
+                &state,
+                SecurityEventType::LoginFailed,
+                user.as_ref(),
+                Some(&req.username),
+                ip_address.as_deref(),
+                user_agent,
+            )
+            .await;
+            return Err(AppError::BadRequest("Invalid username or password".into()));
+        }
+    };
 
-    // Create session
-    state
-        .db
-        .create_session(user.id, &token, None, None, state.config.jwt_expires_secs)
-        .await?;
+    let (token, cookie) = start_session(&state, &user, user_agent, ip_address.as_deref()).await?;
 
     let response = LoginResponse {
-        token: token.clone(),
+        token,
         user: UserInfo::from(&user),
     };
 
-    // Set cookie
-    let cookie = format!(
-        "token={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
-        token, state.config.jwt_expires_secs
+    Ok(([(header::SET_COOKIE, cookie)], Json(response)))
+}
+
+/// Create a session for a user, returning the token and its cookie: a
+/// stored session, or a JWT with `USE_JWT`. The login is recorded as a
+/// security event.
+pub async fn start_session(
+    state: &AppState,
+    user: &User,
+    user_agent: Option<&str>,
+    ip_address: Option<&str>,
+) -> AppResult<(String, String)> {
+    let token = if state.config.use_jwt {
+        jwt::issue(&state.config, user.id)?
+    } else {
+        // Generate session token
+        let token = format!(
+            "vmses_{}",
+            uuid::Uuid::new_v4().to_string().replace("-", "")
+        );
+
+        // Create session
+        state
+            .db
+            .create_session(
+                user.id,
+                &token,
+                user_agent,
+                ip_address,
+                state.config.jwt_expires_secs,
+            )
+            .await?;
+        token
+    };
+
+    security::record(
+        state,
+        SecurityEventType::LoginSuccess,
+        Some(user),
+        None,
+        ip_address,
+        user_agent,
+    )
+    .await;
+
+    let cookie = session_cookie(&state.config, &token, state.config.jwt_expires_secs);
+
+    Ok((token, cookie))
+}
+
+/// `Set-Cookie` value for the session cookie with the configured
+/// attributes. An empty `token` with `max_age` 0 clears it.
+fn session_cookie(config: &Config, token: &str, max_age: i64) -> String {
+    let mut cookie = format!(
+        "token={}; Path=/; HttpOnly; SameSite={}; Max-Age={}",
+        token, config.cookie_same_site, max_age
     );
+    if config.cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    if let Some(domain) = &config.cookie_domain {
+        cookie.push_str(&format!("; Domain={}", domain));
+    }
+    cookie
+}
 
-    Ok(([(header::SET_COOKIE, cookie)], Json(response)))
+/// Whether password login is enabled (the `password_login_enabled` setting).
+pub async fn password_login_enabled(state: &AppState) -> AppResult<bool> {
+    Ok(state
+        .db
+        .get_setting("password_login_enabled")
+        .await?
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true))
+}
+
+/// GET /api/auth/methods - Get the available login methods.
+pub async fn login_methods(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    Ok(Json(serde_json::json!({
+        "password": password_login_enabled(&state).await?,
+        "oidc": state.config.oidc.as_ref().map(|o| o.provider.clone())
+    })))
 }
 
 /// GET /api/logout - User logout.
@@ -113,7 +211,7 @@ pub async fn logout(
         }
 
     // Clear cookie
-    let cookie = "token=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0";
+    let cookie = session_cookie(&state.config, "", 0);
 
     Ok((
         [(header::SET_COOKIE, cookie)],
@@ -3,27 +3,64 @@
 //! These endpoints are used by monitoring agents to register and report data.
 
 use axum::{
-    Json,
+    Extension, Json,
     extract::{
-        State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
     },
     http::{HeaderMap, header},
     response::IntoResponse,
 };
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info, warn};
+use subtle::ConstantTimeEq;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+use uuid::Uuid;
 
+use crate::alerts::status;
+use crate::anomaly;
 use crate::api::AppState;
-use crate::db::RecordInput;
+use crate::api::agent_connections::AgentConnection;
+use crate::db::{Client, RecordInput, validate_record};
 use crate::error::{AppError, AppResult};
+use crate::middleware::RealIp;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing::{self, EventType};
+
+/// Transport a report arrived over, stored in `clients.last_report_transport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportTransport {
+    Http,
+    Ws,
+    /// Reports of the built-in client, collected in-process.
+    Builtin,
+}
+
+impl ReportTransport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportTransport::Http => "http",
+            ReportTransport::Ws => "ws",
+            ReportTransport::Builtin => "builtin",
+        }
+    }
+}
 
 /// Register request.
 #[derive(Debug, Deserialize)]
 pub struct RegisterRequest {
     #[serde(default)]
     pub name: String,
+    /// Must match `REGISTRATION_TOKEN` when it is configured.
+    pub registration_token: Option<String>,
 }
 
 /// Register response.
@@ -38,6 +75,13 @@ pub async fn register(
     State(state): State<AppState>,
     Json(req): Json<RegisterRequest>,
 ) -> AppResult<Json<RegisterResponse>> {
+    if let Some(expected) = &state.config.registration_token {
+        let given = req.registration_token.as_deref().unwrap_or_default();
+        if !bool::from(given.as_bytes().ct_eq(expected.as_bytes())) {
+            return Err(AppError::Unauthorized);
+        }
+    }
+
     let name = if req.name.is_empty() {
         "New Server".to_string()
     } else {
@@ -46,7 +90,7 @@ pub async fn register(
 
     let client = state.db.create_client(&name).await?;
 
-    info!("New agent registered: {} ({})", client.name, client.id);
+    info!(client_id = %client.id, client_name = %client.name, "New agent registered");
 
     Ok(Json(RegisterResponse {
         uuid: client.id.to_string(),
@@ -81,6 +125,10 @@ pub struct BasicInfoRequest {
     pub version: String,
     pub ipv4: Option<String>,
     pub ipv6: Option<String>,
+    /// Whether the system clock is NTP synchronized.
+    pub ntp_synced: Option<bool>,
+    /// Clock offset from the NTP source in milliseconds.
+    pub clock_offset_ms: Option<f64>,
 }
 
 /// POST /api/agent/info - Upload basic system information.
@@ -89,12 +137,7 @@ pub async fn upload_basic_info(
     headers: HeaderMap,
     Json(req): Json<BasicInfoRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let token = extract_agent_token(&headers)?;
-    let client = state
-        .db
-        .find_client_by_token(&token)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
+    let client = authenticate_agent(&state, extract_agent_token(&headers)?).await?;
 
     state
         .db
@@ -111,6 +154,8 @@ pub async fn upload_basic_info(
             req.swap_total,
             req.disk_total,
             &req.version,
+            req.ntp_synced,
+            req.clock_offset_ms,
         )
         .await?;
 
@@ -121,115 +166,864 @@ pub async fn upload_basic_info(
             .await?;
     }
 
-    Ok(Json(serde_json::json!({"status": "ok"})))
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "report_interval_seconds": report_interval(&state, &client)
+    })))
+}
+
+/// Seconds between reports advised to a client: its own interval, else the
+/// `report_interval_seconds` setting.
+fn report_interval(state: &AppState, client: &Client) -> Option<u32> {
+    client
+        .report_interval_seconds
+        .map(|secs| secs as u32)
+        .or(state.runtime().report_interval_seconds)
 }
 
 /// POST /api/agent/report - Upload monitoring data.
 pub async fn upload_report(
     State(state): State<AppState>,
     headers: HeaderMap,
+    real_ip: Option<Extension<RealIp>>,
     Json(req): Json<RecordInput>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let token = extract_agent_token(&headers)?;
-    let client = state
-        .db
-        .find_client_by_token(&token)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
+    let client = authenticate_agent(&state, extract_agent_token(&headers)?).await?;
+    let ip = real_ip.map(|Extension(RealIp(ip))| ip.to_canonical());
+
+    // An agent with an open WebSocket must not also report over HTTP
+    if !state.config.allow_mixed_transport && state.ws_agents.is_connected(client.id) {
+        return Err(AppError::Conflict(
+            "Client is reporting over WebSocket; stop sending HTTP reports".into(),
+        ));
+    }
+
+    if check_report_rate(&state, client.id, report_interval(&state, &client)).is_err() {
+        return Err(AppError::TooManyRequests);
+    }
+
+    validate_record(&req)?;
+    check_clock_drift(&state, &client, &req, Utc::now())?;
+    check_report_ip(&state, &client, ip);
 
     // Update online status
-    state.db.update_client_online(client.id, true).await?;
+    let came_online = state
+        .db
+        .mark_client_reported(
+            client.id,
+            ReportTransport::Http.as_str(),
+            ip.map(|ip| ip.to_string()).as_deref(),
+        )
+        .await?;
+    if came_online {
+        status::notify(&state, client.id, true);
+    }
 
     // Insert record
     state.db.insert_record(client.id, &req).await?;
+    anomaly::observe(&state, &client, &req).await;
+    store_log_lines(&state, client.id, &req).await?;
 
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
 
+/// Query params of the agent WebSocket.
+#[derive(Debug, Deserialize)]
+pub struct AgentWsQuery {
+    /// Agent token for clients that cannot set headers; only used when the
+    /// headers carry no credentials.
+    pub token: Option<String>,
+}
+
 /// GET /api/agent/ws - WebSocket connection for real-time reporting.
+///
+/// Credentials are read from the headers, else from `?token=`.
 pub async fn ws_report(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(query): Query<AgentWsQuery>,
+    real_ip: Option<Extension<RealIp>>,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, AppError> {
-    let token = extract_agent_token(&headers)?;
-    let client = state
-        .db
-        .find_client_by_token(&token)
-        .await?
-        .ok_or(AppError::Unauthorized)?;
+    let client = match extract_agent_token(&headers) {
+        Ok(credentials) => authenticate_agent(&state, credentials).await?,
+        Err(_) => {
+            let token = query
+                .token
+                .filter(|t| !t.is_empty())
+                .ok_or(AppError::Unauthorized)?;
+            let credentials = AgentCredentials {
+                token,
+                client_id: None,
+            };
+            let client = authenticate_agent(&state, credentials).await?;
+            if state.config.warn_on_query_param_auth {
+                warn!(
+                    client_id = %client.id,
+                    "Agent WebSocket authenticated with a query parameter token, which can leak \
+                     into proxy logs and Referer headers; send Authorization: Bearer instead"
+                );
+            }
+            client
+        }
+    };
+    let ip = real_ip.map(|Extension(RealIp(ip))| ip.to_canonical());
+    check_report_ip(&state, &client, ip);
 
-    let client_id = client.id;
-    let client_name = client.name.clone();
+    Ok(ws
+        .max_message_size(state.config.ws_max_message_bytes)
+        .max_frame_size(state.config.ws_max_frame_bytes)
+        .on_upgrade(move |socket| handle_agent_ws(state, client, ip, socket)))
+}
+
+/// Capacity of the per-connection report and outbound message queues.
+const WS_QUEUE_CAPACITY: usize = 32;
+
+/// Close frame with a status code and reason.
+fn close_message(code: u16, reason: &'static str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }))
+}
 
-    Ok(ws.on_upgrade(move |socket| handle_agent_ws(state, client_id, client_name, socket)))
+/// Malformed messages seen in the last minute.
+struct MalformedMessages {
+    limit: usize,
+    times: VecDeque<Instant>,
+}
+
+impl MalformedMessages {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            times: VecDeque::new(),
+        }
+    }
+
+    /// Count a malformed message, returning whether the limit is reached.
+    fn record(&mut self) -> bool {
+        let now = Instant::now();
+        while self
+            .times
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60))
+        {
+            self.times.pop_front();
+        }
+        self.times.push_back(now);
+        self.times.len() >= self.limit
+    }
 }
 
 /// Handle WebSocket connection from agent.
-async fn handle_agent_ws(
-    state: AppState,
-    client_id: uuid::Uuid,
-    client_name: String,
-    socket: WebSocket,
-) {
+///
+/// Everything logged while the connection lives, including by its report
+/// worker, is inside an `agent_ws` span carrying the client ID and name.
+async fn handle_agent_ws(state: AppState, client: Client, ip: Option<IpAddr>, socket: WebSocket) {
+    let span = info_span!("agent_ws", client_id = %client.id, client_name = %client.name);
+    run_agent_ws(state, client, ip, socket)
+        .instrument(span)
+        .await
+}
+
+/// Serve an agent WebSocket.
+///
+/// This task owns the socket: it forwards received reports to a worker that
+/// stores them and writes the worker's replies back, so slow inserts never
+/// stall reading or Ping/Pong.
+async fn run_agent_ws(state: AppState, client: Client, ip: Option<IpAddr>, socket: WebSocket) {
+    let client_id = client.id;
+    let client_name = client.name.clone();
     let (mut sender, mut receiver) = socket.split();
 
-    info!(
-        "Agent connected via WebSocket: {} ({})",
-        client_name, client_id
-    );
+    info!(client_id = %client_id, client_name = %client_name, "Agent connected via WebSocket");
+
+    let connection = state.ws_agents.register(client_id, client_name.clone(), ip);
+
+    let interval = report_interval(&state, &client);
+    connection.set_report_interval(interval);
+    if let Some(seconds) = interval {
+        let message = ServerMessage::ReportInterval {
+            seconds: Some(seconds),
+        };
+        let _ = sender.send(json_message(&message)).await;
+    }
 
     // Mark as online
-    if let Err(e) = state.db.update_client_online(client_id, true).await {
-        error!("Failed to update client online status: {}", e);
-    }
-
-    // Handle incoming messages
-    while let Some(msg) = receiver.next().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                // Parse and store record
-                match serde_json::from_str::<RecordInput>(&text) {
-                    Ok(record) => {
-                        if let Err(e) = state.db.insert_record(client_id, &record).await {
-                            error!("Failed to insert record: {}", e);
+    match state.db.update_client_online(client_id, true).await {
+        Ok(true) => status::notify(&state, client.id, true),
+        Ok(false) => {}
+        Err(e) => error!(
+            client_id = %client_id,
+            client_name = %client_name,
+            error = %e,
+            "Failed to update client online status"
+        ),
+    }
+
+    let mut malformed = MalformedMessages::new(state.config.ws_max_malformed_per_minute);
+    let (report_tx, report_rx) = mpsc::channel(WS_QUEUE_CAPACITY);
+    let (outbound_tx, mut outbound_rx) = mpsc::channel(WS_QUEUE_CAPACITY);
+    let worker = tokio::spawn(
+        store_ws_reports(
+            state.clone(),
+            client,
+            Arc::clone(&connection),
+            ip,
+            report_rx,
+            outbound_tx,
+        )
+        .in_current_span(),
+    );
+
+    loop {
+        tokio::select! {
+            reason = connection.closed() => {
+                info!(
+                    client_id = %client_id,
+                    client_name = %client_name,
+                    reason = reason.as_str(),
+                    "Closing WebSocket on request"
+                );
+                let close = close_message(reason.close_code(), reason.as_str());
+                let _ = sender.send(close).await;
+                break;
+            }
+            _ = connection.settings_changed() => {
+                if let Some(message) = refresh_report_interval(&state, &connection).await
+                    && sender.send(json_message(&message)).await.is_err()
+                {
+                    break;
+                }
+            }
+            msg = receiver.next() => {
+                if let Some(Ok(_)) = msg {
+                    connection.record_message();
+                }
+                match msg {
+                    Some(Ok(Message::Text(text))) => match parse_report(&text) {
+                        // Never wait on the worker: it may be waiting on
+                        // this task to drain its replies
+                        Ok((seq, record)) => match report_tx.try_send((seq, record, Utc::now())) {
+                            Ok(()) => {}
+                            Err(mpsc::error::TrySendError::Full((seq, _, _))) => {
+                                warn!(
+                                    client_id = %client_id,
+                                    client_name = %client_name,
+                                    "Report queue full, dropping report"
+                                );
+                                if let Some(seq) = seq {
+                                    let nack = ReportReply::Nack {
+                                        nack: seq,
+                                        reason: "Server busy".into(),
+                                    };
+                                    if sender.send(json_message(&nack)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => break,
+                        },
+                        Err((seq, e)) => {
+                            warn!(
+                                client_id = %client_id,
+                                client_name = %client_name,
+                                error = %e,
+                                "Invalid record data"
+                            );
+                            if malformed.record() {
+                                warn!(
+                                    client_id = %client_id,
+                                    client_name = %client_name,
+                                    "Closing WebSocket after too many malformed messages"
+                                );
+                                let close = close_message(
+                                    close_code::POLICY,
+                                    "too many malformed messages",
+                                );
+                                let _ = sender.send(close).await;
+                                break;
+                            }
+                            if let Some(seq) = seq {
+                                let nack = ReportReply::Nack {
+                                    nack: seq,
+                                    reason: e.to_string(),
+                                };
+                                if sender.send(json_message(&nack)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    },
+                    Some(Ok(Message::Binary(_))) => {
+                        warn!(
+                            client_id = %client_id,
+                            client_name = %client_name,
+                            "Closing WebSocket after a binary message"
+                        );
+                        let close = close_message(close_code::UNSUPPORTED, "binary messages are not supported");
+                        let _ = sender.send(close).await;
+                        break;
+                    }
+                    Some(Ok(Message::Ping(data))) => {
+                        if sender.send(Message::Pong(data)).await.is_err() {
+                            break;
                         }
-                        // Update last seen
-                        let _ = state.db.update_client_online(client_id, true).await;
                     }
-                    Err(e) => {
-                        warn!("Invalid record data from {}: {}", client_name, e);
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        error!(
+                            client_id = %client_id,
+                            client_name = %client_name,
+                            error = %e,
+                            "WebSocket error"
+                        );
+                        // The oversized message is not read, so the
+                        // connection can still be closed cleanly
+                        if matches!(
+                            e.into_inner().downcast_ref::<tungstenite::Error>(),
+                            Some(tungstenite::Error::Capacity(_))
+                        ) {
+                            let close = close_message(close_code::SIZE, "message too big");
+                            let _ = sender.send(close).await;
+                        }
+                        break;
                     }
+                    Some(Ok(_)) => {}
                 }
             }
-            Ok(Message::Ping(data)) => {
-                if sender.send(Message::Pong(data)).await.is_err() {
+            Some(message) = outbound_rx.recv() => {
+                if sender.send(message).await.is_err() {
                     break;
                 }
             }
-            Ok(Message::Close(_)) => break,
-            Err(e) => {
-                error!("WebSocket error from {}: {}", client_name, e);
-                break;
-            }
-            _ => {}
         }
     }
 
-    info!("Agent disconnected: {} ({})", client_name, client_id);
+    // Let the worker store what was already received; its replies are dropped
+    drop(report_tx);
+    drop(outbound_rx);
+    let _ = worker.await;
+
+    info!(client_id = %client_id, client_name = %client_name, "Agent disconnected");
+
+    // Another connection from the same agent keeps it online
+    if !state.ws_agents.unregister(&connection) {
+        return;
+    }
 
     // Mark as offline
-    if let Err(e) = state.db.update_client_online(client_id, false).await {
-        error!("Failed to update client offline status: {}", e);
+    match state.db.update_client_online(client_id, false).await {
+        Ok(true) => status::notify(&state, client_id, false),
+        Ok(false) => {}
+        Err(e) => error!(
+            client_id = %client_id,
+            client_name = %client_name,
+            error = %e,
+            "Failed to update client offline status"
+        ),
+    }
+}
+
+/// Reread the client's report interval, returning the message announcing it
+/// when it changed.
+async fn refresh_report_interval(
+    state: &AppState,
+    connection: &AgentConnection,
+) -> Option<ServerMessage> {
+    let client = match state.db.find_client_by_id(connection.client_id).await {
+        Ok(client) => client?,
+        Err(e) => {
+            error!(
+                client_id = %connection.client_id,
+                client_name = %connection.client_name,
+                error = %e,
+                "Failed to reload client settings"
+            );
+            return None;
+        }
+    };
+    let seconds = report_interval(state, &client);
+    if !connection.set_report_interval(seconds) {
+        return None;
+    }
+    info!(
+        client_id = %connection.client_id,
+        client_name = %connection.client_name,
+        report_interval_seconds = ?seconds,
+        "Pushing report interval to agent"
+    );
+    Some(ServerMessage::ReportInterval { seconds })
+}
+
+/// Report with a sequence number that the server acknowledges, so the agent
+/// can resend reports that were not stored.
+#[derive(Debug, Deserialize)]
+struct ReportEnvelope {
+    seq: u64,
+    record: RecordInput,
+}
+
+/// Parse a WebSocket text frame: a bare [`RecordInput`] or a
+/// [`ReportEnvelope`]. Errors carry the sequence number when one was sent.
+fn parse_report(
+    text: &str,
+) -> Result<(Option<u64>, RecordInput), (Option<u64>, serde_json::Error)> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| (None, e))?;
+    match value.get("seq").and_then(|seq| seq.as_u64()) {
+        Some(seq) => serde_json::from_value::<ReportEnvelope>(value)
+            .map(|envelope| (Some(envelope.seq), envelope.record))
+            .map_err(|e| (Some(seq), e)),
+        None => serde_json::from_value(value)
+            .map(|record| (None, record))
+            .map_err(|e| (None, e)),
     }
 }
 
-/// Extract agent token from headers.
-fn extract_agent_token(headers: &HeaderMap) -> AppResult<String> {
-    if let Some(auth) = headers.get(header::AUTHORIZATION)
-        && let Ok(auth_str) = auth.to_str()
-            && let Some(token) = auth_str.strip_prefix("Bearer ") {
-                return Ok(token.to_string());
+/// Store reports received on an agent WebSocket, queueing a reply for each
+/// enveloped report.
+async fn store_ws_reports(
+    state: AppState,
+    client: Client,
+    connection: Arc<AgentConnection>,
+    ip: Option<IpAddr>,
+    mut reports: mpsc::Receiver<(Option<u64>, RecordInput, DateTime<Utc>)>,
+    outbound: mpsc::Sender<Message>,
+) {
+    while let Some((seq, record, received_at)) = reports.recv().await {
+        let result = store_ws_report(
+            &state,
+            &client,
+            connection.report_interval(),
+            ip,
+            &record,
+            received_at,
+            seq.is_some(),
+        )
+        .await;
+        if let Err(WsReject::RateLimited(wait_ms)) = result {
+            let _ = outbound
+                .send(json_message(&ServerMessage::RateLimit { wait_ms }))
+                .await;
+        }
+        let Some(seq) = seq else {
+            continue;
+        };
+        let reply = match result {
+            Ok(()) => ReportReply::Ack { ack: seq },
+            Err(reject) => ReportReply::Nack {
+                nack: seq,
+                reason: reject.to_string(),
+            },
+        };
+        let _ = outbound.send(json_message(&reply)).await;
+    }
+}
+
+/// Why a WebSocket report was not stored.
+#[derive(Debug)]
+enum WsReject {
+    /// Over the rate limit; retry after this many milliseconds.
+    RateLimited(u64),
+    Invalid(AppError),
+    /// The database is down and the agent can resend the report.
+    Unavailable,
+    Storage,
+}
+
+impl std::fmt::Display for WsReject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WsReject::RateLimited(_) => write!(f, "Rate limit exceeded"),
+            WsReject::Invalid(e) => write!(f, "{}", e),
+            WsReject::Unavailable => write!(f, "Database unavailable"),
+            WsReject::Storage => write!(f, "Failed to store record"),
+        }
+    }
+}
+
+/// Validate and store one WebSocket report, received at `received_at`.
+/// `resendable` reports are acknowledged, so they are rejected rather than
+/// buffered while the database is down: an ack for a buffered report would
+/// lose it if the buffer overflows or the server restarts.
+async fn store_ws_report(
+    state: &AppState,
+    client: &Client,
+    report_interval: Option<u32>,
+    ip: Option<IpAddr>,
+    record: &RecordInput,
+    received_at: DateTime<Utc>,
+    resendable: bool,
+) -> Result<(), WsReject> {
+    check_report_rate(state, client.id, report_interval).map_err(WsReject::RateLimited)?;
+    if let Err(e) =
+        validate_record(record).and_then(|_| check_clock_drift(state, client, record, received_at))
+    {
+        warn!(client_id = %client.id, client_name = %client.name, error = %e, "Rejected record");
+        return Err(WsReject::Invalid(e));
+    }
+    if let Err(e) = state.db.insert_record(client.id, record).await {
+        // Buffer instead of failing while the database is down
+        if matches!(e, AppError::ServiceUnavailable(_)) {
+            if resendable {
+                return Err(WsReject::Unavailable);
             }
+            state
+                .report_buffer
+                .push(client.id, record.clone(), received_at);
+            return Ok(());
+        }
+        error!(
+            client_id = %client.id,
+            client_name = %client.name,
+            error = %e,
+            "Failed to insert record"
+        );
+        return Err(WsReject::Storage);
+    }
+    anomaly::observe(state, client, record).await;
+    if let Err(e) = store_log_lines(state, client.id, record).await {
+        error!(
+            client_id = %client.id,
+            client_name = %client.name,
+            error = %e,
+            "Failed to insert log lines"
+        );
+    }
+    // Update last seen
+    let came_online = state
+        .db
+        .mark_client_reported(
+            client.id,
+            ReportTransport::Ws.as_str(),
+            ip.map(|ip| ip.to_string()).as_deref(),
+        )
+        .await;
+    if matches!(came_online, Ok(true)) {
+        status::notify(state, client.id, true);
+    }
+    Ok(())
+}
+
+/// Messages the server sends to agents over the WebSocket.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The report was dropped; wait before sending the next one.
+    RateLimit { wait_ms: u64 },
+    /// Report every this many seconds; `null` leaves the interval to the
+    /// agent. Sent on connect and whenever it changes.
+    ReportInterval { seconds: Option<u32> },
+}
+
+/// Reply to a [`ReportEnvelope`].
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ReportReply {
+    /// The report was stored.
+    Ack { ack: u64 },
+    /// The report was dropped and may be resent.
+    Nack { nack: u64, reason: String },
+}
+
+/// Serialize a server message as a WebSocket text frame.
+fn json_message(message: &impl Serialize) -> Message {
+    Message::Text(serde_json::to_string(message).unwrap_or_default().into())
+}
+
+/// Reports per minute allowed at an advised interval: twice the advised
+/// rate, so jitter and reconnects are not dropped, and at least two.
+fn interval_report_limit(interval_secs: u32) -> u32 {
+    (120 / interval_secs.max(1)).max(2)
+}
+
+/// Count a report against the client's per-minute limit: the
+/// `max_reports_per_minute` setting, lowered by an advised report interval.
+///
+/// Returns the milliseconds until the current window ends when the limit
+/// is exceeded.
+fn check_report_rate(
+    state: &AppState,
+    client_id: Uuid,
+    report_interval: Option<u32>,
+) -> Result<(), u64> {
+    let mut max = state.runtime().max_reports_per_minute;
+    if let Some(interval) = report_interval {
+        max = max.min(interval_report_limit(interval));
+    }
+    let window = Duration::from_secs(60);
+    let mut entry = state
+        .report_rate_limits
+        .entry(client_id)
+        .or_insert((Instant::now(), 0));
+    let (window_start, count) = entry.value_mut();
+    if window_start.elapsed() >= window {
+        *window_start = Instant::now();
+        *count = 0;
+    }
+    *count += 1;
+    if *count <= max {
+        return Ok(());
+    }
+
+    if *count == max + 1 {
+        warn!(client_id = %client_id, "Client exceeded the report rate limit");
+    }
+    state.http_metrics.record_rate_limited(client_id);
+    Err(window.saturating_sub(window_start.elapsed()).as_millis() as u64)
+}
+
+/// Log lines accepted per report; older lines beyond this are dropped.
+const MAX_LOG_LINES_PER_REPORT: usize = 50;
+
+/// Store the log lines sent with a report, keeping the latest ones.
+async fn store_log_lines(state: &AppState, client_id: Uuid, record: &RecordInput) -> AppResult<()> {
+    let Some(lines) = &record.log_lines else {
+        return Ok(());
+    };
+    let start = lines.len().saturating_sub(MAX_LOG_LINES_PER_REPORT);
+    state.db.insert_log_lines(client_id, &lines[start..]).await
+}
+
+/// Record timestamp drift in milliseconds above which a warning is logged.
+const CLOCK_DRIFT_WARN_MS: i64 = 1000;
+
+/// Notify when a client with `alert_on_ip_change` starts reporting from an
+/// address other than its recorded ones.
+fn check_report_ip(state: &AppState, client: &Client, ip: Option<IpAddr>) {
+    let Some(ip) = ip else {
+        return;
+    };
+    let unchanged = client
+        .last_report_ip
+        .as_deref()
+        .and_then(|last| last.parse::<IpAddr>().ok())
+        .is_some_and(|last| last.to_canonical() == ip);
+    let in_maintenance = client
+        .maintenance_until
+        .is_some_and(|until| until > Utc::now());
+    if !client.alert_on_ip_change
+        || unchanged
+        || in_maintenance
+        || !is_unexpected_report_ip(ip, client.ipv4.as_deref(), client.ipv6.as_deref())
+    {
+        return;
+    }
+
+    warn!(
+        client_id = %client.id,
+        client_name = %client.name,
+        ip = %ip,
+        "Client is reporting from an unexpected address"
+    );
+    let state = state.clone();
+    let client = client.clone();
+    tokio::spawn(async move {
+        let known: Vec<&str> = [client.ipv4.as_deref(), client.ipv6.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let params = [
+            ("client", client.name.clone()),
+            ("ip", ip.to_string()),
+            ("known", known.join(", ")),
+        ];
+        if let Err(e) = routing::dispatch(
+            &state.db,
+            &client,
+            EventType::IpChange,
+            &[],
+            &state.runtime().locale,
+            MessageKey::IpChanged,
+            &params,
+        )
+        .await
+        {
+            error!(
+                client_id = %client.id,
+                client_name = %client.name,
+                error = %e,
+                "Failed to send IP change notification"
+            );
+        }
+    });
+}
+
+/// Whether a report from `ip` differs from the client's recorded address of
+/// the same family.
+///
+/// IPv4 reports are only compared with `ipv4` and IPv6 reports with `ipv6`,
+/// so a dual-stack agent switching families is not a change. Without a
+/// recorded address in that family there is nothing to compare against.
+fn is_unexpected_report_ip(ip: IpAddr, ipv4: Option<&str>, ipv6: Option<&str>) -> bool {
+    let ip = ip.to_canonical();
+    let recorded = match ip {
+        IpAddr::V4(_) => ipv4,
+        IpAddr::V6(_) => ipv6,
+    };
+    recorded
+        .and_then(|recorded| recorded.trim().parse::<IpAddr>().ok())
+        .is_some_and(|recorded| recorded.to_canonical() != ip)
+}
+
+/// Check a record's `recorded_at` against `received_at`, the server time
+/// the report arrived.
+///
+/// Records sampled more than `MAX_CLOCK_DRIFT_SECS` before or after their
+/// arrival are rejected so a wrong agent clock cannot place them far in the
+/// past or future; any drift over a second is logged. Measuring from the
+/// arrival rather than from now keeps reports queued on the server from
+/// counting as late. Records without `recorded_at` are stamped with the
+/// server time and not checked.
+fn check_clock_drift(
+    state: &AppState,
+    client: &Client,
+    record: &RecordInput,
+    received_at: DateTime<Utc>,
+) -> AppResult<()> {
+    let Some(recorded_at) = record.recorded_at else {
+        return Ok(());
+    };
+    let drift_ms = (received_at - recorded_at).num_milliseconds();
+    if drift_ms.abs() <= CLOCK_DRIFT_WARN_MS {
+        return Ok(());
+    }
+    warn!(
+        client_id = %client.id,
+        client_name = %client.name,
+        drift_ms,
+        clock_offset_ms = client.clock_offset_ms,
+        "Record timestamp is off server time"
+    );
+    if drift_ms.abs() > state.config.max_clock_drift_secs.saturating_mul(1000) {
+        return Err(AppError::BadRequest(format!(
+            "Clock drift too large: {} seconds",
+            drift_ms / 1000
+        )));
+    }
+    Ok(())
+}
+
+/// Header carrying the agent token when `Authorization` is unavailable.
+const AGENT_TOKEN_HEADER: &str = "x-agent-token";
+
+/// Header carrying the client UUID alongside [`AGENT_TOKEN_HEADER`].
+const CLIENT_UUID_HEADER: &str = "x-client-uuid";
+
+/// Agent credentials taken from the request.
+#[derive(Debug)]
+struct AgentCredentials {
+    token: String,
+    /// Client the token must belong to, from `X-Client-UUID`.
+    client_id: Option<Uuid>,
+}
+
+/// Extract agent credentials from headers.
+///
+/// `Authorization: Bearer` is preferred. Proxies that strip `Authorization`
+/// can be worked around with `X-Agent-Token` plus `X-Client-UUID`.
+fn extract_agent_token(headers: &HeaderMap) -> AppResult<AgentCredentials> {
+    let header_str = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let client_id = match header_str(CLIENT_UUID_HEADER) {
+        Some(uuid) => Some(Uuid::parse_str(uuid.trim()).map_err(|_| AppError::Unauthorized)?),
+        None => None,
+    };
+
+    if let Some(token) =
+        header_str(header::AUTHORIZATION.as_str()).and_then(|auth| auth.strip_prefix("Bearer "))
+    {
+        return Ok(AgentCredentials {
+            token: token.to_string(),
+            client_id,
+        });
+    }
+    if let Some(token) = header_str(AGENT_TOKEN_HEADER)
+        && client_id.is_some()
+    {
+        return Ok(AgentCredentials {
+            token: token.to_string(),
+            client_id,
+        });
+    }
     Err(AppError::Unauthorized)
 }
+
+/// Authenticate agent credentials, returning their client.
+async fn authenticate_agent(state: &AppState, credentials: AgentCredentials) -> AppResult<Client> {
+    let client = match credentials.client_id {
+        Some(id) => {
+            debug!(client_id = %id, "Agent authenticating with token and client UUID");
+            state
+                .db
+                .find_client_by_id_and_token(id, &credentials.token)
+                .await?
+        }
+        None => {
+            debug!("Agent authenticating with bearer token");
+            state.db.find_client_by_token(&credentials.token).await?
+        }
+    };
+    let client = client.ok_or(AppError::Unauthorized)?;
+    if client.archived {
+        return Err(AppError::Gone("Client is archived".into()));
+    }
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unexpected(ip: &str, ipv4: Option<&str>, ipv6: Option<&str>) -> bool {
+        is_unexpected_report_ip(ip.parse().unwrap(), ipv4, ipv6)
+    }
+
+    #[test]
+    fn report_ip_ipv4() {
+        assert!(!unexpected("203.0.113.7", Some("203.0.113.7"), None));
+        assert!(!unexpected("203.0.113.7", Some(" 203.0.113.7 "), None));
+        assert!(unexpected("198.51.100.1", Some("203.0.113.7"), None));
+        // Nothing recorded in that family
+        assert!(!unexpected("198.51.100.1", None, Some("2001:db8::1")));
+        assert!(!unexpected("198.51.100.1", Some("not an ip"), None));
+    }
+
+    #[test]
+    fn report_ip_ipv6() {
+        assert!(!unexpected("2001:db8::1", None, Some("2001:db8::1")));
+        assert!(!unexpected("2001:db8::1", None, Some("2001:DB8:0::1")));
+        assert!(unexpected(
+            "2001:db8::2",
+            Some("203.0.113.7"),
+            Some("2001:db8::1")
+        ));
+        assert!(!unexpected("2001:db8::2", Some("203.0.113.7"), None));
+    }
+
+    #[test]
+    fn report_ip_ipv4_mapped() {
+        // A dual-stack socket reports IPv4 peers as ::ffff:a.b.c.d
+        assert!(!unexpected("::ffff:203.0.113.7", Some("203.0.113.7"), None));
+        assert!(!unexpected("203.0.113.7", Some("::ffff:203.0.113.7"), None));
+        assert!(unexpected(
+            "::ffff:198.51.100.1",
+            Some("203.0.113.7"),
+            Some("2001:db8::1")
+        ));
+    }
+
+    #[test]
+    fn report_ip_foreign() {
+        assert!(unexpected(
+            "192.0.2.99",
+            Some("203.0.113.7"),
+            Some("2001:db8::1")
+        ));
+        assert!(unexpected(
+            "2001:db8:ffff::99",
+            Some("203.0.113.7"),
+            Some("2001:db8::1")
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+//! Period-over-period metric comparison.
+//!
+//! Compares a record metric of the current day, week or month so far with
+//! the whole previous one, for a client or across the clients of a group.
+//! Period boundaries are midnights in the `timezone` setting; weeks start on
+//! Monday. Statistics of a period without records are `null`, and so are the
+//! deltas that depend on them.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::MetricAggregate;
+use crate::error::{AppError, AppResult};
+
+/// Metrics that can be compared.
+const METRICS: &[&str] = &[
+    "cpu",
+    "gpu",
+    "ram_pct",
+    "swap_pct",
+    "disk_pct",
+    "load",
+    "load5",
+    "load15",
+    "temp",
+    "net_in",
+    "net_out",
+    "process",
+    "connections",
+];
+
+/// Length of the compared periods.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComparePeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl ComparePeriod {
+    /// First day of the period containing `date`.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            ComparePeriod::Day => date,
+            ComparePeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday().into())
+            }
+            ComparePeriod::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// First day of the period before the one starting on `start`.
+    fn previous(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            ComparePeriod::Day => start - Duration::days(1),
+            ComparePeriod::Week => start - Duration::weeks(1),
+            ComparePeriod::Month => start - Months::new(1),
+        }
+    }
+}
+
+/// Query params for comparisons.
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    #[serde(default = "default_metric")]
+    pub metric: String,
+    #[serde(default)]
+    pub period: ComparePeriod,
+}
+
+fn default_metric() -> String {
+    "cpu".to_string()
+}
+
+/// Aggregate of a metric over one period.
+#[derive(Debug, Serialize)]
+pub struct PeriodAggregate {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(flatten)]
+    pub aggregate: MetricAggregate,
+}
+
+/// Current minus previous statistics.
+#[derive(Debug, Serialize)]
+pub struct AggregateDelta {
+    pub avg: Option<f64>,
+    pub p95: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Comparison of the current period so far with the previous period.
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub metric: String,
+    pub period: ComparePeriod,
+    pub timezone: String,
+    /// Clients whose records were aggregated.
+    pub clients: usize,
+    pub current: PeriodAggregate,
+    pub previous: PeriodAggregate,
+    pub delta: AggregateDelta,
+}
+
+/// GET /api/admin/clients/:id/compare - Compare a client metric with the previous period.
+pub async fn compare_client(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<CompareQuery>,
+) -> AppResult<Json<Comparison>> {
+    validate_metric(&query.metric)?;
+    state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    compare(&state, &[id], query).await.map(Json)
+}
+
+/// GET /api/admin/groups/:name/compare - Compare a metric across the clients of a group.
+pub async fn compare_group(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<CompareQuery>,
+) -> AppResult<Json<Comparison>> {
+    validate_metric(&query.metric)?;
+    let ids: Vec<Uuid> = state
+        .db
+        .get_all_clients()
+        .await?
+        .into_iter()
+        .filter(|c| c.group_name == name)
+        .map(|c| c.id)
+        .collect();
+    if ids.is_empty() {
+        return Err(AppError::NotFound("Group not found".into()));
+    }
+
+    compare(&state, &ids, query).await.map(Json)
+}
+
+fn validate_metric(metric: &str) -> AppResult<()> {
+    if METRICS.contains(&metric) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Unknown metric: {} (use one of {})",
+            metric,
+            METRICS.join(", ")
+        )))
+    }
+}
+
+async fn compare(
+    state: &AppState,
+    client_ids: &[Uuid],
+    query: CompareQuery,
+) -> AppResult<Comparison> {
+    let tz = load_timezone(state).await?;
+    let now = Utc::now();
+    let start = query.period.start(now.with_timezone(&tz).date_naive());
+    let previous_start = query.period.previous(start);
+    let (start, previous_start) = (midnight(tz, start), midnight(tz, previous_start));
+
+    let (current, previous) = tokio::try_join!(
+        state
+            .db
+            .get_metric_aggregate(client_ids, &query.metric, start, now),
+        state
+            .db
+            .get_metric_aggregate(client_ids, &query.metric, previous_start, start),
+    )?;
+    let delta = |current: Option<f64>, previous: Option<f64>| Some(current? - previous?);
+
+    Ok(Comparison {
+        delta: AggregateDelta {
+            avg: delta(current.avg, previous.avg),
+            p95: delta(current.p95, previous.p95),
+            max: delta(current.max, previous.max),
+        },
+        current: PeriodAggregate {
+            start,
+            end: now,
+            aggregate: current,
+        },
+        previous: PeriodAggregate {
+            start: previous_start,
+            end: start,
+            aggregate: previous,
+        },
+        metric: query.metric,
+        period: query.period,
+        timezone: tz.name().to_string(),
+        clients: client_ids.len(),
+    })
+}
+
+/// Start of `date` in `tz`.
+fn midnight(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let local = date.and_time(chrono::NaiveTime::MIN);
+    tz.from_local_datetime(&local)
+        .earliest()
+        // Midnight falls into a DST gap; use the UTC reading instead
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local).with_timezone(&tz))
+        .with_timezone(&Utc)
+}
+
+/// The `timezone` setting, UTC when unset.
+pub async fn load_timezone(state: &AppState) -> AppResult<Tz> {
+    Ok(state
+        .db
+        .get_setting("timezone")
+        .await?
+        .and_then(|v| v.as_str().and_then(|name| name.parse().ok()))
+        .unwrap_or(Tz::UTC))
+}
@@ -0,0 +1,132 @@
+//! Client group totals.
+//!
+//! A group is the set of clients sharing a `group_name`. Its statistics sum
+//! the members' capacity, usage and network rates, for fleets where the
+//! totals matter more than the individual nodes. Hidden clients are left
+//! out unless a signed-in user asks for them with `?include_hidden=true`.
+
+use axum::{
+    Json,
+    extract::{Extension, Path, Query, State},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::AppState;
+use crate::db::{GroupStats, GroupStatsPoint, User};
+use crate::error::{AppError, AppResult};
+
+/// Longest time series, in hours.
+const MAX_HOURS: i64 = 30 * 24;
+
+/// Shortest time series step, in seconds.
+const MIN_STEP_SECS: i64 = 60;
+
+/// Default time series step, in seconds.
+const DEFAULT_STEP_SECS: i64 = 600;
+
+/// Most buckets in one time series.
+const MAX_POINTS: i64 = 2000;
+
+/// Query params for group endpoints.
+#[derive(Debug, Deserialize)]
+pub struct GroupsQuery {
+    /// Count hidden clients; honored for signed-in users only.
+    #[serde(default)]
+    pub include_hidden: bool,
+}
+
+/// Query params for group statistics.
+#[derive(Debug, Deserialize)]
+pub struct GroupStatsQuery {
+    #[serde(default)]
+    pub include_hidden: bool,
+    /// Add a time series over the last `hours`.
+    pub hours: Option<i64>,
+    /// Bucket size of the time series in seconds.
+    pub step: Option<i64>,
+}
+
+/// Group statistics with an optional time series.
+#[derive(Debug, Serialize)]
+pub struct GroupStatsResponse {
+    #[serde(flatten)]
+    pub stats: GroupStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<Vec<GroupStatsPoint>>,
+}
+
+/// GET /api/groups - Totals of every client group.
+pub async fn list_groups(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<User>>,
+    Query(query): Query<GroupsQuery>,
+) -> AppResult<Json<Vec<GroupStats>>> {
+    let include_hidden = query.include_hidden && user.is_some();
+    let groups = state.db.get_group_stats(None, include_hidden).await?;
+    Ok(Json(groups))
+}
+
+/// GET /api/groups/:name/stats - Totals of a client group, with a time
+/// series when `?hours=` is given.
+pub async fn get_group_stats(
+    State(state): State<AppState>,
+    Extension(user): Extension<Option<User>>,
+    Path(name): Path<String>,
+    Query(query): Query<GroupStatsQuery>,
+) -> AppResult<Json<GroupStatsResponse>> {
+    let include_hidden = query.include_hidden && user.is_some();
+    let range = match query.hours {
+        Some(hours) => Some(validate_range(
+            hours,
+            query.step.unwrap_or(DEFAULT_STEP_SECS),
+        )?),
+        None => None,
+    };
+
+    let stats = state
+        .db
+        .get_group_stats(Some(&name), include_hidden)
+        .await?
+        .pop()
+        .ok_or(AppError::NotFound("Group not found".into()))?;
+
+    let series = match range {
+        Some((hours, step)) => {
+            let until = Utc::now();
+            let since = until - Duration::hours(hours);
+            Some(
+                state
+                    .db
+                    .get_group_stats_points(&name, include_hidden, since, until, step)
+                    .await?,
+            )
+        }
+        None => None,
+    };
+
+    Ok(Json(GroupStatsResponse { stats, series }))
+}
+
+/// Check the time series range, returning `(hours, step)`.
+fn validate_range(hours: i64, step: i64) -> AppResult<(i64, i64)> {
+    if !(1..=MAX_HOURS).contains(&hours) {
+        return Err(AppError::BadRequest(format!(
+            "hours must be 1-{}",
+            MAX_HOURS
+        )));
+    }
+    if step < MIN_STEP_SECS {
+        return Err(AppError::BadRequest(format!(
+            "step must be at least {} seconds",
+            MIN_STEP_SECS
+        )));
+    }
+    if hours * 3600 / step > MAX_POINTS {
+        return Err(AppError::BadRequest(format!(
+            "hours and step give more than {} points; use a larger step",
+            MAX_POINTS
+        )));
+    }
+    Ok((hours, step))
+}
@@ -3,39 +3,103 @@
 //! HTTP API endpoints and router configuration.
 
 mod admin;
+pub mod agent_connections;
 pub mod auth;
-mod client;
+pub mod client;
+mod compare;
+mod groups;
+pub mod oidc;
+mod overview;
+mod pagination;
 mod public;
+pub mod report_buffer;
+pub mod runtime;
+pub mod secrets;
+mod widget;
 
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Instant;
 
+use arc_swap::ArcSwap;
 use axum::{
     Router, middleware,
     routing::{get, post},
 };
+use dashmap::DashMap;
 use tower_http::{
-    compression::CompressionLayer,
     cors::{Any, CorsLayer},
     services::ServeDir,
     trace::TraceLayer,
 };
+use uuid::Uuid;
 
+pub use admin::DASHBOARD_CACHE_TTL;
+pub use agent_connections::AgentConnections;
+pub use pagination::{CursorPage, PageQuery, PagedResponse, decode_cursor};
+pub use report_buffer::ReportBuffer;
+pub use runtime::RuntimeSettings;
+
+use crate::anomaly::AnomalyDetector;
 use crate::config::Config;
 use crate::db::Database;
-use crate::middleware::auth_middleware;
+use crate::middleware::{
+    HttpMetrics, auth_middleware, compression_layer, metrics_middleware, no_compression,
+    real_ip_middleware, require_admin_middleware,
+};
 
 /// Application state shared across handlers.
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub config: Arc<Config>,
+    /// Settings snapshot, see [`RuntimeSettings`].
+    pub runtime: Arc<ArcSwap<RuntimeSettings>>,
+    /// Per client IP share link request counts as `(window start, count)`.
+    pub share_rate_limits: Arc<DashMap<IpAddr, (Instant, u32)>>,
+    /// Per heartbeat token request counts as `(window start, count)`.
+    pub heartbeat_rate_limits: Arc<DashMap<String, (Instant, u32)>>,
+    /// Per client IP heartbeat counts as `(window start, count)`.
+    pub heartbeat_ip_rate_limits: Arc<DashMap<IpAddr, (Instant, u32)>>,
+    /// Per client report counts as `(window start, count)`.
+    pub report_rate_limits: Arc<DashMap<Uuid, (Instant, u32)>>,
+    /// OIDC logins waiting for their callback, keyed by state.
+    pub oidc_pending: Arc<DashMap<String, oidc::PendingLogin>>,
+    /// Live agent WebSocket connections.
+    pub ws_agents: Arc<AgentConnections>,
+    /// Per-route request counters and latency histograms.
+    pub http_metrics: Arc<HttpMetrics>,
+    /// Cached anomaly baselines and ongoing deviations.
+    pub anomalies: Arc<AnomalyDetector>,
+    /// WebSocket reports waiting for the database to recover.
+    pub report_buffer: Arc<ReportBuffer>,
+    /// Last successful responses of public list endpoints, keyed by
+    /// endpoint, served while the database circuit is open.
+    pub public_cache: Arc<DashMap<String, Arc<serde_json::Value>>>,
+    /// Admin dashboard payloads as `(built at, payload)`, keyed by session.
+    pub dashboard_cache: Arc<DashMap<Uuid, (Instant, Arc<serde_json::Value>)>>,
+    /// Held while a retention cleanup runs.
+    pub cleanup_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl AppState {
     pub fn new(db: Database, config: Config) -> Self {
         Self {
             db,
+            runtime: Arc::new(ArcSwap::from_pointee(RuntimeSettings::defaults(&config))),
             config: Arc::new(config),
+            share_rate_limits: Arc::new(DashMap::new()),
+            heartbeat_rate_limits: Arc::new(DashMap::new()),
+            heartbeat_ip_rate_limits: Arc::new(DashMap::new()),
+            report_rate_limits: Arc::new(DashMap::new()),
+            oidc_pending: Arc::new(DashMap::new()),
+            ws_agents: Arc::new(AgentConnections::default()),
+            http_metrics: Arc::new(HttpMetrics::default()),
+            anomalies: Arc::new(AnomalyDetector::default()),
+            report_buffer: Arc::new(ReportBuffer::default()),
+            public_cache: Arc::new(DashMap::new()),
+            dashboard_cache: Arc::new(DashMap::new()),
+            cleanup_lock: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 }
@@ -47,57 +111,293 @@ pub fn create_router(state: AppState) -> Router {
         .route("/api/login", post(auth::login))
         .route("/api/logout", get(auth::logout))
         .route("/api/me", get(auth::me))
+        .route("/api/auth/methods", get(auth::login_methods))
+        .route("/api/auth/oidc/login", get(oidc::login))
+        .route("/api/auth/oidc/callback", get(oidc::callback))
+        .route("/api/version", get(public::version))
+        .route("/api/health", get(public::health))
+        .route("/healthz", get(public::health))
+        .route("/api/health/ready", get(public::ready))
+        .route("/metrics", get(public::metrics))
         .route("/api/clients", get(public::get_clients))
         .route("/api/nodes", get(public::get_nodes))
+        .route("/api/groups", get(groups::list_groups))
+        .route("/api/groups/{name}/stats", get(groups::get_group_stats))
+        .route("/api/announcement", get(public::get_announcement))
+        .route("/api/settings", get(public::get_public_settings))
+        .route("/api/timeline", get(public::get_status_timeline))
+        .route(
+            "/api/clients/{uuid}/overview",
+            get(overview::get_public_client_overview),
+        )
         .route("/api/recent/{uuid}", get(public::get_recent_records))
+        .route("/api/share/{token}", get(public::get_shared_client))
+        .route(
+            "/api/share/{token}/recent",
+            get(public::get_shared_recent_records),
+        )
         .route("/api/ping", get(public::get_ping_tasks))
-        .route("/api/ping/{id}/records", get(public::get_ping_records));
+        .route("/api/ping/{id}/records", get(public::get_ping_records))
+        .route("/api/ping/{id}/summary", get(public::get_ping_summary))
+        .route("/api/monitors", get(public::get_monitors))
+        .route("/api/heartbeats", get(public::get_heartbeats))
+        .route("/api/heartbeat/{token}", post(public::record_heartbeat))
+        .route("/widget/{uuid}", get(widget::client_widget))
+        .route("/widget/group/{name}", get(widget::group_widget));
 
     // Agent API routes (token auth)
     let agent_routes = Router::new()
         .route("/api/agent/register", post(client::register))
         .route("/api/agent/report", post(client::upload_report))
         .route("/api/agent/info", post(client::upload_basic_info))
-        .route("/api/agent/ws", get(client::ws_report));
+        .route("/api/agent/ws", get(client::ws_report))
+        .route_layer(middleware::map_response(no_compression));
 
-    // Admin API routes (session auth required)
+    // Admin API routes (session auth required); GETs returning secrets are
+    // limited to admins since viewers may read everything else
+    let admin_only = || middleware::from_fn(require_admin_middleware);
     let admin_routes = Router::new()
         .route("/api/admin/clients", get(admin::list_clients))
         .route("/api/admin/clients", post(admin::add_client))
+        .route("/api/admin/clients/search", get(admin::search_clients))
         .route("/api/admin/clients/{id}", get(admin::get_client))
         .route("/api/admin/clients/{id}", post(admin::edit_client))
         .route(
             "/api/admin/clients/{id}",
             axum::routing::delete(admin::delete_client),
         )
+        .route(
+            "/api/admin/clients/{id}/archive",
+            post(admin::archive_client),
+        )
+        .route(
+            "/api/admin/clients/{id}/unarchive",
+            post(admin::unarchive_client),
+        )
+        .route(
+            "/api/admin/clients/{id}/online",
+            axum::routing::patch(admin::set_online_override),
+        )
+        .route(
+            "/api/admin/clients/{id}/online/clear-override",
+            post(admin::clear_online_override),
+        )
+        .route(
+            "/api/admin/clients/{id}/records/duplicate",
+            axum::routing::delete(admin::delete_duplicate_records),
+        )
         .route(
             "/api/admin/clients/{id}/token",
-            get(admin::get_client_token),
+            get(admin::get_client_token).route_layer(admin_only()),
+        )
+        .route("/api/admin/clients/{id}/logs", get(admin::get_client_logs))
+        .route(
+            "/api/admin/clients/{id}/status-history",
+            get(admin::get_status_history),
+        )
+        .route(
+            "/api/admin/clients/{id}/timeline",
+            get(admin::get_client_timeline),
+        )
+        .route(
+            "/api/admin/clients/{id}/peers",
+            get(admin::get_client_peers),
+        )
+        .route(
+            "/api/admin/clients/{id}/neighbors",
+            get(admin::get_client_neighbors),
+        )
+        .route(
+            "/api/admin/clients/{id}/overview",
+            get(overview::get_client_overview),
+        )
+        .route(
+            "/api/admin/clients/{id}/compare",
+            get(compare::compare_client),
+        )
+        .route(
+            "/api/admin/groups/{name}/compare",
+            get(compare::compare_group),
+        )
+        .route(
+            "/api/admin/clients/{id}/alert-rules",
+            get(admin::list_client_alert_rules),
+        )
+        .route(
+            "/api/admin/clients/{id}/alert-rules/active",
+            get(admin::list_active_client_alert_rules),
+        )
+        .route(
+            "/api/admin/clients/{id}/alert-rules/bootstrap",
+            post(admin::bootstrap_alert_rules),
+        )
+        .route(
+            "/api/admin/clients/{id}/tags",
+            axum::routing::patch(admin::modify_client_tags),
+        )
+        .route(
+            "/api/admin/settings",
+            get(admin::get_settings).route_layer(admin_only()),
         )
-        .route("/api/admin/settings", get(admin::get_settings))
         .route("/api/admin/settings", post(admin::update_settings))
-        .route("/api/admin/notifications", get(admin::list_notifications))
+        .route("/api/admin/settings/reload", post(admin::reload_settings))
+        .route(
+            "/api/admin/notifications",
+            get(admin::list_notifications).route_layer(admin_only()),
+        )
         .route("/api/admin/notifications", post(admin::add_notification))
+        .route(
+            "/api/admin/notifications/{id}",
+            get(admin::get_notification)
+                .route_layer(admin_only())
+                .post(admin::edit_notification),
+        )
         .route(
             "/api/admin/notifications/{id}",
             axum::routing::delete(admin::delete_notification),
         )
+        .route(
+            "/api/admin/notifications/{id}/duplicate",
+            post(admin::duplicate_notification),
+        )
+        .route(
+            "/api/admin/notifications/{id}/test",
+            post(admin::test_saved_notification),
+        )
+        .route(
+            "/api/admin/notifications/validate",
+            post(admin::validate_notification),
+        )
         .route(
             "/api/admin/notifications/test",
             post(admin::test_notification),
         )
+        .route(
+            "/api/admin/notifications/digest/test",
+            post(admin::test_digest),
+        )
+        .route(
+            "/api/admin/notification-routes",
+            get(admin::list_notification_routes),
+        )
+        .route(
+            "/api/admin/notification-routes",
+            post(admin::add_notification_route),
+        )
+        .route(
+            "/api/admin/notification-routes/{id}",
+            post(admin::edit_notification_route),
+        )
+        .route(
+            "/api/admin/notification-routes/{id}",
+            axum::routing::delete(admin::delete_notification_route),
+        )
+        .route(
+            "/api/admin/share-links",
+            get(admin::list_share_links).route_layer(admin_only()),
+        )
+        .route("/api/admin/share-links", post(admin::add_share_link))
+        .route(
+            "/api/admin/share-links/{id}",
+            axum::routing::delete(admin::revoke_share_link),
+        )
         .route("/api/admin/ping", get(admin::list_ping_tasks))
         .route("/api/admin/ping", post(admin::add_ping_task))
+        .route("/api/admin/ping/bulk", post(admin::bulk_add_ping_tasks))
+        .route("/api/admin/ping/{id}/run", post(admin::run_ping_task))
+        .route(
+            "/api/admin/ping/{id}/duplicate",
+            post(admin::duplicate_ping_task),
+        )
         .route(
             "/api/admin/ping/{id}",
-            axum::routing::delete(admin::delete_ping_task),
+            get(admin::get_ping_task).delete(admin::delete_ping_task),
+        )
+        .route("/api/admin/timeline", get(admin::get_incident_timeline))
+        .route("/api/admin/monitors", get(admin::list_monitor_groups))
+        .route("/api/admin/monitors", post(admin::add_monitor_group))
+        .route("/api/admin/monitors/{id}", post(admin::edit_monitor_group))
+        .route(
+            "/api/admin/monitors/{id}",
+            axum::routing::delete(admin::delete_monitor_group),
+        )
+        .route(
+            "/api/admin/heartbeats",
+            get(admin::list_heartbeats).route_layer(admin_only()),
+        )
+        .route("/api/admin/heartbeats", post(admin::add_heartbeat))
+        .route("/api/admin/heartbeats/{id}", post(admin::edit_heartbeat))
+        .route(
+            "/api/admin/heartbeats/{id}",
+            axum::routing::delete(admin::delete_heartbeat),
+        )
+        .route("/api/admin/silences", get(admin::list_silences))
+        .route("/api/admin/silences", post(admin::add_silence))
+        .route("/api/admin/silences/{id}", post(admin::edit_silence))
+        .route(
+            "/api/admin/silences/{id}",
+            axum::routing::delete(admin::delete_silence),
+        )
+        .route("/api/admin/alert-rules", get(admin::list_alert_rules))
+        .route("/api/admin/alert-rules", post(admin::add_alert_rule))
+        .route(
+            "/api/admin/alert-rules/defaults",
+            post(admin::add_default_alert_rules),
+        )
+        .route(
+            "/api/admin/alert-rules/{id}",
+            axum::routing::delete(admin::delete_alert_rule),
         )
+        .route("/api/admin/alert-history", get(admin::list_alert_history))
         .route("/api/admin/user/password", post(admin::change_password))
+        .route(
+            "/api/admin/user/username",
+            post(admin::change_username).patch(admin::change_username),
+        )
+        .route("/api/admin/user/jwks", get(admin::get_jwks))
+        .route("/api/admin/audit-logs", get(admin::list_audit_logs))
+        .route(
+            "/api/admin/security/events",
+            get(admin::list_security_events),
+        )
+        .route("/api/admin/db/vacuum", post(admin::vacuum))
+        .route(
+            "/api/admin/records/old",
+            axum::routing::delete(admin::delete_old_records),
+        )
+        .route("/api/admin/archive/status", get(admin::archive_status))
+        .route(
+            "/api/admin/backup/upload-now",
+            post(admin::upload_backup_now),
+        )
+        .route(
+            "/api/admin/backup/remote",
+            get(admin::list_remote_backups).route_layer(admin_only()),
+        )
+        .route("/api/admin/debug/http", get(admin::debug_http))
+        .route(
+            "/api/admin/debug/agent-connections",
+            get(admin::debug_agent_connections),
+        )
+        .route(
+            "/api/admin/debug/agent-connections/{client_id}/disconnect",
+            post(admin::disconnect_agent),
+        )
+        .route(
+            "/api/admin/debug/buildinfo",
+            get(admin::get_build_info).route_layer(admin_only()),
+        )
+        .route("/api/admin/dashboard", get(admin::get_dashboard))
         .route("/api/admin/sessions", get(admin::list_sessions))
+        .route("/api/admin/sessions/revoke", post(admin::revoke_sessions))
         .route(
             "/api/admin/sessions/{id}",
             axum::routing::delete(admin::delete_session),
         )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::middleware::idempotency_middleware,
+        ))
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
             crate::middleware::require_auth_middleware,
@@ -122,8 +422,16 @@ pub fn create_router(state: AppState) -> Router {
     Router::new()
         .merge(api_routes)
         .fallback_service(static_service)
-        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics_middleware,
+        ))
+        .layer(compression_layer(&state.config))
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            real_ip_middleware,
+        ))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
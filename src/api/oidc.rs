@@ -0,0 +1,379 @@
+//! OAuth2/OIDC login for the admin panel.
+//!
+//! Supports GitHub (OAuth2 with org membership checks) and generic OpenID
+//! Connect providers (discovery, ID token signature and nonce validation).
+//! Both use the authorization code flow with `state` and PKCE (S256). Users
+//! are matched by email or provisioned as external users with the
+//! configured default role.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Extension, Query, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Redirect},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{DecodingKey, Validation, jwk::JwkSet};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::api::auth::start_session;
+use crate::config::OidcConfig;
+use crate::error::{AppError, AppResult};
+use crate::middleware::RealIp;
+
+/// How long a started login may take before its state expires.
+pub const PENDING_LOGIN_TTL: Duration = Duration::from_secs(600);
+
+const GITHUB_AUTHORIZE_URL: &str = "https://github.com/login/oauth/authorize";
+const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// A login flow waiting for its callback, keyed by `state`.
+#[derive(Debug, Clone)]
+pub struct PendingLogin {
+    pub code_verifier: String,
+    pub nonce: String,
+    pub created_at: Instant,
+}
+
+/// Subset of the OpenID provider metadata.
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    id_token: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    email: Option<String>,
+    email_verified: Option<bool>,
+    nonce: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubOrg {
+    login: String,
+}
+
+/// Identity returned by the provider.
+struct Identity {
+    email: String,
+    orgs: Vec<String>,
+}
+
+fn oidc_config(state: &AppState) -> AppResult<&OidcConfig> {
+    state
+        .config
+        .oidc
+        .as_ref()
+        .ok_or(AppError::NotFound("OIDC login is not configured".into()))
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent("vanmoi")
+        .build()
+        .unwrap_or_default()
+}
+
+fn upstream_error(e: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("OIDC provider request failed: {}", e))
+}
+
+async fn discover(http: &reqwest::Client, config: &OidcConfig) -> AppResult<Discovery> {
+    http.get(format!(
+        "{}/.well-known/openid-configuration",
+        config.issuer_url
+    ))
+    .send()
+    .await
+    .map_err(upstream_error)?
+    .error_for_status()
+    .map_err(upstream_error)?
+    .json()
+    .await
+    .map_err(upstream_error)
+}
+
+/// PKCE S256 code challenge for a verifier.
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Check an identity against the email and organization allow-lists.
+///
+/// Nobody is allowed when both lists are empty.
+fn is_allowed(config: &OidcConfig, email: &str, orgs: &[String]) -> bool {
+    let email = email.to_lowercase();
+    config.allowed_emails.contains(&email)
+        || orgs
+            .iter()
+            .any(|org| config.allowed_orgs.contains(&org.to_lowercase()))
+}
+
+/// Take a pending login by state, rejecting unknown or expired states.
+fn take_pending(state: &AppState, login_state: &str) -> Option<PendingLogin> {
+    let (_, pending) = state.oidc_pending.remove(login_state)?;
+    (pending.created_at.elapsed() < PENDING_LOGIN_TTL).then_some(pending)
+}
+
+/// GET /api/auth/oidc/login - Start the OIDC login flow.
+pub async fn login(State(state): State<AppState>) -> AppResult<Redirect> {
+    let config = oidc_config(&state)?;
+
+    let login_state = Uuid::new_v4().simple().to_string();
+    let nonce = Uuid::new_v4().simple().to_string();
+    let code_verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let challenge = code_challenge(&code_verifier);
+
+    let (authorize_url, scope) = if config.provider == "github" {
+        (
+            GITHUB_AUTHORIZE_URL.to_string(),
+            "read:user user:email read:org",
+        )
+    } else {
+        let discovery = discover(&http_client(), config).await?;
+        (discovery.authorization_endpoint, "openid email profile")
+    };
+
+    let mut url = reqwest::Url::parse(&authorize_url).map_err(upstream_error)?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &config.redirect_url)
+        .append_pair("scope", scope)
+        .append_pair("state", &login_state)
+        .append_pair("nonce", &nonce)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    state.oidc_pending.insert(
+        login_state,
+        PendingLogin {
+            code_verifier,
+            nonce,
+            created_at: Instant::now(),
+        },
+    );
+
+    Ok(Redirect::to(url.as_str()))
+}
+
+/// OIDC callback query params.
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// GET /api/auth/oidc/callback - Finish the OIDC login flow.
+pub async fn callback(
+    State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> AppResult<impl IntoResponse> {
+    let config = oidc_config(&state)?;
+
+    if let Some(error) = query.error {
+        return Err(AppError::BadRequest(format!(
+            "OIDC login failed: {}",
+            error
+        )));
+    }
+    let (Some(code), Some(login_state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("Missing code or state".into()));
+    };
+    let pending = take_pending(&state, &login_state).ok_or(AppError::BadRequest(
+        "Invalid or expired login state".into(),
+    ))?;
+
+    let http = http_client();
+    let identity = if config.provider == "github" {
+        github_identity(&http, config, &code, &pending).await?
+    } else {
+        oidc_identity(&http, config, &code, &pending).await?
+    };
+
+    if !is_allowed(config, &identity.email, &identity.orgs) {
+        warn!("OIDC login rejected for {}", identity.email);
+        return Err(AppError::Forbidden);
+    }
+
+    let user = match state.db.find_user_by_email(&identity.email).await? {
+        Some(user) => user,
+        None => {
+            info!(
+                "Provisioning external user {} as {}",
+                identity.email, config.default_role
+            );
+            state
+                .db
+                .create_external_user(&identity.email, &config.default_role)
+                .await?
+        }
+    };
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    let ip_address = real_ip.map(|Extension(RealIp(ip))| ip.to_string());
+    let (_, cookie) = start_session(&state, &user, user_agent, ip_address.as_deref()).await?;
+
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to("/")))
+}
+
+/// Exchange the code with a generic OIDC provider and validate the ID token.
+async fn oidc_identity(
+    http: &reqwest::Client,
+    config: &OidcConfig,
+    code: &str,
+    pending: &PendingLogin,
+) -> AppResult<Identity> {
+    let discovery = discover(http, config).await?;
+    let token: TokenResponse = http
+        .post(&discovery.token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &config.redirect_url),
+            ("client_id", &config.client_id),
+            ("client_secret", &config.client_secret),
+            ("code_verifier", &pending.code_verifier),
+        ])
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)?;
+
+    let id_token = token
+        .id_token
+        .ok_or_else(|| upstream_error(token.error.unwrap_or_else(|| "no id_token".to_string())))?;
+
+    let jwks: JwkSet = http
+        .get(&discovery.jwks_uri)
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)?;
+
+    let header = jsonwebtoken::decode_header(&id_token)
+        .map_err(|e| AppError::BadRequest(format!("Invalid ID token: {}", e)))?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    }
+    .ok_or(AppError::BadRequest("Unknown ID token signing key".into()))?;
+    let key = DecodingKey::from_jwk(jwk)
+        .map_err(|e| AppError::BadRequest(format!("Invalid signing key: {}", e)))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_issuer(&[&discovery.issuer]);
+    let claims = jsonwebtoken::decode::<IdTokenClaims>(&id_token, &key, &validation)
+        .map_err(|e| AppError::BadRequest(format!("Invalid ID token: {}", e)))?
+        .claims;
+
+    if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+        return Err(AppError::BadRequest("ID token nonce mismatch".into()));
+    }
+    if claims.email_verified == Some(false) {
+        return Err(AppError::Forbidden);
+    }
+    let email = claims
+        .email
+        .ok_or(AppError::BadRequest("ID token has no email".into()))?;
+
+    Ok(Identity {
+        email,
+        orgs: Vec::new(),
+    })
+}
+
+/// Exchange the code with GitHub and look up the primary email and orgs.
+async fn github_identity(
+    http: &reqwest::Client,
+    config: &OidcConfig,
+    code: &str,
+    pending: &PendingLogin,
+) -> AppResult<Identity> {
+    let token: TokenResponse = http
+        .post(GITHUB_TOKEN_URL)
+        .header(header::ACCEPT, "application/json")
+        .form(&[
+            ("code", code),
+            ("redirect_uri", &config.redirect_url),
+            ("client_id", &config.client_id),
+            ("client_secret", &config.client_secret),
+            ("code_verifier", &pending.code_verifier),
+        ])
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)?;
+
+    let access_token = token.access_token.ok_or_else(|| {
+        upstream_error(token.error.unwrap_or_else(|| "no access_token".to_string()))
+    })?;
+
+    let emails: Vec<GithubEmail> = github_get(http, &access_token, "/user/emails").await?;
+    let email = emails
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email)
+        .ok_or(AppError::Forbidden)?;
+
+    let orgs: Vec<GithubOrg> = github_get(http, &access_token, "/user/orgs").await?;
+
+    Ok(Identity {
+        email,
+        orgs: orgs.into_iter().map(|o| o.login).collect(),
+    })
+}
+
+async fn github_get<T: serde::de::DeserializeOwned>(
+    http: &reqwest::Client,
+    access_token: &str,
+    path: &str,
+) -> AppResult<T> {
+    http.get(format!("{}{}", GITHUB_API_URL, path))
+        .bearer_auth(access_token)
+        .header(header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .error_for_status()
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)
+}
@@ -0,0 +1,183 @@
+//! Client detail overview.
+//!
+//! Everything the client detail page shows, assembled in one response from
+//! concurrent repository queries instead of one request per widget.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::public::ClientStatus;
+use crate::api::AppState;
+use crate::db::{AlertHistory, Client, ClientPublic, Record, RecordPoint};
+use crate::error::{AppError, AppResult};
+use crate::silences;
+
+/// Chart points in an overview.
+const OVERVIEW_POINTS: i32 = 60;
+
+/// Hours covered by the chart points.
+const POINTS_WINDOW_HOURS: i64 = 24;
+
+/// Days covered by the uptime percentage.
+const UPTIME_WINDOW_DAYS: i64 = 30;
+
+/// Used and total amount of a resource.
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub used: i64,
+    pub total: i64,
+    /// `used` as a percentage of `total`, 0 when the total is unknown.
+    pub percent: f64,
+}
+
+impl Usage {
+    fn new(used: i64, total: i64) -> Self {
+        let percent = if total > 0 {
+            used as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        Self {
+            used,
+            total,
+            percent,
+        }
+    }
+}
+
+/// Resource usage from the latest record.
+#[derive(Debug, Serialize)]
+pub struct HardwareBreakdown {
+    pub memory: Usage,
+    pub swap: Usage,
+    pub disk: Usage,
+    pub inodes: Usage,
+    pub file_descriptors: Usage,
+}
+
+impl From<&Record> for HardwareBreakdown {
+    fn from(r: &Record) -> Self {
+        Self {
+            memory: Usage::new(r.ram, r.ram_total),
+            swap: Usage::new(r.swap, r.swap_total),
+            disk: Usage::new(r.disk, r.disk_total),
+            inodes: Usage::new(r.inode_used, r.inode_total),
+            file_descriptors: Usage::new(r.fd_used.into(), r.fd_total.into()),
+        }
+    }
+}
+
+/// Maintenance state of a client.
+#[derive(Debug, Serialize)]
+pub struct Maintenance {
+    pub active: bool,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Admin client overview.
+#[derive(Debug, Serialize)]
+pub struct ClientOverview {
+    pub client: Client,
+    pub latest: Option<Record>,
+    pub hardware: Option<HardwareBreakdown>,
+    /// Averaged records of the last 24 hours, oldest first.
+    pub points: Vec<RecordPoint>,
+    pub active_alerts: Vec<AlertHistory>,
+    pub maintenance: Maintenance,
+    /// An active silence suppresses the client's notifications.
+    pub silenced: bool,
+    /// Online percentage of the last 30 days, `null` without status history.
+    pub uptime_percent: Option<f64>,
+}
+
+/// Public client overview.
+#[derive(Debug, Serialize)]
+pub struct PublicClientOverview {
+    pub client: ClientPublic,
+    pub status: Option<ClientStatus>,
+    pub hardware: Option<HardwareBreakdown>,
+    /// Averaged records of the last 24 hours, oldest first.
+    pub points: Vec<RecordPoint>,
+    /// Online percentage of the last 30 days, `null` without status history.
+    pub uptime_percent: Option<f64>,
+}
+
+/// GET /api/admin/clients/:id/overview - Everything the client detail page shows.
+pub async fn get_client_overview(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<ClientOverview>> {
+    let now = Utc::now();
+    let (client, latest, points, active_alerts, uptime_percent, active_silences) = tokio::try_join!(
+        state.db.find_client_by_id(id),
+        state.db.get_latest_record(id),
+        record_points(&state, id, now),
+        state.db.get_open_alerts_by_client(id),
+        uptime(&state, id, now),
+        silences::active(&state.db),
+    )?;
+    let client = client.ok_or(AppError::NotFound("Client not found".into()))?;
+
+    Ok(Json(ClientOverview {
+        hardware: latest.as_ref().map(HardwareBreakdown::from),
+        latest,
+        points,
+        active_alerts,
+        maintenance: Maintenance {
+            active: client.maintenance_until.is_some_and(|until| until > now),
+            until: client.maintenance_until,
+        },
+        silenced: silences::client_silenced(&active_silences, &client),
+        uptime_percent,
+        client,
+    }))
+}
+
+/// GET /api/clients/:uuid/overview - Public overview of a visible client.
+pub async fn get_public_client_overview(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PublicClientOverview>> {
+    let now = Utc::now();
+    let (client, latest, points, uptime_percent) = tokio::try_join!(
+        state.db.find_client_by_id(id),
+        state.db.get_latest_record(id),
+        record_points(&state, id, now),
+        uptime(&state, id, now),
+    )?;
+    let client = client
+        .filter(|c| !c.hidden)
+        .ok_or(AppError::NotFound("Client not found".into()))?;
+
+    // Like the client list, an offline client has no current status
+    let latest = latest.filter(|_| client.online);
+    Ok(Json(PublicClientOverview {
+        hardware: latest.as_ref().map(HardwareBreakdown::from),
+        status: latest.map(ClientStatus::from),
+        points,
+        uptime_percent,
+        client: ClientPublic::new(client, state.runtime().stale_after_secs),
+    }))
+}
+
+async fn record_points(
+    state: &AppState,
+    client_id: Uuid,
+    now: DateTime<Utc>,
+) -> AppResult<Vec<RecordPoint>> {
+    let since = now - Duration::hours(POINTS_WINDOW_HOURS);
+    state
+        .db
+        .get_record_points(client_id, since, now, OVERVIEW_POINTS)
+        .await
+}
+
+async fn uptime(state: &AppState, client_id: Uuid, now: DateTime<Utc>) -> AppResult<Option<f64>> {
+    let since = now - Duration::days(UPTIME_WINDOW_DAYS);
+    state.db.get_client_uptime(client_id, since).await
+}
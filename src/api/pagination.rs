@@ -0,0 +1,105 @@
+//! Pagination for list endpoints.
+//!
+//! Lists use `?limit=&offset=`; feeds that grow while being read use an
+//! opaque `?cursor=` naming the last item of the previous page.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::db::Cursor;
+use crate::error::{AppError, AppResult};
+
+/// Default page size.
+const DEFAULT_LIMIT: i32 = 100;
+
+/// Largest page size a caller may request.
+const MAX_LIMIT: i32 = 1000;
+
+/// `?limit=&offset=` query params.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PageQuery {
+    #[serde(default = "default_limit")]
+    pub limit: i32,
+    #[serde(default)]
+    pub offset: i32,
+}
+
+fn default_limit() -> i32 {
+    DEFAULT_LIMIT
+}
+
+impl PageQuery {
+    /// Page size clamped to `1..=MAX_LIMIT`.
+    pub fn limit(&self) -> i32 {
+        self.limit.clamp(1, MAX_LIMIT)
+    }
+
+    /// Non-negative offset.
+    pub fn offset(&self) -> i32 {
+        self.offset.max(0)
+    }
+}
+
+/// One page of a list with the total number of items.
+#[derive(Debug, Serialize)]
+pub struct PagedResponse<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i32,
+    pub offset: i32,
+    pub has_more: bool,
+}
+
+impl<T> PagedResponse<T> {
+    pub fn new(items: Vec<T>, total: i64, page: PageQuery) -> Self {
+        let offset = page.offset();
+        Self {
+            has_more: (offset as i64 + items.len() as i64) < total,
+            items,
+            total,
+            limit: page.limit(),
+            offset,
+        }
+    }
+}
+
+/// One page of a feed with the cursor of the next page, if any.
+#[derive(Debug, Serialize)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// Build a page from up to `limit + 1` items, the extra one only
+    /// signalling that another page exists.
+    pub fn new(mut items: Vec<T>, limit: i64, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit.max(0) as usize);
+        let next_cursor = items
+            .last()
+            .filter(|_| has_more)
+            .map(|last| encode_cursor(&cursor_of(last)));
+        Self { items, next_cursor }
+    }
+}
+
+/// Encode a cursor as an opaque URL-safe token.
+pub fn encode_cursor(cursor: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}|{}", cursor.at.to_rfc3339(), cursor.id))
+}
+
+/// Decode a token produced by [`encode_cursor`].
+pub fn decode_cursor(token: &str) -> AppResult<Cursor> {
+    let invalid = || AppError::BadRequest("Invalid cursor".into());
+    let decoded = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (at, id) = decoded.split_once('|').ok_or_else(invalid)?;
+    Ok(Cursor {
+        at: DateTime::parse_from_rfc3339(at)
+            .map_err(|_| invalid())?
+            .to_utc(),
+        id: id.to_string(),
+    })
+}
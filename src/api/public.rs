@@ -1,20 +1,44 @@
 //! Public API endpoints (no auth required).
 
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use axum::{
     Json,
-    extract::{Path, Query, State},
+    body::Body,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
-use crate::api::AppState;
-use crate::db::{ClientPublic, PingRecord, PingTask, Record};
-use crate::error::AppResult;
+use crate::api::{AppState, CursorPage, decode_cursor};
+use crate::build_info;
+use crate::db::{
+    CircuitState, Client, ClientPublic, HeartbeatPublic, IncidentEvent, MonitorGroupPublic,
+    PingRecord, PingSource, PingTask, PingTaskSource, PingTaskSummary, PoolHealth, Record,
+    ShareLink, User,
+};
+use crate::error::{AppError, AppResult, with_timeout};
+use crate::heartbeats;
+use crate::middleware::RealIp;
+use crate::silences;
+use crate::tasks::retention;
 
 /// Get clients response.
 #[derive(Debug, Serialize)]
 pub struct ClientsResponse {
     pub clients: Vec<ClientWithStatus>,
+    pub total: usize,
+    pub online_count: usize,
 }
 
 /// Client with current status.
@@ -24,55 +48,137 @@ pub struct ClientWithStatus {
     pub client: ClientPublic,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<ClientStatus>,
+    /// An active silence suppresses the client's notifications.
+    pub silenced: bool,
 }
 
 /// Client current status.
+///
+/// Metrics that not every agent reports are omitted when zero.
 #[derive(Debug, Serialize)]
 pub struct ClientStatus {
     pub cpu: f32,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub gpu: f32,
     pub ram: i64,
     pub ram_total: i64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub swap: i64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub swap_total: i64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub temp: f32,
     pub disk: i64,
     pub disk_total: i64,
     pub net_in: i64,
     pub net_out: i64,
     pub load: f32,
+    pub load5: f32,
+    pub load15: f32,
     pub uptime: i64,
+    pub fd_used: i32,
+    pub fd_total: i32,
+    pub inode_used: i64,
+    pub inode_total: i64,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub process: i32,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub connections: i32,
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl From<Record> for ClientStatus {
+    fn from(r: Record) -> Self {
+        Self {
+            cpu: r.cpu,
+            gpu: r.gpu,
+            ram: r.ram,
+            ram_total: r.ram_total,
+            swap: r.swap,
+            swap_total: r.swap_total,
+            temp: r.temp,
+            disk: r.disk,
+            disk_total: r.disk_total,
+            net_in: r.net_in,
+            net_out: r.net_out,
+            load: r.load,
+            load5: r.load5,
+            load15: r.load15,
+            uptime: r.uptime,
+            fd_used: r.fd_used,
+            fd_total: r.fd_total,
+            inode_used: r.inode_used,
+            inode_total: r.inode_total,
+            process: r.process,
+            connections: r.connections,
+        }
+    }
+}
+
+/// Latest records of the online clients, keyed by client ID.
+async fn latest_records(state: &AppState, clients: &[Client]) -> AppResult<HashMap<Uuid, Record>> {
+    let ids: Vec<Uuid> = clients.iter().filter(|c| c.online).map(|c| c.id).collect();
+    let records = state.db.get_latest_records(&ids).await?;
+    Ok(records.into_iter().map(|r| (r.client_id, r)).collect())
 }
 
 /// GET /api/clients - Get all visible clients with their current status.
-pub async fn get_clients(State(state): State<AppState>) -> AppResult<Json<ClientsResponse>> {
+pub async fn get_clients(State(state): State<AppState>) -> AppResult<Json<serde_json::Value>> {
+    with_fallback(&state, "clients", clients_response(&state)).await
+}
+
+async fn clients_response(state: &AppState) -> AppResult<ClientsResponse> {
     let clients = state.db.get_visible_clients().await?;
+    let mut latest = latest_records(state, &clients).await?;
 
+    let active_silences = silences::active(&state.db).await?;
+
+    let stale_after_secs = state.runtime().stale_after_secs;
     let mut result = Vec::new();
     for client in clients {
-        let status = if client.online {
-            state
-                .db
-                .get_latest_record(client.id)
-                .await?
-                .map(|r| ClientStatus {
-                    cpu: r.cpu,
-                    ram: r.ram,
-                    ram_total: r.ram_total,
-                    disk: r.disk,
-                    disk_total: r.disk_total,
-                    net_in: r.net_in,
-                    net_out: r.net_out,
-                    load: r.load,
-                    uptime: r.uptime,
-                })
-        } else {
-            None
-        };
+        let status = latest.remove(&client.id).map(ClientStatus::from);
+        let silenced = silences::client_silenced(&active_silences, &client);
 
         result.push(ClientWithStatus {
-            client: client.into(),
+            client: ClientPublic::new(client, stale_after_secs),
             status,
+            silenced,
         });
     }
 
-    Ok(Json(ClientsResponse { clients: result }))
+    let online_count = result.iter().filter(|c| c.client.online).count();
+    Ok(ClientsResponse {
+        total: result.len(),
+        online_count,
+        clients: result,
+    })
+}
+
+/// Serve the response of `fetch`, remembering it under `key`. While the
+/// database circuit is open, serve the last remembered response instead.
+async fn with_fallback<T: Serialize>(
+    state: &AppState,
+    key: &str,
+    fetch: impl Future<Output = AppResult<T>>,
+) -> AppResult<Json<serde_json::Value>> {
+    match fetch.await {
+        Ok(response) => {
+            let value = serde_json::to_value(response)
+                .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+            state
+                .public_cache
+                .insert(key.to_string(), Arc::new(value.clone()));
+            Ok(Json(value))
+        }
+        Err(AppError::ServiceUnavailable(reason)) => match state.public_cache.get(key) {
+            Some(cached) => Ok(Json(cached.as_ref().clone())),
+            None => Err(AppError::ServiceUnavailable(reason)),
+        },
+        Err(e) => Err(e),
+    }
 }
 
 /// Node information for API compatibility.
@@ -82,58 +188,699 @@ pub struct NodeInfo {
     pub name: String,
     pub group: String,
     pub online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Online and reported within `REPORT_TIMEOUT_SECS`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_online: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<NodeStats>,
+}
+
+/// Compact latest stats of a node.
+#[derive(Debug, Serialize)]
+pub struct NodeStats {
+    pub cpu: f32,
+    pub mem_percent: f64,
+    pub net_in: i64,
+    pub net_out: i64,
+}
+
+impl From<Record> for NodeStats {
+    fn from(r: Record) -> Self {
+        Self {
+            cpu: r.cpu,
+            mem_percent: if r.ram_total > 0 {
+                r.ram as f64 * 100.0 / r.ram_total as f64
+            } else {
+                0.0
+            },
+            net_in: r.net_in,
+            net_out: r.net_out,
+        }
+    }
+}
+
+/// Query params for nodes.
+#[derive(Debug, Deserialize)]
+pub struct NodesQuery {
+    /// Include region, effective online state and latest stats.
+    #[serde(default)]
+    pub detail: bool,
 }
 
 /// GET /api/nodes - Get node list (simplified).
-pub async fn get_nodes(State(state): State<AppState>) -> AppResult<Json<Vec<NodeInfo>>> {
+pub async fn get_nodes(
+    State(state): State<AppState>,
+    Query(query): Query<NodesQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let key = if query.detail {
+        "nodes_detail"
+    } else {
+        "nodes"
+    };
+    with_fallback(&state, key, nodes(&state, &query)).await
+}
+
+async fn nodes(state: &AppState, query: &NodesQuery) -> AppResult<Vec<NodeInfo>> {
     let clients = state.db.get_visible_clients().await?;
+    let mut latest = if query.detail {
+        latest_records(state, &clients).await?
+    } else {
+        HashMap::new()
+    };
+    let now = Utc::now();
+    let report_timeout_secs = state.runtime().report_timeout_secs;
 
     let nodes: Vec<NodeInfo> = clients
         .into_iter()
-        .map(|c| NodeInfo {
-            id: c.id.to_string(),
-            name: c.name,
-            group: c.group_name,
-            online: c.online,
+        .map(|c| {
+            let (region, effective_online, stats) = if query.detail {
+                (
+                    Some(c.region.clone()),
+                    Some(c.effective_online(now, report_timeout_secs)),
+                    latest.remove(&c.id).map(NodeStats::from),
+                )
+            } else {
+                (None, None, None)
+            };
+            NodeInfo {
+                id: c.id.to_string(),
+                name: c.name,
+                group: c.group_name,
+                online: c.online,
+                region,
+                effective_online,
+                stats,
+            }
         })
         .collect();
 
-    Ok(Json(nodes))
+    Ok(nodes)
 }
 
 /// Query params for records.
 #[derive(Debug, Deserialize)]
 pub struct RecordsQuery {
+    /// Number of records, or of points with `step`.
     #[serde(default = "default_limit")]
     pub limit: i32,
+    /// Average records over buckets of this many seconds, covering the last
+    /// `limit` buckets.
+    pub step: Option<i64>,
+    /// Send the records as newline-delimited JSON while they are read.
+    #[serde(default)]
+    pub stream: bool,
 }
 
+/// Longest record bucket, in seconds.
+const MAX_STEP_SECS: i64 = 24 * 3600;
+
 fn default_limit() -> i32 {
     60
 }
 
-/// GET /api/recent/:uuid - Get recent records for a client.
+/// Clamp a requested record limit to the configured maximum for the caller.
+fn clamp_limit(state: &AppState, user: &Option<User>, limit: i32) -> i32 {
+    let runtime = state.runtime();
+    let max = if user.is_some() {
+        runtime.admin_max_records
+    } else {
+        runtime.public_max_records
+    };
+
+    limit.clamp(1, max.max(1))
+}
+
+/// Recent records of a client as a response: a JSON array, or with
+/// `stream` newline-delimited JSON written as the rows are read, so large
+/// limits do not hold every record in memory.
+async fn recent_records_response(
+    state: &AppState,
+    client_id: Uuid,
+    limit: i32,
+    query: RecordsQuery,
+) -> AppResult<Response> {
+    if !query.stream {
+        let records = recent_records(state, client_id, limit, query.step).await?;
+        return Ok(Json(records).into_response());
+    }
+    if query.step.is_some() {
+        return Err(AppError::BadRequest(
+            "stream cannot be combined with step".into(),
+        ));
+    }
+
+    let lines = state
+        .db
+        .stream_recent_records(client_id, limit)?
+        .map(|record| {
+            let mut line = serde_json::to_vec(&record?)
+                .map_err(|e| AppError::Internal(format!("Failed to encode record: {}", e)))?;
+            line.push(b'\n');
+            Ok::<_, AppError>(line)
+        });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// Recent records of a client: the latest `limit` records, or with `step`
+/// at most `limit` averaged points.
+///
+/// The window is `limit` buckets ending with the bucket that contains now,
+/// so it spans whole buckets and the newest point can be partial.
+async fn recent_records(
+    state: &AppState,
+    client_id: Uuid,
+    limit: i32,
+    step: Option<i64>,
+) -> AppResult<Vec<Record>> {
+    let Some(step) = step else {
+        return state.db.get_recent_records(client_id, limit).await;
+    };
+    if !(1..=MAX_STEP_SECS).contains(&step) {
+        return Err(AppError::BadRequest(format!(
+            "step must be 1-{} seconds",
+            MAX_STEP_SECS
+        )));
+    }
+
+    let now = Utc::now().timestamp();
+    let until = (now.div_euclid(step) + 1) * step;
+    let since = until - step * i64::from(limit);
+    let to_time = |secs| DateTime::from_timestamp(secs, 0).unwrap_or_default();
+    state
+        .db
+        .get_record_buckets(client_id, to_time(since), to_time(until), step)
+        .await
+}
+
+/// GET /api/recent/:uuid - Get recent records for a client, averaged into
+/// points with `?step=` or streamed as NDJSON with `?stream=true`.
 pub async fn get_recent_records(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<User>>,
     Path(uuid): Path<Uuid>,
     Query(query): Query<RecordsQuery>,
-) -> AppResult<Json<Vec<Record>>> {
-    let records = state.db.get_recent_records(uuid, query.limit).await?;
-    Ok(Json(records))
+) -> AppResult<Response> {
+    let limit = clamp_limit(&state, &user, query.limit);
+    recent_records_response(&state, uuid, limit, query).await
+}
+
+/// Ping task with the clients that have performed it.
+#[derive(Debug, Serialize)]
+pub struct PingTaskWithSources {
+    #[serde(flatten)]
+    pub task: PingTask,
+    /// Visible clients with records of the task, for a source selector.
+    pub sources: Vec<PingTaskSource>,
 }
 
 /// GET /api/ping - Get all ping tasks.
-pub async fn get_ping_tasks(State(state): State<AppState>) -> AppResult<Json<Vec<PingTask>>> {
-    let tasks = state.db.get_all_ping_tasks().await?;
+pub async fn get_ping_tasks(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<PingTaskWithSources>>> {
+    let (tasks, sources) = tokio::try_join!(
+        state.db.get_all_ping_tasks(),
+        state.db.get_ping_task_sources(),
+    )?;
+
+    let mut sources_by_task: HashMap<Uuid, Vec<PingTaskSource>> = HashMap::new();
+    for source in sources {
+        sources_by_task
+            .entry(source.task_id)
+            .or_default()
+            .push(source);
+    }
+    let tasks = tasks
+        .into_iter()
+        .map(|task| PingTaskWithSources {
+            sources: sources_by_task.remove(&task.id).unwrap_or_default(),
+            task,
+        })
+        .collect();
     Ok(Json(tasks))
 }
 
+/// Query params for ping records.
+#[derive(Debug, Deserialize)]
+pub struct PingRecordsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: i32,
+    /// `server` or a client UUID; all sources when absent.
+    pub source: Option<String>,
+}
+
 /// GET /api/ping/:id/records - Get ping records for a task.
 pub async fn get_ping_records(
     State(state): State<AppState>,
+    Extension(user): Extension<Option<User>>,
     Path(id): Path<Uuid>,
-    Query(query): Query<RecordsQuery>,
+    Query(query): Query<PingRecordsQuery>,
 ) -> AppResult<Json<Vec<PingRecord>>> {
-    let records = state.db.get_recent_ping_records(id, query.limit).await?;
+    let limit = clamp_limit(&state, &user, query.limit);
+    let source = match query.source.as_deref() {
+        None => None,
+        Some("server") => Some(PingSource::Server),
+        Some(source) => Some(PingSource::Client(source.parse().map_err(|_| {
+            AppError::BadRequest("source must be \"server\" or a client UUID".into())
+        })?)),
+    };
+    let records = state.db.get_recent_ping_records(id, limit, source).await?;
     Ok(Json(records))
 }
+
+/// GET /api/ping/:id/summary - Get uptime and latency of a ping task.
+pub async fn get_ping_summary(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<PingTaskSummary>> {
+    let raw_retention_days = retention::ping_retention_days(&state).await?;
+    let summary = state
+        .db
+        .get_ping_task_summary(id, raw_retention_days)
+        .await?;
+    Ok(Json(summary))
+}
+
+/// GET /api/monitors - Get monitor groups with their status.
+pub async fn get_monitors(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<MonitorGroupPublic>>> {
+    let groups = state.db.get_all_monitor_groups().await?;
+    let active_silences = silences::active(&state.db).await?;
+
+    let monitors = groups
+        .into_iter()
+        .map(|group| {
+            let silenced = silences::tasks_silenced(&active_silences, &group.task_ids);
+            MonitorGroupPublic {
+                silenced,
+                ..group.into()
+            }
+        })
+        .collect();
+    Ok(Json(monitors))
+}
+
+/// Share link requests allowed per client IP per minute.
+const SHARE_REQUESTS_PER_MINUTE: u32 = 60;
+
+/// Beats allowed per heartbeat token per minute.
+const HEARTBEAT_REQUESTS_PER_MINUTE: u32 = 10;
+
+/// Beats allowed per client IP per minute, across all tokens, so one host
+/// can run many monitors but cannot guess tokens quickly.
+const HEARTBEAT_REQUESTS_PER_IP_PER_MINUTE: u32 = 120;
+
+/// Count a request against a per-key limit of `per_minute` requests.
+fn check_rate_limit<K: Eq + Hash>(
+    limits: &DashMap<K, (Instant, u32)>,
+    key: K,
+    per_minute: u32,
+) -> AppResult<()> {
+    let mut entry = limits.entry(key).or_insert((Instant::now(), 0));
+    let (window_start, count) = entry.value_mut();
+    if window_start.elapsed() >= Duration::from_secs(60) {
+        *window_start = Instant::now();
+        *count = 0;
+    }
+    *count += 1;
+    if *count > per_minute {
+        return Err(AppError::TooManyRequests);
+    }
+    Ok(())
+}
+
+/// Key of a request in per-IP rate limits; requests without a known IP
+/// share one bucket.
+pub fn rate_limit_ip(real_ip: Option<Extension<RealIp>>) -> IpAddr {
+    real_ip.map_or(
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        |Extension(RealIp(ip))| ip.to_canonical(),
+    )
+}
+
+/// Resolve an active share link, applying the per-IP rate limit first so
+/// guessing tokens is limited too.
+pub async fn resolve_share_link(state: &AppState, ip: IpAddr, token: &str) -> AppResult<ShareLink> {
+    check_rate_limit(&state.share_rate_limits, ip, SHARE_REQUESTS_PER_MINUTE)?;
+
+    state
+        .db
+        .find_active_share_link(token)
+        .await?
+        .ok_or(AppError::NotFound("Share link not found".into()))
+}
+
+/// POST /api/heartbeat/:token - Record a beat of a heartbeat monitor.
+pub async fn record_heartbeat(
+    State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    check_rate_limit(
+        &state.heartbeat_ip_rate_limits,
+        rate_limit_ip(real_ip),
+        HEARTBEAT_REQUESTS_PER_IP_PER_MINUTE,
+    )?;
+    check_rate_limit(
+        &state.heartbeat_rate_limits,
+        token.clone(),
+        HEARTBEAT_REQUESTS_PER_MINUTE,
+    )?;
+
+    let beat = state
+        .db
+        .record_heartbeat(&token)
+        .await?
+        .ok_or(AppError::NotFound("Heartbeat not found".into()))?;
+    if beat.previous_status == "down" {
+        // Do not hold up the caller while providers are contacted
+        tokio::spawn(async move {
+            heartbeats::notify_recovered(&state, &beat.heartbeat).await;
+        });
+    }
+    Ok(Json(serde_json::json!({"status": "ok"})))
+}
+
+/// GET /api/heartbeats - Get heartbeats shown on the status page.
+pub async fn get_heartbeats(
+    State(state): State<AppState>,
+) -> AppResult<Json<Vec<HeartbeatPublic>>> {
+    let heartbeats = state.db.get_public_heartbeats().await?;
+    Ok(Json(heartbeats.into_iter().map(Into::into).collect()))
+}
+
+/// Shared client response.
+#[derive(Debug, Serialize)]
+pub struct SharedClient {
+    #[serde(flatten)]
+    pub client: ClientWithStatus,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// GET /api/share/:token - Get a shared client with its current status.
+pub async fn get_shared_client(
+    State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<SharedClient>> {
+    let link = resolve_share_link(&state, rate_limit_ip(real_ip), &token).await?;
+    let client = state
+        .db
+        .find_client_by_id(link.client_id)
+        .await?
+        .ok_or(AppError::NotFound("Share link not found".into()))?;
+
+    let status = if client.online {
+        state
+            .db
+            .get_latest_record(client.id)
+            .await?
+            .map(ClientStatus::from)
+    } else {
+        None
+    };
+
+    let silenced = silences::client_silenced(&silences::active(&state.db).await?, &client);
+
+    Ok(Json(SharedClient {
+        client: ClientWithStatus {
+            client: ClientPublic::new(client, state.runtime().stale_after_secs),
+            status,
+            silenced,
+        },
+        expires_at: link.expires_at,
+    }))
+}
+
+/// GET /api/share/:token/recent - Get recent records for a shared client.
+pub async fn get_shared_recent_records(
+    State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    Path(token): Path<String>,
+    Query(query): Query<RecordsQuery>,
+) -> AppResult<Response> {
+    let link = resolve_share_link(&state, rate_limit_ip(real_ip), &token).await?;
+    let limit = clamp_limit(&state, &None, query.limit);
+    recent_records_response(&state, link.client_id, limit, query).await
+}
+
+/// Announcement banner.
+#[derive(Debug, Serialize)]
+pub struct Announcement {
+    pub active: bool,
+    pub text: String,
+    pub color: String,
+}
+
+/// GET /api/announcement - Get the current announcement banner.
+pub async fn get_announcement(State(state): State<AppState>) -> AppResult<Json<Announcement>> {
+    Ok(Json(load_announcement(&state).await?))
+}
+
+async fn load_announcement(state: &AppState) -> AppResult<Announcement> {
+    let text = setting_str(state, "announcement_text", "").await?;
+    let color = setting_str(state, "announcement_color", "info").await?;
+    let until = state
+        .db
+        .get_setting("announcement_until")
+        .await?
+        .and_then(|v| serde_json::from_value::<DateTime<Utc>>(v).ok());
+
+    let active = !text.is_empty() && until.is_some_and(|until| until > Utc::now());
+
+    Ok(Announcement {
+        active,
+        text: if active { text } else { String::new() },
+        color,
+    })
+}
+
+/// Read a string setting, falling back to `default`.
+async fn setting_str(state: &AppState, key: &str, default: &str) -> AppResult<String> {
+    Ok(state
+        .db
+        .get_setting(key)
+        .await?
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_else(|| default.to_string()))
+}
+
+/// Status timeline query params.
+#[derive(Debug, Deserialize)]
+pub struct StatusTimelineQuery {
+    /// Start of the window (default: 7 days before `to`).
+    pub from: Option<DateTime<Utc>>,
+    /// End of the window (default: now).
+    pub to: Option<DateTime<Utc>>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    /// Events per page (default 50, max 200).
+    pub limit: Option<i64>,
+}
+
+/// GET /api/timeline - Online/offline changes of visible clients, newest
+/// first.
+pub async fn get_status_timeline(
+    State(state): State<AppState>,
+    Query(query): Query<StatusTimelineQuery>,
+) -> AppResult<Json<CursorPage<IncidentEvent>>> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - chrono::Duration::days(7));
+    if from > to {
+        return Err(AppError::BadRequest("from must not be after to".into()));
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let events = state
+        .db
+        .get_public_status_events(from, to, cursor.as_ref(), limit + 1)
+        .await?;
+    Ok(Json(CursorPage::new(events, limit, IncidentEvent::cursor)))
+}
+
+/// Branding settings applied by the frontend at load time.
+#[derive(Debug, Serialize)]
+pub struct PublicSettings {
+    pub site_name: String,
+    pub site_description: String,
+    pub logo_url: String,
+    pub custom_css: String,
+    pub custom_js_url: String,
+    pub announcement: Announcement,
+}
+
+/// GET /api/settings - Get the public branding settings.
+pub async fn get_public_settings(State(state): State<AppState>) -> AppResult<Json<PublicSettings>> {
+    Ok(Json(PublicSettings {
+        site_name: setting_str(&state, "site_name", "Vanmoi").await?,
+        site_description: setting_str(&state, "site_description", "Server Monitoring").await?,
+        logo_url: setting_str(&state, "logo_url", "").await?,
+        custom_css: setting_str(&state, "custom_css", "").await?,
+        custom_js_url: setting_str(&state, "custom_js_url", "").await?,
+        announcement: load_announcement(&state).await?,
+    }))
+}
+
+/// Health check response.
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// `ok`, `degraded` (a replica is unreachable or its circuit is open) or
+    /// `error`.
+    pub status: &'static str,
+    /// Server version, as reported by `/api/version`.
+    pub version: &'static str,
+    pub database: bool,
+    /// Circuit breaker state of the primary pool.
+    pub circuit: CircuitState,
+    pub replicas: Vec<PoolHealth>,
+    /// WebSocket reports waiting for the database.
+    pub buffered_reports: usize,
+    /// Buffered reports dropped because the buffer was full.
+    pub dropped_reports: u64,
+}
+
+/// GET /api/version - Server version and commit.
+pub async fn version() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "version": build_info::VERSION,
+        "commit": build_info::git_commit_short(),
+    }))
+}
+
+/// GET /api/health, /healthz - Check database and replica connectivity.
+pub async fn health(State(state): State<AppState>) -> (StatusCode, Json<HealthResponse>) {
+    let (database, replicas) = state.db.health().await;
+    let circuit = state.db.circuit_state();
+    let (code, status) = if !database || circuit != CircuitState::Closed {
+        (StatusCode::SERVICE_UNAVAILABLE, "error")
+    } else if replicas
+        .iter()
+        .any(|r| !r.ok || r.circuit != CircuitState::Closed)
+    {
+        (StatusCode::OK, "degraded")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+
+    (
+        code,
+        Json(HealthResponse {
+            status,
+            version: build_info::VERSION,
+            database,
+            circuit,
+            replicas,
+            buffered_reports: state.report_buffer.len(),
+            dropped_reports: state.report_buffer.dropped(),
+        }),
+    )
+}
+
+/// Limit on the readiness probe's database query.
+const READY_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// GET /api/health/ready - 200 when the primary database answers a query in
+/// time, for load balancer and orchestrator readiness checks.
+pub async fn ready(State(state): State<AppState>) -> AppResult<Json<serde_json::Value>> {
+    with_timeout(READY_PROBE_TIMEOUT, state.db.ping_primary()).await??;
+    Ok(Json(serde_json::json!({"status": "ready"})))
+}
+
+/// GET /metrics - Prometheus metrics, for scrapers holding `METRICS_TOKEN`.
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> AppResult<Response> {
+    let Some(expected) = &state.config.metrics_token else {
+        return Err(AppError::NotFound("Metrics are disabled".into()));
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !bool::from(given.as_bytes().ct_eq(expected.as_bytes())) {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.http_metrics.render_prometheus(),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fields that are only serialized when non-zero.
+    const OPTIONAL: &[&str] = &[
+        "gpu",
+        "swap",
+        "swap_total",
+        "temp",
+        "process",
+        "connections",
+    ];
+
+    fn status() -> ClientStatus {
+        ClientStatus {
+            cpu: 0.0,
+            gpu: 0.0,
+            ram: 0,
+            ram_total: 0,
+            swap: 0,
+            swap_total: 0,
+            temp: 0.0,
+            disk: 0,
+            disk_total: 0,
+            net_in: 0,
+            net_out: 0,
+            load: 0.0,
+            load5: 0.0,
+            load15: 0.0,
+            uptime: 0,
+            fd_used: 0,
+            fd_total: 0,
+            inode_used: 0,
+            inode_total: 0,
+            process: 0,
+            connections: 0,
+        }
+    }
+
+    #[test]
+    fn zero_optional_metrics_are_omitted() {
+        let value = serde_json::to_value(status()).unwrap();
+        for field in OPTIONAL {
+            assert!(value.get(field).is_none(), "{} serialized", field);
+        }
+        // Core metrics are always present
+        assert_eq!(value["cpu"], 0.0);
+        assert_eq!(value["ram_total"], 0);
+    }
+
+    #[test]
+    fn set_optional_metrics_are_serialized() {
+        let value = serde_json::to_value(ClientStatus {
+            gpu: 12.5,
+            swap: 1,
+            swap_total: 2,
+            temp: 45.0,
+            process: 120,
+            connections: 30,
+            ..status()
+        })
+        .unwrap();
+        for field in OPTIONAL {
+            assert!(value.get(field).is_some(), "{} missing", field);
+        }
+        assert_eq!(value["gpu"], 12.5);
+        assert_eq!(value["connections"], 30);
+    }
+}
@@ -0,0 +1,141 @@
+//! Buffer for agent reports received while the database is unavailable.
+//!
+//! While the primary's circuit is open, WebSocket reports are queued here
+//! with the time they arrived instead of being rejected. HTTP reports are
+//! not buffered: they get a 503 with `Retry-After` and the agent resends
+//! them. The queue holds at most [`MAX_BUFFERED_REPORTS`]; beyond that the
+//! oldest report is dropped and counted. Once the circuit closes,
+//! [`flush`] stores the queued reports in arrival order with their
+//! `recorded_at`, or their arrival time without one.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::db::{CircuitState, RecordInput};
+use crate::error::{AppError, AppResult};
+
+/// Reports kept at most.
+pub const MAX_BUFFERED_REPORTS: usize = 10_000;
+
+/// A report waiting to be stored.
+#[derive(Debug)]
+struct BufferedReport {
+    client_id: Uuid,
+    record: RecordInput,
+    received_at: DateTime<Utc>,
+}
+
+/// Bounded queue of reports waiting for the database.
+#[derive(Debug, Default)]
+pub struct ReportBuffer {
+    queue: Mutex<VecDeque<BufferedReport>>,
+    dropped: AtomicU64,
+}
+
+impl ReportBuffer {
+    /// Queue a report, dropping the oldest one when full.
+    pub fn push(&self, client_id: Uuid, record: RecordInput, received_at: DateTime<Utc>) {
+        let mut queue = self.lock();
+        queue.push_back(BufferedReport {
+            client_id,
+            record,
+            received_at,
+        });
+        self.trim(&mut queue);
+    }
+
+    /// Reports waiting to be stored.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no reports are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Reports dropped because the buffer was full, since startup.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn take(&self) -> VecDeque<BufferedReport> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Put reports back in front of those queued since they were taken.
+    fn requeue(&self, reports: impl DoubleEndedIterator<Item = BufferedReport>) {
+        let mut queue = self.lock();
+        for report in reports.rev() {
+            queue.push_front(report);
+        }
+        self.trim(&mut queue);
+    }
+
+    fn trim(&self, queue: &mut VecDeque<BufferedReport>) {
+        while queue.len() > MAX_BUFFERED_REPORTS {
+            queue.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<BufferedReport>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Store the buffered reports once the primary's circuit is closed.
+/// Returns the number of reports stored.
+///
+/// If the database fails again, the remaining reports are requeued. A report
+/// that cannot be stored for another reason, e.g. its client was deleted,
+/// is dropped.
+pub async fn flush(state: &AppState) -> AppResult<usize> {
+    if state.db.circuit_state() != CircuitState::Closed || state.report_buffer.is_empty() {
+        return Ok(0);
+    }
+
+    let mut pending = state.report_buffer.take().into_iter();
+    let mut stored = 0;
+    while let Some(report) = pending.next() {
+        match state
+            .db
+            .insert_record_at(
+                report.client_id,
+                &report.record,
+                report.record.recorded_at.or(Some(report.received_at)),
+            )
+            .await
+        {
+            Ok(_) => stored += 1,
+            Err(e) if is_unavailable(state, &e) => {
+                state
+                    .report_buffer
+                    .requeue(std::iter::once(report).chain(pending));
+                return Err(e);
+            }
+            Err(e) => warn!(
+                client_id = %report.client_id,
+                error = %e,
+                "Dropped buffered report"
+            ),
+        }
+    }
+
+    info!(
+        "Stored {} reports buffered while the database was down",
+        stored
+    );
+    Ok(stored)
+}
+
+fn is_unavailable(state: &AppState, error: &AppError) -> bool {
+    matches!(error, AppError::ServiceUnavailable(_))
+        || state.db.circuit_state() != CircuitState::Closed
+}
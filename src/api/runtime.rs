@@ -0,0 +1,138 @@
+//! Runtime settings.
+//!
+//! Settings that hot paths and background tasks consult on every operation
+//! are loaded from the settings table into an immutable [`RuntimeSettings`]
+//! snapshot. The snapshot is swapped atomically when an admin changes the
+//! settings, on `POST /api/admin/settings/reload` and on every maintenance
+//! run, so changes take effect without restarting the server or its tasks.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use super::AppState;
+use crate::anomaly::AnomalySettings;
+use crate::config::Config;
+use crate::db::{Database, REPORT_TIMEOUT_SECS, STALE_AFTER_SECS};
+use crate::error::AppResult;
+
+/// Default for the `public_max_records` setting.
+pub const DEFAULT_PUBLIC_MAX_RECORDS: i32 = 1440;
+
+/// Default for the `admin_max_records` setting.
+pub const DEFAULT_ADMIN_MAX_RECORDS: i32 = 10000;
+
+/// Snapshot of the settings read per operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeSettings {
+    /// Locale of server-generated notifications without their own
+    /// `notification_locale`.
+    pub locale: String,
+    /// Record limit for anonymous visitors.
+    pub public_max_records: i32,
+    /// Record limit for logged-in admins.
+    pub admin_max_records: i32,
+    /// Reports accepted per client per minute.
+    pub max_reports_per_minute: u32,
+    /// Seconds without a report after which an offline client is stale.
+    pub stale_after_secs: i64,
+    /// Seconds without a report after which an online client is treated as
+    /// offline.
+    pub report_timeout_secs: i64,
+    /// Seconds between reports advised to agents without their own
+    /// interval; `None` leaves the interval to the agent.
+    pub report_interval_seconds: Option<u32>,
+    pub anomaly: AnomalySettings,
+    /// Report the server's own host as the built-in client.
+    pub self_monitor: bool,
+}
+
+impl RuntimeSettings {
+    /// Settings used before the first load; `NOTIFICATION_LOCALE` and
+    /// `MAX_REPORTS_PER_MINUTE` are the defaults of `locale` and
+    /// `max_reports_per_minute`.
+    pub fn defaults(config: &Config) -> Self {
+        Self {
+            locale: config.notification_locale.clone(),
+            public_max_records: DEFAULT_PUBLIC_MAX_RECORDS,
+            admin_max_records: DEFAULT_ADMIN_MAX_RECORDS,
+            max_reports_per_minute: config.max_reports_per_minute,
+            stale_after_secs: STALE_AFTER_SECS,
+            report_timeout_secs: REPORT_TIMEOUT_SECS,
+            report_interval_seconds: None,
+            anomaly: AnomalySettings::default(),
+            self_monitor: false,
+        }
+    }
+
+    /// Load the settings, falling back to defaults for unset keys.
+    pub async fn load(db: &Database, config: &Config) -> AppResult<Self> {
+        let defaults = Self::defaults(config);
+        let int = |value: Option<serde_json::Value>, default: i64| {
+            value.and_then(|v| v.as_i64()).unwrap_or(default)
+        };
+
+        Ok(Self {
+            locale: db
+                .get_setting("locale")
+                .await?
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or(defaults.locale),
+            public_max_records: int(
+                db.get_setting("public_max_records").await?,
+                defaults.public_max_records.into(),
+            ) as i32,
+            admin_max_records: int(
+                db.get_setting("admin_max_records").await?,
+                defaults.admin_max_records.into(),
+            ) as i32,
+            max_reports_per_minute: int(
+                db.get_setting("max_reports_per_minute").await?,
+                defaults.max_reports_per_minute.into(),
+            ) as u32,
+            stale_after_secs: int(
+                db.get_setting("stale_after_secs").await?,
+                defaults.stale_after_secs,
+            ),
+            report_timeout_secs: int(
+                db.get_setting("report_timeout_secs").await?,
+                defaults.report_timeout_secs,
+            ),
+            report_interval_seconds: db
+                .get_setting("report_interval_seconds")
+                .await?
+                .and_then(|v| v.as_u64())
+                .map(|secs| secs as u32),
+            anomaly: db
+                .get_setting("anomaly")
+                .await?
+                .and_then(|v| serde_json::from_value(v).ok())
+                .unwrap_or_default(),
+            self_monitor: db
+                .get_setting("self_monitor")
+                .await?
+                .and_then(|v| v.as_bool())
+                .unwrap_or(defaults.self_monitor),
+        })
+    }
+}
+
+impl AppState {
+    /// The current runtime settings snapshot.
+    pub fn runtime(&self) -> Arc<RuntimeSettings> {
+        self.runtime.load_full()
+    }
+
+    /// Reload the runtime settings from the database and publish them.
+    ///
+    /// Connected agents are told to recheck their report interval when the
+    /// global one changed.
+    pub async fn reload_runtime_settings(&self) -> AppResult<Arc<RuntimeSettings>> {
+        let settings = Arc::new(RuntimeSettings::load(&self.db, &self.config).await?);
+        let previous = self.runtime.swap(settings.clone());
+        if previous.report_interval_seconds != settings.report_interval_seconds {
+            self.ws_agents.settings_changed(None);
+        }
+        Ok(settings)
+    }
+}
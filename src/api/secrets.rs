@@ -0,0 +1,162 @@
+//! Write-only secret fields.
+//!
+//! API responses replace secret values (notification credentials, bot
+//! tokens, storage keys) with [`SENTINEL`]. Clients send the sentinel back
+//! to keep the stored value, or a new value to replace it. Stored configs
+//! are never masked, so the notifier always reads the real values.
+
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+
+pub use crate::db::encryption::notification_secret_fields;
+
+/// Placeholder returned instead of a set secret.
+pub const SENTINEL: &str = "••••";
+
+/// Call `f` with the name and value of each secret field present in `value`.
+fn visit(
+    value: &mut Value,
+    fields: &[&str],
+    mut f: impl FnMut(&str, Option<&str>, &mut Value) -> AppResult<()>,
+) -> AppResult<()> {
+    for field in fields {
+        if let Some(base) = field.strip_suffix(".*") {
+            if let Some(object) = value.get_mut(base).and_then(Value::as_object_mut) {
+                for (key, v) in object.iter_mut() {
+                    f(base, Some(key), v)?;
+                }
+            }
+        } else if let Some(v) = value.get_mut(*field) {
+            f(field, None, v)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the non-empty secret fields of a config with the sentinel.
+pub fn mask(value: &mut Value, fields: &[&str]) {
+    let _ = visit(value, fields, |_, _, v| {
+        if v.as_str().is_some_and(|s| !s.is_empty()) {
+            *v = Value::String(SENTINEL.to_string());
+        }
+        Ok(())
+    });
+}
+
+/// Replace sentinel values in an updated config with the stored values.
+///
+/// Fails when a sentinel has no stored value to stand for, so the sentinel
+/// itself is never saved.
+pub fn restore(value: &mut Value, stored: Option<&Value>, fields: &[&str]) -> AppResult<()> {
+    visit(value, fields, |base, key, v| {
+        if v.as_str() != Some(SENTINEL) {
+            return Ok(());
+        }
+        let previous = stored
+            .and_then(|s| s.get(base))
+            .and_then(|s| match key {
+                Some(key) => s.get(key),
+                None => Some(s),
+            })
+            .filter(|s| s.is_string());
+        match previous {
+            Some(previous) => {
+                *v = previous.clone();
+                Ok(())
+            }
+            None => Err(AppError::BadRequest(format!(
+                "No stored value for secret field: {}",
+                key.unwrap_or(base)
+            ))),
+        }
+    })
+}
+
+/// The sentinel for a set secret string, or the empty string.
+pub fn mask_str(secret: &str) -> String {
+    if secret.is_empty() {
+        String::new()
+    } else {
+        SENTINEL.to_string()
+    }
+}
+
+/// Keep the stored secret when an update sends the sentinel back.
+///
+/// Like [`restore`], fails when nothing is stored for `field`.
+pub fn restore_str(secret: &mut String, stored: &str, field: &str) -> AppResult<()> {
+    if secret != SENTINEL {
+        return Ok(());
+    }
+    if stored.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "No stored value for secret field: {}",
+            field
+        )));
+    }
+    *secret = stored.to_string();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const FIELDS: &[&str] = &["token", "headers.*"];
+
+    #[test]
+    fn mask_restore_round_trip() {
+        let stored = json!({
+            "url": "https://example.com",
+            "token": "t0ken",
+            "headers": {"Authorization": "Bearer x", "X-Empty": ""},
+        });
+        let mut masked = stored.clone();
+        mask(&mut masked, FIELDS);
+        assert_eq!(masked["url"], "https://example.com");
+        assert_eq!(masked["token"], SENTINEL);
+        assert_eq!(masked["headers"]["Authorization"], SENTINEL);
+        assert_eq!(masked["headers"]["X-Empty"], "");
+
+        restore(&mut masked, Some(&stored), FIELDS).unwrap();
+        assert_eq!(masked, stored);
+    }
+
+    #[test]
+    fn restore_keeps_new_values() {
+        let stored = json!({"token": "old"});
+        let mut update = json!({"token": "new"});
+        restore(&mut update, Some(&stored), FIELDS).unwrap();
+        assert_eq!(update["token"], "new");
+    }
+
+    #[test]
+    fn restore_rejects_sentinel_without_stored_value() {
+        let mut update = json!({"token": SENTINEL});
+        assert!(restore(&mut update, None, FIELDS).is_err());
+        let mut update = json!({"headers": {"Authorization": SENTINEL}});
+        assert!(restore(&mut update, Some(&json!({"headers": {}})), FIELDS).is_err());
+    }
+
+    #[test]
+    fn mask_restore_str_round_trip() {
+        assert_eq!(mask_str(""), "");
+        let mut secret = mask_str("s3cret");
+        assert_eq!(secret, SENTINEL);
+        restore_str(&mut secret, "s3cret", "secret_key").unwrap();
+        assert_eq!(secret, "s3cret");
+
+        let mut secret = "replacement".to_string();
+        restore_str(&mut secret, "s3cret", "secret_key").unwrap();
+        assert_eq!(secret, "replacement");
+    }
+
+    #[test]
+    fn restore_str_rejects_sentinel_without_stored_value() {
+        let mut secret = SENTINEL.to_string();
+        assert!(restore_str(&mut secret, "", "bot_token").is_err());
+        assert_eq!(secret, SENTINEL);
+    }
+}
@@ -0,0 +1,184 @@
+//! Embeddable status widget.
+//!
+//! Self-contained HTML pages meant to be loaded in an iframe. The page is
+//! rendered on the server and refreshes itself from the public API, so it
+//! works without the SPA bundle.
+
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::public::{ClientStatus, ClientWithStatus, rate_limit_ip, resolve_share_link};
+use crate::api::AppState;
+use crate::db::{Client, ClientPublic};
+use crate::error::{AppError, AppResult};
+use crate::middleware::RealIp;
+use crate::silences;
+
+/// Seconds between widget refreshes.
+const REFRESH_SECS: u32 = 30;
+
+/// Widget query params.
+#[derive(Debug, Deserialize)]
+pub struct WidgetQuery {
+    /// Share token allowing a hidden client to be shown.
+    pub share: Option<String>,
+}
+
+/// GET /widget/:uuid - Status widget for one client.
+pub async fn client_widget(
+    State(state): State<AppState>,
+    real_ip: Option<Extension<RealIp>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<WidgetQuery>,
+) -> AppResult<Response> {
+    let not_found = || AppError::NotFound("Client not found".into());
+
+    let refresh_url = match &query.share {
+        Some(token) => {
+            let link = resolve_share_link(&state, rate_limit_ip(real_ip), token).await?;
+            if link.client_id != id {
+                return Err(not_found());
+            }
+            format!("/api/share/{}", token)
+        }
+        None => "/api/clients".to_string(),
+    };
+
+    let client = state
+        .db
+        .find_client_by_id(id)
+        .await?
+        .ok_or_else(not_found)?;
+    if client.hidden && query.share.is_none() {
+        return Err(not_found());
+    }
+
+    let clients = vec![with_status(&state, client).await?];
+    Ok(widget_response(&state, &clients, &refresh_url))
+}
+
+/// GET /widget/group/:name - Status widget for the visible clients of a group.
+pub async fn group_widget(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> AppResult<Response> {
+    let mut clients = Vec::new();
+    for client in state.db.get_visible_clients().await? {
+        if client.group_name == name {
+            clients.push(with_status(&state, client).await?);
+        }
+    }
+    if clients.is_empty() {
+        return Err(AppError::NotFound("Group not found".into()));
+    }
+
+    Ok(widget_response(&state, &clients, "/api/clients"))
+}
+
+async fn with_status(state: &AppState, client: Client) -> AppResult<ClientWithStatus> {
+    let status = if client.online {
+        state
+            .db
+            .get_latest_record(client.id)
+            .await?
+            .map(ClientStatus::from)
+    } else {
+        None
+    };
+    let silenced = silences::client_silenced(&silences::active(&state.db).await?, &client);
+    Ok(ClientWithStatus {
+        client: ClientPublic::new(client, state.runtime().stale_after_secs),
+        status,
+        silenced,
+    })
+}
+
+fn widget_response(state: &AppState, clients: &[ClientWithStatus], refresh_url: &str) -> Response {
+    let csp = format!(
+        "default-src 'none'; style-src 'unsafe-inline'; script-src 'unsafe-inline'; \
+         connect-src 'self'; frame-ancestors {}",
+        state.config.widget_frame_ancestors
+    );
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            (header::CONTENT_SECURITY_POLICY, csp),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        render_widget(clients, refresh_url),
+    )
+        .into_response()
+}
+
+/// Render the widget page.
+fn render_widget(clients: &[ClientWithStatus], refresh_url: &str) -> String {
+    let mut rows = String::new();
+    for c in clients {
+        let (cpu, ram) = c
+            .status
+            .as_ref()
+            .map(|s| (s.cpu as f64, percent(s.ram, s.ram_total)))
+            .unwrap_or((0.0, 0.0));
+        let state = serde_json::to_value(c.client.online_status)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        rows.push_str(&format!(
+            r#"<div class="c" id="c-{id}"><b>{name}</b> <span class="s {state}">{state_text}</span>
+<div class="m">CPU <span class="b"><i class="cpu" style="width:{cpu:.0}%"></i></span></div>
+<div class="m">RAM <span class="b"><i class="ram" style="width:{ram:.0}%"></i></span></div></div>
+"#,
+            id = c.client.id,
+            name = escape_html(&c.client.name),
+            state = state,
+            state_text = state.replace('_', " "),
+            cpu = cpu.clamp(0.0, 100.0),
+            ram = ram.clamp(0.0, 100.0),
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width">
+<style>
+body{{margin:0;padding:8px;font:13px system-ui,sans-serif;color:#222;background:#fff}}
+.c{{margin-bottom:8px}}.s{{font-size:11px;padding:0 4px;border-radius:3px;color:#fff;background:#999}}
+.online{{background:#2a2}}.recently_offline{{background:#d80}}.stale{{background:#c33}}
+.m{{display:flex;align-items:center;gap:6px;font-size:11px}}
+.b{{flex:1;height:6px;background:#eee;border-radius:3px;overflow:hidden}}
+.b i{{display:block;height:100%;background:#48c}}
+</style></head><body>
+{rows}<script>
+setInterval(async()=>{{try{{const d=await(await fetch({url})).json();
+for(const c of d.clients||[d]){{const e=document.getElementById("c-"+c.id);if(!e)continue;
+const s=e.querySelector(".s");s.className="s "+c.online_status;s.textContent=c.online_status.replace("_"," ");
+const t=c.status||{{cpu:0,ram:0,ram_total:0}};
+e.querySelector(".cpu").style.width=Math.min(100,t.cpu)+"%";
+e.querySelector(".ram").style.width=(t.ram_total?Math.min(100,t.ram*100/t.ram_total):0)+"%";}}}}catch(_){{}}}},{refresh}000);
+</script></body></html>
+"#,
+        rows = rows,
+        url = serde_json::to_string(refresh_url).unwrap_or_default(),
+        refresh = REFRESH_SECS,
+    )
+}
+
+fn percent(used: i64, total: i64) -> f64 {
+    if total > 0 {
+        used as f64 * 100.0 / total as f64
+    } else {
+        0.0
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
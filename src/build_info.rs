@@ -0,0 +1,53 @@
+//! Build metadata embedded by `build.rs`.
+//!
+//! Every place that reports the server version reads these constants, so
+//! the startup banner, `/api/version` and the health check cannot drift.
+
+use chrono::{DateTime, Utc};
+
+/// Package version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Full commit hash, or `unknown` when built outside a git checkout
+/// without `GIT_COMMIT`.
+pub const GIT_COMMIT: &str = env!("VANMOI_GIT_COMMIT");
+
+/// Output of `rustc --version` for the compiler that built the server.
+pub const RUSTC_VERSION: &str = env!("VANMOI_RUSTC_VERSION");
+
+/// Unix seconds the build script last ran at.
+const BUILD_EPOCH: &str = env!("VANMOI_BUILD_EPOCH");
+
+/// Commit hash shortened to 7 characters.
+pub fn git_commit_short() -> &'static str {
+    GIT_COMMIT.get(..7).unwrap_or(GIT_COMMIT)
+}
+
+/// When the server was built.
+pub fn build_timestamp() -> Option<DateTime<Utc>> {
+    BUILD_EPOCH
+        .parse()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
+
+/// Cargo features the server was built with.
+pub fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "integration") {
+        features.push("integration");
+    }
+    features
+}
+
+/// One-line build description for the startup banner.
+pub fn banner() -> String {
+    let built = build_timestamp().map_or_else(|| "unknown".to_string(), |t| t.to_rfc3339());
+    format!(
+        "Vanmoi {} (commit {}, built {}, {})",
+        VERSION,
+        git_commit_short(),
+        built,
+        RUSTC_VERSION
+    )
+}
@@ -0,0 +1,97 @@
+//! Command-line subcommands.
+//!
+//! Running `vanmoi` without arguments starts the server. Subcommands:
+//!
+//! - `vanmoi secrets rotate`: re-encrypt every stored secret from
+//!   `OLD_SECRET_KEY` (unset for plaintext secrets) to `NEW_SECRET_KEY`.
+//!   Stop the server first, then restart it with `SECRET_KEY` set to the new
+//!   key. Keys are read from the environment so they stay out of shell
+//!   history and process listings.
+//! - `vanmoi db check [--fix]`: list inconsistent rows, such as clients
+//!   stuck online or ping records of deleted tasks, and with `--fix` correct
+//!   them in one transaction. Exits with an error when problems are left.
+
+use std::env;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::db::encryption::SecretCipher;
+
+const USAGE: &str = "Usage: vanmoi [secrets rotate | db check [--fix]]";
+
+/// Run the subcommand given by `args`.
+pub async fn run(args: &[String]) -> Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["secrets", "rotate"] => rotate_secrets().await,
+        ["db", "check"] => check_db(false).await,
+        ["db", "check", "--fix"] => check_db(true).await,
+        _ => bail!("Unknown command: {}\n{}", args.join(" "), USAGE),
+    }
+}
+
+/// Re-encrypt stored secrets under a new key.
+async fn rotate_secrets() -> Result<()> {
+    let old_key = env::var("OLD_SECRET_KEY").ok().filter(|v| !v.is_empty());
+    let new_key = env::var("NEW_SECRET_KEY")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .context("NEW_SECRET_KEY must be set")?;
+
+    let config = Config::from_env()?;
+    let db = Database::connect(
+        &config.database_url,
+        &[],
+        Duration::from_secs(config.db_query_timeout_secs),
+    )
+    .await?
+    .with_secret_key(old_key.as_deref());
+    db.init_schema().await?;
+
+    let rewritten = db
+        .reencrypt_secrets(Some(&SecretCipher::new(&new_key)), true)
+        .await
+        .context("Secret rotation failed, nothing was changed")?;
+    info!(
+        "Re-encrypted secrets in {} rows, restart the server with SECRET_KEY set to the new key",
+        rewritten
+    );
+
+    Ok(())
+}
+
+/// Check the database for inconsistent rows, optionally fixing them.
+async fn check_db(fix: bool) -> Result<()> {
+    let config = Config::from_env()?;
+    let db = Database::connect(
+        &config.database_url,
+        &[],
+        Duration::from_secs(config.db_query_timeout_secs),
+    )
+    .await?;
+    db.init_schema().await?;
+
+    let report = db.check_consistency(fix).await?;
+    for finding in &report.findings {
+        println!(
+            "{} {}: {} (fix: {})",
+            finding.kind, finding.subject, finding.detail, finding.fix
+        );
+    }
+    if report.findings.is_empty() {
+        info!("No problems found");
+    } else if report.fixed {
+        info!("Fixed {} problems", report.findings.len());
+    } else {
+        bail!(
+            "Found {} problems, run `vanmoi db check --fix` to correct them",
+            report.findings.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Database circuit breaker.
+//!
+//! When PostgreSQL is overloaded or unreachable, every query waits for the
+//! pool timeout. After [`FAILURE_THRESHOLD`] consecutive connection-level
+//! errors within [`FAILURE_WINDOW`], the circuit opens and queries fail
+//! immediately instead. A background task probes the database with
+//! `SELECT 1` after an exponentially growing backoff and closes the circuit
+//! once a probe succeeds.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use serde::Serialize;
+use sqlx::postgres::{PgArguments, PgQueryResult, PgRow, PgStatement, PgTypeInfo};
+use sqlx::query::QueryAs;
+use sqlx::{Describe, Either, Execute, Executor, FromRow, PgPool, Postgres};
+use tracing::warn;
+
+use crate::error::{AppError, AppResult};
+
+/// Consecutive failures that open the circuit.
+const FAILURE_THRESHOLD: u32 = 5;
+
+/// Failures older than this no longer count towards the threshold.
+const FAILURE_WINDOW: Duration = Duration::from_secs(30);
+
+/// Delay before the first probe after the circuit opens.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound of the probe backoff.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Circuit breaker state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Queries run normally.
+    Closed,
+    /// Queries fail immediately while the database recovers.
+    Open,
+    /// A probe query is in flight; queries still fail immediately.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    circuit: CircuitState,
+    consecutive_failures: u32,
+    /// When the current run of consecutive failures started.
+    first_failure: Option<Instant>,
+}
+
+/// Circuit breaker guarding one connection pool.
+#[derive(Debug)]
+pub struct DatabaseCircuitBreaker {
+    /// Pool name used in log messages.
+    name: String,
+    /// Pool the recovery probe runs against.
+    pool: PgPool,
+    /// Consecutive failures that open the circuit.
+    threshold: u32,
+    state: Mutex<BreakerState>,
+}
+
+impl DatabaseCircuitBreaker {
+    pub fn new(name: String, pool: PgPool) -> Arc<Self> {
+        Self::with_threshold(name, pool, FAILURE_THRESHOLD)
+    }
+
+    /// Breaker opening after `threshold` consecutive failures.
+    pub fn with_threshold(name: String, pool: PgPool, threshold: u32) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            pool,
+            threshold: threshold.max(1),
+            state: Mutex::new(BreakerState {
+                circuit: CircuitState::Closed,
+                consecutive_failures: 0,
+                first_failure: None,
+            }),
+        })
+    }
+
+    /// Current circuit state.
+    pub fn state(&self) -> CircuitState {
+        self.lock().circuit
+    }
+
+    /// Fail fast unless the circuit is closed.
+    pub fn check(&self) -> AppResult<()> {
+        match self.state() {
+            CircuitState::Closed => Ok(()),
+            _ => Err(AppError::ServiceUnavailable("Database circuit open".into())),
+        }
+    }
+
+    /// Record the outcome of a query, opening the circuit on repeated failures.
+    fn record(self: &Arc<Self>, error: Option<&sqlx::Error>) {
+        let mut state = self.lock();
+        if !error.is_some_and(is_connection_error) {
+            state.consecutive_failures = 0;
+            state.first_failure = None;
+            return;
+        }
+
+        let now = Instant::now();
+        if state
+            .first_failure
+            .is_none_or(|t| now.duration_since(t) > FAILURE_WINDOW)
+        {
+            state.consecutive_failures = 0;
+            state.first_failure = Some(now);
+        }
+        state.consecutive_failures += 1;
+
+        if state.circuit == CircuitState::Closed && state.consecutive_failures >= self.threshold {
+            state.circuit = CircuitState::Open;
+            warn!(
+                "Database circuit for {} opened after {} consecutive errors",
+                self.name, state.consecutive_failures
+            );
+            tokio::spawn(Arc::clone(self).recover());
+        }
+    }
+
+    /// Open the circuit of a database found unreachable outside a query.
+    pub fn trip(self: &Arc<Self>) {
+        let mut state = self.lock();
+        if state.circuit != CircuitState::Closed {
+            return;
+        }
+        state.circuit = CircuitState::Open;
+        warn!("Database circuit for {} opened, unreachable", self.name);
+        tokio::spawn(Arc::clone(self).recover());
+    }
+
+    /// Probe the database with growing backoff until it answers, then close
+    /// the circuit.
+    async fn recover(self: Arc<Self>) {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            tokio::time::sleep(backoff).await;
+
+            self.transition(CircuitState::HalfOpen);
+            warn!("Database circuit for {} half-open, probing", self.name);
+            if sqlx::query("SELECT 1").execute(&self.pool).await.is_ok() {
+                let mut state = self.lock();
+                state.circuit = CircuitState::Closed;
+                state.consecutive_failures = 0;
+                state.first_failure = None;
+                warn!("Database circuit for {} closed", self.name);
+                return;
+            }
+
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            self.transition(CircuitState::Open);
+            warn!(
+                "Database probe for {} failed, circuit open, retrying in {}s",
+                self.name,
+                backoff.as_secs()
+            );
+        }
+    }
+
+    fn transition(&self, circuit: CircuitState) {
+        self.lock().circuit = circuit;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Errors that indicate an overloaded or unreachable database, as opposed to
+/// errors in the query itself.
+pub fn is_connection_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::Protocol(_)
+        | sqlx::Error::WorkerCrashed => true,
+        // Connection exceptions, insufficient resources, operator intervention
+        // except a query cancelled by the statement timeout
+        sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+            code != "57014" && ["08", "53", "57"].iter().any(|c| code.starts_with(c))
+        }),
+        _ => false,
+    }
+}
+
+/// A pool whose queries are gated by and reported to a circuit breaker.
+#[derive(Debug, Clone)]
+pub struct GuardedPool {
+    pool: PgPool,
+    breaker: Arc<DatabaseCircuitBreaker>,
+}
+
+impl GuardedPool {
+    /// Guard `pool`, failing fast if its circuit is open.
+    pub fn new(pool: &PgPool, breaker: &Arc<DatabaseCircuitBreaker>) -> AppResult<Self> {
+        breaker.check()?;
+        Ok(Self {
+            pool: pool.clone(),
+            breaker: Arc::clone(breaker),
+        })
+    }
+
+    /// Stream the rows of `query` as they arrive instead of buffering them
+    /// like the [`Executor`] implementation does. The stream borrows the
+    /// pool.
+    pub fn fetch_stream<'q, T>(
+        &'q self,
+        query: QueryAs<'q, Postgres, T, PgArguments>,
+    ) -> BoxStream<'q, Result<T, sqlx::Error>>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin + 'q,
+    {
+        query
+            .fetch(&self.pool)
+            .inspect(|result| self.breaker.record(result.as_ref().err()))
+            .boxed()
+    }
+}
+
+impl<'c> Executor<'c> for GuardedPool {
+    type Database = Postgres;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<PgQueryResult, PgRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        let Self { pool, breaker } = self;
+        fetch_many_owned(pool, query)
+            .inspect(move |result| breaker.record(result.as_ref().err()))
+            .boxed()
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxFuture<'e, Result<Option<PgRow>, sqlx::Error>>
+    where
+        'c: 'e,
+        E: 'q + Execute<'q, Postgres>,
+    {
+        let Self { pool, breaker } = self;
+        async move {
+            let result = pool.fetch_optional(query).await;
+            breaker.record(result.as_ref().err());
+            result
+        }
+        .boxed()
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [PgTypeInfo],
+    ) -> BoxFuture<'e, Result<PgStatement<'q>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        async move { self.pool.prepare_with(sql, parameters).await }.boxed()
+    }
+
+    fn describe<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+    ) -> BoxFuture<'e, Result<Describe<Postgres>, sqlx::Error>>
+    where
+        'c: 'e,
+    {
+        async move { self.pool.describe(sql).await }.boxed()
+    }
+}
+
+/// Run `query` on an owned pool handle, so the stream does not borrow it.
+///
+/// Results are buffered; use [`GuardedPool::fetch_stream`] to stream rows.
+fn fetch_many_owned<'e, 'q: 'e, E>(
+    pool: PgPool,
+    query: E,
+) -> BoxStream<'e, Result<Either<PgQueryResult, PgRow>, sqlx::Error>>
+where
+    E: 'q + Execute<'q, Postgres>,
+{
+    futures::stream::once(async move { pool.fetch_many(query).collect::<Vec<_>>().await })
+        .flat_map(futures::stream::iter)
+        .boxed()
+}
@@ -0,0 +1,290 @@
+//! Consistency checks of stored data.
+//!
+//! An unclean shutdown or a dump restored without its constraints can leave
+//! rows the server never writes itself: clients still flagged online long
+//! after their last report, ping records of deleted tasks, sessions of
+//! deleted users, notification configs their provider cannot read, and
+//! settings whose values have the wrong JSON type. The server runs the
+//! checks on startup and `vanmoi db check [--fix]` runs them on demand.
+//!
+//! With `fix`, each finding is corrected in one transaction: stale clients
+//! are marked offline, orphaned rows are deleted, broken notifications are
+//! disabled (not deleted, so their config can still be repaired) and
+//! invalid settings are removed so their defaults apply again.
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{Database, REPORT_TIMEOUT_SECS};
+use crate::error::AppResult;
+
+/// Expected JSON type of a setting value.
+#[derive(Debug, Clone, Copy)]
+enum SettingKind {
+    String,
+    Integer,
+    Bool,
+    Object,
+}
+
+impl SettingKind {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            Self::String => value.is_string(),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Bool => value.is_boolean(),
+            Self::Object => value.is_object(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::String => "a string",
+            Self::Integer => "an integer",
+            Self::Bool => "a boolean",
+            Self::Object => "an object",
+        }
+    }
+}
+
+/// Settings written by the admin API, with the type of their value. `null`
+/// is accepted for every key and means unset.
+const KNOWN_SETTINGS: &[(&str, SettingKind)] = &[
+    ("site_name", SettingKind::String),
+    ("site_description", SettingKind::String),
+    ("logo_url", SettingKind::String),
+    ("custom_css", SettingKind::String),
+    ("custom_js_url", SettingKind::String),
+    ("locale", SettingKind::String),
+    ("timezone", SettingKind::String),
+    ("telegram_bot", SettingKind::Object),
+    ("announcement_text", SettingKind::String),
+    ("announcement_color", SettingKind::String),
+    ("announcement_until", SettingKind::String),
+    ("public_max_records", SettingKind::Integer),
+    ("admin_max_records", SettingKind::Integer),
+    ("max_reports_per_minute", SettingKind::Integer),
+    ("stale_after_secs", SettingKind::Integer),
+    ("report_timeout_secs", SettingKind::Integer),
+    ("report_interval_seconds", SettingKind::Integer),
+    ("anomaly", SettingKind::Object),
+    ("self_monitor", SettingKind::Bool),
+    ("record_retention_days", SettingKind::Integer),
+    ("ping_retention_days", SettingKind::Integer),
+    ("retain_archived_records", SettingKind::Bool),
+    ("default_notification_id", SettingKind::String),
+    ("password_login_enabled", SettingKind::Bool),
+    ("digest", SettingKind::Object),
+    ("archive", SettingKind::Object),
+    ("object_storage", SettingKind::Object),
+    ("security_alerts", SettingKind::Object),
+];
+
+/// A problem found by [`Database::check_consistency`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsistencyFinding {
+    /// `stale_online_client`, `orphaned_ping_records`, `orphaned_sessions`,
+    /// `invalid_notification_config` or `invalid_setting`.
+    pub kind: &'static str,
+    /// The affected row: a client, task, user or notification ID, or a
+    /// setting key.
+    pub subject: String,
+    pub detail: String,
+    /// Correction applied with `fix`, or that `fix` would apply.
+    pub fix: String,
+}
+
+/// Result of a consistency check.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsistencyReport {
+    pub findings: Vec<ConsistencyFinding>,
+    /// Whether the corrections were applied.
+    pub fixed: bool,
+}
+
+impl Database {
+    /// Look for inconsistent rows, correcting them when `fix` is set.
+    ///
+    /// Clients are stale when they are flagged online but have not reported
+    /// within the `report_timeout_secs` setting.
+    pub async fn check_consistency(&self, fix: bool) -> AppResult<ConsistencyReport> {
+        let report_timeout_secs = self
+            .get_setting("report_timeout_secs")
+            .await?
+            .and_then(|v| v.as_i64())
+            .unwrap_or(REPORT_TIMEOUT_SECS);
+
+        let mut tx = self.pool.begin().await?;
+        let mut findings = Vec::new();
+
+        let stale: Vec<(Uuid, String, Option<chrono::DateTime<chrono::Utc>>)> = sqlx::query_as(
+            r#"
+            SELECT id, name, last_seen_at FROM clients
+            WHERE online
+              AND (last_seen_at IS NULL
+                   OR last_seen_at < NOW() - make_interval(secs => $1))
+            ORDER BY name
+            FOR UPDATE
+            "#,
+        )
+        .bind(report_timeout_secs as f64)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (id, name, last_seen_at) in &stale {
+            let seen = last_seen_at.map_or_else(|| "never".to_string(), |t| t.to_rfc3339());
+            findings.push(ConsistencyFinding {
+                kind: "stale_online_client",
+                subject: id.to_string(),
+                detail: format!("Client {:?} is online but last reported {}", name, seen),
+                fix: "mark offline".into(),
+            });
+        }
+
+        let orphaned_pings: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT r.task_id, COUNT(*) FROM ping_records r
+            WHERE NOT EXISTS (SELECT 1 FROM ping_tasks t WHERE t.id = r.task_id)
+            GROUP BY r.task_id
+            ORDER BY r.task_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for (task_id, count) in &orphaned_pings {
+            findings.push(ConsistencyFinding {
+                kind: "orphaned_ping_records",
+                subject: task_id.to_string(),
+                detail: format!("{} ping records of a deleted task", count),
+                fix: "delete the records".into(),
+            });
+        }
+
+        let orphaned_sessions: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT s.user_id, COUNT(*) FROM sessions s
+            WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = s.user_id)
+            GROUP BY s.user_id
+            ORDER BY s.user_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for (user_id, count) in &orphaned_sessions {
+            findings.push(ConsistencyFinding {
+                kind: "orphaned_sessions",
+                subject: user_id.to_string(),
+                detail: format!("{} sessions of a deleted user", count),
+                fix: "delete the sessions".into(),
+            });
+        }
+
+        let notifications: Vec<(Uuid, String, String, Value, Option<bool>)> = sqlx::query_as(
+            "SELECT id, name, provider, config, enabled FROM notifications ORDER BY name FOR UPDATE",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut broken_notifications = Vec::new();
+        for (id, name, provider, config, enabled) in notifications {
+            if let Err(e) = crate::notifier::parse_config(&provider, &config) {
+                let enabled = enabled.unwrap_or(true);
+                findings.push(ConsistencyFinding {
+                    kind: "invalid_notification_config",
+                    subject: id.to_string(),
+                    detail: format!("Notification {:?} ({}): {}", name, provider, e),
+                    fix: if enabled {
+                        "disable"
+                    } else {
+                        "none, already disabled"
+                    }
+                    .into(),
+                });
+                if enabled {
+                    broken_notifications.push(id);
+                }
+            }
+        }
+
+        let keys: Vec<&str> = KNOWN_SETTINGS.iter().map(|(key, _)| *key).collect();
+        let settings: Vec<(String, Value)> = sqlx::query_as(
+            "SELECT key, value FROM settings WHERE key = ANY($1) ORDER BY key FOR UPDATE",
+        )
+        .bind(&keys)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut invalid_settings = Vec::new();
+        for (key, value) in settings {
+            let Some((_, kind)) = KNOWN_SETTINGS.iter().find(|(k, _)| *k == key) else {
+                continue;
+            };
+            if value.is_null() || kind.matches(&value) {
+                continue;
+            }
+            findings.push(ConsistencyFinding {
+                kind: "invalid_setting",
+                subject: key.clone(),
+                detail: format!("Value {} is not {}", value, kind.name()),
+                fix: "delete to restore the default".into(),
+            });
+            invalid_settings.push(key);
+        }
+
+        if !fix || findings.is_empty() {
+            return Ok(ConsistencyReport {
+                findings,
+                fixed: false,
+            });
+        }
+
+        for (id, name, _) in &stale {
+            sqlx::query("UPDATE clients SET online = FALSE WHERE id = $1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            info!("Marked stale client {} ({}) offline", name, id);
+        }
+        for (task_id, _) in &orphaned_pings {
+            let deleted = sqlx::query("DELETE FROM ping_records WHERE task_id = $1")
+                .bind(task_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            info!(
+                "Deleted {} ping records of deleted task {}",
+                deleted, task_id
+            );
+        }
+        for (user_id, _) in &orphaned_sessions {
+            let deleted = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            info!("Deleted {} sessions of deleted user {}", deleted, user_id);
+        }
+        for id in &broken_notifications {
+            sqlx::query(
+                "UPDATE notifications SET enabled = FALSE, updated_at = NOW() WHERE id = $1",
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            info!("Disabled notification {} with an invalid config", id);
+        }
+        for key in &invalid_settings {
+            sqlx::query("DELETE FROM settings WHERE key = $1")
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+            info!("Deleted invalid setting {}", key);
+        }
+
+        tx.commit().await?;
+
+        Ok(ConsistencyReport {
+            findings,
+            fixed: true,
+        })
+    }
+}
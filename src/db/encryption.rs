@@ -0,0 +1,292 @@
+//! Encryption of secrets at rest.
+//!
+//! Agent tokens, notification credentials and secret settings are encrypted
+//! with ChaCha20-Poly1305. Each value gets its own key, derived from
+//! `SECRET_KEY` with HKDF-SHA256 and a random salt, and is stored as
+//! `enc:v2:<base64(salt || nonce || ciphertext)>`. HKDF does not stretch the
+//! passphrase, so `SECRET_KEY` should be a long random string.
+//!
+//! `enc:v1:<base64(nonce || ciphertext)>` values, encrypted under the
+//! SHA-256 of `SECRET_KEY` by earlier versions, are still read and are
+//! re-encrypted at startup. Values without a prefix are legacy plaintext:
+//! they are read as-is and encrypted at startup or on their next write.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Prefix marking any encrypted value.
+const ENCRYPTED_MARKER: &str = "enc:";
+
+/// Prefix of values encrypted under a per-value HKDF key.
+pub const ENCRYPTED_PREFIX: &str = "enc:v2:";
+
+/// Prefix of values encrypted under the SHA-256 of `SECRET_KEY`.
+const LEGACY_PREFIX: &str = "enc:v1:";
+
+/// HKDF context binding derived keys to this use.
+const HKDF_INFO: &[u8] = b"vanmoi secrets v2";
+
+/// HKDF salt length in bytes.
+const SALT_LEN: usize = 16;
+
+/// ChaCha20-Poly1305 nonce length in bytes.
+const NONCE_LEN: usize = 12;
+
+/// Secret fields of a notification provider's config.
+///
+/// `name.*` marks every value of the object field `name`.
+pub fn notification_secret_fields(provider: &str) -> &'static [&'static str] {
+    match provider {
+        "telegram" => &["bot_token"],
+        "email" => &["smtp_pass"],
+        "webhook" => &["headers.*"],
+        _ => &[],
+    }
+}
+
+/// Secret fields of a setting value, empty for settings without secrets.
+pub fn setting_secret_fields(key: &str) -> &'static [&'static str] {
+    match key {
+        "telegram_bot" => &["bot_token"],
+        "object_storage" => &["access_key", "secret_key"],
+        _ => &[],
+    }
+}
+
+/// Settings that contain secret fields.
+pub const SECRET_SETTINGS: &[&str] = &["telegram_bot", "object_storage"];
+
+/// Call `f` with the path and value of each string secret field in `value`.
+pub fn visit_secret_fields<E>(
+    value: &mut Value,
+    fields: &[&str],
+    mut f: impl FnMut(&str, Option<&str>, &mut Value) -> Result<(), E>,
+) -> Result<(), E> {
+    for field in fields {
+        if let Some(base) = field.strip_suffix(".*") {
+            if let Some(object) = value.get_mut(base).and_then(Value::as_object_mut) {
+                for (key, v) in object.iter_mut() {
+                    if v.is_string() {
+                        f(base, Some(key), v)?;
+                    }
+                }
+            }
+        } else if let Some(v) = value.get_mut(*field).filter(|v| v.is_string()) {
+            f(field, None, v)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a stored value is encrypted.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_MARKER)
+}
+
+/// Whether a stored value is encrypted in the legacy `enc:v1:` format.
+pub fn is_legacy(value: &str) -> bool {
+    value.starts_with(LEGACY_PREFIX)
+}
+
+/// Cipher for secret values.
+#[derive(Clone)]
+pub struct SecretCipher {
+    hkdf: Hkdf<Sha256>,
+    /// Cipher for `enc:v1:` values.
+    legacy: ChaCha20Poly1305,
+}
+
+impl SecretCipher {
+    /// Cipher keyed by a `SECRET_KEY` passphrase.
+    pub fn new(secret_key: &str) -> Self {
+        let legacy_key = Sha256::digest(secret_key.as_bytes());
+        Self {
+            hkdf: Hkdf::new(None, secret_key.as_bytes()),
+            legacy: ChaCha20Poly1305::new(Key::from_slice(&legacy_key)),
+        }
+    }
+
+    /// Cipher for the value key derived with `salt`.
+    fn value_cipher(&self, salt: &[u8]) -> ChaCha20Poly1305 {
+        let mut key = [0u8; 32];
+        self.hkdf
+            .expand_multi_info(&[HKDF_INFO, salt], &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        ChaCha20Poly1305::new(Key::from_slice(&key))
+    }
+
+    /// Encrypt a plaintext value.
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .value_cipher(&salt)
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("ChaCha20-Poly1305 encryption does not fail for in-memory input");
+        let mut payload = salt.to_vec();
+        payload.extend(nonce);
+        payload.extend(ciphertext);
+        format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload))
+    }
+
+    /// Decrypt a stored value; plaintext values are returned unchanged.
+    pub fn decrypt(&self, value: &str) -> Result<String, &'static str> {
+        let (cipher, payload) = if let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) {
+            let payload = decode(encoded, SALT_LEN + NONCE_LEN)?;
+            let (salt, payload) = payload.split_at(SALT_LEN);
+            (self.value_cipher(salt), payload.to_vec())
+        } else if let Some(encoded) = value.strip_prefix(LEGACY_PREFIX) {
+            (self.legacy.clone(), decode(encoded, NONCE_LEN)?)
+        } else if is_encrypted(value) {
+            return Err("unknown ciphertext version");
+        } else {
+            return Ok(value.to_string());
+        };
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "wrong SECRET_KEY or corrupted value")?;
+        String::from_utf8(plaintext).map_err(|_| "decrypted value is not UTF-8")
+    }
+}
+
+/// Decode a base64 payload of at least `min_len` bytes.
+fn decode(encoded: &str, min_len: usize) -> Result<Vec<u8>, &'static str> {
+    let payload = STANDARD
+        .decode(encoded)
+        .map_err(|_| "malformed ciphertext")?;
+    if payload.len() < min_len {
+        return Err("malformed ciphertext");
+    }
+    Ok(payload)
+}
+
+/// Encrypt a value when a cipher is configured.
+pub fn seal(cipher: Option<&SecretCipher>, value: &str) -> String {
+    match cipher {
+        Some(cipher) if !value.is_empty() && !is_encrypted(value) => cipher.encrypt(value),
+        _ => value.to_string(),
+    }
+}
+
+/// Decrypt a value, failing on ciphertext when no cipher is configured.
+pub fn open(cipher: Option<&SecretCipher>, value: &str) -> Result<String, &'static str> {
+    match cipher {
+        Some(cipher) => cipher.decrypt(value),
+        None if is_encrypted(value) => Err("value is encrypted but SECRET_KEY is not set"),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// Encrypt the secret fields of a JSON config in place.
+pub fn seal_fields(cipher: Option<&SecretCipher>, value: &mut Value, fields: &[&str]) {
+    let _ = visit_secret_fields::<()>(value, fields, |_, _, v| {
+        if let Some(s) = v.as_str() {
+            *v = Value::String(seal(cipher, s));
+        }
+        Ok(())
+    });
+}
+
+/// Decrypt the secret fields of a JSON config in place.
+///
+/// Errors carry the path of the failing field and the reason.
+pub fn open_fields(
+    cipher: Option<&SecretCipher>,
+    value: &mut Value,
+    fields: &[&str],
+) -> Result<(), (String, &'static str)> {
+    visit_secret_fields(value, fields, |base, key, v| {
+        if let Some(s) = v.as_str() {
+            let plaintext = open(cipher, s).map_err(|e| (field_path(base, key), e))?;
+            *v = Value::String(plaintext);
+        }
+        Ok(())
+    })
+}
+
+/// `base` or `base.key`.
+pub fn field_path(base: &str, key: Option<&str>) -> String {
+    match key {
+        Some(key) => format!("{}.{}", base, key),
+        None => base.to_string(),
+    }
+}
+
+/// SHA-256 hex digest of an agent token, used to look clients up.
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let cipher = SecretCipher::new("correct horse battery staple");
+        let sealed = cipher.encrypt("t0ken");
+        assert!(sealed.starts_with(ENCRYPTED_PREFIX));
+        assert!(!sealed.contains("t0ken"));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "t0ken");
+        assert_eq!(cipher.decrypt("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn values_get_distinct_salts() {
+        let cipher = SecretCipher::new("key");
+        let (a, b) = (cipher.encrypt("same"), cipher.encrypt("same"));
+        assert_ne!(a, b);
+        let salt = |sealed: &str| {
+            STANDARD
+                .decode(sealed.strip_prefix(ENCRYPTED_PREFIX).unwrap())
+                .unwrap()[..SALT_LEN]
+                .to_vec()
+        };
+        assert_ne!(salt(&a), salt(&b));
+    }
+
+    #[test]
+    fn wrong_key_and_tampering_fail() {
+        let sealed = SecretCipher::new("key").encrypt("secret");
+        assert!(SecretCipher::new("other").decrypt(&sealed).is_err());
+
+        let mut payload = STANDARD
+            .decode(sealed.strip_prefix(ENCRYPTED_PREFIX).unwrap())
+            .unwrap();
+        *payload.last_mut().unwrap() ^= 1;
+        let tampered = format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload));
+        assert!(SecretCipher::new("key").decrypt(&tampered).is_err());
+        assert!(SecretCipher::new("key").decrypt("enc:v2:AAAA").is_err());
+        assert!(SecretCipher::new("key").decrypt("enc:v9:AAAA").is_err());
+    }
+
+    #[test]
+    fn reads_legacy_values() {
+        let cipher = SecretCipher::new("key");
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut payload = nonce.to_vec();
+        payload.extend(cipher.legacy.encrypt(&nonce, b"old".as_ref()).unwrap());
+        let legacy = format!("{}{}", LEGACY_PREFIX, STANDARD.encode(payload));
+        assert!(is_encrypted(&legacy) && is_legacy(&legacy));
+        assert_eq!(cipher.decrypt(&legacy).unwrap(), "old");
+    }
+
+    #[test]
+    fn rotation_reseals_under_the_new_key() {
+        let (old, new) = (SecretCipher::new("old key"), SecretCipher::new("new key"));
+        let sealed = old.encrypt("secret");
+        // What `secrets rotate` does for each value
+        let rotated = seal(Some(&new), &open(Some(&old), &sealed).unwrap());
+        assert_eq!(open(Some(&new), &rotated).unwrap(), "secret");
+        assert!(open(Some(&old), &rotated).is_err());
+        assert!(open(None, &rotated).is_err());
+        assert_eq!(seal(Some(&new), &rotated), rotated);
+    }
+}
@@ -2,43 +2,324 @@
 //!
 //! Provides database connection, models, and repository operations.
 
+mod circuit;
+mod consistency;
+pub mod encryption;
 mod models;
+mod normalization;
 mod repository;
 mod schema;
+mod validation;
 
+pub use circuit::{CircuitState, DatabaseCircuitBreaker, GuardedPool, is_connection_error};
+pub use consistency::{ConsistencyFinding, ConsistencyReport};
 pub use models::*;
+pub use validation::validate_record;
+
+use encryption::SecretCipher;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use anyhow::Result;
-use sqlx::PgPool;
-use sqlx::postgres::PgPoolOptions;
-use tracing::info;
+use serde::Serialize;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, PgPool};
+use tracing::{info, warn};
+
+use crate::error::{AppError, AppResult};
+
+/// How long a replica query waits for a connection before failing, so an
+/// unreachable replica is skipped quickly.
+const REPLICA_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tables that may be vacuumed through the admin API.
+pub const VACUUM_TABLES: &[&str] = &[
+    "records",
+    "ping_records",
+    "ping_records_hourly",
+    "client_logs",
+    "sessions",
+    "audit_logs",
+];
 
 /// Database connection wrapper.
 #[derive(Clone)]
 pub struct Database {
     pool: PgPool,
+    breaker: Arc<DatabaseCircuitBreaker>,
+    /// Read replica pools, used round-robin by read-only queries.
+    replicas: Arc<Vec<Replica>>,
+    next_replica: Arc<AtomicUsize>,
+    /// Pool that read-only queries go to, see [`Database::pinned`].
+    read_target: ReadTarget,
+    /// Cipher for secrets at rest, when `SECRET_KEY` is set.
+    cipher: Option<Arc<SecretCipher>>,
+}
+
+/// Where [`Database::read_pool`] sends queries.
+#[derive(Debug, Clone, Copy)]
+enum ReadTarget {
+    /// The next healthy replica for each query.
+    RoundRobin,
+    /// Always this replica while it is healthy.
+    Replica(usize),
+    Primary,
+}
+
+struct Replica {
+    /// `host:port/database`, safe to show in health output.
+    name: String,
+    pool: PgPool,
+    breaker: Arc<DatabaseCircuitBreaker>,
+}
+
+/// Connectivity of one database connection pool.
+#[derive(Debug, Serialize)]
+pub struct PoolHealth {
+    pub name: String,
+    pub ok: bool,
+    pub circuit: CircuitState,
 }
 
 impl Database {
-    /// Connect to the PostgreSQL database.
-    pub async fn connect(database_url: &str) -> Result<Self> {
+    /// Connect to the PostgreSQL primary and any read replicas.
+    ///
+    /// Replicas are connected lazily and checked in the background, so an
+    /// unreachable replica does not hold up startup; its circuit opens and
+    /// reads go elsewhere until it recovers. A replica's circuit opens on the
+    /// first connection error, since the reads can fall back to the primary.
+    ///
+    /// Queries running longer than `query_timeout` (unless zero) are
+    /// cancelled by the server and logged with their text.
+    pub async fn connect(
+        database_url: &str,
+        replica_urls: &[String],
+        query_timeout: Duration,
+    ) -> Result<Self> {
         let pool = PgPoolOptions::new()
             .max_connections(10)
-            .connect(database_url)
+            .connect_with(connect_options(database_url, query_timeout)?)
             .await?;
 
-        Ok(Self { pool })
+        let mut replicas = Vec::new();
+        for url in replica_urls {
+            let options = connect_options(url, query_timeout)?;
+            let name = format!(
+                "{}:{}/{}",
+                options.get_host(),
+                options.get_port(),
+                options.get_database().unwrap_or_default()
+            );
+            let pool = PgPoolOptions::new()
+                .max_connections(10)
+                .acquire_timeout(REPLICA_ACQUIRE_TIMEOUT)
+                .connect_lazy_with(options);
+            let breaker = DatabaseCircuitBreaker::with_threshold(name.clone(), pool.clone(), 1);
+            tokio::spawn({
+                let (name, pool, breaker) = (name.clone(), pool.clone(), Arc::clone(&breaker));
+                async move {
+                    if ping(&pool).await {
+                        info!("Connected to read replica {}", name);
+                    } else {
+                        warn!("Read replica {} is unreachable, reading from others", name);
+                        breaker.trip();
+                    }
+                }
+            });
+            replicas.push(Replica {
+                name,
+                pool,
+                breaker,
+            });
+        }
+
+        Ok(Self {
+            breaker: DatabaseCircuitBreaker::new("primary".into(), pool.clone()),
+            pool,
+            replicas: Arc::new(replicas),
+            next_replica: Arc::new(AtomicUsize::new(0)),
+            read_target: ReadTarget::RoundRobin,
+            cipher: None,
+        })
+    }
+
+    /// Encrypt secrets at rest with a key derived from `secret_key`.
+    pub fn with_secret_key(mut self, secret_key: Option<&str>) -> Self {
+        self.cipher = secret_key.map(|key| Arc::new(SecretCipher::new(key)));
+        self
+    }
+
+    /// Cipher for secrets at rest, if configured.
+    pub fn cipher(&self) -> Option<&SecretCipher> {
+        self.cipher.as_deref()
     }
 
     /// Initialize the database schema.
+    ///
+    /// Creates missing tables, adds the columns of [`schema::ADDED_COLUMNS`]
+    /// that older deployments lack, then migrates their data. Index builds
+    /// and data migrations on large tables may take long, so they run on a
+    /// separate connection without the query timeout.
     pub async fn init_schema(&self) -> Result<()> {
-        schema::init_schema(&self.pool).await?;
+        let pool = self.untimed_pool();
+        schema::create_tables(&pool).await?;
+        for (table, column, column_def) in schema::ADDED_COLUMNS {
+            if self.ensure_column(table, column, column_def).await? {
+                info!("Added column {}.{}", table, column);
+            }
+        }
+        schema::migrate(&pool).await?;
+        pool.close().await;
         info!("Database schema initialized successfully");
         Ok(())
     }
 
-    /// Get a reference to the connection pool.
-    pub fn pool(&self) -> &PgPool {
-        &self.pool
+    /// Add `column` to `table` with `column_def` (type, default and
+    /// constraints) unless it exists. Returns whether it was added.
+    ///
+    /// Adding a column with a default rewrites large tables on old servers,
+    /// so the `ALTER TABLE` runs without the query timeout.
+    pub async fn ensure_column(
+        &self,
+        table: &str,
+        column: &str,
+        column_def: &str,
+    ) -> AppResult<bool> {
+        for name in [table, column] {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(AppError::Internal(format!("Invalid identifier: {}", name)));
+            }
+        }
+
+        let exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2
+            )
+            "#,
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await?;
+        if exists {
+            return Ok(false);
+        }
+
+        let pool = self.untimed_pool();
+        let added = sqlx::raw_sql(&format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+            table, column, column_def
+        ))
+        .execute(&pool)
+        .await;
+        pool.close().await;
+        added?;
+        Ok(true)
+    }
+
+    /// A single-connection pool to the primary without the query timeout,
+    /// for maintenance statements. Close it when done.
+    pub fn untimed_pool(&self) -> PgPool {
+        let options = (*self.pool.connect_options())
+            .clone()
+            .options([("statement_timeout", "0")])
+            // sqlx's default slow statement logging
+            .log_slow_statements(log::LevelFilter::Warn, Duration::from_secs(1));
+        PgPoolOptions::new()
+            .max_connections(1)
+            .connect_lazy_with(options)
+    }
+
+    /// The primary pool, failing fast while its circuit is open.
+    pub fn primary(&self) -> AppResult<GuardedPool> {
+        GuardedPool::new(&self.pool, &self.breaker)
+    }
+
+    /// Pool for read-only queries: the next replica whose circuit is closed,
+    /// or the primary when there is none.
+    ///
+    /// Replicas may lag behind the primary, so reads that must see a
+    /// preceding write (sessions, alert state) keep using `primary()`.
+    pub fn read_pool(&self) -> AppResult<GuardedPool> {
+        match self.read_target {
+            ReadTarget::RoundRobin => {}
+            ReadTarget::Replica(index) => {
+                let replica = &self.replicas[index];
+                return GuardedPool::new(&replica.pool, &replica.breaker)
+                    .or_else(|_| self.primary());
+            }
+            ReadTarget::Primary => return self.primary(),
+        }
+        let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.replicas.len() {
+            let replica = &self.replicas[(start + offset) % self.replicas.len()];
+            if let Ok(pool) = GuardedPool::new(&replica.pool, &replica.breaker) {
+                return Ok(pool);
+            }
+        }
+        self.primary()
+    }
+
+    /// A handle whose reads all go to one pool, the next healthy replica or
+    /// else the primary, so related reads such as a page and its total see
+    /// the same data. Falls back to the primary if that replica fails.
+    pub fn pinned(&self) -> Self {
+        let start = self.next_replica.fetch_add(1, Ordering::Relaxed);
+        let read_target = (0..self.replicas.len())
+            .map(|offset| (start + offset) % self.replicas.len())
+            .find(|index| self.replicas[*index].breaker.state() == CircuitState::Closed)
+            .map_or(ReadTarget::Primary, ReadTarget::Replica);
+        Self {
+            read_target,
+            ..self.clone()
+        }
+    }
+
+    /// Circuit state of the primary pool.
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    /// Run a trivial query on the primary.
+    pub async fn ping_primary(&self) -> AppResult<()> {
+        sqlx::query("SELECT 1").execute(self.primary()?).await?;
+        Ok(())
+    }
+
+    /// Check connectivity of the primary and every replica.
+    pub async fn health(&self) -> (bool, Vec<PoolHealth>) {
+        let primary = ping(&self.pool).await;
+        let mut replicas = Vec::new();
+        for replica in self.replicas.iter() {
+            replicas.push(PoolHealth {
+                name: replica.name.clone(),
+                ok: ping(&replica.pool).await,
+                circuit: replica.breaker.state(),
+            });
+        }
+        (primary, replicas)
+    }
+}
+
+async fn ping(pool: &PgPool) -> bool {
+    sqlx::query("SELECT 1").execute(pool).await.is_ok()
+}
+
+/// Connection options for `url` with the query timeout applied.
+fn connect_options(url: &str, query_timeout: Duration) -> Result<PgConnectOptions> {
+    let options: PgConnectOptions = url.parse()?;
+    if query_timeout.is_zero() {
+        return Ok(options);
     }
+    Ok(options
+        .options([(
+            "statement_timeout",
+            format!("{}ms", query_timeout.as_millis()),
+        )])
+        // Queries reaching the timeout are cancelled; log them with their text
+        .log_slow_statements(log::LevelFilter::Error, query_timeout))
 }
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::utils::flags;
+
 /// User model.
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct User {
@@ -14,6 +16,11 @@ pub struct User {
     pub username: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
+    pub email: Option<String>,
+    /// Provisioned through OIDC login; has no usable password.
+    pub external: bool,
+    /// `admin` or `viewer` (read-only admin access).
+    pub role: String,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -56,16 +63,110 @@ pub struct Client {
     pub version: String,
     pub weight: i32,
     pub group_name: String,
-    pub tags: String,
+    pub tags: Vec<String>,
     pub hidden: bool,
     pub traffic_limit: i64,
     pub traffic_limit_type: String,
     pub online: bool,
     pub last_seen_at: Option<DateTime<Utc>>,
+    /// Alerts are not notified while in maintenance.
+    pub maintenance_until: Option<DateTime<Utc>>,
+    /// Transport of the latest report (`http`, `ws` or `builtin`).
+    pub last_report_transport: Option<String>,
+    /// Whether the agent's clock is NTP synchronized, as last reported.
+    pub ntp_synced: Option<bool>,
+    /// Agent clock offset from its NTP source in milliseconds.
+    pub clock_offset_ms: Option<f32>,
+    /// Hex color (`#rrggbb`) shown for the client, or empty.
+    pub display_color: String,
+    /// Icon name shown for the client, or empty.
+    pub display_icon: String,
+    /// Source IP of the latest report.
+    pub last_report_ip: Option<String>,
+    /// Notify when reports come from an IP other than `ipv4`/`ipv6`.
+    pub alert_on_ip_change: bool,
+    /// Seconds between reports advised to the agent, overriding the
+    /// `report_interval_seconds` setting.
+    pub report_interval_seconds: Option<i32>,
+    /// The server's own host, reported by the self-monitor. It has no token
+    /// and cannot be deleted.
+    pub builtin: bool,
+    /// Decommissioned: reports are rejected and the client is left out of
+    /// live views and background jobs, but its history is kept.
+    pub archived: bool,
+    /// `online` was set by an admin and is kept until the override is
+    /// cleared, whatever the agent's connection does.
+    pub manually_overridden: bool,
+    /// Latest [`TcpStates`] snapshot, for agents that send one.
+    pub tcp_states: Option<serde_json::Value>,
+    /// Sample time of `tcp_states`.
+    pub tcp_states_at: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+/// Default for the `stale_after_secs` setting: seconds without a report
+/// after which an offline client is stale.
+pub const STALE_AFTER_SECS: i64 = 3600;
+
+/// Default for the `report_timeout_secs` setting: seconds without a report
+/// after which an online client is treated as offline, e.g. an HTTP agent
+/// that stopped without disconnecting.
+pub const REPORT_TIMEOUT_SECS: i64 = 60;
+
+impl Client {
+    /// Whether the client is online and has reported within
+    /// `report_timeout_secs`. A manual override is taken as is.
+    pub fn effective_online(&self, now: DateTime<Utc>, report_timeout_secs: i64) -> bool {
+        if self.manually_overridden {
+            return self.online;
+        }
+        self.online
+            && self
+                .last_seen_at
+                .is_some_and(|seen| now - seen < chrono::Duration::seconds(report_timeout_secs))
+    }
+}
+
+/// Filter for client list queries.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClientsFilter {
+    /// Full-text search query.
+    pub q: Option<String>,
+    /// List archived clients instead of active ones.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+/// Client reachability derived from `online` and `last_seen_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnlineStatus {
+    Online,
+    RecentlyOffline,
+    Stale,
+    Unknown,
+}
+
+impl OnlineStatus {
+    /// Compute the status of a client at `now`.
+    pub fn of(
+        online: bool,
+        last_seen_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        stale_after_secs: i64,
+    ) -> Self {
+        match last_seen_at {
+            _ if online => OnlineStatus::Online,
+            None => OnlineStatus::Unknown,
+            Some(seen) if now - seen < chrono::Duration::seconds(stale_after_secs) => {
+                OnlineStatus::RecentlyOffline
+            }
+            Some(_) => OnlineStatus::Stale,
+        }
+    }
+}
+
 /// Public client info (for non-admin users).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientPublic {
@@ -76,17 +177,28 @@ pub struct ClientPublic {
     pub cpu_cores: i32,
     pub os: String,
     pub region: String,
+    /// `region` uppercased when it is an ISO 3166-1 alpha-2 code.
+    pub country_code: Option<String>,
+    /// Flag emoji of `country_code`.
+    pub country_flag: Option<String>,
     pub public_remark: String,
     pub mem_total: i64,
     pub disk_total: i64,
     pub group_name: String,
+    pub display_color: String,
+    pub display_icon: String,
     pub online: bool,
+    pub online_status: OnlineStatus,
     pub last_seen_at: Option<DateTime<Utc>>,
 }
 
-impl From<Client> for ClientPublic {
-    fn from(c: Client) -> Self {
+impl ClientPublic {
+    /// Public view of `c`, stale after `stale_after_secs` without a report.
+    pub fn new(c: Client, stale_after_secs: i64) -> Self {
         Self {
+            online_status: OnlineStatus::of(c.online, c.last_seen_at, Utc::now(), stale_after_secs),
+            country_code: flags::country_code(&c.region),
+            country_flag: flags::country_code_to_flag(&c.region),
             id: c.id,
             name: c.name,
             cpu_name: c.cpu_name,
@@ -98,6 +210,8 @@ impl From<Client> for ClientPublic {
             mem_total: c.mem_total,
             disk_total: c.disk_total,
             group_name: c.group_name,
+            display_color: c.display_color,
+            display_icon: c.display_icon,
             online: c.online,
             last_seen_at: c.last_seen_at,
         }
@@ -117,6 +231,8 @@ pub struct Record {
     pub swap: i64,
     pub swap_total: i64,
     pub load: f32,
+    pub load5: f32,
+    pub load15: f32,
     pub temp: f32,
     pub disk: i64,
     pub disk_total: i64,
@@ -128,6 +244,10 @@ pub struct Record {
     pub connections: i32,
     pub connections_udp: i32,
     pub uptime: i64,
+    pub fd_used: i32,
+    pub fd_total: i32,
+    pub inode_used: i64,
+    pub inode_total: i64,
 }
 
 /// Record input from agent.
@@ -145,6 +265,10 @@ pub struct RecordInput {
     #[serde(default)]
     pub load: f32,
     #[serde(default)]
+    pub load5: f32,
+    #[serde(default)]
+    pub load15: f32,
+    #[serde(default)]
     pub temp: f32,
     pub disk: i64,
     pub disk_total: i64,
@@ -160,6 +284,73 @@ pub struct RecordInput {
     pub connections_udp: i32,
     #[serde(default)]
     pub uptime: i64,
+    #[serde(default)]
+    pub fd_used: i32,
+    #[serde(default)]
+    pub fd_total: i32,
+    #[serde(default)]
+    pub inode_used: i64,
+    #[serde(default)]
+    pub inode_total: i64,
+    /// Agent's local time when the record was sampled.
+    #[serde(default)]
+    pub recorded_at: Option<DateTime<Utc>>,
+    /// Latest system log lines (e.g. from `journalctl`), oldest first.
+    #[serde(default)]
+    pub log_lines: Option<Vec<String>>,
+    /// TCP connection counts by state. `connections` stays the total.
+    #[serde(default)]
+    pub tcp_states: Option<TcpStates>,
+}
+
+/// TCP connection counts by state, named as in `/proc/net/tcp`. States an
+/// agent does not send are left out; unknown keys are ignored so newer
+/// agents keep working.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TcpStates {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub established: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syn_sent: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syn_recv: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fin_wait1: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fin_wait2: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_wait: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_wait: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_ack: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closing: Option<i32>,
+}
+
+impl TcpStates {
+    /// Counts by state name, for the states that were sent.
+    pub fn counts(&self) -> impl Iterator<Item = (&'static str, i32)> {
+        [
+            ("established", self.established),
+            ("syn_sent", self.syn_sent),
+            ("syn_recv", self.syn_recv),
+            ("fin_wait1", self.fin_wait1),
+            ("fin_wait2", self.fin_wait2),
+            ("time_wait", self.time_wait),
+            ("close", self.close),
+            ("close_wait", self.close_wait),
+            ("last_ack", self.last_ack),
+            ("listen", self.listen),
+            ("closing", self.closing),
+        ]
+        .into_iter()
+        .filter_map(|(state, count)| count.map(|c| (state, c)))
+    }
 }
 
 /// Notification provider configuration.
@@ -170,6 +361,8 @@ pub struct Notification {
     pub provider: String,
     pub config: serde_json::Value,
     pub enabled: bool,
+    /// Locale of server-generated messages; `None` uses the global locale.
+    pub notification_locale: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -183,6 +376,13 @@ pub struct PingTask {
     pub interval_seconds: i32,
     pub timeout_seconds: i32,
     pub enabled: bool,
+    /// HTTP checks fail unless the response body contains this.
+    pub expected_body_contains: Option<String>,
+    /// HTTP checks fail if the response body contains this.
+    pub expected_body_not_contains: Option<String>,
+    /// Headers sent with HTTP checks, e.g. for authentication. Write-only.
+    #[serde(skip_serializing)]
+    pub request_headers: serde_json::Value,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -196,6 +396,485 @@ pub struct PingRecord {
     pub time: Option<DateTime<Utc>>,
     pub latency_ms: Option<f32>,
     pub success: bool,
+    /// Why a failed check failed.
+    pub failure_reason: Option<String>,
+}
+
+/// Usual value of a client metric in one hour of the week (0 is Monday
+/// 00:00-01:00 UTC), see [`crate::anomaly`].
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ClientBaseline {
+    pub client_id: Uuid,
+    pub metric: String,
+    pub hour_of_week: i16,
+    /// Decayed number of samples behind the statistics.
+    pub weight: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+/// Online/offline transition of a client, a row of `client_status_history`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct StatusTransition {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    /// State the client changed to.
+    pub online: bool,
+    pub transitioned_at: Option<DateTime<Utc>>,
+    /// Seconds the client stayed in this state, `null` while it still is.
+    pub duration_seconds: Option<i64>,
+    /// Reason given for a manual override.
+    pub reason: Option<String>,
+}
+
+/// Aggregate of one record metric over a time range.
+///
+/// The statistics are `null` when the range has no samples.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct MetricAggregate {
+    pub avg: Option<f64>,
+    pub p95: Option<f64>,
+    pub max: Option<f64>,
+    pub samples: i64,
+}
+
+/// Fleet totals of a client group.
+///
+/// Capacities cover every member; usage, rates and CPU come from the latest
+/// record of each online member.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct GroupStats {
+    pub name: String,
+    pub members: i64,
+    pub online: i64,
+    pub ram_total: i64,
+    pub ram_used: i64,
+    pub disk_total: i64,
+    pub disk_used: i64,
+    pub net_in: i64,
+    pub net_out: i64,
+    /// Mean CPU usage of the online members, `null` without reports.
+    pub avg_cpu: Option<f64>,
+}
+
+/// Group totals over one time bucket. Each member's records are averaged
+/// over the bucket first, then summed (CPU: averaged) across the members.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct GroupStatsPoint {
+    /// Start of the bucket.
+    pub time: DateTime<Utc>,
+    /// Members with records in the bucket.
+    pub reporting: i64,
+    pub avg_cpu: f64,
+    pub ram_total: i64,
+    pub ram_used: i64,
+    pub disk_total: i64,
+    pub disk_used: i64,
+    pub net_in: i64,
+    pub net_out: i64,
+}
+
+/// Who performed a ping check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingSource {
+    /// The server itself (`client_id` is NULL).
+    Server,
+    /// An agent.
+    Client(Uuid),
+}
+
+/// A client that has performed a ping task.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct PingTaskSource {
+    #[serde(skip_serializing)]
+    pub task_id: Uuid,
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Uptime and latency of a ping task, for public status pages.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PingTaskSummary {
+    #[sqlx(flatten)]
+    pub task: PingTask,
+    /// Share of successful checks in the last 24 hours; 0 without checks.
+    pub uptime_24h_pct: f64,
+    /// Share of successful checks in the last 7 days; 0 without checks.
+    pub uptime_7d_pct: f64,
+    /// Average latency of successful checks in the last 24 hours.
+    pub avg_latency_ms: Option<f64>,
+    pub last_check_at: Option<DateTime<Utc>>,
+    /// Whether the latest check succeeded.
+    pub last_success: bool,
+}
+
+/// Composite monitor over ping tasks.
+///
+/// `status` is the last evaluated status (`up`, `degraded`, `down` or
+/// `unknown`), see [`crate::monitors`].
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct MonitorGroup {
+    pub id: Uuid,
+    pub name: String,
+    /// `all` or `any`.
+    pub logic: String,
+    pub task_ids: Vec<Uuid>,
+    pub notification_id: Option<Uuid>,
+    pub status: String,
+    pub status_changed_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Monitor group as shown on the public status page.
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorGroupPublic {
+    pub id: Uuid,
+    pub name: String,
+    pub logic: String,
+    pub task_ids: Vec<Uuid>,
+    pub status: String,
+    pub status_changed_at: Option<DateTime<Utc>>,
+    /// A silence covering one of the member tasks is active.
+    pub silenced: bool,
+}
+
+impl From<MonitorGroup> for MonitorGroupPublic {
+    fn from(group: MonitorGroup) -> Self {
+        Self {
+            id: group.id,
+            name: group.name,
+            logic: group.logic,
+            task_ids: group.task_ids,
+            status: group.status,
+            status_changed_at: group.status_changed_at,
+            silenced: false,
+        }
+    }
+}
+
+/// Heartbeat monitor, alerting when an external job stops calling its URL.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub id: Uuid,
+    pub name: String,
+    /// Secret part of the beat URL `/api/heartbeat/{token}`.
+    pub token: String,
+    pub interval_secs: i32,
+    pub grace_secs: i32,
+    pub notification_id: Option<Uuid>,
+    /// Shown on the public status page.
+    pub public: bool,
+    /// `pending` (no beat yet), `up` or `down`.
+    pub status: String,
+    pub last_beat_at: Option<DateTime<Utc>>,
+    pub status_changed_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Heartbeat after a recorded beat, with its status before the beat.
+#[derive(Debug, Clone, FromRow)]
+pub struct HeartbeatBeat {
+    #[sqlx(flatten)]
+    pub heartbeat: Heartbeat,
+    pub previous_status: String,
+}
+
+/// Heartbeat as shown on the public status page.
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatPublic {
+    pub id: Uuid,
+    pub name: String,
+    pub interval_secs: i32,
+    pub status: String,
+    pub last_beat_at: Option<DateTime<Utc>>,
+}
+
+impl From<Heartbeat> for HeartbeatPublic {
+    fn from(heartbeat: Heartbeat) -> Self {
+        Self {
+            id: heartbeat.id,
+            name: heartbeat.name,
+            interval_secs: heartbeat.interval_secs,
+            status: heartbeat.status,
+            last_beat_at: heartbeat.last_beat_at,
+        }
+    }
+}
+
+/// When a silence is active.
+///
+/// `once` silences run from `starts_at` to `ends_at`. `daily` and `weekly`
+/// silences start at the local `time` in `timezone` (on each of `weekdays`
+/// for weekly ones) and last `duration_minutes`.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SilenceSchedule {
+    /// `once`, `daily` or `weekly`.
+    pub kind: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Local start time as `HH:MM`.
+    pub time: Option<String>,
+    /// Days of week such as `Mon`, for weekly silences.
+    #[serde(default)]
+    pub weekdays: Vec<String>,
+    /// IANA timezone name, e.g. `Asia/Shanghai`.
+    #[serde(default = "default_silence_timezone")]
+    pub timezone: String,
+    #[serde(default)]
+    pub duration_minutes: i32,
+}
+
+fn default_silence_timezone() -> String {
+    "UTC".to_string()
+}
+
+/// Silence window suppressing notifications for matching events.
+///
+/// A silence matches a client by ID and/or group, or a ping task by ID;
+/// every matcher that is set must match.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Silence {
+    pub id: Uuid,
+    pub client_id: Option<Uuid>,
+    pub group_name: Option<String>,
+    pub task_id: Option<Uuid>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub schedule: SilenceSchedule,
+    pub comment: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Latest check results of a monitor group's enabled member tasks.
+#[derive(Debug, Clone, FromRow)]
+pub struct MonitorGroupChecks {
+    pub group_id: Uuid,
+    pub up: i64,
+    pub down: i64,
+}
+
+/// Alert rule model.
+///
+/// A rule fires when the current value of `metric` exceeds `threshold`.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: Uuid,
+    pub client_id: Uuid,
+    pub notification_id: Option<Uuid>,
+    pub metric: String,
+    pub threshold: f32,
+    pub severity: String,
+    pub enabled: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Idempotency key of an admin mutation. The status code, content type and
+/// body are set once the request has completed.
+#[derive(Debug, Clone, FromRow)]
+pub struct IdempotencyKey {
+    pub user_id: Uuid,
+    pub key: String,
+    pub endpoint: String,
+    pub request_hash: String,
+    pub status_code: Option<i16>,
+    pub content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Alert rule of a client with the name and provider of its notification.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ClientAlertRule {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub rule: AlertRule,
+    pub notification_name: Option<String>,
+    pub notification_provider: Option<String>,
+    /// When the rule last fired, if ever.
+    pub last_fired_at: Option<DateTime<Utc>>,
+}
+
+/// Alert history entry (one per firing).
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AlertHistory {
+    pub id: Uuid,
+    /// `null` for anomalies.
+    pub rule_id: Option<Uuid>,
+    /// `threshold` for alert rules, `anomaly` for baseline deviations.
+    pub kind: String,
+    pub client_id: Uuid,
+    pub metric: String,
+    pub value: f32,
+    pub threshold: f32,
+    pub severity: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Share link model.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub token: String,
+    pub client_id: Uuid,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Notification route model.
+///
+/// Matches clients by group and/or tag; an empty `event_types` matches all
+/// events.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct NotificationRoute {
+    pub id: Uuid,
+    pub name: String,
+    pub group_name: Option<String>,
+    pub tag: Option<String>,
+    pub event_types: Vec<String>,
+    pub notification_id: Uuid,
+    pub enabled: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Alert rule joined with its client's current metric value and open alert.
+#[derive(Debug, Clone, FromRow)]
+pub struct AlertEvaluation {
+    pub rule_id: Uuid,
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub metric: String,
+    pub threshold: f32,
+    pub severity: String,
+    pub value: Option<f64>,
+    pub in_maintenance: bool,
+    pub open_alert_id: Option<Uuid>,
+    pub notification_id: Option<Uuid>,
+}
+
+/// Per-client aggregate value used in reports.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ClientAggregate {
+    pub client_id: Uuid,
+    pub name: String,
+    pub value: f64,
+}
+
+/// Traffic of a client over a time window, corrected for counter resets.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TrafficTotals {
+    pub client_id: Uuid,
+    pub name: String,
+    pub up: i64,
+    pub down: i64,
+}
+
+/// A UTC day of records for one client.
+#[derive(Debug, Clone, FromRow)]
+pub struct RecordDay {
+    pub client_id: Uuid,
+    /// Start of the day (midnight UTC).
+    pub day: DateTime<Utc>,
+}
+
+/// Records of one time bucket averaged into a chart point.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct RecordPoint {
+    /// Start of the bucket.
+    pub time: DateTime<Utc>,
+    pub cpu: f32,
+    pub ram: i64,
+    pub disk: i64,
+    pub load: f32,
+    pub temp: f32,
+    pub net_in: i64,
+    pub net_out: i64,
+}
+
+/// System log line forwarded by an agent.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ClientLogLine {
+    pub id: i64,
+    pub client_id: Uuid,
+    pub line: String,
+    pub recorded_at: Option<DateTime<Utc>>,
+}
+
+/// Entry of a client's event timeline.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub at: DateTime<Utc>,
+    /// `alert_fired`, `alert_resolved`, `ip_change` or `config_change`.
+    pub event_type: String,
+    pub summary: String,
+    pub metadata: serde_json::Value,
+}
+
+/// Entry of the incident timeline.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct IncidentEvent {
+    /// Unique within the timeline, e.g. `alert_fired:<alert id>`.
+    pub id: String,
+    pub at: DateTime<Utc>,
+    /// `client_online`, `client_offline`, `alert_fired`, `alert_resolved`,
+    /// `ping_failed` or `audit`.
+    pub event_type: String,
+    pub client_id: Option<Uuid>,
+    pub client_name: Option<String>,
+    pub summary: String,
+    pub metadata: serde_json::Value,
+}
+
+impl IncidentEvent {
+    /// Cursor of the page after this event.
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            at: self.at,
+            id: self.id.clone(),
+        }
+    }
+}
+
+/// Position in a feed ordered by time and ID, newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub at: DateTime<Utc>,
+    pub id: String,
+}
+
+/// Audit log entry.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AuditLog {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub action: String,
+    pub details: serde_json::Value,
+    pub ip_address: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Login or account security event.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SecurityEvent {
+    pub id: i64,
+    /// `login_success`, `login_failed`, `password_changed`,
+    /// `session_revoked` or `api_key_created`.
+    pub event_type: String,
+    pub user_id: Option<Uuid>,
+    /// Username of the account, or the name given in a failed login.
+    pub username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// Whether a notification was sent for the event.
+    pub notified: bool,
+    pub created_at: Option<DateTime<Utc>>,
 }
 
 /// Settings model (key-value).
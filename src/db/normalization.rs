@@ -0,0 +1,80 @@
+//! Normalization of agent-reported values.
+//!
+//! Agents built from different toolchains report the same thing under
+//! different names. Values are normalized before they are stored, and the
+//! schema migration rewrites existing rows from the same alias table.
+
+/// Canonical CPU architecture names with their lowercase aliases.
+pub const ARCH_ALIASES: &[(&str, &[&str])] = &[
+    ("x86_64", &["x86_64", "x86-64", "amd64", "x64"]),
+    ("i386", &["i386", "i486", "i586", "i686", "x86", "386"]),
+    ("aarch64", &["aarch64", "arm64", "armv8", "arm64v8"]),
+    ("armv7", &["armv7", "armv7l", "armv7a", "armhf", "arm"]),
+    ("armv6", &["armv6", "armv6l", "armel"]),
+    ("riscv64", &["riscv64", "riscv64gc"]),
+    ("ppc64le", &["ppc64le", "powerpc64le"]),
+    ("ppc64", &["ppc64", "powerpc64"]),
+    ("s390x", &["s390x"]),
+    ("mips64le", &["mips64le", "mips64el"]),
+    ("mipsle", &["mipsle", "mipsel"]),
+    ("loongarch64", &["loongarch64", "loong64"]),
+];
+
+/// Map a CPU architecture alias to its canonical name.
+///
+/// Aliases are matched case-insensitively and ignoring surrounding
+/// whitespace. Unknown values are returned unchanged.
+pub fn normalize_arch(raw: &str) -> &str {
+    let arch = raw.trim().to_ascii_lowercase();
+    ARCH_ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&arch.as_str()))
+        .map_or(raw, |(canonical, _)| canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_arch_aliases() {
+        for (raw, expected) in [
+            ("x86_64", "x86_64"),
+            ("amd64", "x86_64"),
+            ("X64", "x86_64"),
+            (" x86-64\n", "x86_64"),
+            ("i686", "i386"),
+            ("386", "i386"),
+            ("arm64", "aarch64"),
+            ("ARMv8", "aarch64"),
+            ("armv7l", "armv7"),
+            ("armhf", "armv7"),
+            ("armel", "armv6"),
+            ("riscv64gc", "riscv64"),
+            ("powerpc64le", "ppc64le"),
+            ("powerpc64", "ppc64"),
+            ("s390x", "s390x"),
+            ("mips64el", "mips64le"),
+            ("mipsel", "mipsle"),
+            ("loong64", "loongarch64"),
+            // Unknown values are kept as reported
+            ("sparc64", "sparc64"),
+            (" Unknown ", " Unknown "),
+            ("", ""),
+        ] {
+            assert_eq!(normalize_arch(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn aliases_are_unique_and_canonical() {
+        let mut seen = std::collections::HashSet::new();
+        for (canonical, aliases) in ARCH_ALIASES {
+            assert!(aliases.contains(canonical), "{}", canonical);
+            for alias in *aliases {
+                assert_eq!(*alias, alias.to_ascii_lowercase());
+                assert!(seen.insert(alias), "duplicate alias {}", alias);
+            }
+        }
+    }
+}
@@ -2,13 +2,54 @@
 //!
 //! CRUD operations for all database models.
 
-use super::Database;
+use super::encryption::{
+    self, SECRET_SETTINGS, SecretCipher, is_encrypted, is_legacy, notification_secret_fields,
+    setting_secret_fields,
+};
 use super::models::*;
-use crate::error::AppResult;
-use chrono::{Duration, Utc};
+use super::normalization::normalize_arch;
+use super::{Database, VACUUM_TABLES};
+use crate::error::{AppError, AppResult};
+use chrono::{DateTime, Duration, Utc};
+use futures::{Stream, StreamExt};
 use sqlx::Row;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
+/// Largest drop in a traffic total, in bytes, still treated as jitter rather
+/// than a counter reset.
+const COUNTER_RESET_TOLERANCE: i64 = 1024 * 1024;
+
+/// Records read ahead of a slow reader by [`Database::stream_recent_records`].
+const RECORD_STREAM_BUFFER: usize = 64;
+
+/// Rows deleted per statement by the retention deletes, so each statement
+/// finishes well within the query timeout.
+const RETENTION_DELETE_BATCH: i64 = 10_000;
+
+/// Most records deleted by one [`Database::delete_duplicate_records`] call.
+pub const MAX_DUPLICATE_DELETES: i64 = 100_000;
+
+/// Records of client `$1` repeating an earlier record within `$2`
+/// milliseconds, at most `$3`.
+const DUPLICATE_RECORDS_CTE: &str = r#"
+    WITH duplicates AS (
+        SELECT r.id FROM records r
+        WHERE r.client_id = $1
+            AND EXISTS (
+                SELECT 1 FROM records e
+                WHERE e.client_id = r.client_id
+                    AND e.time >= r.time - make_interval(secs => $2 / 1000.0)
+                    AND (e.time < r.time OR (e.time = r.time AND e.id < r.id))
+                    AND ROUND(e.cpu::numeric, 1) = ROUND(r.cpu::numeric, 1)
+                    AND e.ram = r.ram
+                    AND e.disk = r.disk
+            )
+        ORDER BY r.id
+        LIMIT $3
+    )
+"#;
+
 impl Database {
     // ==================== User Operations ====================
 
@@ -23,38 +64,84 @@ impl Database {
         )
         .bind(username)
         .bind(password_hash)
-        .fetch_one(&self.pool)
+        .fetch_one(self.primary()?)
         .await?;
 
         Ok(user)
     }
 
-    /// Find user by username.
+    /// Find user by username (case-insensitive). An exact match wins over
+    /// names that only differ in case, which can predate the unique index.
     pub async fn find_user_by_username(&self, username: &str) -> AppResult<Option<User>> {
-        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
-            .bind(username)
-            .fetch_optional(&self.pool)
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE LOWER(username) = LOWER($1)
+            ORDER BY username = $1 DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Find user by email (case-insensitive).
+    pub async fn find_user_by_email(&self, email: &str) -> AppResult<Option<User>> {
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE LOWER(email) = LOWER($1)")
+            .bind(email)
+            .fetch_optional(self.primary()?)
             .await?;
 
         Ok(user)
     }
 
+    /// Create an external (SSO) user without a usable password.
+    pub async fn create_external_user(&self, email: &str, role: &str) -> AppResult<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            INSERT INTO users (username, password_hash, email, external, role)
+            VALUES ($1, '!', $1, TRUE, $2)
+            RETURNING *
+            "#,
+        )
+        .bind(email)
+        .bind(role)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(user)
+    }
+
     /// Find user by ID.
     pub async fn find_user_by_id(&self, id: Uuid) -> AppResult<Option<User>> {
         let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
             .bind(id)
-            .fetch_optional(&self.pool)
+            .fetch_optional(self.primary()?)
             .await?;
 
         Ok(user)
     }
 
+    /// Update username.
+    pub async fn update_username(&self, id: Uuid, username: &str) -> AppResult<()> {
+        sqlx::query("UPDATE users SET username = $1, updated_at = NOW() WHERE id = $2")
+            .bind(username)
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
     /// Update user password.
     pub async fn update_user_password(&self, id: Uuid, password_hash: &str) -> AppResult<()> {
         sqlx::query("UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2")
             .bind(password_hash)
             .bind(id)
-            .execute(&self.pool)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
@@ -63,7 +150,7 @@ impl Database {
     /// Check if any users exist.
     pub async fn has_users(&self) -> AppResult<bool> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM users")
-            .fetch_one(&self.pool)
+            .fetch_one(self.primary()?)
             .await?;
 
         let count: i64 = row.get("count");
@@ -95,7 +182,7 @@ impl Database {
         .bind(user_agent)
         .bind(ip_address)
         .bind(expires_at)
-        .fetch_one(&self.pool)
+        .fetch_one(self.primary()?)
         .await?;
 
         Ok(session)
@@ -107,7 +194,7 @@ impl Database {
             "SELECT * FROM sessions WHERE token = $1 AND expires_at > NOW()",
         )
         .bind(token)
-        .fetch_optional(&self.pool)
+        .fetch_optional(self.primary()?)
         .await?;
 
         Ok(session)
@@ -117,7 +204,7 @@ impl Database {
     pub async fn delete_session(&self, token: &str) -> AppResult<()> {
         sqlx::query("DELETE FROM sessions WHERE token = $1")
             .bind(token)
-            .execute(&self.pool)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
@@ -127,7 +214,7 @@ impl Database {
     pub async fn delete_user_sessions(&self, user_id: Uuid) -> AppResult<()> {
         sqlx::query("DELETE FROM sessions WHERE user_id = $1")
             .bind(user_id)
-            .execute(&self.pool)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
@@ -139,28 +226,110 @@ impl Database {
             "SELECT * FROM sessions WHERE user_id = $1 AND expires_at > NOW() ORDER BY created_at DESC",
         )
         .bind(user_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Get one page of a user's active sessions, newest first.
+    pub async fn get_user_sessions_paged(
+        &self,
+        user_id: Uuid,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<Session>> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT * FROM sessions
+            WHERE user_id = $1 AND expires_at > NOW()
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
         .await?;
 
         Ok(sessions)
     }
 
+    /// Count a user's active sessions.
+    pub async fn count_user_sessions(&self, user_id: Uuid) -> AppResult<i64> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) as count FROM sessions WHERE user_id = $1 AND expires_at > NOW()",
+        )
+        .bind(user_id)
+        .fetch_one(self.read_pool()?)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Delete a user's sessions from an IP address, except the session with
+    /// token `keep`. Returns the number of deleted sessions.
+    pub async fn delete_user_sessions_by_ip(
+        &self,
+        user_id: Uuid,
+        ip: &str,
+        keep: &str,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM sessions WHERE user_id = $1 AND ip_address = $2 AND token <> $3",
+        )
+        .bind(user_id)
+        .bind(ip)
+        .bind(keep)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     // ==================== Client Operations ====================
 
-    /// Create a new client.
+    /// Create a new client. The returned client carries the plaintext token.
     pub async fn create_client(&self, name: &str) -> AppResult<Client> {
         let token = format!("vmoi_{}", Uuid::new_v4().to_string().replace("-", ""));
 
+        let mut client = sqlx::query_as::<_, Client>(
+            r#"
+            INSERT INTO clients (name, token, token_hash)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(encryption::seal(self.cipher(), &token))
+        .bind(encryption::token_hash(&token))
+        .fetch_one(self.primary()?)
+        .await?;
+
+        client.token = token;
+        Ok(client)
+    }
+
+    /// Get the built-in client, creating it with `name` if missing. It has no
+    /// token: the empty hash matches no token.
+    pub async fn ensure_builtin_client(&self, name: &str) -> AppResult<Client> {
+        let existing = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE builtin LIMIT 1")
+            .fetch_optional(self.primary()?)
+            .await?;
+        if let Some(client) = existing {
+            return Ok(client);
+        }
+
         let client = sqlx::query_as::<_, Client>(
             r#"
-            INSERT INTO clients (name, token)
-            VALUES ($1, $2)
+            INSERT INTO clients (name, token, token_hash, builtin)
+            VALUES ($1, '', '', TRUE)
             RETURNING *
             "#,
         )
         .bind(name)
-        .bind(&token)
-        .fetch_one(&self.pool)
+        .fetch_one(self.primary()?)
         .await?;
 
         Ok(client)
@@ -170,44 +339,231 @@ impl Database {
     pub async fn find_client_by_id(&self, id: Uuid) -> AppResult<Option<Client>> {
         let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = $1")
             .bind(id)
-            .fetch_optional(&self.pool)
+            .fetch_optional(self.primary()?)
             .await?;
 
         Ok(client)
     }
 
-    /// Find client by token.
+    /// Find client by token, looked up by its hash.
     pub async fn find_client_by_token(&self, token: &str) -> AppResult<Option<Client>> {
-        let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE token = $1")
-            .bind(token)
-            .fetch_optional(&self.pool)
+        let client = sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE token_hash = $1")
+            .bind(encryption::token_hash(token))
+            .fetch_optional(self.primary()?)
             .await?;
 
         Ok(client)
     }
 
+    /// Find client by id and token; both must belong to the same client.
+    pub async fn find_client_by_id_and_token(
+        &self,
+        id: Uuid,
+        token: &str,
+    ) -> AppResult<Option<Client>> {
+        let client =
+            sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = $1 AND token_hash = $2")
+                .bind(id)
+                .bind(encryption::token_hash(token))
+                .fetch_optional(self.primary()?)
+                .await?;
+
+        Ok(client)
+    }
+
+    /// Decrypt a client's stored token.
+    pub fn client_token(&self, client: &Client) -> AppResult<String> {
+        encryption::open(self.cipher(), &client.token).map_err(|e| {
+            AppError::Internal(format!(
+                "Cannot decrypt clients.token of client {}: {}",
+                client.id, e
+            ))
+        })
+    }
+
+    /// Find client by name (case-insensitive).
+    pub async fn find_client_by_name(&self, name: &str) -> AppResult<Option<Client>> {
+        let client = sqlx::query_as::<_, Client>(
+            "SELECT * FROM clients WHERE LOWER(name) = LOWER($1) ORDER BY created_at LIMIT 1",
+        )
+        .bind(name)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(client)
+    }
+
     /// Get all clients.
     pub async fn get_all_clients(&self) -> AppResult<Vec<Client>> {
         let clients =
             sqlx::query_as::<_, Client>("SELECT * FROM clients ORDER BY weight DESC, name")
-                .fetch_all(&self.pool)
+                .fetch_all(self.read_pool()?)
                 .await?;
 
         Ok(clients)
     }
 
-    /// Get visible clients (not hidden).
+    /// Get one page of clients matching a filter.
+    pub async fn get_clients_paged(
+        &self,
+        filter: &ClientsFilter,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<Client>> {
+        let clients = sqlx::query_as::<_, Client>(
+            r#"
+            SELECT * FROM clients
+            WHERE ($1::text IS NULL OR search_vector @@ to_tsquery('english', $1))
+                AND archived = $4
+            ORDER BY
+                CASE WHEN $1::text IS NULL THEN 0
+                     ELSE ts_rank(search_vector, to_tsquery('english', $1)) END DESC,
+                weight DESC, name
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(filter_tsquery(filter))
+        .bind(limit)
+        .bind(offset)
+        .bind(filter.archived)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Count clients matching a filter.
+    pub async fn count_clients(&self, filter: &ClientsFilter) -> AppResult<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM clients
+            WHERE ($1::text IS NULL OR search_vector @@ to_tsquery('english', $1))
+                AND archived = $2
+            "#,
+        )
+        .bind(filter_tsquery(filter))
+        .bind(filter.archived)
+        .fetch_one(self.read_pool()?)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Get the clients that are not archived.
+    pub async fn get_active_clients(&self) -> AppResult<Vec<Client>> {
+        let clients = sqlx::query_as::<_, Client>(
+            "SELECT * FROM clients WHERE NOT archived ORDER BY weight DESC, name",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Get visible clients (neither hidden nor archived).
     pub async fn get_visible_clients(&self) -> AppResult<Vec<Client>> {
         let clients = sqlx::query_as::<_, Client>(
-            "SELECT * FROM clients WHERE hidden = FALSE ORDER BY weight DESC, name",
+            "SELECT * FROM clients WHERE hidden = FALSE AND NOT archived ORDER BY weight DESC, name",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Find up to `limit` other clients in `group_name` running `os`.
+    ///
+    /// When fewer than 3 such clients exist, clients of the group running
+    /// any OS are included as well, same-OS clients first.
+    pub async fn find_peer_clients(
+        &self,
+        id: Uuid,
+        group_name: &str,
+        os: &str,
+        limit: i32,
+    ) -> AppResult<Vec<Client>> {
+        let clients = sqlx::query_as::<_, Client>(
+            r#"
+            WITH candidates AS (
+                SELECT * FROM clients WHERE group_name = $2 AND id <> $1 AND NOT archived
+            )
+            SELECT c.* FROM candidates c
+            WHERE c.os = $3 OR (SELECT COUNT(*) FROM candidates WHERE os = $3) < 3
+            ORDER BY c.os = $3 DESC, c.weight DESC, c.name
+            LIMIT $4
+            "#,
+        )
+        .bind(id)
+        .bind(group_name)
+        .bind(os)
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Find the clients other than `id` whose IPv4 address starts with the
+    /// given three octets, i.e. that share a /24.
+    pub async fn find_ipv4_neighbors(&self, id: Uuid, prefix: [u8; 3]) -> AppResult<Vec<Client>> {
+        let clients = sqlx::query_as::<_, Client>(
+            r#"
+            SELECT * FROM clients
+            WHERE split_part(ipv4, '.', 1) = $1 AND split_part(ipv4, '.', 2) = $2
+                AND split_part(ipv4, '.', 3) = $3 AND id != $4 AND NOT archived
+            ORDER BY weight DESC, name
+            "#,
+        )
+        .bind(prefix[0].to_string())
+        .bind(prefix[1].to_string())
+        .bind(prefix[2].to_string())
+        .bind(id)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Get the clients other than `id` that have an IPv6 address.
+    pub async fn get_ipv6_clients(&self, id: Uuid) -> AppResult<Vec<Client>> {
+        let clients = sqlx::query_as::<_, Client>(
+            r#"
+            SELECT * FROM clients
+            WHERE ipv6 IS NOT NULL AND ipv6 <> '' AND id != $1 AND NOT archived
+            ORDER BY weight DESC, name
+            "#,
+        )
+        .bind(id)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    /// Full-text search clients by name, CPU, OS, IPv4 and remarks.
+    ///
+    /// Every word of the query must match, each as a prefix.
+    pub async fn search_clients(&self, query: &str) -> AppResult<Vec<Client>> {
+        let Some(tsquery) = prefix_tsquery(query) else {
+            return Ok(Vec::new());
+        };
+
+        let clients = sqlx::query_as::<_, Client>(
+            r#"
+            SELECT * FROM clients
+            WHERE search_vector @@ to_tsquery('english', $1)
+            ORDER BY ts_rank(search_vector, to_tsquery('english', $1)) DESC, weight DESC, name
+            "#,
         )
-        .fetch_all(&self.pool)
+        .bind(tsquery)
+        .fetch_all(self.read_pool()?)
         .await?;
 
         Ok(clients)
     }
 
     /// Update client basic info.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_client_basic_info(
         &self,
         id: Uuid,
@@ -222,6 +578,8 @@ impl Database {
         swap_total: i64,
         disk_total: i64,
         version: &str,
+        ntp_synced: Option<bool>,
+        clock_offset_ms: Option<f64>,
     ) -> AppResult<()> {
         sqlx::query(
             r#"
@@ -229,13 +587,13 @@ impl Database {
                 cpu_name = $2, arch = $3, cpu_cores = $4, os = $5,
                 kernel_version = $6, gpu_name = $7, virtualization = $8,
                 mem_total = $9, swap_total = $10, disk_total = $11,
-                version = $12, updated_at = NOW()
+                version = $12, ntp_synced = $13, clock_offset_ms = $14, updated_at = NOW()
             WHERE id = $1
             "#,
         )
         .bind(id)
         .bind(cpu_name)
-        .bind(arch)
+        .bind(normalize_arch(arch))
         .bind(cpu_cores)
         .bind(os)
         .bind(kernel_version)
@@ -245,18 +603,152 @@ impl Database {
         .bind(swap_total)
         .bind(disk_total)
         .bind(version)
-        .execute(&self.pool)
+        .bind(ntp_synced)
+        .bind(clock_offset_ms)
+        .execute(self.primary()?)
         .await?;
 
         Ok(())
     }
 
-    /// Update client online status.
-    pub async fn update_client_online(&self, id: Uuid, online: bool) -> AppResult<()> {
-        sqlx::query("UPDATE clients SET online = $2, last_seen_at = NOW() WHERE id = $1")
+    /// Update client online status. Returns whether the status changed.
+    ///
+    /// A change is also added to `client_status_history`, closing the
+    /// duration of the previous event. Nothing changes while the status is
+    /// manually overridden.
+    pub async fn update_client_online(&self, id: Uuid, online: bool) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            WITH previous AS (
+                SELECT online FROM clients WHERE id = $1 AND NOT manually_overridden
+            ), updated AS (
+                UPDATE clients SET online = $2, last_seen_at = NOW()
+                WHERE id = $1 AND NOT manually_overridden
+            ), changed AS (
+                SELECT 1 FROM previous WHERE online IS DISTINCT FROM $2
+            ), closed AS (
+                UPDATE client_status_history
+                SET duration_seconds = EXTRACT(EPOCH FROM NOW() - transitioned_at)::bigint
+                WHERE id = (
+                    SELECT id FROM client_status_history WHERE client_id = $1
+                    ORDER BY transitioned_at DESC LIMIT 1
+                ) AND duration_seconds IS NULL AND EXISTS (SELECT 1 FROM changed)
+            )
+            INSERT INTO client_status_history (client_id, online)
+            SELECT $1, $2 FROM changed
+            "#,
+        )
+        .bind(id)
+        .bind(online)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark clients offline that have not reported within
+    /// `report_timeout_secs`, returning their IDs.
+    ///
+    /// Manually overridden and archived clients, and those in `connected`,
+    /// are left alone. Each change is added to `client_status_history`.
+    pub async fn mark_stale_clients_offline(
+        &self,
+        report_timeout_secs: i64,
+        connected: &[Uuid],
+    ) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            WITH stale AS (
+                UPDATE clients SET online = FALSE
+                WHERE online AND NOT manually_overridden AND NOT archived
+                    AND NOT (id = ANY($2))
+                    AND (last_seen_at IS NULL
+                         OR last_seen_at < NOW() - make_interval(secs => $1))
+                RETURNING id
+            ), closed AS (
+                UPDATE client_status_history e
+                SET duration_seconds = EXTRACT(EPOCH FROM NOW() - e.transitioned_at)::bigint
+                FROM stale s
+                WHERE e.id = (
+                    SELECT id FROM client_status_history WHERE client_id = s.id
+                    ORDER BY transitioned_at DESC LIMIT 1
+                ) AND e.duration_seconds IS NULL
+            ), went_offline AS (
+                INSERT INTO client_status_history (client_id, online)
+                SELECT id, FALSE FROM stale
+            )
+            SELECT id FROM stale
+            "#,
+        )
+        .bind(report_timeout_secs as f64)
+        .bind(connected)
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Mark a client online after a report and record the transport used.
+    /// Returns whether the client came online.
+    ///
+    /// A report from a new address is also added to `client_ip_history`, and
+    /// a client coming online to `client_status_history`. A manually
+    /// overridden status is left as is.
+    pub async fn mark_client_reported(
+        &self,
+        id: Uuid,
+        transport: &str,
+        ip: Option<&str>,
+    ) -> AppResult<bool> {
+        let came_online = sqlx::query_scalar::<_, bool>(
+            r#"
+            WITH previous AS (
+                SELECT online OR manually_overridden AS online, last_report_ip
+                FROM clients WHERE id = $1
+            ), updated AS (
+                UPDATE clients
+                SET online = online OR NOT manually_overridden, last_seen_at = NOW(),
+                    last_report_transport = $2,
+                    last_report_ip = COALESCE($3, last_report_ip)
+                WHERE id = $1
+            ), closed AS (
+                UPDATE client_status_history
+                SET duration_seconds = EXTRACT(EPOCH FROM NOW() - transitioned_at)::bigint
+                WHERE id = (
+                    SELECT id FROM client_status_history WHERE client_id = $1
+                    ORDER BY transitioned_at DESC LIMIT 1
+                ) AND duration_seconds IS NULL
+                    AND EXISTS (SELECT 1 FROM previous WHERE online IS NOT TRUE)
+            ), came_online AS (
+                INSERT INTO client_status_history (client_id, online)
+                SELECT $1, TRUE FROM previous WHERE online IS NOT TRUE
+            ), moved AS (
+                INSERT INTO client_ip_history (client_id, ip, previous_ip)
+                SELECT $1, $3, last_report_ip FROM previous
+                WHERE $3 IS NOT NULL AND last_report_ip IS DISTINCT FROM $3
+            )
+            SELECT EXISTS (SELECT 1 FROM previous WHERE online IS NOT TRUE)
+            "#,
+        )
+        .bind(id)
+        .bind(transport)
+        .bind(ip)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(came_online)
+    }
+
+    /// Set or clear client maintenance mode.
+    pub async fn set_client_maintenance(
+        &self,
+        id: Uuid,
+        until: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        sqlx::query("UPDATE clients SET maintenance_until = $2, updated_at = NOW() WHERE id = $1")
             .bind(id)
-            .bind(online)
-            .execute(&self.pool)
+            .bind(until)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
@@ -273,23 +765,96 @@ impl Database {
             .bind(id)
             .bind(ipv4)
             .bind(ipv6)
-            .execute(&self.pool)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
     }
 
+    /// Archive or unarchive a client. Archiving clears a manual online
+    /// override.
+    pub async fn set_client_archived(&self, id: Uuid, archived: bool) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE clients
+            SET archived = $2, manually_overridden = manually_overridden AND NOT $2,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(archived)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Override the online status of a client until
+    /// [`clear_client_online_override`](Self::clear_client_online_override).
+    ///
+    /// A change is added to `client_status_history` with `reason`.
+    pub async fn set_client_online_override(
+        &self,
+        id: Uuid,
+        online: bool,
+        reason: Option<&str>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            WITH previous AS (
+                SELECT online FROM clients WHERE id = $1
+            ), updated AS (
+                UPDATE clients SET online = $2, manually_overridden = TRUE, updated_at = NOW()
+                WHERE id = $1
+            ), changed AS (
+                SELECT 1 FROM previous WHERE online IS DISTINCT FROM $2
+            ), closed AS (
+                UPDATE client_status_history
+                SET duration_seconds = EXTRACT(EPOCH FROM NOW() - transitioned_at)::bigint
+                WHERE id = (
+                    SELECT id FROM client_status_history WHERE client_id = $1
+                    ORDER BY transitioned_at DESC LIMIT 1
+                ) AND duration_seconds IS NULL AND EXISTS (SELECT 1 FROM changed)
+            )
+            INSERT INTO client_status_history (client_id, online, reason)
+            SELECT $1, $2, $3 FROM changed
+            "#,
+        )
+        .bind(id)
+        .bind(online)
+        .bind(reason)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Clear a manual online override, returning the client to the status
+    /// its agent reports.
+    pub async fn clear_client_online_override(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query(
+            "UPDATE clients SET manually_overridden = FALSE, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
     /// Delete client.
     pub async fn delete_client(&self, id: Uuid) -> AppResult<()> {
         sqlx::query("DELETE FROM clients WHERE id = $1")
             .bind(id)
-            .execute(&self.pool)
+            .execute(self.primary()?)
             .await?;
 
         Ok(())
     }
 
     /// Update client editable fields.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_client(
         &self,
         id: Uuid,
@@ -299,6 +864,13 @@ impl Database {
         public_remark: Option<&str>,
         hidden: Option<bool>,
         weight: Option<i32>,
+        tags: Option<&[String]>,
+        display_color: Option<&str>,
+        display_icon: Option<&str>,
+        alert_on_ip_change: Option<bool>,
+        report_interval_seconds: Option<Option<i32>>,
+        traffic_limit: Option<i64>,
+        traffic_limit_type: Option<&str>,
     ) -> AppResult<()> {
         let mut query = String::from("UPDATE clients SET updated_at = NOW()");
         let mut param_count = 1;
@@ -327,6 +899,34 @@ impl Database {
             param_count += 1;
             query.push_str(&format!(", weight = ${}", param_count));
         }
+        if tags.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", tags = ${}", param_count));
+        }
+        if display_color.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", display_color = ${}", param_count));
+        }
+        if display_icon.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", display_icon = ${}", param_count));
+        }
+        if alert_on_ip_change.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", alert_on_ip_change = ${}", param_count));
+        }
+        if report_interval_seconds.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", report_interval_seconds = ${}", param_count));
+        }
+        if traffic_limit.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", traffic_limit = ${}", param_count));
+        }
+        if traffic_limit_type.is_some() {
+            param_count += 1;
+            query.push_str(&format!(", traffic_limit_type = ${}", param_count));
+        }
 
         query.push_str(" WHERE id = $1");
 
@@ -350,24 +950,116 @@ impl Database {
         if let Some(v) = weight {
             q = q.bind(v);
         }
-
-        q.execute(&self.pool).await?;
-
-        Ok(())
-    }
-
-    // ==================== Record Operations ====================
-
-    /// Insert a monitoring record.
-    pub async fn insert_record(&self, client_id: Uuid, record: &RecordInput) -> AppResult<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO records (
+        if let Some(v) = tags {
+            q = q.bind(v);
+        }
+        if let Some(v) = display_color {
+            q = q.bind(v);
+        }
+        if let Some(v) = display_icon {
+            q = q.bind(v);
+        }
+        if let Some(v) = alert_on_ip_change {
+            q = q.bind(v);
+        }
+        if let Some(v) = report_interval_seconds {
+            q = q.bind(v);
+        }
+        if let Some(v) = traffic_limit {
+            q = q.bind(v);
+        }
+        if let Some(v) = traffic_limit_type {
+            q = q.bind(v);
+        }
+
+        q.execute(self.primary()?).await?;
+
+        Ok(())
+    }
+
+    /// Atomically add and remove client tags, returning the resulting tags.
+    ///
+    /// Existing tag order is preserved, added tags are appended and
+    /// duplicates are collapsed. Returns `None` if the client does not exist.
+    pub async fn modify_client_tags(
+        &self,
+        id: Uuid,
+        add: &[String],
+        remove: &[String],
+    ) -> AppResult<Option<Vec<String>>> {
+        let tags = sqlx::query_scalar::<_, Vec<String>>(
+            r#"
+            UPDATE clients SET
+                tags = ARRAY(
+                    SELECT t FROM unnest(COALESCE(tags, '{}') || $2::text[]) WITH ORDINALITY AS u(t, ord)
+                    WHERE t <> ALL($3::text[])
+                    GROUP BY t
+                    ORDER BY MIN(ord)
+                ),
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING tags
+            "#,
+        )
+        .bind(id)
+        .bind(add)
+        .bind(remove)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(tags)
+    }
+
+    // ==================== Record Operations ====================
+
+    /// Insert a monitoring record, timestamped with its `recorded_at` or,
+    /// without one, the server time.
+    ///
+    /// Traffic totals lower than the previous record's (the agent rebooted)
+    /// are logged in `counter_resets`, in the same transaction as the
+    /// record, so corrected totals stay monotonic; the stored raw values are
+    /// not changed.
+    ///
+    /// A record with the same traffic counters and uptime as one already
+    /// stored in the current second, or with the same timestamp, is a
+    /// duplicate (e.g. an agent reporting over HTTP and WebSocket at once)
+    /// and is dropped; the unique index on `(client_id, time)` makes this
+    /// hold for concurrent inserts. Returns whether the record was inserted.
+    pub async fn insert_record(&self, client_id: Uuid, record: &RecordInput) -> AppResult<bool> {
+        self.insert_record_at(client_id, record, record.recorded_at)
+            .await
+    }
+
+    /// Insert a monitoring record like [`Self::insert_record`], timestamped
+    /// `time` instead, or now when `None`.
+    pub async fn insert_record_at(
+        &self,
+        client_id: Uuid,
+        record: &RecordInput,
+        time: Option<DateTime<Utc>>,
+    ) -> AppResult<bool> {
+        self.breaker.check()?;
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query_as::<_, (DateTime<Utc>,)>(
+            r#"
+            INSERT INTO records (
                 client_id, cpu, gpu, ram, ram_total, swap, swap_total,
                 load, temp, disk, disk_total, net_in, net_out,
-                net_total_up, net_total_down, process, connections, connections_udp, uptime
+                net_total_up, net_total_down, process, connections, connections_udp, uptime,
+                fd_used, fd_total, inode_used, inode_total, load5, load15, time
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+                   $20, $21, $22, $23, $24, $25, COALESCE($26, NOW())
+            WHERE NOT EXISTS (
+                SELECT 1 FROM records
+                WHERE client_id = $1
+                  AND time >= date_trunc('second', COALESCE($26, NOW()))
+                  AND time < date_trunc('second', COALESCE($26, NOW())) + INTERVAL '1 second'
+                  AND net_total_up = $14 AND net_total_down = $15 AND uptime = $19
+            )
+            ON CONFLICT (client_id, time) DO NOTHING
+            RETURNING time
             "#,
         )
         .bind(client_id)
@@ -389,10 +1081,59 @@ impl Database {
         .bind(record.connections)
         .bind(record.connections_udp)
         .bind(record.uptime)
-        .execute(&self.pool)
+        .bind(record.fd_used)
+        .bind(record.fd_total)
+        .bind(record.inode_used)
+        .bind(record.inode_total)
+        .bind(record.load5)
+        .bind(record.load15)
+        .bind(time)
+        .fetch_optional(&mut *tx)
         .await?;
+        let Some((time,)) = inserted else {
+            return Ok(false);
+        };
 
-        Ok(())
+        // Compared with the record before this one, so late records are
+        // checked at their own place in the series
+        sqlx::query(
+            r#"
+            INSERT INTO counter_resets (client_id, time, prev_up, prev_down)
+            SELECT $1, $2,
+                CASE WHEN prev.net_total_up > $3 + $5 THEN prev.net_total_up ELSE 0 END,
+                CASE WHEN prev.net_total_down > $4 + $5 THEN prev.net_total_down ELSE 0 END
+            FROM (
+                SELECT net_total_up, net_total_down FROM records
+                WHERE client_id = $1 AND time < $2 ORDER BY time DESC LIMIT 1
+            ) prev
+            WHERE prev.net_total_up > $3 + $5 OR prev.net_total_down > $4 + $5
+            "#,
+        )
+        .bind(client_id)
+        .bind(time)
+        .bind(record.net_total_up)
+        .bind(record.net_total_down)
+        .bind(COUNTER_RESET_TOLERANCE)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(tcp_states) = &record.tcp_states {
+            // Buffered reports arrive late and must not replace a newer snapshot
+            sqlx::query(
+                r#"
+                UPDATE clients SET tcp_states = $2, tcp_states_at = $3
+                WHERE id = $1 AND (tcp_states_at IS NULL OR tcp_states_at <= $3)
+                "#,
+            )
+            .bind(client_id)
+            .bind(serde_json::to_value(tcp_states).unwrap_or_default())
+            .bind(time)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
     }
 
     /// Get recent records for a client.
@@ -402,194 +1143,2892 @@ impl Database {
         )
         .bind(client_id)
         .bind(limit)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool()?)
         .await?;
 
         Ok(records)
     }
 
+    /// Stream the recent records of a client like [`Self::get_recent_records`]
+    /// without loading them all: a task reads the rows and hands them over
+    /// through a queue of [`RECORD_STREAM_BUFFER`] records.
+    pub fn stream_recent_records(
+        &self,
+        client_id: Uuid,
+        limit: i32,
+    ) -> AppResult<impl Stream<Item = AppResult<Record>> + Send + 'static> {
+        let pool = self.read_pool()?;
+        let (tx, rx) = mpsc::channel(RECORD_STREAM_BUFFER);
+        tokio::spawn(async move {
+            let query = sqlx::query_as::<_, Record>(
+                "SELECT * FROM records WHERE client_id = $1 ORDER BY time DESC LIMIT $2",
+            )
+            .bind(client_id)
+            .bind(limit);
+            let mut records = pool.fetch_stream(query);
+            while let Some(record) = records.next().await {
+                // Stop reading once the receiver is gone
+                if tx.send(record.map_err(AppError::from)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|record| (record, rx))
+        }))
+    }
+
+    /// Get the records of a client in `[since, until)` averaged over buckets
+    /// of `step_secs` aligned to the Unix epoch, newest first. Each point is
+    /// timestamped with the start of its bucket and carries the last record
+    /// ID; cumulative counters and uptime take the bucket maximum. Buckets
+    /// without records are left out.
+    pub async fn get_record_buckets(
+        &self,
+        client_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        step_secs: i64,
+    ) -> AppResult<Vec<Record>> {
+        let points = sqlx::query_as::<_, Record>(
+            r#"
+            SELECT MAX(id) AS id, client_id,
+                to_timestamp(floor(EXTRACT(EPOCH FROM time) / $4) * $4) AS time,
+                AVG(cpu)::real AS cpu, AVG(gpu)::real AS gpu,
+                AVG(ram)::bigint AS ram, AVG(ram_total)::bigint AS ram_total,
+                AVG(swap)::bigint AS swap, AVG(swap_total)::bigint AS swap_total,
+                AVG(load)::real AS load, AVG(load5)::real AS load5,
+                AVG(load15)::real AS load15, AVG(temp)::real AS temp,
+                AVG(disk)::bigint AS disk, AVG(disk_total)::bigint AS disk_total,
+                AVG(net_in)::bigint AS net_in, AVG(net_out)::bigint AS net_out,
+                MAX(net_total_up) AS net_total_up, MAX(net_total_down) AS net_total_down,
+                AVG(process)::integer AS process,
+                AVG(connections)::integer AS connections,
+                AVG(connections_udp)::integer AS connections_udp,
+                MAX(uptime) AS uptime,
+                AVG(fd_used)::integer AS fd_used, AVG(fd_total)::integer AS fd_total,
+                AVG(inode_used)::bigint AS inode_used, AVG(inode_total)::bigint AS inode_total
+            FROM records
+            WHERE client_id = $1 AND time >= $2 AND time < $3
+            GROUP BY client_id, 3
+            ORDER BY 3 DESC
+            "#,
+        )
+        .bind(client_id)
+        .bind(since)
+        .bind(until)
+        .bind(step_secs as f64)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(points)
+    }
+
     /// Get the latest record for a client.
     pub async fn get_latest_record(&self, client_id: Uuid) -> AppResult<Option<Record>> {
         let record = sqlx::query_as::<_, Record>(
             "SELECT * FROM records WHERE client_id = $1 ORDER BY time DESC LIMIT 1",
         )
         .bind(client_id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(self.read_pool()?)
         .await?;
 
         Ok(record)
     }
 
-    /// Delete old records (retention policy).
-    pub async fn delete_old_records(&self, days: i32) -> AppResult<u64> {
-        let result =
-            sqlx::query("DELETE FROM records WHERE time < NOW() - INTERVAL '1 day' * $1::integer")
-                .bind(days)
-                .execute(&self.pool)
-                .await?;
+    /// Get the latest record of each of the given clients in one query.
+    pub async fn get_latest_records(&self, client_ids: &[Uuid]) -> AppResult<Vec<Record>> {
+        let records = sqlx::query_as::<_, Record>(
+            r#"
+            SELECT DISTINCT ON (client_id) * FROM records
+            WHERE client_id = ANY($1)
+            ORDER BY client_id, time DESC
+            "#,
+        )
+        .bind(client_ids)
+        .fetch_all(self.read_pool()?)
+        .await?;
 
-        Ok(result.rows_affected())
+        Ok(records)
     }
 
-    // ==================== Notification Operations ====================
-
-    /// Create a notification provider.
-    pub async fn create_notification(
+    /// Get the records of a client in `[since, until)` averaged into up to
+    /// `points` equal buckets, oldest first. Buckets without records are left out.
+    pub async fn get_record_points(
         &self,
-        name: &str,
-        provider: &str,
-        config: serde_json::Value,
-    ) -> AppResult<Notification> {
-        let notification = sqlx::query_as::<_, Notification>(
+        client_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        points: i32,
+    ) -> AppResult<Vec<RecordPoint>> {
+        let bucket_secs = ((until - since).num_seconds() as f64 / points.max(1) as f64).max(1.0);
+        let points = sqlx::query_as::<_, RecordPoint>(
             r#"
-            INSERT INTO notifications (name, provider, config)
-            VALUES ($1, $2, $3)
-            RETURNING *
+            SELECT $2 + make_interval(secs => floor(EXTRACT(EPOCH FROM time - $2) / $4) * $4) AS time,
+                AVG(cpu)::real AS cpu, AVG(ram)::bigint AS ram, AVG(disk)::bigint AS disk,
+                AVG(load)::real AS load, AVG(temp)::real AS temp,
+                AVG(net_in)::bigint AS net_in, AVG(net_out)::bigint AS net_out
+            FROM records
+            WHERE client_id = $1 AND time >= $2 AND time < $3
+            GROUP BY 1
+            ORDER BY 1
             "#,
         )
-        .bind(name)
-        .bind(provider)
-        .bind(config)
-        .fetch_one(&self.pool)
+        .bind(client_id)
+        .bind(since)
+        .bind(until)
+        .bind(bucket_secs)
+        .fetch_all(self.read_pool()?)
         .await?;
 
-        Ok(notification)
+        Ok(points)
     }
 
-    /// Get all notifications.
-    pub async fn get_all_notifications(&self) -> AppResult<Vec<Notification>> {
-        let notifications =
-            sqlx::query_as::<_, Notification>("SELECT * FROM notifications ORDER BY name")
-                .fetch_all(&self.pool)
-                .await?;
+    /// Aggregate a metric over the records of some clients between `since`
+    /// (inclusive) and `until` (exclusive).
+    ///
+    /// `metric` is a record column or one of `ram_pct`, `swap_pct` and
+    /// `disk_pct`; unknown metrics have no samples.
+    pub async fn get_metric_aggregate(
+        &self,
+        client_ids: &[Uuid],
+        metric: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<MetricAggregate> {
+        let aggregate = sqlx::query_as::<_, MetricAggregate>(
+            r#"
+            WITH samples AS (
+                SELECT CASE $2
+                    WHEN 'cpu' THEN cpu::float8
+                    WHEN 'gpu' THEN gpu::float8
+                    WHEN 'ram_pct' THEN (ram * 100.0 / NULLIF(ram_total, 0))::float8
+                    WHEN 'swap_pct' THEN (swap * 100.0 / NULLIF(swap_total, 0))::float8
+                    WHEN 'disk_pct' THEN (disk * 100.0 / NULLIF(disk_total, 0))::float8
+                    WHEN 'load' THEN load::float8
+                    WHEN 'load5' THEN load5::float8
+                    WHEN 'load15' THEN load15::float8
+                    WHEN 'temp' THEN temp::float8
+                    WHEN 'net_in' THEN net_in::float8
+                    WHEN 'net_out' THEN net_out::float8
+                    WHEN 'process' THEN process::float8
+                    WHEN 'connections' THEN connections::float8
+                END AS value
+                FROM records
+                WHERE client_id = ANY($1) AND time >= $3 AND time < $4
+            )
+            SELECT AVG(value) AS avg,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY value) AS p95,
+                MAX(value) AS max,
+                COUNT(value) AS samples
+            FROM samples
+            "#,
+        )
+        .bind(client_ids)
+        .bind(metric)
+        .bind(since)
+        .bind(until)
+        .fetch_one(self.read_pool()?)
+        .await?;
 
-        Ok(notifications)
+        Ok(aggregate)
     }
 
-    /// Delete notification.
-    pub async fn delete_notification(&self, id: Uuid) -> AppResult<()> {
-        sqlx::query("DELETE FROM notifications WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    /// Get the fleet totals of each client group, or only of the group
+    /// `name`, ordered by group name. Clients without a group are left out.
+    pub async fn get_group_stats(
+        &self,
+        name: Option<&str>,
+        include_hidden: bool,
+    ) -> AppResult<Vec<GroupStats>> {
+        let stats = sqlx::query_as::<_, GroupStats>(
+            r#"
+            WITH members AS (
+                SELECT id, group_name, online, mem_total, disk_total FROM clients
+                WHERE group_name <> '' AND NOT archived
+                    AND ($1::text IS NULL OR group_name = $1)
+                    AND ($2 OR NOT hidden)
+            ),
+            latest AS (
+                SELECT DISTINCT ON (r.client_id) r.*
+                FROM records r
+                JOIN members m ON m.id = r.client_id AND m.online
+                ORDER BY r.client_id, r.time DESC
+            )
+            SELECT m.group_name AS name,
+                COUNT(*) AS members,
+                COUNT(*) FILTER (WHERE m.online) AS online,
+                COALESCE(SUM(m.mem_total), 0)::bigint AS ram_total,
+                COALESCE(SUM(l.ram), 0)::bigint AS ram_used,
+                COALESCE(SUM(m.disk_total), 0)::bigint AS disk_total,
+                COALESCE(SUM(l.disk), 0)::bigint AS disk_used,
+                COALESCE(SUM(l.net_in), 0)::bigint AS net_in,
+                COALESCE(SUM(l.net_out), 0)::bigint AS net_out,
+                AVG(l.cpu)::float8 AS avg_cpu
+            FROM members m
+            LEFT JOIN latest l ON l.client_id = m.id
+            GROUP BY m.group_name
+            ORDER BY m.group_name
+            "#,
+        )
+        .bind(name)
+        .bind(include_hidden)
+        .fetch_all(self.read_pool()?)
+        .await?;
 
-        Ok(())
+        Ok(stats)
     }
 
-    // ==================== Ping Task Operations ====================
-
-    /// Create a ping task.
-    pub async fn create_ping_task(
+    /// Get the totals of the group `name` in `[since, until)` in buckets of
+    /// `step_secs` aligned to the Unix epoch, oldest first. Buckets without
+    /// records are left out.
+    pub async fn get_group_stats_points(
         &self,
         name: &str,
-        target: &str,
-        interval_seconds: i32,
-        timeout_seconds: i32,
-    ) -> AppResult<PingTask> {
-        let task = sqlx::query_as::<_, PingTask>(
+        include_hidden: bool,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        step_secs: i64,
+    ) -> AppResult<Vec<GroupStatsPoint>> {
+        let points = sqlx::query_as::<_, GroupStatsPoint>(
             r#"
-            INSERT INTO ping_tasks (name, target, interval_seconds, timeout_seconds)
-            VALUES ($1, $2, $3, $4)
-            RETURNING *
+            WITH per_client AS (
+                SELECT to_timestamp(floor(EXTRACT(EPOCH FROM r.time) / $5) * $5) AS time,
+                    r.client_id,
+                    AVG(r.cpu) AS cpu,
+                    AVG(r.ram_total) AS ram_total, AVG(r.ram) AS ram,
+                    AVG(r.disk_total) AS disk_total, AVG(r.disk) AS disk,
+                    AVG(r.net_in) AS net_in, AVG(r.net_out) AS net_out
+                FROM records r
+                JOIN clients c ON c.id = r.client_id
+                WHERE c.group_name = $1 AND ($2 OR NOT c.hidden)
+                    AND r.time >= $3 AND r.time < $4
+                GROUP BY 1, 2
+            )
+            SELECT time,
+                COUNT(*) AS reporting,
+                AVG(cpu)::float8 AS avg_cpu,
+                SUM(ram_total)::bigint AS ram_total,
+                SUM(ram)::bigint AS ram_used,
+                SUM(disk_total)::bigint AS disk_total,
+                SUM(disk)::bigint AS disk_used,
+                SUM(net_in)::bigint AS net_in,
+                SUM(net_out)::bigint AS net_out
+            FROM per_client
+            GROUP BY time
+            ORDER BY time
             "#,
         )
         .bind(name)
-        .bind(target)
-        .bind(interval_seconds)
-        .bind(timeout_seconds)
-        .fetch_one(&self.pool)
+        .bind(include_hidden)
+        .bind(since)
+        .bind(until)
+        .bind(step_secs as f64)
+        .fetch_all(self.read_pool()?)
         .await?;
 
-        Ok(task)
+        Ok(points)
     }
 
-    /// Get all ping tasks.
-    pub async fn get_all_ping_tasks(&self) -> AppResult<Vec<PingTask>> {
-        let tasks = sqlx::query_as::<_, PingTask>("SELECT * FROM ping_tasks ORDER BY name")
-            .fetch_all(&self.pool)
-            .await?;
+    /// Delete up to [`MAX_DUPLICATE_DELETES`] records of a client that
+    /// repeat an earlier record within `window_ms` milliseconds (same CPU to
+    /// one decimal, RAM and disk). The earliest record of each run is kept.
+    pub async fn delete_duplicate_records(
+        &self,
+        client_id: Uuid,
+        window_ms: i32,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(&format!(
+            "{} DELETE FROM records WHERE id IN (SELECT id FROM duplicates)",
+            DUPLICATE_RECORDS_CTE
+        ))
+        .bind(client_id)
+        .bind(window_ms)
+        .bind(MAX_DUPLICATE_DELETES)
+        .execute(self.primary()?)
+        .await?;
 
-        Ok(tasks)
+        Ok(result.rows_affected())
     }
 
-    /// Get enabled ping tasks.
-    pub async fn get_enabled_ping_tasks(&self) -> AppResult<Vec<PingTask>> {
-        let tasks = sqlx::query_as::<_, PingTask>(
-            "SELECT * FROM ping_tasks WHERE enabled = TRUE ORDER BY name",
+    /// Count the records [`Self::delete_duplicate_records`] would delete.
+    pub async fn count_duplicate_records(&self, client_id: Uuid, window_ms: i32) -> AppResult<u64> {
+        let row = sqlx::query(&format!(
+            "{} SELECT COUNT(*) AS count FROM duplicates",
+            DUPLICATE_RECORDS_CTE
+        ))
+        .bind(client_id)
+        .bind(window_ms)
+        .bind(MAX_DUPLICATE_DELETES)
+        .fetch_one(self.read_pool()?)
+        .await?;
+
+        Ok(row.get::<i64, _>("count") as u64)
+    }
+
+    /// Delete old records (retention policy), except those of archived
+    /// clients with `keep_archived`, in batches of [`RETENTION_DELETE_BATCH`].
+    pub async fn delete_old_records(&self, days: i32, keep_archived: bool) -> AppResult<u64> {
+        let mut deleted = 0;
+        loop {
+            let result = sqlx::query(
+                r#"
+                DELETE FROM records WHERE id IN (
+                    SELECT id FROM records
+                    WHERE time < NOW() - INTERVAL '1 day' * $1::integer
+                        AND NOT ($2 AND client_id IN (SELECT id FROM clients WHERE archived))
+                    LIMIT $3
+                )
+                "#,
+            )
+            .bind(days)
+            .bind(keep_archived)
+            .bind(RETENTION_DELETE_BATCH)
+            .execute(self.primary()?)
+            .await?;
+            deleted += result.rows_affected();
+            if result.rows_affected() < RETENTION_DELETE_BATCH as u64 {
+                return Ok(deleted);
+            }
+        }
+    }
+
+    /// Get the client days that have records before `before`, oldest first,
+    /// leaving out archived clients with `keep_archived`.
+    pub async fn get_record_days_before(
+        &self,
+        before: DateTime<Utc>,
+        keep_archived: bool,
+    ) -> AppResult<Vec<RecordDay>> {
+        let days = sqlx::query_as::<_, RecordDay>(
+            r#"
+            SELECT DISTINCT client_id,
+                date_trunc('day', time AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS day
+            FROM records
+            WHERE time < $1
+                AND NOT ($2 AND client_id IN (SELECT id FROM clients WHERE archived))
+            ORDER BY day, client_id
+            "#,
         )
-        .fetch_all(&self.pool)
+        .bind(before)
+        .bind(keep_archived)
+        .fetch_all(self.primary()?)
         .await?;
 
-        Ok(tasks)
+        Ok(days)
     }
 
-    /// Insert ping record.
-    pub async fn insert_ping_record(
+    /// Get the records of a client in `[since, until)`, oldest first.
+    pub async fn get_records_between(
         &self,
-        task_id: Uuid,
-        client_id: Option<Uuid>,
-        latency_ms: Option<f32>,
-        success: bool,
-    ) -> AppResult<()> {
-        sqlx::query(
+        client_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<Record>> {
+        let records = sqlx::query_as::<_, Record>(
             r#"
-            INSERT INTO ping_records (task_id, client_id, latency_ms, success)
-            VALUES ($1, $2, $3, $4)
+            SELECT * FROM records
+            WHERE client_id = $1 AND time >= $2 AND time < $3
+            ORDER BY time, id
             "#,
         )
-        .bind(task_id)
         .bind(client_id)
-        .bind(latency_ms)
-        .bind(success)
-        .execute(&self.pool)
+        .bind(since)
+        .bind(until)
+        .fetch_all(self.primary()?)
         .await?;
 
-        Ok(())
+        Ok(records)
     }
 
-    /// Get recent ping records for a task.
-    pub async fn get_recent_ping_records(
+    /// Delete the records of a client in `[since, until)` with an ID up to `max_id`.
+    pub async fn delete_records_between(
         &self,
-        task_id: Uuid,
+        client_id: Uuid,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        max_id: i64,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM records WHERE client_id = $1 AND time >= $2 AND time < $3 AND id <= $4",
+        )
+        .bind(client_id)
+        .bind(since)
+        .bind(until)
+        .bind(max_id)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Delete old ping records (retention policy), in batches of
+    /// [`RETENTION_DELETE_BATCH`].
+    pub async fn delete_old_ping_records(&self, days: i32) -> AppResult<u64> {
+        let mut deleted = 0;
+        loop {
+            let result = sqlx::query(
+                r#"
+                DELETE FROM ping_records WHERE id IN (
+                    SELECT id FROM ping_records
+                    WHERE time < NOW() - INTERVAL '1 day' * $1::integer
+                    LIMIT $2
+                )
+                "#,
+            )
+            .bind(days)
+            .bind(RETENTION_DELETE_BATCH)
+            .execute(self.primary()?)
+            .await?;
+            deleted += result.rows_affected();
+            if result.rows_affected() < RETENTION_DELETE_BATCH as u64 {
+                return Ok(deleted);
+            }
+        }
+    }
+
+    /// Roll completed hours of ping records up into `ping_records_hourly`.
+    ///
+    /// Each task starts from its own latest rolled-up hour, which is
+    /// recomputed to pick up records that arrived late, so running it again
+    /// changes nothing. Returns the number of hourly rows written.
+    pub async fn rollup_ping_records(&self) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            WITH marks AS (
+                SELECT task_id, MAX(hour) AS hour FROM ping_records_hourly GROUP BY task_id
+            )
+            INSERT INTO ping_records_hourly
+                (task_id, hour, sample_count, success_ratio, avg_latency_ms, min_latency_ms, max_latency_ms)
+            SELECT
+                p.task_id,
+                date_trunc('hour', p.time),
+                COUNT(*),
+                AVG(CASE WHEN success THEN 1.0 ELSE 0.0 END)::float8,
+                AVG(latency_ms) FILTER (WHERE success),
+                MIN(latency_ms) FILTER (WHERE success),
+                MAX(latency_ms) FILTER (WHERE success)
+            FROM ping_records p
+            LEFT JOIN marks m USING (task_id)
+            WHERE p.time < date_trunc('hour', NOW())
+                AND p.time >= COALESCE(m.hour, '-infinity')
+            GROUP BY p.task_id, date_trunc('hour', p.time)
+            ON CONFLICT (task_id, hour) DO UPDATE SET
+                sample_count = EXCLUDED.sample_count,
+                success_ratio = EXCLUDED.success_ratio,
+                avg_latency_ms = EXCLUDED.avg_latency_ms,
+                min_latency_ms = EXCLUDED.min_latency_ms,
+                max_latency_ms = EXCLUDED.max_latency_ms
+            "#,
+        )
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Delete hourly ping rollups older than `days`.
+    pub async fn delete_old_ping_rollups(&self, days: i32) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM ping_records_hourly WHERE hour < NOW() - INTERVAL '1 day' * $1::integer",
+        )
+        .bind(days)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get the clients with the highest average CPU usage in a time window.
+    pub async fn get_top_cpu_clients(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
         limit: i32,
-    ) -> AppResult<Vec<PingRecord>> {
-        let records = sqlx::query_as::<_, PingRecord>(
-            "SELECT * FROM ping_records WHERE task_id = $1 ORDER BY time DESC LIMIT $2",
+    ) -> AppResult<Vec<ClientAggregate>> {
+        let clients = sqlx::query_as::<_, ClientAggregate>(
+            r#"
+            SELECT c.id AS client_id, c.name, AVG(r.cpu)::float8 AS value
+            FROM records r
+            JOIN clients c ON c.id = r.client_id AND NOT c.archived
+            WHERE r.time >= $1 AND r.time < $2
+            GROUP BY c.id, c.name
+            ORDER BY value DESC
+            LIMIT $3
+            "#,
         )
-        .bind(task_id)
+        .bind(since)
+        .bind(until)
         .bind(limit)
-        .fetch_all(&self.pool)
+        .fetch_all(self.read_pool()?)
         .await?;
 
-        Ok(records)
+        Ok(clients)
     }
 
-    // ==================== Settings Operations ====================
+    /// Get the clients with the most traffic (bytes up + down) in a time window.
+    pub async fn get_top_traffic_clients(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: i32,
+    ) -> AppResult<Vec<ClientAggregate>> {
+        let mut clients: Vec<ClientAggregate> = self
+            .get_traffic_totals(since, until)
+            .await?
+            .into_iter()
+            .map(|t| ClientAggregate {
+                client_id: t.client_id,
+                name: t.name,
+                value: (t.up + t.down) as f64,
+            })
+            .collect();
+        clients.sort_by(|a, b| b.value.total_cmp(&a.value));
+        clients.truncate(limit.max(0) as usize);
 
-    /// Get a setting value.
-    pub async fn get_setting(&self, key: &str) -> AppResult<Option<serde_json::Value>> {
-        let setting = sqlx::query_as::<_, Setting>("SELECT * FROM settings WHERE key = $1")
-            .bind(key)
-            .fetch_optional(&self.pool)
-            .await?;
+        Ok(clients)
+    }
+
+    /// Get per-client traffic in a time window, corrected for counter resets.
+    ///
+    /// Traffic is the last minus the first total in the window plus the
+    /// totals lost to every reset in between.
+    pub async fn get_traffic_totals(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<TrafficTotals>> {
+        let totals = sqlx::query_as::<_, TrafficTotals>(
+            r#"
+            SELECT
+                c.id AS client_id, c.name,
+                (l.net_total_up - f.net_total_up + COALESCE(cr.up, 0))::int8 AS up,
+                (l.net_total_down - f.net_total_down + COALESCE(cr.down, 0))::int8 AS down
+            FROM clients c
+            JOIN LATERAL (
+                SELECT time, net_total_up, net_total_down FROM records
+                WHERE client_id = c.id AND time >= $1 AND time < $2
+                ORDER BY time ASC LIMIT 1
+            ) f ON TRUE
+            JOIN LATERAL (
+                SELECT time, net_total_up, net_total_down FROM records
+                WHERE client_id = c.id AND time >= $1 AND time < $2
+                ORDER BY time DESC LIMIT 1
+            ) l ON TRUE
+            LEFT JOIN LATERAL (
+                SELECT SUM(prev_up) AS up, SUM(prev_down) AS down FROM counter_resets
+                WHERE client_id = c.id AND time > f.time AND time <= l.time
+            ) cr ON TRUE
+            WHERE NOT c.archived
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(totals)
+    }
+
+    /// Get clients whose latest disk usage is above a percentage.
+    pub async fn get_high_disk_clients(&self, percent: f64) -> AppResult<Vec<ClientAggregate>> {
+        let clients = sqlx::query_as::<_, ClientAggregate>(
+            r#"
+            SELECT client_id, name, value FROM (
+                SELECT DISTINCT ON (r.client_id)
+                    r.client_id, c.name,
+                    (r.disk * 100.0 / NULLIF(r.disk_total, 0))::float8 AS value
+                FROM records r
+                JOIN clients c ON c.id = r.client_id AND NOT c.archived
+                ORDER BY r.client_id, r.time DESC
+            ) latest
+            WHERE value > $1
+            ORDER BY value DESC
+            "#,
+        )
+        .bind(percent)
+        .fetch_all(self.read_pool()?)
+        .await?;
 
-        Ok(setting.map(|s| s.value))
+        Ok(clients)
     }
 
-    /// Set a setting value.
-    pub async fn set_setting(&self, key: &str, value: serde_json::Value) -> AppResult<()> {
+    // ==================== Client Log Operations ====================
+
+    /// Insert log lines reported by a client, keeping their order.
+    pub async fn insert_log_lines(&self, client_id: Uuid, lines: &[String]) -> AppResult<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+
         sqlx::query(
             r#"
-            INSERT INTO settings (key, value)
-            VALUES ($1, $2)
-            ON CONFLICT (key) DO UPDATE SET value = $2, updated_at = NOW()
+            INSERT INTO client_logs (client_id, line)
+            SELECT $1, line FROM UNNEST($2::text[]) WITH ORDINALITY AS t(line, n)
+            ORDER BY n
             "#,
         )
-        .bind(key)
-        .bind(value)
-        .execute(&self.pool)
+        .bind(client_id)
+        .bind(lines)
+        .execute(self.primary()?)
         .await?;
 
         Ok(())
     }
+
+    /// Get the latest log lines of a client, newest first, optionally
+    /// filtered by a case-insensitive substring.
+    pub async fn get_log_lines(
+        &self,
+        client_id: Uuid,
+        limit: i32,
+        search: Option<&str>,
+    ) -> AppResult<Vec<ClientLogLine>> {
+        let pattern = search.map(|s| {
+            let escaped = s
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        });
+
+        let lines = sqlx::query_as::<_, ClientLogLine>(
+            r#"
+            SELECT * FROM client_logs
+            WHERE client_id = $1 AND ($2::text IS NULL OR line ILIKE $2)
+            ORDER BY id DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(client_id)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(lines)
+    }
+
+    /// Delete all but the latest `keep` log lines of each client.
+    pub async fn trim_log_lines(&self, keep: i64) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM client_logs
+            WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY client_id ORDER BY id DESC) AS n
+                    FROM client_logs
+                ) ranked
+                WHERE n > $1
+            )
+            "#,
+        )
+        .bind(keep)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // ==================== Timeline Operations ====================
+
+    /// Get a client's alert firings and resolutions, report address changes
+    /// and audited configuration changes between `from` and `to`, newest
+    /// first.
+    pub async fn get_client_timeline(
+        &self,
+        client_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> AppResult<Vec<TimelineEvent>> {
+        let events = sqlx::query_as::<_, TimelineEvent>(
+            r#"
+            SELECT * FROM (
+                SELECT h.created_at AS at, 'alert_fired' AS event_type,
+                    format('%s alert: %s %s (threshold %s)', h.severity, h.metric, h.value, h.threshold) AS summary,
+                    jsonb_build_object('alert_id', h.id, 'rule_id', h.rule_id, 'metric', h.metric,
+                        'value', h.value, 'threshold', h.threshold, 'severity', h.severity) AS metadata
+                FROM alert_history h
+                WHERE h.client_id = $1 AND h.created_at BETWEEN $2 AND $3
+                UNION ALL
+                SELECT h.resolved_at, 'alert_resolved',
+                    format('%s alert resolved: %s', h.severity, h.metric),
+                    jsonb_build_object('alert_id', h.id, 'rule_id', h.rule_id, 'metric', h.metric,
+                        'severity', h.severity, 'duration_secs', EXTRACT(EPOCH FROM h.resolved_at - h.created_at)::bigint)
+                FROM alert_history h
+                WHERE h.client_id = $1 AND h.resolved_at BETWEEN $2 AND $3
+                UNION ALL
+                SELECT created_at, 'ip_change',
+                    CASE WHEN previous_ip IS NULL THEN format('Reporting from %s', ip)
+                        ELSE format('Report address changed from %s to %s', previous_ip, ip) END,
+                    jsonb_build_object('ip', ip, 'previous_ip', previous_ip)
+                FROM client_ip_history
+                WHERE client_id = $1 AND created_at BETWEEN $2 AND $3
+                UNION ALL
+                SELECT a.created_at, 'config_change', a.action,
+                    jsonb_build_object('action', a.action, 'user', u.username,
+                        'ip_address', a.ip_address, 'details', a.details)
+                FROM audit_logs a
+                LEFT JOIN users u ON u.id = a.user_id
+                WHERE a.details->>'client_id' = $1::text AND a.created_at BETWEEN $2 AND $3
+            ) timeline
+            ORDER BY at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(client_id)
+        .bind(from)
+        .bind(to)
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Get client status changes, alert firings and resolutions, ping
+    /// failures and audit entries between `from` and `to`, newest first,
+    /// starting after `cursor`.
+    ///
+    /// With `client_id`, only that client's events are returned.
+    pub async fn get_incident_timeline(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        client_id: Option<Uuid>,
+        cursor: Option<&Cursor>,
+        limit: i64,
+    ) -> AppResult<Vec<IncidentEvent>> {
+        let events = sqlx::query_as::<_, IncidentEvent>(
+            r#"
+            SELECT * FROM (
+                SELECT 'status:' || e.id AS id, e.transitioned_at AS at,
+                    CASE WHEN e.online THEN 'client_online' ELSE 'client_offline' END AS event_type,
+                    e.client_id, c.name AS client_name,
+                    CASE WHEN e.online THEN format('%s came online', c.name)
+                        ELSE format('%s went offline', c.name) END AS summary,
+                    '{}'::jsonb AS metadata
+                FROM client_status_history e
+                JOIN clients c ON c.id = e.client_id
+                WHERE e.transitioned_at BETWEEN $1 AND $2
+                    AND ($3::uuid IS NULL OR e.client_id = $3)
+                UNION ALL
+                SELECT 'alert_fired:' || h.id, h.created_at, 'alert_fired', h.client_id, c.name,
+                    format('%s %s alert: %s %s (threshold %s)', c.name, h.severity, h.metric, h.value, h.threshold),
+                    jsonb_build_object('alert_id', h.id, 'rule_id', h.rule_id, 'metric', h.metric,
+                        'value', h.value, 'threshold', h.threshold, 'severity', h.severity)
+                FROM alert_history h
+                JOIN clients c ON c.id = h.client_id
+                WHERE h.created_at BETWEEN $1 AND $2
+                    AND ($3::uuid IS NULL OR h.client_id = $3)
+                UNION ALL
+                SELECT 'alert_resolved:' || h.id, h.resolved_at, 'alert_resolved', h.client_id, c.name,
+                    format('%s %s alert resolved: %s', c.name, h.severity, h.metric),
+                    jsonb_build_object('alert_id', h.id, 'rule_id', h.rule_id, 'metric', h.metric,
+                        'severity', h.severity, 'duration_secs', EXTRACT(EPOCH FROM h.resolved_at - h.created_at)::bigint)
+                FROM alert_history h
+                JOIN clients c ON c.id = h.client_id
+                WHERE h.resolved_at BETWEEN $1 AND $2
+                    AND ($3::uuid IS NULL OR h.client_id = $3)
+                UNION ALL
+                -- Only the first failure after a success
+                SELECT 'ping_failed:' || p.id, p.time, 'ping_failed', p.client_id, c.name,
+                    format('Ping task %s failed', t.name),
+                    jsonb_build_object('task_id', t.id, 'task_name', t.name, 'target', t.target)
+                FROM ping_records p
+                JOIN ping_tasks t ON t.id = p.task_id
+                LEFT JOIN clients c ON c.id = p.client_id
+                WHERE p.success IS NOT TRUE AND p.time BETWEEN $1 AND $2
+                    AND ($3::uuid IS NULL OR p.client_id = $3)
+                    AND COALESCE((
+                        SELECT prev.success FROM ping_records prev
+                        WHERE prev.task_id = p.task_id AND prev.time < p.time
+                        ORDER BY prev.time DESC
+                        LIMIT 1
+                    ), TRUE)
+                UNION ALL
+                SELECT 'audit:' || a.id, a.created_at, 'audit', c.id, c.name, a.action,
+                    jsonb_build_object('action', a.action, 'user', u.username,
+                        'ip_address', a.ip_address, 'details', a.details)
+                FROM audit_logs a
+                LEFT JOIN users u ON u.id = a.user_id
+                LEFT JOIN clients c ON c.id::text = a.details->>'client_id'
+                WHERE a.created_at BETWEEN $1 AND $2
+                    AND ($3::uuid IS NULL OR a.details->>'client_id' = $3::text)
+            ) timeline
+            WHERE $4::timestamptz IS NULL OR (at, id) < ($4, $5)
+            ORDER BY at DESC, id DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .bind(client_id)
+        .bind(cursor.map(|c| c.at))
+        .bind(cursor.map(|c| c.id.as_str()))
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Get online/offline changes of visible clients between `from` and
+    /// `to`, newest first, starting after `cursor`.
+    pub async fn get_public_status_events(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        cursor: Option<&Cursor>,
+        limit: i64,
+    ) -> AppResult<Vec<IncidentEvent>> {
+        let events = sqlx::query_as::<_, IncidentEvent>(
+            r#"
+            SELECT 'status:' || e.id AS id, e.transitioned_at AS at,
+                CASE WHEN e.online THEN 'client_online' ELSE 'client_offline' END AS event_type,
+                e.client_id, c.name AS client_name,
+                CASE WHEN e.online THEN format('%s came online', c.name)
+                    ELSE format('%s went offline', c.name) END AS summary,
+                '{}'::jsonb AS metadata
+            FROM client_status_history e
+            JOIN clients c ON c.id = e.client_id AND c.hidden = FALSE
+            WHERE e.transitioned_at BETWEEN $1 AND $2
+                AND ($3::timestamptz IS NULL OR (e.transitioned_at, 'status:' || e.id) < ($3, $4))
+            ORDER BY e.transitioned_at DESC, 'status:' || e.id DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .bind(cursor.map(|c| c.at))
+        .bind(cursor.map(|c| c.id.as_str()))
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Get the latest online/offline transitions of a client, newest first.
+    pub async fn get_status_history(
+        &self,
+        client_id: Uuid,
+        limit: i32,
+    ) -> AppResult<Vec<StatusTransition>> {
+        let transitions = sqlx::query_as::<_, StatusTransition>(
+            r#"
+            SELECT id, client_id, online, transitioned_at, duration_seconds, reason
+            FROM client_status_history
+            WHERE client_id = $1
+            ORDER BY transitioned_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(client_id)
+        .bind(limit)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(transitions)
+    }
+
+    /// Percentage of `[since, now)` a client was online, from its status
+    /// events. `None` if no status change was ever recorded for the client.
+    pub async fn get_client_uptime(
+        &self,
+        client_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> AppResult<Option<f64>> {
+        let row = sqlx::query(
+            r#"
+            WITH initial AS (
+                SELECT online FROM client_status_history
+                WHERE client_id = $1 AND transitioned_at < $2
+                ORDER BY transitioned_at DESC
+                LIMIT 1
+            ), changes AS (
+                SELECT $2::timestamptz AS at, online FROM initial
+                UNION ALL
+                SELECT transitioned_at, online FROM client_status_history
+                WHERE client_id = $1 AND transitioned_at >= $2
+            ), spans AS (
+                SELECT online,
+                    EXTRACT(EPOCH FROM LEAD(at, 1, NOW()) OVER (ORDER BY at) - at)::float8 AS secs
+                FROM changes
+            )
+            SELECT 100 * COALESCE(SUM(secs) FILTER (WHERE online), 0) / NULLIF(SUM(secs), 0) AS uptime
+            FROM spans
+            "#,
+        )
+        .bind(client_id)
+        .bind(since)
+        .fetch_one(self.read_pool()?)
+        .await?;
+
+        Ok(row
+            .get::<Option<f64>, _>("uptime")
+            .map(|u| u.clamp(0.0, 100.0)))
+    }
+
+    // ==================== Share Link Operations ====================
+
+    /// Create a share link for a client.
+    pub async fn create_share_link(
+        &self,
+        client_id: Uuid,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Uuid,
+    ) -> AppResult<ShareLink> {
+        let token = format!(
+            "vmsh_{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+            INSERT INTO share_links (token, client_id, expires_at, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(&token)
+        .bind(client_id)
+        .bind(expires_at)
+        .bind(created_by)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Get all share links.
+    pub async fn get_all_share_links(&self) -> AppResult<Vec<ShareLink>> {
+        let links =
+            sqlx::query_as::<_, ShareLink>("SELECT * FROM share_links ORDER BY created_at DESC")
+                .fetch_all(self.read_pool()?)
+                .await?;
+
+        Ok(links)
+    }
+
+    /// Find a share link by token if it is neither revoked nor expired.
+    pub async fn find_active_share_link(&self, token: &str) -> AppResult<Option<ShareLink>> {
+        let link = sqlx::query_as::<_, ShareLink>(
+            r#"
+            SELECT * FROM share_links
+            WHERE token = $1 AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Revoke a share link. Returns false if it does not exist.
+    pub async fn revoke_share_link(&self, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE share_links SET revoked_at = COALESCE(revoked_at, NOW()) WHERE id = $1",
+        )
+        .bind(id)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ==================== Notification Operations ====================
+
+    /// Create a notification provider.
+    pub async fn create_notification(
+        &self,
+        name: &str,
+        provider: &str,
+        mut config: serde_json::Value,
+        notification_locale: Option<&str>,
+    ) -> AppResult<Notification> {
+        encryption::seal_fields(
+            self.cipher(),
+            &mut config,
+            notification_secret_fields(provider),
+        );
+        let notification = sqlx::query_as::<_, Notification>(
+            r#"
+            INSERT INTO notifications (name, provider, config, notification_locale)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(provider)
+        .bind(config)
+        .bind(notification_locale)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        self.open_notification(notification)
+    }
+
+    /// Copy a notification, appending " (copy)" to its name. Returns `None`
+    /// if the notification does not exist.
+    pub async fn duplicate_notification(&self, id: Uuid) -> AppResult<Option<Notification>> {
+        let notification = sqlx::query_as::<_, Notification>(
+            r#"
+            INSERT INTO notifications (name, provider, config, enabled, notification_locale)
+            SELECT left(name, 93) || ' (copy)', provider, config, enabled, notification_locale
+            FROM notifications WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        notification.map(|n| self.open_notification(n)).transpose()
+    }
+
+    /// Update notification.
+    pub async fn update_notification(
+        &self,
+        id: Uuid,
+        name: &str,
+        provider: &str,
+        mut config: serde_json::Value,
+        enabled: bool,
+        notification_locale: Option<&str>,
+    ) -> AppResult<Option<Notification>> {
+        encryption::seal_fields(
+            self.cipher(),
+            &mut config,
+            notification_secret_fields(provider),
+        );
+        let notification = sqlx::query_as::<_, Notification>(
+            r#"
+            UPDATE notifications SET
+                name = $2, provider = $3, config = $4, enabled = $5,
+                notification_locale = $6, updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(provider)
+        .bind(config)
+        .bind(enabled)
+        .bind(notification_locale)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        notification.map(|n| self.open_notification(n)).transpose()
+    }
+
+    /// Get all notifications, with secret fields as stored.
+    pub async fn get_all_notifications(&self) -> AppResult<Vec<Notification>> {
+        let notifications =
+            sqlx::query_as::<_, Notification>("SELECT * FROM notifications ORDER BY name")
+                .fetch_all(self.read_pool()?)
+                .await?;
+
+        Ok(notifications)
+    }
+
+    /// Get one page of notifications, with secret fields as stored.
+    pub async fn get_notifications_paged(
+        &self,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<Notification>> {
+        let notifications = sqlx::query_as::<_, Notification>(
+            "SELECT * FROM notifications ORDER BY name LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    /// Count notifications.
+    pub async fn count_notifications(&self) -> AppResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM notifications")
+            .fetch_one(self.read_pool()?)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Find notification by ID.
+    pub async fn find_notification_by_id(&self, id: Uuid) -> AppResult<Option<Notification>> {
+        let notification =
+            sqlx::query_as::<_, Notification>("SELECT * FROM notifications WHERE id = $1")
+                .bind(id)
+                .fetch_optional(self.primary()?)
+                .await?;
+
+        notification.map(|n| self.open_notification(n)).transpose()
+    }
+
+    /// Count the offline notification links and alert rules that use a
+    /// notification.
+    pub async fn count_notification_usage(&self, id: Uuid) -> AppResult<(i64, i64)> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM offline_notifications WHERE notification_id = $1)
+                    AS offline_notification_count,
+                (SELECT COUNT(*) FROM alert_rules WHERE notification_id = $1)
+                    AS alert_rule_count
+            "#,
+        )
+        .bind(id)
+        .fetch_one(self.read_pool()?)
+        .await?;
+
+        Ok((
+            row.get("offline_notification_count"),
+            row.get("alert_rule_count"),
+        ))
+    }
+
+    /// Get enabled notifications among the given IDs.
+    ///
+    /// Notifications whose secrets cannot be decrypted are logged and skipped,
+    /// so one broken row does not silence the others.
+    pub async fn get_enabled_notifications_by_ids(
+        &self,
+        ids: &[Uuid],
+    ) -> AppResult<Vec<Notification>> {
+        let notifications = sqlx::query_as::<_, Notification>(
+            "SELECT * FROM notifications WHERE id = ANY($1) AND enabled = TRUE",
+        )
+        .bind(ids)
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(notifications
+            .into_iter()
+            .filter_map(|n| {
+                self.open_notification(n)
+                    .inspect_err(|e| tracing::error!("{}", e))
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// Decrypt the secret fields of a notification's config.
+    fn open_notification(&self, mut notification: Notification) -> AppResult<Notification> {
+        encryption::open_fields(
+            self.cipher(),
+            &mut notification.config,
+            notification_secret_fields(&notification.provider),
+        )
+        .map_err(|(field, e)| {
+            AppError::Internal(format!(
+                "Cannot decrypt notifications.config.{} of notification {}: {}",
+                field, notification.id, e
+            ))
+        })?;
+        Ok(notification)
+    }
+
+    /// Get the notification IDs of a client's enabled offline notifications.
+    pub async fn get_offline_notification_ids(&self, client_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT notification_id FROM offline_notifications
+            WHERE client_id = $1 AND enabled = TRUE AND notification_id IS NOT NULL
+            "#,
+        )
+        .bind(client_id)
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Delete notification.
+    pub async fn delete_notification(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM notifications WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== Notification Route Operations ====================
+
+    /// Create a notification route.
+    pub async fn create_notification_route(
+        &self,
+        name: &str,
+        group_name: Option<&str>,
+        tag: Option<&str>,
+        event_types: &[String],
+        notification_id: Uuid,
+        enabled: bool,
+    ) -> AppResult<NotificationRoute> {
+        let route = sqlx::query_as::<_, NotificationRoute>(
+            r#"
+            INSERT INTO notification_routes
+                (name, group_name, tag, event_types, notification_id, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(group_name)
+        .bind(tag)
+        .bind(event_types)
+        .bind(notification_id)
+        .bind(enabled)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(route)
+    }
+
+    /// Get all notification routes.
+    pub async fn get_all_notification_routes(&self) -> AppResult<Vec<NotificationRoute>> {
+        let routes = sqlx::query_as::<_, NotificationRoute>(
+            "SELECT * FROM notification_routes ORDER BY name",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(routes)
+    }
+
+    /// Get enabled notification routes.
+    pub async fn get_enabled_notification_routes(&self) -> AppResult<Vec<NotificationRoute>> {
+        let routes = sqlx::query_as::<_, NotificationRoute>(
+            "SELECT * FROM notification_routes WHERE enabled = TRUE",
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(routes)
+    }
+
+    /// Update a notification route.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_notification_route(
+        &self,
+        id: Uuid,
+        name: &str,
+        group_name: Option<&str>,
+        tag: Option<&str>,
+        event_types: &[String],
+        notification_id: Uuid,
+        enabled: bool,
+    ) -> AppResult<Option<NotificationRoute>> {
+        let route = sqlx::query_as::<_, NotificationRoute>(
+            r#"
+            UPDATE notification_routes SET
+                name = $2, group_name = $3, tag = $4, event_types = $5,
+                notification_id = $6, enabled = $7, updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(group_name)
+        .bind(tag)
+        .bind(event_types)
+        .bind(notification_id)
+        .bind(enabled)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(route)
+    }
+
+    /// Delete notification route.
+    pub async fn delete_notification_route(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM notification_routes WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== Ping Task Operations ====================
+
+    /// Create a ping task.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_ping_task(
+        &self,
+        name: &str,
+        target: &str,
+        interval_seconds: i32,
+        timeout_seconds: i32,
+        expected_body_contains: Option<&str>,
+        expected_body_not_contains: Option<&str>,
+        request_headers: &serde_json::Value,
+    ) -> AppResult<PingTask> {
+        let task = sqlx::query_as::<_, PingTask>(
+            r#"
+            INSERT INTO ping_tasks (name, target, interval_seconds, timeout_seconds,
+                expected_body_contains, expected_body_not_contains, request_headers)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(target)
+        .bind(interval_seconds)
+        .bind(timeout_seconds)
+        .bind(expected_body_contains)
+        .bind(expected_body_not_contains)
+        .bind(request_headers)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(task)
+    }
+
+    /// Create ping tasks sharing the same settings in one transaction, each
+    /// named after its target.
+    ///
+    /// Each insert runs in its own savepoint, so a failed target is reported
+    /// in its slot without aborting the others.
+    pub async fn create_ping_tasks(
+        &self,
+        targets: &[&str],
+        interval_seconds: i32,
+        timeout_seconds: i32,
+    ) -> AppResult<Vec<Result<PingTask, sqlx::Error>>> {
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mut savepoint = sqlx::Connection::begin(&mut *tx).await?;
+            let result = sqlx::query_as::<_, PingTask>(
+                r#"
+                INSERT INTO ping_tasks (name, target, interval_seconds, timeout_seconds)
+                VALUES (left($1, 100), $1, $2, $3)
+                RETURNING *
+                "#,
+            )
+            .bind(target)
+            .bind(interval_seconds)
+            .bind(timeout_seconds)
+            .fetch_one(&mut *savepoint)
+            .await;
+            match result {
+                Ok(_) => savepoint.commit().await?,
+                Err(_) => savepoint.rollback().await?,
+            }
+            results.push(result);
+        }
+        tx.commit().await?;
+
+        Ok(results)
+    }
+
+    /// Copy a ping task, appending " (copy)" to its name. Returns `None` if
+    /// the task does not exist.
+    pub async fn duplicate_ping_task(&self, id: Uuid) -> AppResult<Option<PingTask>> {
+        let task = sqlx::query_as::<_, PingTask>(
+            r#"
+            INSERT INTO ping_tasks (name, target, interval_seconds, timeout_seconds, enabled,
+                expected_body_contains, expected_body_not_contains, request_headers)
+            SELECT left(name, 93) || ' (copy)', target, interval_seconds, timeout_seconds, enabled,
+                expected_body_contains, expected_body_not_contains, request_headers
+            FROM ping_tasks WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(task)
+    }
+
+    /// Get all ping tasks.
+    pub async fn get_all_ping_tasks(&self) -> AppResult<Vec<PingTask>> {
+        let tasks = sqlx::query_as::<_, PingTask>("SELECT * FROM ping_tasks ORDER BY name")
+            .fetch_all(self.read_pool()?)
+            .await?;
+
+        Ok(tasks)
+    }
+
+    /// Get one page of ping tasks.
+    pub async fn get_ping_tasks_paged(&self, limit: i32, offset: i32) -> AppResult<Vec<PingTask>> {
+        let tasks = sqlx::query_as::<_, PingTask>(
+            "SELECT * FROM ping_tasks ORDER BY name LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(tasks)
+    }
+
+    /// Count ping tasks.
+    pub async fn count_ping_tasks(&self) -> AppResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM ping_tasks")
+            .fetch_one(self.read_pool()?)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Find a ping task by ID.
+    pub async fn find_ping_task_by_id(&self, id: Uuid) -> AppResult<Option<PingTask>> {
+        let task = sqlx::query_as::<_, PingTask>("SELECT * FROM ping_tasks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(self.read_pool()?)
+            .await?;
+
+        Ok(task)
+    }
+
+    /// Get enabled ping tasks.
+    pub async fn get_enabled_ping_tasks(&self) -> AppResult<Vec<PingTask>> {
+        let tasks = sqlx::query_as::<_, PingTask>(
+            "SELECT * FROM ping_tasks WHERE enabled = TRUE ORDER BY name",
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(tasks)
+    }
+
+    /// Insert ping record.
+    pub async fn insert_ping_record(
+        &self,
+        task_id: Uuid,
+        client_id: Option<Uuid>,
+        latency_ms: Option<f32>,
+        success: bool,
+        failure_reason: Option<&str>,
+    ) -> AppResult<PingRecord> {
+        let record = sqlx::query_as::<_, PingRecord>(
+            r#"
+            INSERT INTO ping_records (task_id, client_id, latency_ms, success, failure_reason)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(task_id)
+        .bind(client_id)
+        .bind(latency_ms)
+        .bind(success)
+        .bind(failure_reason)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Get recent ping records for a task.
+    ///
+    /// `source_filter` limits the records to those of one source.
+    pub async fn get_recent_ping_records(
+        &self,
+        task_id: Uuid,
+        limit: i32,
+        source_filter: Option<PingSource>,
+    ) -> AppResult<Vec<PingRecord>> {
+        let client_id = match source_filter {
+            Some(PingSource::Client(id)) => Some(id),
+            _ => None,
+        };
+        let records = sqlx::query_as::<_, PingRecord>(
+            r#"
+            SELECT * FROM ping_records
+            WHERE task_id = $1 AND (NOT $3 OR client_id IS NOT DISTINCT FROM $4)
+            ORDER BY time DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(task_id)
+        .bind(limit)
+        .bind(source_filter.is_some())
+        .bind(client_id)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Get the most recent ping record of each task.
+    pub async fn get_latest_ping_records(&self) -> AppResult<Vec<PingRecord>> {
+        let records = sqlx::query_as::<_, PingRecord>(
+            r#"
+            SELECT DISTINCT ON (task_id) * FROM ping_records
+            ORDER BY task_id, time DESC
+            "#,
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// Get the visible clients that have performed each ping task, ordered
+    /// by task and client name.
+    pub async fn get_ping_task_sources(&self) -> AppResult<Vec<PingTaskSource>> {
+        let sources = sqlx::query_as::<_, PingTaskSource>(
+            r#"
+            SELECT t.id AS task_id, c.id, c.name
+            FROM ping_tasks t
+            CROSS JOIN clients c
+            WHERE NOT c.hidden
+              AND EXISTS (
+                  SELECT 1 FROM ping_records r
+                  WHERE r.task_id = t.id AND r.client_id = c.id
+              )
+            ORDER BY t.id, c.name
+            "#,
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(sources)
+    }
+
+    /// Get uptime and latency of a ping task over the last 24 hours and 7 days.
+    ///
+    /// Raw ping records cover the last `raw_retention_days` (all of them when
+    /// `None`); older parts of the windows come from the hourly rollups. The
+    /// hour containing the raw retention cutoff is taken from the rollup,
+    /// since its raw records are partly deleted.
+    pub async fn get_ping_task_summary(
+        &self,
+        task_id: Uuid,
+        raw_retention_days: Option<i32>,
+    ) -> AppResult<PingTaskSummary> {
+        let summary = sqlx::query_as::<_, PingTaskSummary>(
+            r#"
+            WITH boundary AS (
+                SELECT COALESCE(
+                    date_trunc('hour', NOW() - INTERVAL '1 day' * $2::integer) + INTERVAL '1 hour',
+                    '-infinity'
+                ) AS at
+            ),
+            samples AS (
+                SELECT
+                    hour AS at,
+                    sample_count::float8 AS total,
+                    success_ratio * sample_count AS ok,
+                    avg_latency_ms * success_ratio * sample_count AS latency_sum
+                FROM ping_records_hourly, boundary
+                WHERE task_id = $1 AND hour < boundary.at
+                    AND hour >= NOW() - INTERVAL '7 days'
+                UNION ALL
+                SELECT
+                    time,
+                    1.0,
+                    CASE WHEN success THEN 1.0 ELSE 0.0 END,
+                    CASE WHEN success THEN latency_ms END
+                FROM ping_records, boundary
+                WHERE task_id = $1 AND time >= boundary.at
+                    AND time > NOW() - INTERVAL '7 days'
+            ),
+            day AS (
+                SELECT
+                    SUM(total) AS total,
+                    SUM(ok) AS ok,
+                    SUM(latency_sum) / NULLIF(SUM(ok) FILTER (WHERE latency_sum IS NOT NULL), 0)
+                        AS avg_latency
+                FROM samples
+                WHERE at >= NOW() - INTERVAL '24 hours'
+            ),
+            week AS (
+                SELECT SUM(total) AS total, SUM(ok) AS ok FROM samples
+            ),
+            latest AS (
+                SELECT time, success FROM ping_records
+                WHERE task_id = $1 ORDER BY time DESC LIMIT 1
+            )
+            SELECT
+                t.*,
+                COALESCE(day.ok * 100.0 / NULLIF(day.total, 0), 0)::float8 AS uptime_24h_pct,
+                COALESCE(week.ok * 100.0 / NULLIF(week.total, 0), 0)::float8 AS uptime_7d_pct,
+                day.avg_latency::float8 AS avg_latency_ms,
+                latest.time AS last_check_at,
+                COALESCE(latest.success, FALSE) AS last_success
+            FROM ping_tasks t
+            CROSS JOIN day
+            CROSS JOIN week
+            LEFT JOIN latest ON TRUE
+            WHERE t.id = $1
+            "#,
+        )
+        .bind(task_id)
+        .bind(raw_retention_days)
+        .fetch_optional(self.read_pool()?)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Ping task not found".into()))?;
+
+        Ok(summary)
+    }
+
+    // ==================== Monitor Group Operations ====================
+
+    /// Create a monitor group over the given ping tasks.
+    pub async fn create_monitor_group(
+        &self,
+        name: &str,
+        logic: &str,
+        task_ids: &[Uuid],
+        notification_id: Option<Uuid>,
+    ) -> AppResult<MonitorGroup> {
+        let group = sqlx::query_as::<_, MonitorGroup>(
+            r#"
+            WITH g AS (
+                INSERT INTO monitor_groups (name, logic, notification_id)
+                VALUES ($1, $2, $4)
+                RETURNING *
+            ), members AS (
+                INSERT INTO monitor_group_members (group_id, task_id)
+                SELECT g.id, task_id FROM g, UNNEST($3::uuid[]) AS task_id
+            )
+            SELECT g.*, $3::uuid[] AS task_ids FROM g
+            "#,
+        )
+        .bind(name)
+        .bind(logic)
+        .bind(task_ids)
+        .bind(notification_id)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Update a monitor group and replace its members.
+    pub async fn update_monitor_group(
+        &self,
+        id: Uuid,
+        name: &str,
+        logic: &str,
+        task_ids: &[Uuid],
+        notification_id: Option<Uuid>,
+    ) -> AppResult<Option<MonitorGroup>> {
+        let group = sqlx::query_as::<_, MonitorGroup>(
+            r#"
+            WITH g AS (
+                UPDATE monitor_groups
+                SET name = $2, logic = $3, notification_id = $5, updated_at = NOW()
+                WHERE id = $1
+                RETURNING *
+            ), removed AS (
+                DELETE FROM monitor_group_members
+                WHERE group_id = $1 AND task_id <> ALL($4::uuid[])
+            ), added AS (
+                INSERT INTO monitor_group_members (group_id, task_id)
+                SELECT g.id, task_id FROM g, UNNEST($4::uuid[]) AS task_id
+                ON CONFLICT DO NOTHING
+            )
+            SELECT g.*, $4::uuid[] AS task_ids FROM g
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(logic)
+        .bind(task_ids)
+        .bind(notification_id)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Get all monitor groups with their member task IDs.
+    pub async fn get_all_monitor_groups(&self) -> AppResult<Vec<MonitorGroup>> {
+        let groups = sqlx::query_as::<_, MonitorGroup>(
+            r#"
+            SELECT g.*, ARRAY(
+                SELECT task_id FROM monitor_group_members m
+                WHERE m.group_id = g.id ORDER BY task_id
+            ) AS task_ids
+            FROM monitor_groups g
+            ORDER BY g.name
+            "#,
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// Get the latest check result of every enabled member task, counted per
+    /// group. Groups without checked members are omitted.
+    pub async fn get_monitor_group_checks(&self) -> AppResult<Vec<MonitorGroupChecks>> {
+        let checks = sqlx::query_as::<_, MonitorGroupChecks>(
+            r#"
+            SELECT
+                m.group_id,
+                COUNT(*) FILTER (WHERE latest.success IS TRUE) AS up,
+                COUNT(*) FILTER (WHERE latest.success IS NOT TRUE) AS down
+            FROM monitor_group_members m
+            JOIN ping_tasks t ON t.id = m.task_id AND t.enabled IS TRUE
+            CROSS JOIN LATERAL (
+                SELECT success FROM ping_records r
+                WHERE r.task_id = m.task_id
+                ORDER BY time DESC
+                LIMIT 1
+            ) latest
+            GROUP BY m.group_id
+            "#,
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(checks)
+    }
+
+    /// Store a monitor group's evaluated status, stamping the change time.
+    pub async fn set_monitor_group_status(&self, id: Uuid, status: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE monitor_groups SET status = $2, status_changed_at = NOW()
+            WHERE id = $1 AND status <> $2
+            "#,
+        )
+        .bind(id)
+        .bind(status)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete monitor group.
+    pub async fn delete_monitor_group(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM monitor_groups WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Count how many of the given IDs are existing ping tasks.
+    pub async fn count_ping_tasks_by_ids(&self, ids: &[Uuid]) -> AppResult<i64> {
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM ping_tasks WHERE id = ANY($1)")
+                .bind(ids)
+                .fetch_one(self.primary()?)
+                .await?;
+
+        Ok(count)
+    }
+
+    // ==================== Heartbeat Operations ====================
+
+    /// Create a heartbeat with a new random token.
+    pub async fn create_heartbeat(
+        &self,
+        name: &str,
+        interval_secs: i32,
+        grace_secs: i32,
+        notification_id: Option<Uuid>,
+        public: bool,
+    ) -> AppResult<Heartbeat> {
+        let token = format!(
+            "vmhb_{}{}",
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+
+        let heartbeat = sqlx::query_as::<_, Heartbeat>(
+            r#"
+            INSERT INTO heartbeats (name, token, interval_secs, grace_secs, notification_id, public)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(name)
+        .bind(&token)
+        .bind(interval_secs)
+        .bind(grace_secs)
+        .bind(notification_id)
+        .bind(public)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(heartbeat)
+    }
+
+    /// Update a heartbeat, keeping its token and state.
+    pub async fn update_heartbeat(
+        &self,
+        id: Uuid,
+        name: &str,
+        interval_secs: i32,
+        grace_secs: i32,
+        notification_id: Option<Uuid>,
+        public: bool,
+    ) -> AppResult<Option<Heartbeat>> {
+        let heartbeat = sqlx::query_as::<_, Heartbeat>(
+            r#"
+            UPDATE heartbeats
+            SET name = $2, interval_secs = $3, grace_secs = $4, notification_id = $5,
+                public = $6, updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(interval_secs)
+        .bind(grace_secs)
+        .bind(notification_id)
+        .bind(public)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(heartbeat)
+    }
+
+    /// Get all heartbeats.
+    pub async fn get_all_heartbeats(&self) -> AppResult<Vec<Heartbeat>> {
+        let heartbeats = sqlx::query_as::<_, Heartbeat>("SELECT * FROM heartbeats ORDER BY name")
+            .fetch_all(self.primary()?)
+            .await?;
+
+        Ok(heartbeats)
+    }
+
+    /// Get heartbeats shown on the public status page.
+    pub async fn get_public_heartbeats(&self) -> AppResult<Vec<Heartbeat>> {
+        let heartbeats = sqlx::query_as::<_, Heartbeat>(
+            "SELECT * FROM heartbeats WHERE public = TRUE ORDER BY name",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(heartbeats)
+    }
+
+    /// Record a beat for the heartbeat with `token`, marking it up.
+    pub async fn record_heartbeat(&self, token: &str) -> AppResult<Option<HeartbeatBeat>> {
+        let beat = sqlx::query_as::<_, HeartbeatBeat>(
+            r#"
+            UPDATE heartbeats h
+            SET last_beat_at = NOW(), status = 'up',
+                status_changed_at = CASE WHEN h.status = 'up' THEN h.status_changed_at ELSE NOW() END
+            FROM (SELECT id, status FROM heartbeats WHERE token = $1 FOR UPDATE) previous
+            WHERE h.id = previous.id
+            RETURNING h.*, previous.status AS previous_status
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(beat)
+    }
+
+    /// Mark heartbeats without a beat for their interval plus grace period
+    /// as down, returning the ones that changed.
+    pub async fn mark_overdue_heartbeats(&self) -> AppResult<Vec<Heartbeat>> {
+        let heartbeats = sqlx::query_as::<_, Heartbeat>(
+            r#"
+            UPDATE heartbeats SET status = 'down', status_changed_at = NOW()
+            WHERE status <> 'down'
+                AND COALESCE(last_beat_at, created_at)
+                    + make_interval(secs => interval_secs + grace_secs) < NOW()
+            RETURNING *
+            "#,
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(heartbeats)
+    }
+
+    /// Delete heartbeat.
+    pub async fn delete_heartbeat(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM heartbeats WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== Silence Operations ====================
+
+    /// Create a silence.
+    pub async fn create_silence(
+        &self,
+        client_id: Option<Uuid>,
+        group_name: Option<&str>,
+        task_id: Option<Uuid>,
+        schedule: &SilenceSchedule,
+        comment: &str,
+    ) -> AppResult<Silence> {
+        let silence = sqlx::query_as::<_, Silence>(
+            r#"
+            INSERT INTO silences (
+                client_id, group_name, task_id, kind, starts_at, ends_at,
+                time, weekdays, timezone, duration_minutes, comment
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING *
+            "#,
+        )
+        .bind(client_id)
+        .bind(group_name)
+        .bind(task_id)
+        .bind(&schedule.kind)
+        .bind(schedule.starts_at)
+        .bind(schedule.ends_at)
+        .bind(&schedule.time)
+        .bind(&schedule.weekdays)
+        .bind(&schedule.timezone)
+        .bind(schedule.duration_minutes)
+        .bind(comment)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(silence)
+    }
+
+    /// Update a silence.
+    pub async fn update_silence(
+        &self,
+        id: Uuid,
+        client_id: Option<Uuid>,
+        group_name: Option<&str>,
+        task_id: Option<Uuid>,
+        schedule: &SilenceSchedule,
+        comment: &str,
+    ) -> AppResult<Option<Silence>> {
+        let silence = sqlx::query_as::<_, Silence>(
+            r#"
+            UPDATE silences
+            SET client_id = $2, group_name = $3, task_id = $4, kind = $5,
+                starts_at = $6, ends_at = $7, time = $8, weekdays = $9,
+                timezone = $10, duration_minutes = $11, comment = $12,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(client_id)
+        .bind(group_name)
+        .bind(task_id)
+        .bind(&schedule.kind)
+        .bind(schedule.starts_at)
+        .bind(schedule.ends_at)
+        .bind(&schedule.time)
+        .bind(&schedule.weekdays)
+        .bind(&schedule.timezone)
+        .bind(schedule.duration_minutes)
+        .bind(comment)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(silence)
+    }
+
+    /// Get all silences.
+    pub async fn get_all_silences(&self) -> AppResult<Vec<Silence>> {
+        let silences =
+            sqlx::query_as::<_, Silence>("SELECT * FROM silences ORDER BY created_at DESC")
+                .fetch_all(self.primary()?)
+                .await?;
+
+        Ok(silences)
+    }
+
+    /// Delete silence.
+    pub async fn delete_silence(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM silences WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    // ==================== Alert Rule Operations ====================
+
+    /// Create an alert rule.
+    pub async fn create_alert_rule(
+        &self,
+        client_id: Uuid,
+        notification_id: Option<Uuid>,
+        metric: &str,
+        threshold: f32,
+        severity: &str,
+    ) -> AppResult<AlertRule> {
+        let rule = sqlx::query_as::<_, AlertRule>(
+            r#"
+            INSERT INTO alert_rules (client_id, notification_id, metric, threshold, severity)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(client_id)
+        .bind(notification_id)
+        .bind(metric)
+        .bind(threshold)
+        .bind(severity)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(rule)
+    }
+
+    /// Get all alert rules.
+    pub async fn get_all_alert_rules(&self) -> AppResult<Vec<AlertRule>> {
+        let rules = sqlx::query_as::<_, AlertRule>(
+            "SELECT * FROM alert_rules ORDER BY client_id, metric, threshold",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Get a client's alert rules.
+    pub async fn get_alert_rules_by_client(&self, client_id: Uuid) -> AppResult<Vec<AlertRule>> {
+        let rules = sqlx::query_as::<_, AlertRule>(
+            "SELECT * FROM alert_rules WHERE client_id = $1 ORDER BY metric, threshold",
+        )
+        .bind(client_id)
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Get a client's alert rules with their notification's name and
+    /// provider. With `active_only`, only rules that fired in the last 24
+    /// hours are returned.
+    pub async fn get_client_alert_rules(
+        &self,
+        client_id: Uuid,
+        active_only: bool,
+    ) -> AppResult<Vec<ClientAlertRule>> {
+        let rules = sqlx::query_as::<_, ClientAlertRule>(
+            r#"
+            SELECT ar.*, n.name AS notification_name, n.provider AS notification_provider,
+                   h.last_fired_at
+            FROM alert_rules ar
+            LEFT JOIN notifications n ON n.id = ar.notification_id
+            LEFT JOIN LATERAL (
+                SELECT MAX(created_at) AS last_fired_at
+                FROM alert_history
+                WHERE rule_id = ar.id
+            ) h ON TRUE
+            WHERE ar.client_id = $1
+              AND (NOT $2 OR h.last_fired_at > NOW() - INTERVAL '24 hours')
+            ORDER BY ar.metric, ar.threshold
+            "#,
+        )
+        .bind(client_id)
+        .bind(active_only)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Delete alert rule.
+    pub async fn delete_alert_rule(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("DELETE FROM alert_rules WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Evaluate all enabled alert rules against their client's latest record.
+    ///
+    /// `value_expr` is a trusted SQL expression over `ar` (alert_rules),
+    /// `c` (clients) and `r` (latest record) computing the metric value.
+    pub async fn get_alert_evaluations(
+        &self,
+        value_expr: &str,
+        stale_after_secs: i64,
+    ) -> AppResult<Vec<AlertEvaluation>> {
+        let query = format!(
+            r#"
+            SELECT
+                ar.id AS rule_id, ar.client_id, c.name AS client_name,
+                ar.metric, ar.threshold, ar.severity,
+                {} AS value,
+                COALESCE(c.maintenance_until > NOW(), FALSE) AS in_maintenance,
+                ah.id AS open_alert_id,
+                ar.notification_id
+            FROM alert_rules ar
+            JOIN clients c ON c.id = ar.client_id AND NOT c.archived
+            LEFT JOIN LATERAL (
+                SELECT * FROM records WHERE client_id = ar.client_id ORDER BY time DESC LIMIT 1
+            ) r ON TRUE
+            LEFT JOIN LATERAL (
+                SELECT id FROM alert_history
+                WHERE rule_id = ar.id AND resolved_at IS NULL
+                ORDER BY created_at DESC LIMIT 1
+            ) ah ON TRUE
+            WHERE ar.enabled = TRUE
+            "#,
+            value_expr
+        );
+
+        let evaluations = sqlx::query_as::<_, AlertEvaluation>(&query)
+            .bind(stale_after_secs as f64)
+            .fetch_all(self.primary()?)
+            .await?;
+
+        Ok(evaluations)
+    }
+
+    /// Record a fired alert.
+    pub async fn insert_alert_history(
+        &self,
+        rule_id: Uuid,
+        client_id: Uuid,
+        metric: &str,
+        value: f32,
+        threshold: f32,
+        severity: &str,
+    ) -> AppResult<AlertHistory> {
+        let alert = sqlx::query_as::<_, AlertHistory>(
+            r#"
+            INSERT INTO alert_history (rule_id, client_id, metric, value, threshold, severity)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(rule_id)
+        .bind(client_id)
+        .bind(metric)
+        .bind(value)
+        .bind(threshold)
+        .bind(severity)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(alert)
+    }
+
+    /// Record an anomaly: `value` deviated beyond `bound` from the client's
+    /// baseline for `metric`.
+    pub async fn insert_anomaly(
+        &self,
+        client_id: Uuid,
+        metric: &str,
+        value: f32,
+        bound: f32,
+    ) -> AppResult<AlertHistory> {
+        let alert = sqlx::query_as::<_, AlertHistory>(
+            r#"
+            INSERT INTO alert_history (kind, client_id, metric, value, threshold, severity)
+            VALUES ('anomaly', $1, $2, $3, $4, 'warning')
+            RETURNING *
+            "#,
+        )
+        .bind(client_id)
+        .bind(metric)
+        .bind(value)
+        .bind(bound)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(alert)
+    }
+
+    /// Get the unresolved anomalies.
+    pub async fn get_open_anomalies(&self) -> AppResult<Vec<AlertHistory>> {
+        let alerts = sqlx::query_as::<_, AlertHistory>(
+            "SELECT * FROM alert_history WHERE kind = 'anomaly' AND resolved_at IS NULL",
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(alerts)
+    }
+
+    /// Mark an alert as resolved.
+    pub async fn resolve_alert(&self, id: Uuid) -> AppResult<()> {
+        sqlx::query("UPDATE alert_history SET resolved_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get recent alert history.
+    pub async fn get_alert_history(&self, limit: i32, offset: i32) -> AppResult<Vec<AlertHistory>> {
+        let alerts = sqlx::query_as::<_, AlertHistory>(
+            "SELECT * FROM alert_history ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(alerts)
+    }
+
+    /// Get the unresolved alerts of a client, newest first.
+    pub async fn get_open_alerts_by_client(&self, client_id: Uuid) -> AppResult<Vec<AlertHistory>> {
+        let alerts = sqlx::query_as::<_, AlertHistory>(
+            r#"
+            SELECT * FROM alert_history
+            WHERE client_id = $1 AND resolved_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(client_id)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(alerts)
+    }
+
+    /// Get the unresolved alerts of all clients, newest first.
+    pub async fn get_open_alerts(&self) -> AppResult<Vec<AlertHistory>> {
+        let alerts = sqlx::query_as::<_, AlertHistory>(
+            "SELECT * FROM alert_history WHERE resolved_at IS NULL ORDER BY created_at DESC",
+        )
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(alerts)
+    }
+
+    /// Count alert history entries.
+    pub async fn count_alert_history(&self) -> AppResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM alert_history")
+            .fetch_one(self.read_pool()?)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Count fired alerts per client in a time window.
+    pub async fn count_alerts_by_client(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<ClientAggregate>> {
+        let clients = sqlx::query_as::<_, ClientAggregate>(
+            r#"
+            SELECT c.id AS client_id, c.name, COUNT(*)::float8 AS value
+            FROM alert_history ah
+            JOIN clients c ON c.id = ah.client_id
+            WHERE ah.created_at >= $1 AND ah.created_at < $2
+            GROUP BY c.id, c.name
+            ORDER BY value DESC, c.name
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(clients)
+    }
+
+    // ==================== Baseline Operations ====================
+
+    /// Compute statistics of the records between `since` and `until` per
+    /// client, metric and hour of week (UTC). `weight` is the sample count.
+    ///
+    /// `metric_values` is a trusted SQL `VALUES` list of `(name, value)`
+    /// rows whose value expressions read the record `r`.
+    pub async fn get_baseline_samples(
+        &self,
+        metric_values: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> AppResult<Vec<ClientBaseline>> {
+        let query = format!(
+            r#"
+            SELECT r.client_id, m.metric,
+                ((EXTRACT(ISODOW FROM r.time AT TIME ZONE 'UTC') - 1) * 24
+                    + EXTRACT(HOUR FROM r.time AT TIME ZONE 'UTC'))::smallint AS hour_of_week,
+                COUNT(*)::float8 AS weight,
+                AVG(m.value) AS mean,
+                COALESCE(stddev_pop(m.value), 0) AS stddev
+            FROM records r
+            CROSS JOIN LATERAL (VALUES {}) AS m(metric, value)
+            WHERE r.time >= $1 AND r.time < $2 AND m.value IS NOT NULL
+            GROUP BY 1, 2, 3
+            "#,
+            metric_values
+        );
+
+        let samples = sqlx::query_as::<_, ClientBaseline>(&query)
+            .bind(since)
+            .bind(until)
+            .fetch_all(self.read_pool()?)
+            .await?;
+
+        Ok(samples)
+    }
+
+    /// Get all client baselines.
+    pub async fn get_client_baselines(&self) -> AppResult<Vec<ClientBaseline>> {
+        let baselines = sqlx::query_as::<_, ClientBaseline>(
+            "SELECT client_id, metric, hour_of_week, weight, mean, stddev FROM client_baselines",
+        )
+        .fetch_all(self.primary()?)
+        .await?;
+
+        Ok(baselines)
+    }
+
+    /// Insert or replace client baselines. Baselines of deleted clients are
+    /// skipped.
+    pub async fn upsert_client_baselines(&self, baselines: &[ClientBaseline]) -> AppResult<()> {
+        let client_ids: Vec<Uuid> = baselines.iter().map(|b| b.client_id).collect();
+        let metrics: Vec<&str> = baselines.iter().map(|b| b.metric.as_str()).collect();
+        let hours: Vec<i16> = baselines.iter().map(|b| b.hour_of_week).collect();
+        let weights: Vec<f64> = baselines.iter().map(|b| b.weight).collect();
+        let means: Vec<f64> = baselines.iter().map(|b| b.mean).collect();
+        let stddevs: Vec<f64> = baselines.iter().map(|b| b.stddev).collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO client_baselines (client_id, metric, hour_of_week, weight, mean, stddev)
+            SELECT * FROM UNNEST($1::uuid[], $2::varchar[], $3::smallint[],
+                $4::float8[], $5::float8[], $6::float8[]) AS b(client_id)
+            WHERE b.client_id IN (SELECT id FROM clients)
+            ON CONFLICT (client_id, metric, hour_of_week) DO UPDATE
+            SET weight = EXCLUDED.weight, mean = EXCLUDED.mean, stddev = EXCLUDED.stddev,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(&client_ids)
+        .bind(&metrics)
+        .bind(&hours)
+        .bind(&weights)
+        .bind(&means)
+        .bind(&stddevs)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    // ==================== Audit Log Operations ====================
+
+    /// Append an audit log entry.
+    pub async fn insert_audit_log(
+        &self,
+        user_id: Option<Uuid>,
+        action: &str,
+        details: serde_json::Value,
+        ip_address: Option<&str>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs (user_id, action, details, ip_address)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(action)
+        .bind(details)
+        .bind(ip_address)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get one page of audit log entries, newest first.
+    pub async fn get_audit_logs_paged(&self, limit: i32, offset: i32) -> AppResult<Vec<AuditLog>> {
+        let logs = sqlx::query_as::<_, AuditLog>(
+            "SELECT * FROM audit_logs ORDER BY created_at DESC, id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(logs)
+    }
+
+    /// Count audit log entries.
+    pub async fn count_audit_logs(&self) -> AppResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM audit_logs")
+            .fetch_one(self.read_pool()?)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    // ==================== Security Event Operations ====================
+
+    /// Record a security event.
+    pub async fn insert_security_event(
+        &self,
+        event_type: &str,
+        user_id: Option<Uuid>,
+        username: Option<&str>,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> AppResult<SecurityEvent> {
+        let event = sqlx::query_as::<_, SecurityEvent>(
+            r#"
+            INSERT INTO security_events (event_type, user_id, username, ip_address, user_agent)
+            VALUES ($1, $2, left($3, 255), left($4, 100), $5)
+            RETURNING *
+            "#,
+        )
+        .bind(event_type)
+        .bind(user_id)
+        .bind(username)
+        .bind(ip_address)
+        .bind(user_agent)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(event)
+    }
+
+    /// Get one page of security events, newest first.
+    pub async fn get_security_events_paged(
+        &self,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<SecurityEvent>> {
+        let events = sqlx::query_as::<_, SecurityEvent>(
+            "SELECT * FROM security_events ORDER BY created_at DESC, id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.read_pool()?)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Count security events.
+    pub async fn count_security_events(&self) -> AppResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM security_events")
+            .fetch_one(self.read_pool()?)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Count events of a type within the last `minutes`.
+    pub async fn count_recent_security_events(
+        &self,
+        event_type: &str,
+        minutes: i64,
+    ) -> AppResult<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM security_events
+            WHERE event_type = $1 AND created_at > NOW() - make_interval(mins => $2)
+            "#,
+        )
+        .bind(event_type)
+        .bind(minutes as i32)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
+    /// Whether the user logged in from `ip` within `days`, other than in
+    /// event `exclude_id`.
+    pub async fn has_recent_login_from_ip(
+        &self,
+        user_id: Uuid,
+        ip: &str,
+        exclude_id: i64,
+        days: i64,
+    ) -> AppResult<bool> {
+        let seen: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM security_events
+                WHERE event_type = 'login_success' AND user_id = $1 AND ip_address = $2
+                    AND id <> $3 AND created_at > NOW() - make_interval(days => $4)
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(ip)
+        .bind(exclude_id)
+        .bind(days as i32)
+        .fetch_one(self.primary()?)
+        .await?;
+
+        Ok(seen)
+    }
+
+    /// Mark an event as notified. With `dedup_minutes`, the mark is only
+    /// set when no other event of its type was notified within that many
+    /// minutes; returns whether it was set.
+    pub async fn claim_security_notification(
+        &self,
+        id: i64,
+        dedup_minutes: Option<i64>,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE security_events e SET notified = TRUE
+            WHERE e.id = $1 AND ($2::int IS NULL OR NOT EXISTS (
+                SELECT 1 FROM security_events o
+                WHERE o.event_type = e.event_type AND o.notified AND o.id <> e.id
+                    AND o.created_at > NOW() - make_interval(mins => $2)
+            ))
+            "#,
+        )
+        .bind(id)
+        .bind(dedup_minutes.map(|m| m as i32))
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ==================== Idempotency Key Operations ====================
+
+    /// Claim an idempotency key for a request. Returns false when the user
+    /// already holds the key and it is younger than `ttl_hours`; an expired
+    /// key, or one left in progress for `claim_timeout_secs`, is taken over.
+    pub async fn claim_idempotency_key(
+        &self,
+        user_id: Uuid,
+        key: &str,
+        endpoint: &str,
+        request_hash: &str,
+        ttl_hours: i32,
+        claim_timeout_secs: i32,
+    ) -> AppResult<bool> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (user_id, key, endpoint, request_hash)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, key) DO UPDATE
+            SET endpoint = EXCLUDED.endpoint, request_hash = EXCLUDED.request_hash,
+                status_code = NULL, content_type = NULL, response_body = NULL,
+                created_at = NOW()
+            WHERE idempotency_keys.created_at < NOW() - make_interval(hours => $5::int)
+               OR (idempotency_keys.status_code IS NULL
+                   AND idempotency_keys.created_at < NOW() - make_interval(secs => $6::int))
+            RETURNING user_id
+            "#,
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(endpoint)
+        .bind(request_hash)
+        .bind(ttl_hours)
+        .bind(claim_timeout_secs)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Find a user's idempotency key.
+    pub async fn find_idempotency_key(
+        &self,
+        user_id: Uuid,
+        key: &str,
+    ) -> AppResult<Option<IdempotencyKey>> {
+        let row = sqlx::query_as::<_, IdempotencyKey>(
+            "SELECT * FROM idempotency_keys WHERE user_id = $1 AND key = $2",
+        )
+        .bind(user_id)
+        .bind(key)
+        .fetch_optional(self.primary()?)
+        .await?;
+
+        Ok(row)
+    }
+
+    /// Store the response of the request holding an idempotency key.
+    pub async fn complete_idempotency_key(
+        &self,
+        user_id: Uuid,
+        key: &str,
+        status_code: i16,
+        content_type: Option<&str>,
+        response_body: &[u8],
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status_code = $3, content_type = $4, response_body = $5
+            WHERE user_id = $1 AND key = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(key)
+        .bind(status_code)
+        .bind(content_type)
+        .bind(response_body)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Release an idempotency key so its request can be retried.
+    pub async fn release_idempotency_key(&self, user_id: Uuid, key: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND key = $2")
+            .bind(user_id)
+            .bind(key)
+            .execute(self.primary()?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete idempotency keys older than `ttl_hours`, returning how many
+    /// were deleted.
+    pub async fn delete_expired_idempotency_keys(&self, ttl_hours: i32) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(hours => $1::int)",
+        )
+        .bind(ttl_hours)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // ==================== Maintenance Operations ====================
+
+    /// Run `VACUUM` (optionally with `ANALYZE`) on a table in `VACUUM_TABLES`.
+    pub async fn vacuum_table(&self, table: &str, analyze: bool) -> AppResult<()> {
+        // Table names cannot be bound as parameters, so only allow known ones
+        if !VACUUM_TABLES.contains(&table) {
+            return Err(AppError::BadRequest(format!(
+                "Table cannot be vacuumed: {}",
+                table
+            )));
+        }
+
+        // Sent as a simple query: VACUUM cannot run inside a transaction
+        let sql = if analyze {
+            format!("VACUUM ANALYZE {}", table)
+        } else {
+            format!("VACUUM {}", table)
+        };
+        // Vacuuming a large table may outlast the query timeout
+        self.breaker.check()?;
+        let pool = self.untimed_pool();
+        let result = sqlx::raw_sql(&sql).execute(&pool).await;
+        pool.close().await;
+        result?;
+
+        Ok(())
+    }
+
+    // ==================== Settings Operations ====================
+
+    /// Get a setting value, with secret fields decrypted.
+    pub async fn get_setting(&self, key: &str) -> AppResult<Option<serde_json::Value>> {
+        let setting = sqlx::query_as::<_, Setting>("SELECT * FROM settings WHERE key = $1")
+            .bind(key)
+            .fetch_optional(self.primary()?)
+            .await?;
+
+        let Some(mut value) = setting.map(|s| s.value) else {
+            return Ok(None);
+        };
+        encryption::open_fields(self.cipher(), &mut value, setting_secret_fields(key)).map_err(
+            |(field, e)| {
+                AppError::Internal(format!(
+                    "Cannot decrypt settings.value.{} of setting {}: {}",
+                    field, key, e
+                ))
+            },
+        )?;
+        Ok(Some(value))
+    }
+
+    /// Set a setting value, encrypting its secret fields.
+    pub async fn set_setting(&self, key: &str, mut value: serde_json::Value) -> AppResult<()> {
+        encryption::seal_fields(self.cipher(), &mut value, setting_secret_fields(key));
+        sqlx::query(
+            r#"
+            INSERT INTO settings (key, value)
+            VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET value = $2, updated_at = NOW()
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .execute(self.primary()?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get all settings, with secret fields as stored.
+    pub async fn get_all_settings(&self) -> AppResult<Vec<Setting>> {
+        let settings = sqlx::query_as::<_, Setting>("SELECT * FROM settings ORDER BY key")
+            .fetch_all(self.primary()?)
+            .await?;
+
+        Ok(settings)
+    }
+
+    // ==================== Secret Operations ====================
+
+    /// Re-encrypt stored secrets from this database's cipher to `target`.
+    ///
+    /// With `rewrite_encrypted` unset only plaintext and legacy `enc:v1:`
+    /// secrets are rewritten, which migrates values stored before
+    /// `SECRET_KEY` was set or by earlier versions. Everything
+    /// is decrypted before anything is written, in one transaction, so a
+    /// value that cannot be decrypted aborts without changes and names its
+    /// row. Returns the number of rows rewritten.
+    pub async fn reencrypt_secrets(
+        &self,
+        target: Option<&SecretCipher>,
+        rewrite_encrypted: bool,
+    ) -> AppResult<u64> {
+        let source = self.cipher();
+        // Legacy ciphertexts are always rewritten in the current format
+        let skip = |value: &str| {
+            value.is_empty() || (!rewrite_encrypted && is_encrypted(value) && !is_legacy(value))
+        };
+        let reseal_fields = |value: &mut serde_json::Value, fields: &[&str]| {
+            let mut changed = false;
+            encryption::visit_secret_fields(value, fields, |base, key, v| {
+                let stored = v.as_str().unwrap_or_default();
+                if skip(stored) {
+                    return Ok(());
+                }
+                let plaintext = encryption::open(source, stored)
+                    .map_err(|e| (encryption::field_path(base, key), e))?;
+                *v = serde_json::Value::String(encryption::seal(target, &plaintext));
+                changed = true;
+                Ok(())
+            })?;
+            Ok::<_, (String, &'static str)>(changed)
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let mut rewritten = 0;
+
+        let clients: Vec<(Uuid, String)> =
+            sqlx::query_as("SELECT id, token FROM clients WHERE NOT builtin FOR UPDATE")
+                .fetch_all(&mut *tx)
+                .await?;
+        let mut client_updates = Vec::new();
+        for (id, token) in clients {
+            if skip(&token) {
+                continue;
+            }
+            let plaintext = encryption::open(source, &token).map_err(|e| {
+                AppError::Internal(format!(
+                    "Cannot decrypt clients.token of client {}: {}",
+                    id, e
+                ))
+            })?;
+            client_updates.push((
+                id,
+                encryption::seal(target, &plaintext),
+                encryption::token_hash(&plaintext),
+            ));
+        }
+
+        let notifications: Vec<(Uuid, String, serde_json::Value)> =
+            sqlx::query_as("SELECT id, provider, config FROM notifications FOR UPDATE")
+                .fetch_all(&mut *tx)
+                .await?;
+        let mut notification_updates = Vec::new();
+        for (id, provider, mut config) in notifications {
+            let changed = reseal_fields(&mut config, notification_secret_fields(&provider))
+                .map_err(|(field, e)| {
+                    AppError::Internal(format!(
+                        "Cannot decrypt notifications.config.{} of notification {}: {}",
+                        field, id, e
+                    ))
+                })?;
+            if changed {
+                notification_updates.push((id, config));
+            }
+        }
+
+        let settings: Vec<(String, serde_json::Value)> =
+            sqlx::query_as("SELECT key, value FROM settings WHERE key = ANY($1) FOR UPDATE")
+                .bind(SECRET_SETTINGS)
+                .fetch_all(&mut *tx)
+                .await?;
+        let mut setting_updates = Vec::new();
+        for (key, mut value) in settings {
+            let changed =
+                reseal_fields(&mut value, setting_secret_fields(&key)).map_err(|(field, e)| {
+                    AppError::Internal(format!(
+                        "Cannot decrypt settings.value.{} of setting {}: {}",
+                        field, key, e
+                    ))
+                })?;
+            if changed {
+                setting_updates.push((key, value));
+            }
+        }
+
+        for (id, token, token_hash) in client_updates {
+            sqlx::query("UPDATE clients SET token = $2, token_hash = $3 WHERE id = $1")
+                .bind(id)
+                .bind(token)
+                .bind(token_hash)
+                .execute(&mut *tx)
+                .await?;
+            rewritten += 1;
+        }
+        for (id, config) in notification_updates {
+            sqlx::query("UPDATE notifications SET config = $2 WHERE id = $1")
+                .bind(id)
+                .bind(config)
+                .execute(&mut *tx)
+                .await?;
+            rewritten += 1;
+        }
+        for (key, value) in setting_updates {
+            sqlx::query("UPDATE settings SET value = $2 WHERE key = $1")
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            rewritten += 1;
+        }
+
+        tx.commit().await?;
+        Ok(rewritten)
+    }
+}
+
+/// Turn free-form input into a prefix-matching `tsquery`, e.g. `web 10.0` into
+/// `'web':* & '10.0':*`. Returns `None` when nothing searchable is left.
+fn prefix_tsquery(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|c: char| c.is_whitespace() || "&|!():*<>'\\".contains(c))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("'{}':*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+fn filter_tsquery(filter: &ClientsFilter) -> Option<String> {
+    filter.q.as_deref().and_then(prefix_tsquery)
 }
@@ -3,9 +3,11 @@
 use anyhow::Result;
 use sqlx::PgPool;
 
-/// Initialize the database schema.
-pub async fn init_schema(pool: &PgPool) -> Result<()> {
-    sqlx::query(
+use super::normalization::ARCH_ALIASES;
+
+/// Create the tables and indexes that do not exist yet.
+pub async fn create_tables(pool: &PgPool) -> Result<()> {
+    sqlx::raw_sql(
         r#"
         -- Users table
         CREATE TABLE IF NOT EXISTS users (
@@ -50,7 +52,7 @@ pub async fn init_schema(pool: &PgPool) -> Result<()> {
             version VARCHAR(50) DEFAULT '',
             weight INTEGER DEFAULT 0,
             group_name VARCHAR(100) DEFAULT '',
-            tags TEXT DEFAULT '',
+            tags TEXT[] DEFAULT '{}',
             hidden BOOLEAN DEFAULT FALSE,
             traffic_limit BIGINT DEFAULT 0,
             traffic_limit_type VARCHAR(10) DEFAULT 'max',
@@ -134,14 +136,432 @@ pub async fn init_schema(pool: &PgPool) -> Result<()> {
         -- Index for ping records
         CREATE INDEX IF NOT EXISTS idx_ping_records_task_time ON ping_records(task_id, time DESC);
 
+        -- Hourly ping rollups, kept after raw ping records expire
+        CREATE TABLE IF NOT EXISTS ping_records_hourly (
+            task_id UUID NOT NULL REFERENCES ping_tasks(id) ON DELETE CASCADE,
+            hour TIMESTAMPTZ NOT NULL,
+            sample_count INTEGER NOT NULL,
+            success_ratio DOUBLE PRECISION NOT NULL,
+            avg_latency_ms REAL,
+            min_latency_ms REAL,
+            max_latency_ms REAL,
+            PRIMARY KEY (task_id, hour)
+        );
+
+        -- Composite monitors combining ping tasks into one service status
+        CREATE TABLE IF NOT EXISTS monitor_groups (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            name VARCHAR(100) NOT NULL,
+            logic VARCHAR(10) NOT NULL DEFAULT 'all',
+            notification_id UUID REFERENCES notifications(id) ON DELETE SET NULL,
+            status VARCHAR(20) NOT NULL DEFAULT 'unknown',
+            status_changed_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE TABLE IF NOT EXISTS monitor_group_members (
+            group_id UUID NOT NULL REFERENCES monitor_groups(id) ON DELETE CASCADE,
+            task_id UUID NOT NULL REFERENCES ping_tasks(id) ON DELETE CASCADE,
+            PRIMARY KEY (group_id, task_id)
+        );
+
+        -- Heartbeat monitors fed by external jobs
+        CREATE TABLE IF NOT EXISTS heartbeats (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            name VARCHAR(100) NOT NULL,
+            token VARCHAR(100) UNIQUE NOT NULL,
+            interval_secs INTEGER NOT NULL,
+            grace_secs INTEGER NOT NULL DEFAULT 0,
+            notification_id UUID REFERENCES notifications(id) ON DELETE SET NULL,
+            public BOOLEAN NOT NULL DEFAULT FALSE,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            last_beat_at TIMESTAMPTZ,
+            status_changed_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        -- Silence windows suppressing notifications for matching events
+        CREATE TABLE IF NOT EXISTS silences (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            client_id UUID REFERENCES clients(id) ON DELETE CASCADE,
+            group_name VARCHAR(100),
+            task_id UUID REFERENCES ping_tasks(id) ON DELETE CASCADE,
+            kind VARCHAR(10) NOT NULL DEFAULT 'once',
+            starts_at TIMESTAMPTZ,
+            ends_at TIMESTAMPTZ,
+            time VARCHAR(5),
+            weekdays TEXT[] NOT NULL DEFAULT '{}',
+            timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+            duration_minutes INTEGER NOT NULL DEFAULT 0,
+            comment TEXT NOT NULL DEFAULT '',
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
         -- Settings table (key-value store)
         CREATE TABLE IF NOT EXISTS settings (
             key VARCHAR(100) PRIMARY KEY,
             value JSONB NOT NULL DEFAULT '{}',
             updated_at TIMESTAMPTZ DEFAULT NOW()
         );
+
+        -- Alert rules table
+        CREATE TABLE IF NOT EXISTS alert_rules (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            notification_id UUID REFERENCES notifications(id) ON DELETE SET NULL,
+            metric VARCHAR(50) NOT NULL,
+            threshold REAL NOT NULL,
+            severity VARCHAR(20) NOT NULL DEFAULT 'warning',
+            enabled BOOLEAN DEFAULT TRUE,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_alert_rules_client ON alert_rules(client_id);
+
+        -- Alert history table (one row per firing, resolved_at set on recovery)
+        CREATE TABLE IF NOT EXISTS alert_history (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            rule_id UUID NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            metric VARCHAR(50) NOT NULL,
+            value REAL NOT NULL,
+            threshold REAL NOT NULL,
+            severity VARCHAR(20) NOT NULL,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            resolved_at TIMESTAMPTZ
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_alert_history_rule ON alert_history(rule_id, created_at DESC);
+
+        -- Notification routes (group/tag matchers to notification targets)
+        CREATE TABLE IF NOT EXISTS notification_routes (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            name VARCHAR(100) NOT NULL,
+            group_name VARCHAR(100),
+            tag VARCHAR(50),
+            event_types TEXT[] NOT NULL DEFAULT '{}',
+            notification_id UUID NOT NULL REFERENCES notifications(id) ON DELETE CASCADE,
+            enabled BOOLEAN DEFAULT TRUE,
+            created_at TIMESTAMPTZ DEFAULT NOW(),
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        -- Read-only share links for individual clients
+        CREATE TABLE IF NOT EXISTS share_links (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            token VARCHAR(100) UNIQUE NOT NULL,
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            expires_at TIMESTAMPTZ,
+            created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            revoked_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        -- Traffic counter resets (agent reboots), holding the totals before the reset
+        CREATE TABLE IF NOT EXISTS counter_resets (
+            id BIGSERIAL PRIMARY KEY,
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            time TIMESTAMPTZ DEFAULT NOW(),
+            prev_up BIGINT NOT NULL DEFAULT 0,
+            prev_down BIGINT NOT NULL DEFAULT 0
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_counter_resets_client_time ON counter_resets(client_id, time);
+
+        -- Audit log of administrative changes
+        CREATE TABLE IF NOT EXISTS audit_logs (
+            id BIGSERIAL PRIMARY KEY,
+            user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+            action VARCHAR(100) NOT NULL,
+            details JSONB NOT NULL DEFAULT '{}',
+            ip_address VARCHAR(100),
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_audit_logs_created ON audit_logs(created_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_audit_logs_client ON audit_logs((details->>'client_id'), created_at DESC);
+
+        -- Logins, failed logins and account changes
+        CREATE TABLE IF NOT EXISTS security_events (
+            id BIGSERIAL PRIMARY KEY,
+            event_type VARCHAR(30) NOT NULL,
+            user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+            username VARCHAR(255),
+            ip_address VARCHAR(100),
+            user_agent TEXT,
+            notified BOOLEAN NOT NULL DEFAULT FALSE,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_security_events_created ON security_events(created_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_security_events_type ON security_events(event_type, created_at DESC);
+
+        -- Idempotency keys of admin mutations with their stored response
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            key VARCHAR(255) NOT NULL,
+            endpoint TEXT NOT NULL,
+            request_hash VARCHAR(64) NOT NULL,
+            status_code SMALLINT,
+            content_type TEXT,
+            response_body BYTEA,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (user_id, key)
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created ON idempotency_keys(created_at);
+
+        -- System log lines forwarded by agents
+        CREATE TABLE IF NOT EXISTS client_logs (
+            id BIGSERIAL PRIMARY KEY,
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            line TEXT NOT NULL,
+            recorded_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_client_logs_client ON client_logs(client_id, id DESC);
+
+        -- Addresses agents reported from, one row per change
+        CREATE TABLE IF NOT EXISTS client_ip_history (
+            id BIGSERIAL PRIMARY KEY,
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            ip VARCHAR(45) NOT NULL,
+            previous_ip VARCHAR(45),
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_client_ip_history_client ON client_ip_history(client_id, created_at DESC);
+
+        -- Client online/offline transitions; duration_seconds is filled
+        -- when the next transition occurs
+        CREATE TABLE IF NOT EXISTS client_status_history (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            online BOOLEAN NOT NULL,
+            transitioned_at TIMESTAMPTZ DEFAULT NOW(),
+            duration_seconds BIGINT,
+            -- Reason given for a manual override
+            reason TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_client_status_history_transitioned ON client_status_history(transitioned_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_client_status_history_client ON client_status_history(client_id, transitioned_at DESC);
+
+        -- Per-client metric baselines by hour of week, for anomaly detection
+        CREATE TABLE IF NOT EXISTS client_baselines (
+            client_id UUID NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+            metric VARCHAR(50) NOT NULL,
+            hour_of_week SMALLINT NOT NULL,
+            weight DOUBLE PRECISION NOT NULL,
+            mean DOUBLE PRECISION NOT NULL,
+            stddev DOUBLE PRECISION NOT NULL,
+            updated_at TIMESTAMPTZ DEFAULT NOW(),
+            PRIMARY KEY (client_id, metric, hour_of_week)
+        );
+
+        -- Incident timeline lookups
+        CREATE INDEX IF NOT EXISTS idx_alert_history_created ON alert_history(created_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_alert_history_resolved ON alert_history(resolved_at DESC) WHERE resolved_at IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_ping_records_failed ON ping_records(time DESC) WHERE success IS NOT TRUE;
+
+        -- Ping sources per task
+        CREATE INDEX IF NOT EXISTS idx_ping_records_task_client ON ping_records(task_id, client_id) WHERE client_id IS NOT NULL;
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Columns added to existing tables after the initial release, as
+/// `(table, column, definition)`, in the order they are added.
+///
+/// New columns go at the end of this list rather than into the `CREATE TABLE`
+/// statements above, so deployments created before them are migrated.
+pub const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("records", "fd_used", "INTEGER DEFAULT 0"),
+    ("records", "fd_total", "INTEGER DEFAULT 0"),
+    ("records", "inode_used", "BIGINT DEFAULT 0"),
+    ("records", "inode_total", "BIGINT DEFAULT 0"),
+    ("records", "load5", "REAL DEFAULT 0"),
+    ("records", "load15", "REAL DEFAULT 0"),
+    ("clients", "maintenance_until", "TIMESTAMPTZ"),
+    ("notifications", "notification_locale", "VARCHAR(10)"),
+    ("users", "email", "VARCHAR(255)"),
+    ("users", "external", "BOOLEAN DEFAULT FALSE"),
+    ("users", "role", "VARCHAR(20) DEFAULT 'admin'"),
+    ("clients", "last_report_transport", "VARCHAR(10)"),
+    ("clients", "ntp_synced", "BOOLEAN"),
+    ("clients", "clock_offset_ms", "REAL"),
+    (
+        "clients",
+        "display_color",
+        "VARCHAR(20) NOT NULL DEFAULT ''",
+    ),
+    (
+        "clients",
+        "display_icon",
+        "VARCHAR(100) NOT NULL DEFAULT ''",
+    ),
+    ("clients", "token_hash", "VARCHAR(64)"),
+    ("clients", "last_report_ip", "VARCHAR(45)"),
+    (
+        "clients",
+        "alert_on_ip_change",
+        "BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    ("clients", "report_interval_seconds", "INTEGER"),
+    ("clients", "builtin", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("clients", "archived", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    (
+        "clients",
+        "manually_overridden",
+        "BOOLEAN NOT NULL DEFAULT FALSE",
+    ),
+    ("clients", "tcp_states", "JSONB"),
+    ("clients", "tcp_states_at", "TIMESTAMPTZ"),
+    ("ping_tasks", "expected_body_contains", "TEXT"),
+    ("ping_tasks", "expected_body_not_contains", "TEXT"),
+    (
+        "ping_tasks",
+        "request_headers",
+        "JSONB NOT NULL DEFAULT '{}'",
+    ),
+    ("ping_records", "failure_reason", "TEXT"),
+    (
+        "alert_history",
+        "kind",
+        "VARCHAR(20) NOT NULL DEFAULT 'threshold'",
+    ),
+];
+
+/// Migrate data once every column in [`ADDED_COLUMNS`] exists.
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    sqlx::raw_sql(
+        r#"
+        ALTER TABLE users ALTER COLUMN username TYPE VARCHAR(255);
+
+        -- Usernames are unique regardless of case; skipped while existing ones collide
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM users GROUP BY LOWER(username) HAVING COUNT(*) > 1
+            ) THEN
+                CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username_lower
+                    ON users (LOWER(username));
+            END IF;
+        END $$;
+
+        -- A report delivered twice, e.g. over HTTP and WebSocket at once, is
+        -- stored once; duplicates from before the index are dropped first
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM pg_indexes WHERE indexname = 'idx_records_client_time_unique'
+            ) THEN
+                DELETE FROM records r USING records d
+                    WHERE r.client_id = d.client_id AND r.time = d.time AND r.id > d.id;
+                CREATE UNIQUE INDEX idx_records_client_time_unique ON records (client_id, time);
+            END IF;
+        END $$;
+
+        -- Anomalies are recorded in alert_history without a rule
+        ALTER TABLE alert_history ALTER COLUMN rule_id DROP NOT NULL;
+
+        -- Display columns were added nullable at first
+        UPDATE clients SET display_color = '' WHERE display_color IS NULL;
+        UPDATE clients SET display_icon = '' WHERE display_icon IS NULL;
+        ALTER TABLE clients ALTER COLUMN display_color SET DEFAULT '';
+        ALTER TABLE clients ALTER COLUMN display_color SET NOT NULL;
+        ALTER TABLE clients ALTER COLUMN display_icon SET DEFAULT '';
+        ALTER TABLE clients ALTER COLUMN display_icon SET NOT NULL;
+
+        -- Rebuild the search vector when its expression predates the display columns
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_name = 'clients' AND column_name = 'search_vector'
+                    AND generation_expression LIKE '%display_icon%'
+            ) THEN
+                ALTER TABLE clients DROP COLUMN IF EXISTS search_vector;
+                ALTER TABLE clients ADD COLUMN search_vector TSVECTOR
+                    GENERATED ALWAYS AS (to_tsvector('english',
+                        coalesce(name, '') || ' ' || coalesce(cpu_name, '') || ' ' ||
+                        coalesce(os, '') || ' ' || coalesce(ipv4, '') || ' ' ||
+                        coalesce(remark, '') || ' ' || coalesce(public_remark, '') || ' ' ||
+                        coalesce(display_color, '') || ' ' || coalesce(display_icon, ''))) STORED;
+            END IF;
+        END $$;
+        CREATE INDEX IF NOT EXISTS idx_clients_search ON clients USING GIN(search_vector);
+
+        -- Agent tokens may be encrypted at rest, so clients are looked up by token hash
+        UPDATE clients SET token_hash = encode(sha256(convert_to(token, 'UTF8')), 'hex')
+            WHERE token_hash IS NULL AND token NOT LIKE 'enc:%';
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_clients_token_hash ON clients(token_hash);
+
+        -- Superseded by the alias table in db::normalization
+        DROP FUNCTION IF EXISTS normalize_arch(TEXT);
+
+        -- Move transitions recorded in client_status_events, which may
+        -- predate its duration_seconds and reason columns
+        DO $$
+        BEGIN
+            IF to_regclass('client_status_events') IS NOT NULL THEN
+                ALTER TABLE client_status_events ADD COLUMN IF NOT EXISTS duration_seconds BIGINT;
+                ALTER TABLE client_status_events ADD COLUMN IF NOT EXISTS reason TEXT;
+                INSERT INTO client_status_history
+                    (client_id, online, transitioned_at, duration_seconds, reason)
+                SELECT client_id, online, created_at, duration_seconds, reason
+                FROM client_status_events;
+                DROP TABLE client_status_events;
+            END IF;
+        END $$;
+
+        -- Fill in the durations of transitions recorded before duration_seconds
+        UPDATE client_status_history e
+        SET duration_seconds = EXTRACT(EPOCH FROM n.next_at - e.transitioned_at)::bigint
+        FROM (
+            SELECT id, LEAD(transitioned_at) OVER (PARTITION BY client_id ORDER BY transitioned_at) AS next_at
+            FROM client_status_history
+        ) n
+        WHERE e.id = n.id AND e.duration_seconds IS NULL AND n.next_at IS NOT NULL;
+
+        -- Convert comma-separated tags to a text array
+        DO $$
+        BEGIN
+            IF (SELECT data_type FROM information_schema.columns
+                WHERE table_name = 'clients' AND column_name = 'tags') = 'text' THEN
+                ALTER TABLE clients ALTER COLUMN tags DROP DEFAULT;
+                ALTER TABLE clients ALTER COLUMN tags TYPE TEXT[]
+                    USING array_remove(string_to_array(COALESCE(tags, ''), ','), '');
+                ALTER TABLE clients ALTER COLUMN tags SET DEFAULT '{}';
+            END IF;
+        END $$;
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Normalize CPU architecture aliases of existing clients
+    let (aliases, canonical): (Vec<&str>, Vec<&str>) = ARCH_ALIASES
+        .iter()
+        .flat_map(|(canonical, aliases)| aliases.iter().map(move |alias| (*alias, *canonical)))
+        .unzip();
+    sqlx::query(
+        r#"
+        UPDATE clients c SET arch = m.canonical
+        FROM UNNEST($1::text[], $2::text[]) AS m(alias, canonical)
+        WHERE lower(trim(c.arch)) = m.alias AND c.arch <> m.canonical
         "#,
     )
+    .bind(&aliases)
+    .bind(&canonical)
     .execute(pool)
     .await?;
 
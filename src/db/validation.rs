@@ -0,0 +1,80 @@
+//! Validation of agent-reported records.
+//!
+//! Buggy agents and broken sensors report values such as `cpu = -1` or
+//! `temp = 32767` that distort graphs and trigger false alerts. Records with
+//! physically impossible values are rejected before they are stored.
+
+use super::models::RecordInput;
+use crate::error::{AppError, AppResult};
+
+/// Highest plausible temperature in °C; 0 means the sensor is unsupported.
+const MAX_TEMP: f32 = 200.0;
+
+/// Check that a record's values are physically possible.
+///
+/// The error names the first field that failed.
+pub fn validate_record(record: &RecordInput) -> AppResult<()> {
+    check_range("cpu", record.cpu, 0.0, 100.0)?;
+    check_range("gpu", record.gpu, 0.0, 100.0)?;
+    check_range("temp", record.temp, 0.0, MAX_TEMP)?;
+    for (field, value) in [
+        ("load", record.load),
+        ("load5", record.load5),
+        ("load15", record.load15),
+    ] {
+        check_range(field, value, 0.0, f32::MAX)?;
+    }
+
+    for (field, value) in [
+        ("ram", record.ram),
+        ("ram_total", record.ram_total),
+        ("swap", record.swap),
+        ("swap_total", record.swap_total),
+        ("disk", record.disk),
+        ("disk_total", record.disk_total),
+        ("net_in", record.net_in),
+        ("net_out", record.net_out),
+        ("net_total_up", record.net_total_up),
+        ("net_total_down", record.net_total_down),
+    ] {
+        if value < 0 {
+            return Err(invalid(field, value));
+        }
+    }
+
+    if let Some(tcp_states) = &record.tcp_states {
+        for (state, count) in tcp_states.counts() {
+            if count < 0 {
+                return Err(invalid(&format!("tcp_states.{}", state), count));
+            }
+        }
+    }
+
+    for (field, used, total) in [
+        ("ram", record.ram, record.ram_total),
+        ("swap", record.swap, record.swap_total),
+        ("disk", record.disk, record.disk_total),
+    ] {
+        if used > total {
+            return Err(AppError::BadRequest(format!(
+                "{} ({}) exceeds {}_total ({})",
+                field, used, field, total
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that `value` is a number within `min..=max`.
+fn check_range(field: &str, value: f32, min: f32, max: f32) -> AppResult<()> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(invalid(field, value))
+    }
+}
+
+fn invalid(field: &str, value: impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("{} out of range: {}", field, value))
+}
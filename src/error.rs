@@ -1,12 +1,16 @@
 //! Application error types and HTTP response handling.
 
+use std::future::Future;
+use std::time::Duration;
+
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use thiserror::Error;
+use tracing::error;
 
 /// Application error type.
 #[derive(Error, Debug)]
@@ -26,13 +30,46 @@ pub enum AppError {
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    #[error("Too many requests")]
+    TooManyRequests,
+
+    #[error("Gone: {0}")]
+    Gone(String),
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
 
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+/// Seconds clients are asked to wait after a 503.
+const RETRY_AFTER_SECS: u32 = 5;
+
+/// SQLSTATE of a query cancelled by `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.code().as_deref() == Some(QUERY_CANCELED) => {
+                error!("Database query timed out: {}", db.message());
+                AppError::Timeout("Database query timed out".into())
+            }
+            _ if crate::db::is_connection_error(&e) => {
+                AppError::ServiceUnavailable(format!("Database unavailable: {}", e))
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
 /// Error response body.
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -48,6 +85,12 @@ impl IntoResponse for AppError {
             AppError::NotFound(_) => (StatusCode::NOT_FOUND, "NOT_FOUND"),
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, "BAD_REQUEST"),
             AppError::Conflict(_) => (StatusCode::CONFLICT, "CONFLICT"),
+            AppError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "TOO_MANY_REQUESTS"),
+            AppError::Gone(_) => (StatusCode::GONE, "GONE"),
+            AppError::ServiceUnavailable(_) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "SERVICE_UNAVAILABLE")
+            }
+            AppError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "TIMEOUT"),
             AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "DATABASE_ERROR"),
             AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
         };
@@ -57,9 +100,26 @@ impl IntoResponse for AppError {
             message: self.to_string(),
         };
 
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+        }
+        response
     }
 }
 
 /// Result type alias for application handlers.
 pub type AppResult<T> = Result<T, AppError>;
+
+/// Run `future`, failing with [`AppError::Timeout`] if it takes longer than
+/// `duration`.
+pub async fn with_timeout<F, T>(duration: Duration, future: F) -> AppResult<T>
+where
+    F: Future<Output = T>,
+{
+    tokio::time::timeout(duration, future)
+        .await
+        .map_err(|_| AppError::Timeout(format!("No result within {} seconds", duration.as_secs())))
+}
@@ -0,0 +1,66 @@
+//! Heartbeat monitors.
+//!
+//! External jobs (CI, cron) call `POST /api/heartbeat/{token}` after each
+//! run. A heartbeat without a beat for `interval_secs + grace_secs` goes
+//! down and its missed notification is sent; the next beat brings it back up
+//! with a recovery notification. Heartbeats that never received a beat count
+//! from their creation time. Events are routed like monitor groups, by
+//! `group_name` equal to the heartbeat name.
+
+use chrono::Utc;
+use tracing::{error, info};
+
+use crate::api::AppState;
+use crate::db::Heartbeat;
+use crate::error::AppResult;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing;
+
+/// Mark overdue heartbeats down and notify them.
+pub async fn check(state: &AppState) -> AppResult<()> {
+    for heartbeat in state.db.mark_overdue_heartbeats().await? {
+        info!("Heartbeat {} missed", heartbeat.name);
+
+        let since = heartbeat.last_beat_at.or(heartbeat.created_at);
+        let minutes = since.map_or(0, |since| (Utc::now() - since).num_minutes());
+        notify(
+            state,
+            &heartbeat,
+            MessageKey::HeartbeatMissed,
+            &[("minutes", minutes.to_string())],
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Notify that a down heartbeat received a beat again.
+pub async fn notify_recovered(state: &AppState, heartbeat: &Heartbeat) {
+    info!("Heartbeat {} recovered", heartbeat.name);
+    notify(state, heartbeat, MessageKey::HeartbeatRecovered, &[]).await;
+}
+
+/// Send a heartbeat state change to the providers routed for it.
+async fn notify(
+    state: &AppState,
+    heartbeat: &Heartbeat,
+    key: MessageKey,
+    extra_params: &[(&str, String)],
+) {
+    let mut params = vec![("heartbeat", heartbeat.name.clone())];
+    params.extend_from_slice(extra_params);
+    let targets: Vec<_> = heartbeat.notification_id.into_iter().collect();
+
+    if let Err(e) = routing::dispatch_heartbeat(
+        &state.db,
+        &heartbeat.name,
+        &targets,
+        &state.runtime().locale,
+        key,
+        &params,
+    )
+    .await
+    {
+        error!("Failed to send heartbeat notification: {}", e);
+    }
+}
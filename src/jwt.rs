@@ -0,0 +1,92 @@
+//! Stateless JWT sessions.
+//!
+//! With `USE_JWT=true`, logins issue an HS256 JWT signed with `JWT_SECRET`
+//! instead of storing a session. Requests presenting a JWT are authenticated
+//! without a session lookup; the user is still loaded so deleted users and
+//! role changes take effect at once. Session tokens issued before the switch
+//! keep working until they expire.
+//!
+//! A JWT cannot be revoked: logout only clears the cookie and the session
+//! endpoints do not list or revoke JWTs. Keep `JWT_EXPIRES_SECS` short when
+//! that matters. `JWT_SECRET` must be set to at least [`MIN_SECRET_BYTES`]
+//! bytes for `USE_JWT` to start.
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::Session;
+use crate::error::{AppError, AppResult};
+
+/// Shortest `JWT_SECRET` accepted with `USE_JWT`.
+pub const MIN_SECRET_BYTES: usize = 32;
+
+/// Claims of a session JWT.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// User ID.
+    pub sub: Uuid,
+    /// Token ID, exposed as the session ID.
+    pub jti: Uuid,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Whether a bearer token looks like a JWT rather than a session token.
+pub fn is_jwt(token: &str) -> bool {
+    token.starts_with("eyJ")
+}
+
+/// Issue a session JWT for a user, valid for `jwt_expires_secs`.
+pub fn issue(config: &Config, user_id: Uuid) -> AppResult<String> {
+    let iat = Utc::now().timestamp();
+    let claims = Claims {
+        sub: user_id,
+        jti: Uuid::new_v4(),
+        iat,
+        exp: iat + config.jwt_expires_secs,
+    };
+    jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+    .map_err(|e| AppError::Internal(format!("Failed to sign JWT: {}", e)))
+}
+
+/// Verify a session JWT, returning its claims when the signature is valid
+/// and it has not expired.
+pub fn verify(config: &Config, token: &str) -> Option<Claims> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_required_spec_claims(&["exp", "sub"]);
+    jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &validation,
+    )
+    .ok()
+    .map(|data| data.claims)
+}
+
+/// A session standing in for a verified JWT, so handlers see the same
+/// extensions as with a stored session. The client details are those of
+/// the current request.
+pub fn session(
+    claims: &Claims,
+    token: &str,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+) -> Session {
+    let timestamp = |secs| DateTime::from_timestamp(secs, 0).unwrap_or_default();
+    Session {
+        id: claims.jti,
+        user_id: claims.sub,
+        token: token.to_string(),
+        user_agent,
+        ip_address,
+        expires_at: timestamp(claims.exp),
+        created_at: Some(timestamp(claims.iat)),
+    }
+}
@@ -0,0 +1,27 @@
+//! Vanmoi server monitoring master control panel.
+//!
+//! The binary in `main.rs` starts the server from these modules; the
+//! library target exists so integration tests can build the router.
+
+pub mod alerts;
+pub mod anomaly;
+pub mod api;
+pub mod build_info;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod error;
+pub mod heartbeats;
+pub mod jwt;
+pub mod logs;
+pub mod middleware;
+pub mod monitors;
+pub mod notifier;
+pub mod security;
+pub mod silences;
+pub mod storage;
+pub mod tasks;
+#[cfg(feature = "integration")]
+pub mod testing;
+pub mod utils;
+pub mod ws;
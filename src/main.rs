@@ -1,45 +1,85 @@
 use std::net::SocketAddr;
+use std::time::Duration;
 
 use anyhow::Result;
 use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
-mod api;
-mod config;
-mod db;
-mod error;
-mod logs;
-mod middleware;
-mod notifier;
-mod ws;
-
-use config::Config;
-use db::Database;
+use vanmoi::config::Config;
+use vanmoi::db::Database;
+use vanmoi::{api, build_info, cli, logs, tasks};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
     logs::init();
 
-    info!("Starting Vanmoi server...");
+    // Run a subcommand instead of the server when one is given
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args).await;
+    }
+
+    info!("Starting {}", build_info::banner());
 
     // Load configuration
-    let config = Config::from_env();
+    let config = Config::from_env()?;
     info!("Configuration loaded");
 
     // Connect to database
-    let db = Database::connect(&config.database_url).await?;
+    let db = Database::connect(
+        &config.database_url,
+        &config.database_replica_urls,
+        Duration::from_secs(config.db_query_timeout_secs),
+    )
+    .await?
+    .with_secret_key(config.secret_key.as_deref());
     info!("Database connected");
 
     // Initialize database schema
     db.init_schema().await?;
     info!("Database schema initialized");
 
+    // Look for rows left inconsistent by an unclean shutdown or a restore
+    match db.check_consistency(config.startup_db_fix).await {
+        Ok(report) => {
+            for finding in &report.findings {
+                warn!(
+                    "Consistency check: {} {}: {}",
+                    finding.kind, finding.subject, finding.detail
+                );
+            }
+            if !report.findings.is_empty() && !report.fixed {
+                warn!(
+                    "Found {} consistency problems, run `vanmoi db check --fix` or set STARTUP_DB_FIX=true",
+                    report.findings.len()
+                );
+            }
+        }
+        Err(e) => warn!("Consistency check failed: {}", e),
+    }
+
+    // Encrypt secrets stored before SECRET_KEY was set
+    if let Some(cipher) = db.cipher() {
+        let encrypted = db.reencrypt_secrets(Some(cipher), false).await?;
+        if encrypted > 0 {
+            info!("Encrypted plaintext secrets in {} rows", encrypted);
+        }
+    } else {
+        warn!("SECRET_KEY is not set, secrets are stored unencrypted");
+    }
+
     // Initialize admin user if no users exist
     init_admin_user(&db, &config).await?;
 
     // Create application state
     let state = api::AppState::new(db, config.clone());
+    state.reload_runtime_settings().await?;
+
+    // Start background tasks
+    let shutdown = CancellationToken::new();
+    tasks::spawn_all(state.clone(), shutdown.clone());
 
     // Build router
     let app = api::create_router(state);
@@ -50,11 +90,49 @@ async fn main() -> Result<()> {
 
     info!("Server listening on {}", addr);
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(shutdown))
+    .await?;
+
+    info!("Server stopped");
 
     Ok(())
 }
 
+/// Wait for Ctrl+C or SIGTERM, then cancel background tasks.
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received");
+    shutdown.cancel();
+}
+
 /// Initialize admin user if no users exist in the database.
 async fn init_admin_user(db: &Database, config: &Config) -> Result<()> {
     if db.has_users().await? {
@@ -2,13 +2,16 @@
 
 use axum::{
     extract::{Request, State},
-    http::{StatusCode, header},
+    http::{HeaderMap, Method, StatusCode, header},
     middleware::Next,
     response::Response,
 };
 
 use crate::api::AppState;
-use crate::db::User;
+use crate::db::{Session, User};
+use crate::error::AppResult;
+use crate::jwt;
+use crate::middleware::RealIp;
 
 /// Extract session from request and add user to extensions.
 pub async fn auth_middleware(
@@ -19,16 +22,18 @@ pub async fn auth_middleware(
     // Try to get token from Authorization header or cookie
     let token = extract_token(&request);
 
-    if let Some(token) = token {
-        // Find session
-        if let Ok(Some(session)) = state.db.find_session_by_token(&token).await {
-            // Find user
-            if let Ok(Some(user)) = state.db.find_user_by_id(session.user_id).await {
-                request.extensions_mut().insert(user);
-            }
-        }
+    let mut current_user: Option<User> = None;
+    if let Some(token) = token
+        && let Ok(Some((user, _))) =
+            resolve_session(&state, request.headers(), real_ip(&request), &token).await
+    {
+        request.extensions_mut().insert(user.clone());
+        current_user = Some(user);
     }
 
+    // Always present so public handlers can check `Extension<Option<User>>`
+    request.extensions_mut().insert(current_user);
+
     Ok(next.run(request).await)
 }
 
@@ -42,24 +47,72 @@ pub async fn require_auth_middleware(
 
     let token = token.ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let session = state
-        .db
-        .find_session_by_token(&token)
+    let (user, session) = resolve_session(&state, request.headers(), real_ip(&request), &token)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    let user = state
-        .db
-        .find_user_by_id(session.user_id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    // Viewers get read-only access
+    if user.role != "admin" && request.method() != Method::GET {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
     request.extensions_mut().insert(user);
+    request.extensions_mut().insert(session);
     Ok(next.run(request).await)
 }
 
+/// Require the admin role. Layered after [`require_auth_middleware`] on
+/// endpoints that return secrets (agent tokens, share and heartbeat tokens,
+/// notification and settings configs), which viewers may not read.
+pub async fn require_admin_middleware(
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    match request.extensions().get::<User>() {
+        Some(user) if user.role == "admin" => Ok(next.run(request).await),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// Find the user and session of a token.
+///
+/// With `USE_JWT`, a token that looks like a JWT is verified instead of
+/// looked up, and stands in for a session built from the claims and the
+/// request; other tokens are looked up as stored sessions either way.
+async fn resolve_session(
+    state: &AppState,
+    headers: &HeaderMap,
+    real_ip: Option<RealIp>,
+    token: &str,
+) -> AppResult<Option<(User, Session)>> {
+    let session = if state.config.use_jwt && jwt::is_jwt(token) {
+        let Some(claims) = jwt::verify(&state.config, token) else {
+            return Ok(None);
+        };
+        let ip_address = real_ip.map(|RealIp(ip)| ip.to_string());
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        jwt::session(&claims, token, ip_address, user_agent)
+    } else {
+        match state.db.find_session_by_token(token).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        }
+    };
+
+    let user = state.db.find_user_by_id(session.user_id).await?;
+    Ok(user.map(|user| (user, session)))
+}
+
+/// Client IP of a request, set by the real IP middleware.
+fn real_ip(request: &Request) -> Option<RealIp> {
+    request.extensions().get::<RealIp>().copied()
+}
+
 /// Extract token from Authorization header or cookie.
 fn extract_token(request: &Request) -> Option<String> {
     // Try Authorization header first
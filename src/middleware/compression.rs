@@ -0,0 +1,229 @@
+//! Response compression policy.
+//!
+//! Responses are compressed with the algorithms listed in
+//! `COMPRESSION_ALGORITHMS` unless they are smaller than
+//! `COMPRESSION_MIN_SIZE`, an event stream, already compressed, a protocol
+//! upgrade (WebSocket handshakes must pass through untouched), or marked
+//! with [`NoCompression`] (agent endpoints, whose replies are tiny and hot).
+
+use axum::{
+    body::HttpBody,
+    http::{self, header},
+    response::Response,
+};
+use tower_http::compression::{CompressionLayer, Predicate, predicate::NotForContentType};
+use tracing::warn;
+
+use crate::config::Config;
+
+/// Content types that are already compressed or must be streamed unbuffered.
+const SKIPPED_CONTENT_TYPES: &[&str] = &[
+    "text/event-stream",
+    "application/gzip",
+    "application/x-gzip",
+    "application/zstd",
+    "application/zip",
+    "application/x-bzip2",
+    "application/x-xz",
+    "application/x-7z-compressed",
+    "font/woff2",
+    "video/",
+    "audio/",
+];
+
+/// Response extension that opts a response out of compression.
+#[derive(Debug, Clone, Copy)]
+pub struct NoCompression;
+
+/// Mark a response as not to be compressed, for use with `map_response`.
+pub async fn no_compression(mut response: Response) -> Response {
+    response.extensions_mut().insert(NoCompression);
+    response
+}
+
+/// Compression predicate applying the policy above.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionPolicy {
+    min_size: usize,
+}
+
+impl Predicate for CompressionPolicy {
+    fn should_compress<B>(&self, response: &http::Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        if response.extensions().get::<NoCompression>().is_some()
+            || response.status() == http::StatusCode::SWITCHING_PROTOCOLS
+        {
+            return false;
+        }
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if SKIPPED_CONTENT_TYPES
+            .iter()
+            .any(|skipped| content_type.starts_with(skipped))
+        {
+            return false;
+        }
+
+        // Like `SizeAbove`, whose limit is a u16; unknown sizes are compressed
+        let size = response.body().size_hint().exact().or_else(|| {
+            response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+        });
+        size.is_none_or(|size| size >= self.min_size as u64)
+            && NotForContentType::IMAGES.should_compress(response)
+    }
+}
+
+/// Build the compression layer from `COMPRESSION_ALGORITHMS` and
+/// `COMPRESSION_MIN_SIZE`.
+pub fn compression_layer(config: &Config) -> CompressionLayer<CompressionPolicy> {
+    let mut layer = CompressionLayer::new()
+        .no_br()
+        .no_deflate()
+        .no_gzip()
+        .no_zstd();
+    for algorithm in &config.compression_algorithms {
+        layer = match algorithm.as_str() {
+            "br" => layer.br(true),
+            "gzip" => layer.gzip(true),
+            "zstd" => layer.zstd(true),
+            other => {
+                warn!("Ignoring unknown compression algorithm: {}", other);
+                layer
+            }
+        };
+    }
+
+    layer.compress_when(CompressionPolicy {
+        min_size: config.compression_min_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        extract::Path,
+        http::{HeaderMap, Request, StatusCode},
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Router behind the compression layer built from `configure`d config.
+    fn router(configure: impl FnOnce(&mut Config)) -> Router {
+        let mut config = Config::from_env().unwrap();
+        config.compression_algorithms = vec!["zstd".into(), "br".into(), "gzip".into()];
+        config.compression_min_size = 1024;
+        configure(&mut config);
+        Router::new()
+            .route(
+                "/text/{size}",
+                get(|Path(size): Path<usize>| async move { "a".repeat(size) }),
+            )
+            .route(
+                "/upgrade",
+                get(|| async {
+                    (
+                        StatusCode::SWITCHING_PROTOCOLS,
+                        [
+                            (header::UPGRADE, "websocket"),
+                            (header::CONNECTION, "upgrade"),
+                            (header::SEC_WEBSOCKET_ACCEPT, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="),
+                        ],
+                    )
+                }),
+            )
+            .layer(compression_layer(&config))
+    }
+
+    /// Status and headers of a GET of `uri` accepting `accept_encoding`.
+    async fn get_with(router: Router, uri: &str, accept_encoding: &str) -> (StatusCode, HeaderMap) {
+        let request = Request::get(uri)
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        (response.status(), response.headers().clone())
+    }
+
+    #[tokio::test]
+    async fn switching_protocols_passes_through() {
+        // Not skipped for its empty body
+        let router = router(|config| config.compression_min_size = 0);
+        let (status, headers) = get_with(router, "/upgrade", "zstd, br, gzip").await;
+        assert_eq!(status, StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(headers[header::UPGRADE], "websocket");
+        assert_eq!(headers[header::CONNECTION], "upgrade");
+        assert_eq!(
+            headers[header::SEC_WEBSOCKET_ACCEPT],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert!(headers.get(header::CONTENT_ENCODING).is_none());
+        assert!(headers.get(header::VARY).is_none());
+    }
+
+    /// `Content-Encoding` of a GET of `uri`, if any.
+    async fn encoding(router: &Router, uri: &str, accept_encoding: &str) -> Option<String> {
+        let (status, headers) = get_with(router.clone(), uri, accept_encoding).await;
+        assert_eq!(status, StatusCode::OK);
+        headers
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn negotiates_content_encoding() {
+        let app = router(|_| {});
+        for (accept_encoding, expected) in [
+            ("gzip", Some("gzip")),
+            ("br", Some("br")),
+            ("zstd", Some("zstd")),
+            ("gzip;q=0.5, br;q=1.0", Some("br")),
+            ("zstd;q=0, gzip", Some("gzip")),
+            ("deflate", None),
+            ("identity", None),
+        ] {
+            assert_eq!(
+                encoding(&app, "/text/4096", accept_encoding)
+                    .await
+                    .as_deref(),
+                expected,
+                "{}",
+                accept_encoding
+            );
+        }
+
+        // Algorithms left out of COMPRESSION_ALGORITHMS are never used
+        let app = router(|config| config.compression_algorithms = vec!["gzip".into()]);
+        assert_eq!(encoding(&app, "/text/4096", "br").await, None);
+        assert_eq!(
+            encoding(&app, "/text/4096", "br, gzip").await.as_deref(),
+            Some("gzip")
+        );
+        let app = router(|config| config.compression_algorithms.clear());
+        assert_eq!(encoding(&app, "/text/4096", "gzip").await, None);
+    }
+
+    #[tokio::test]
+    async fn min_size() {
+        let app = router(|_| {});
+        assert_eq!(encoding(&app, "/text/1023", "gzip").await, None);
+        assert!(encoding(&app, "/text/1024", "gzip").await.is_some());
+
+        // Limits above u16::MAX are honored
+        let app = router(|config| config.compression_min_size = 100_000);
+        assert_eq!(encoding(&app, "/text/70000", "gzip").await, None);
+        assert!(encoding(&app, "/text/100000", "gzip").await.is_some());
+    }
+}
@@ -0,0 +1,205 @@
+//! Idempotency keys for admin mutations.
+//!
+//! A mutating admin request carrying an `Idempotency-Key` header runs at
+//! most once per user and key. The first request claims the key by
+//! inserting its row before the handler runs, so a concurrent duplicate
+//! finds the row and gets `409 Conflict` instead of running too. Once the
+//! handler finishes, its response is stored and repeats of the same request
+//! get it replayed with `Idempotency-Replayed: true`. Reusing a key for a
+//! different endpoint or body is rejected with `409 Conflict`.
+//!
+//! The handler runs in its own task, so a client disconnecting mid-request
+//! does not abandon the claim. Server errors release the key so the request
+//! can be retried, and a claim still in progress after
+//! [`IDEMPOTENCY_CLAIM_TIMEOUT_SECS`], left by a crash, can be taken over.
+//! Keys expire after [`IDEMPOTENCY_KEY_TTL_HOURS`] and are deleted by the
+//! maintenance task.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderValue, Method, Response, StatusCode, header},
+    middleware::Next,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::{IdempotencyKey, User};
+use crate::error::{AppError, AppResult};
+
+/// Request header carrying the key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header marking a replayed response.
+pub const IDEMPOTENCY_REPLAYED_HEADER: &str = "idempotency-replayed";
+
+/// Hours a key is remembered.
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i32 = 24;
+
+/// Seconds after which a claim with no stored response is considered
+/// abandoned.
+pub const IDEMPOTENCY_CLAIM_TIMEOUT_SECS: i32 = 300;
+
+/// Longest accepted key.
+const MAX_KEY_LEN: usize = 255;
+
+/// Largest request or response body handled; admin requests are far smaller.
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Run a keyed mutation at most once and replay its response.
+pub async fn idempotency_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> AppResult<Response<Body>> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LEN)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Idempotency-Key must be 1-{} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+        })?
+        .to_string();
+    let Some(user_id) = request.extensions().get::<User>().map(|u| u.id) else {
+        return Ok(next.run(request).await);
+    };
+
+    let endpoint = format!("{} {}", request.method(), request.uri());
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::BadRequest("Request body too large".into()))?;
+    let request_hash = request_hash(&endpoint, &body);
+
+    if !state
+        .db
+        .claim_idempotency_key(
+            user_id,
+            &key,
+            &endpoint,
+            &request_hash,
+            IDEMPOTENCY_KEY_TTL_HOURS,
+            IDEMPOTENCY_CLAIM_TIMEOUT_SECS,
+        )
+        .await?
+    {
+        let existing = state.db.find_idempotency_key(user_id, &key).await?;
+        return replay(existing, &request_hash);
+    }
+
+    let request = Request::from_parts(parts, Body::from(body));
+    let task = tokio::spawn(run_claimed(
+        state.clone(),
+        user_id,
+        key.clone(),
+        request,
+        next,
+    ));
+    match task.await {
+        Ok(result) => result,
+        Err(e) => {
+            state.db.release_idempotency_key(user_id, &key).await?;
+            Err(AppError::Internal(format!("Request handler failed: {}", e)))
+        }
+    }
+}
+
+/// Run a request whose key is claimed, then store its response or release
+/// the key.
+async fn run_claimed(
+    state: AppState,
+    user_id: Uuid,
+    key: String,
+    request: Request,
+    next: Next,
+) -> AppResult<Response<Body>> {
+    let response = next.run(request).await;
+    if response.status().is_server_error() {
+        state.db.release_idempotency_key(user_id, &key).await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            state.db.release_idempotency_key(user_id, &key).await?;
+            return Err(AppError::Internal(format!(
+                "Failed to read response: {}",
+                e
+            )));
+        }
+    };
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    state
+        .db
+        .complete_idempotency_key(
+            user_id,
+            &key,
+            parts.status.as_u16() as i16,
+            content_type,
+            &body,
+        )
+        .await?;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Hex SHA-256 of the endpoint and body, so a key reused for anything but
+/// the same request is detected.
+fn request_hash(endpoint: &str, body: &Bytes) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Response for a request whose key is already claimed.
+fn replay(existing: Option<IdempotencyKey>, request_hash: &str) -> AppResult<Response<Body>> {
+    // Released by a failed request since the claim was attempted
+    let Some(existing) = existing else {
+        return Err(AppError::Conflict(
+            "A request with this Idempotency-Key is in progress".into(),
+        ));
+    };
+    if existing.request_hash != request_hash {
+        return Err(AppError::Conflict(
+            "Idempotency-Key was already used for a different request".into(),
+        ));
+    }
+    let Some(status_code) = existing.status_code else {
+        return Err(AppError::Conflict(
+            "A request with this Idempotency-Key is in progress".into(),
+        ));
+    };
+
+    let status = StatusCode::from_u16(status_code as u16)
+        .map_err(|_| AppError::Internal("Invalid stored status code".into()))?;
+    let mut builder = Response::builder().status(status).header(
+        IDEMPOTENCY_REPLAYED_HEADER,
+        HeaderValue::from_static("true"),
+    );
+    if let Some(content_type) = existing.content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(Body::from(existing.response_body.unwrap_or_default()))
+        .map_err(|e| AppError::Internal(format!("Failed to build response: {}", e)))
+}
@@ -0,0 +1,60 @@
+//! Real client IP detection.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::api::AppState;
+
+/// Client IP address of the current request, set by [`real_ip_middleware`].
+#[derive(Debug, Clone, Copy)]
+pub struct RealIp(pub IpAddr);
+
+/// Determine the client IP of a request.
+///
+/// When `trust_proxy` is set, the first `X-Forwarded-For` entry is used,
+/// then `X-Real-IP`. Otherwise (or if neither header parses) the peer
+/// address of the connection is used.
+pub fn extract_real_ip(request: &Request, trust_proxy: bool) -> Option<IpAddr> {
+    if trust_proxy {
+        let headers = request.headers();
+
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+
+        let real_ip = headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+        if real_ip.is_some() {
+            return real_ip;
+        }
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// Add a [`RealIp`] extension to every request.
+pub async fn real_ip_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ip) = extract_real_ip(&request, state.config.trust_proxy) {
+        request.extensions_mut().insert(RealIp(ip));
+    }
+    next.run(request).await
+}
@@ -0,0 +1,283 @@
+//! Per-route HTTP request metrics.
+//!
+//! Requests are counted per method, matched route template and status class
+//! with a latency histogram. Route templates (`/api/admin/clients/{id}`)
+//! rather than raw paths are used so the number of series stays bounded.
+//! Agent reports rejected by the rate limit are counted per client.
+
+use std::fmt::Write;
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use dashmap::DashMap;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::api::AppState;
+
+/// Histogram bucket upper bounds in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Route label of requests that matched no route (static files).
+const FALLBACK_ROUTE: &str = "fallback";
+
+/// Method label of requests with a non-standard method.
+const OTHER_METHOD: &str = "other";
+
+/// Methods labelled as themselves; any other method a client sends would
+/// add series.
+const KNOWN_METHODS: [Method; 9] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::PATCH,
+    Method::OPTIONS,
+    Method::CONNECT,
+    Method::TRACE,
+];
+
+/// Series key of one route.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RouteKey {
+    pub method: String,
+    pub route: String,
+    /// `2xx`, `4xx`, ...
+    pub status: String,
+}
+
+/// Request count and latency histogram of one route.
+#[derive(Debug, Default)]
+struct RouteStats {
+    count: u64,
+    sum_secs: f64,
+    max_secs: f64,
+    /// Per-bucket counts; the last slot counts requests above every bound.
+    buckets: [u64; BUCKETS.len() + 1],
+}
+
+impl RouteStats {
+    fn observe(&mut self, secs: f64) {
+        self.count += 1;
+        self.sum_secs += secs;
+        self.max_secs = self.max_secs.max(secs);
+        let i = BUCKETS
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[i] += 1;
+    }
+
+    /// Estimate a quantile by interpolating within its histogram bucket.
+    fn quantile(&self, q: f64) -> f64 {
+        let rank = q * self.count as f64;
+        let mut seen = 0;
+        for (i, &n) in self.buckets.iter().enumerate() {
+            if n > 0 && (seen + n) as f64 >= rank {
+                let lower = if i == 0 { 0.0 } else { BUCKETS[i - 1] };
+                let upper = BUCKETS.get(i).copied().unwrap_or(self.max_secs);
+                let fraction = (rank - seen as f64) / n as f64;
+                return (lower + (upper - lower) * fraction).min(self.max_secs);
+            }
+            seen += n;
+        }
+        0.0
+    }
+}
+
+/// Summary of one route for the debug endpoint.
+#[derive(Debug, Serialize)]
+pub struct RouteSummary {
+    pub method: String,
+    pub route: String,
+    pub status: String,
+    pub count: u64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+/// HTTP request metrics since startup.
+#[derive(Debug)]
+pub struct HttpMetrics {
+    started_at: Instant,
+    routes: DashMap<RouteKey, RouteStats>,
+    /// Rejected agent reports per client.
+    rate_limited: DashMap<Uuid, u64>,
+}
+
+impl Default for HttpMetrics {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            routes: DashMap::new(),
+            rate_limited: DashMap::new(),
+        }
+    }
+}
+
+impl HttpMetrics {
+    /// Record one request.
+    pub fn observe(&self, key: RouteKey, secs: f64) {
+        self.routes.entry(key).or_default().observe(secs);
+    }
+
+    /// Count a report rejected by the per-client rate limit.
+    pub fn record_rate_limited(&self, client_id: Uuid) {
+        *self.rate_limited.entry(client_id).or_insert(0) += 1;
+    }
+
+    /// Seconds since metrics collection started.
+    pub fn uptime_secs(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
+    /// The `limit` busiest routes, by request count.
+    pub fn top_routes(&self, limit: usize) -> Vec<RouteSummary> {
+        let mut routes: Vec<RouteSummary> = self
+            .routes
+            .iter()
+            .map(|entry| {
+                let (key, stats) = entry.pair();
+                RouteSummary {
+                    method: key.method.clone(),
+                    route: key.route.clone(),
+                    status: key.status.clone(),
+                    count: stats.count,
+                    avg_ms: stats.sum_secs * 1000.0 / stats.count.max(1) as f64,
+                    p95_ms: stats.quantile(0.95) * 1000.0,
+                    max_ms: stats.max_secs * 1000.0,
+                }
+            })
+            .collect();
+        routes.sort_by(|a, b| b.count.cmp(&a.count).then(a.route.cmp(&b.route)));
+        routes.truncate(limit);
+        routes
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP vanmoi_http_requests_total HTTP requests by route and status class."
+        );
+        let _ = writeln!(out, "# TYPE vanmoi_http_requests_total counter");
+        for entry in self.routes.iter() {
+            let (key, stats) = entry.pair();
+            let _ = writeln!(
+                out,
+                "vanmoi_http_requests_total{{{}}} {}",
+                labels(key),
+                stats.count
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vanmoi_http_request_duration_seconds HTTP request latency by route and status class."
+        );
+        let _ = writeln!(out, "# TYPE vanmoi_http_request_duration_seconds histogram");
+        for entry in self.routes.iter() {
+            let (key, stats) = entry.pair();
+            let labels = labels(key);
+            let mut cumulative = 0;
+            for (bound, n) in BUCKETS.iter().zip(stats.buckets) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "vanmoi_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "vanmoi_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, stats.count
+            );
+            let _ = writeln!(
+                out,
+                "vanmoi_http_request_duration_seconds_sum{{{}}} {}",
+                labels, stats.sum_secs
+            );
+            let _ = writeln!(
+                out,
+                "vanmoi_http_request_duration_seconds_count{{{}}} {}",
+                labels, stats.count
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vanmoi_rate_limited_total Agent reports rejected by the rate limit."
+        );
+        let _ = writeln!(out, "# TYPE vanmoi_rate_limited_total counter");
+        for entry in self.rate_limited.iter() {
+            let _ = writeln!(
+                out,
+                "vanmoi_rate_limited_total{{client_id=\"{}\"}} {}",
+                entry.key(),
+                entry.value()
+            );
+        }
+        out
+    }
+}
+
+fn labels(key: &RouteKey) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\",status=\"{}\"",
+        escape_label(&key.method),
+        escape_label(&key.route),
+        key.status
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Series key of a request: its method (`other` when non-standard), matched
+/// route template and the status class of its response.
+pub fn route_key(method: &Method, path: Option<&MatchedPath>, status: StatusCode) -> RouteKey {
+    let method = if KNOWN_METHODS.contains(method) {
+        method.as_str()
+    } else {
+        OTHER_METHOD
+    };
+    RouteKey {
+        method: method.to_string(),
+        route: path.map_or(FALLBACK_ROUTE, MatchedPath::as_str).to_string(),
+        status: format!("{}xx", status.as_u16() / 100),
+    }
+}
+
+/// Record the method, route, status and latency of every request.
+pub async fn metrics_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = request.extensions().get::<MatchedPath>().cloned();
+
+    let response = next.run(request).await;
+
+    let key = route_key(&method, path.as_ref(), response.status());
+    state
+        .http_metrics
+        .observe(key, started.elapsed().as_secs_f64());
+    response
+}
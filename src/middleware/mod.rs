@@ -1,5 +1,13 @@
 //! Middleware module.
 
 pub mod auth;
+pub mod compression;
+pub mod idempotency;
+pub mod ip_extractor;
+pub mod metrics;
 
 pub use auth::*;
+pub use compression::*;
+pub use idempotency::*;
+pub use ip_extractor::*;
+pub use metrics::*;
@@ -0,0 +1,154 @@
+//! Composite monitors.
+//!
+//! A monitor group combines ping tasks into one service status computed from
+//! the latest check of each enabled member:
+//!
+//! - `all`: up when every check is up, down when every check is down,
+//!   degraded in between.
+//! - `any`: up when at least one check is up, down otherwise.
+//!
+//! Groups without checked members are `unknown`. Status changes are routed
+//! like client events; changes from or to `unknown` are not notified, nor
+//! are changes while a silence covers one of the member tasks.
+
+use std::collections::HashMap;
+
+use tracing::{error, info};
+
+use crate::api::AppState;
+use crate::db::MonitorGroup;
+use crate::error::AppResult;
+use crate::notifier::i18n::MessageKey;
+use crate::notifier::routing;
+
+/// How member checks combine into the group status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorLogic {
+    All,
+    Any,
+}
+
+impl MonitorLogic {
+    /// Logic name as stored in `monitor_groups.logic`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MonitorLogic::All => "all",
+            MonitorLogic::Any => "any",
+        }
+    }
+
+    /// Parse a logic from its stored name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [MonitorLogic::All, MonitorLogic::Any]
+            .into_iter()
+            .find(|l| l.as_str() == name)
+    }
+}
+
+/// Overall status of a monitor group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorStatus {
+    Up,
+    Degraded,
+    Down,
+    Unknown,
+}
+
+impl MonitorStatus {
+    /// Combine `up` and `down` member checks under `logic`.
+    pub fn of(logic: MonitorLogic, up: i64, down: i64) -> Self {
+        match (logic, up, down) {
+            (_, 0, 0) => MonitorStatus::Unknown,
+            (_, _, 0) => MonitorStatus::Up,
+            (_, 0, _) => MonitorStatus::Down,
+            (MonitorLogic::All, _, _) => MonitorStatus::Degraded,
+            (MonitorLogic::Any, _, _) => MonitorStatus::Up,
+        }
+    }
+
+    /// Status name as stored in `monitor_groups.status`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MonitorStatus::Up => "up",
+            MonitorStatus::Degraded => "degraded",
+            MonitorStatus::Down => "down",
+            MonitorStatus::Unknown => "unknown",
+        }
+    }
+
+    fn message_key(&self) -> Option<MessageKey> {
+        match self {
+            MonitorStatus::Up => Some(MessageKey::MonitorUp),
+            MonitorStatus::Degraded => Some(MessageKey::MonitorDegraded),
+            MonitorStatus::Down => Some(MessageKey::MonitorDown),
+            MonitorStatus::Unknown => None,
+        }
+    }
+}
+
+/// Evaluate every monitor group, store status changes and notify them.
+pub async fn evaluate_groups(state: &AppState) -> AppResult<()> {
+    let groups = state.db.get_all_monitor_groups().await?;
+    if groups.is_empty() {
+        return Ok(());
+    }
+    let checks: HashMap<_, _> = state
+        .db
+        .get_monitor_group_checks()
+        .await?
+        .into_iter()
+        .map(|c| (c.group_id, (c.up, c.down)))
+        .collect();
+
+    for group in groups {
+        let logic = MonitorLogic::from_name(&group.logic).unwrap_or(MonitorLogic::All);
+        let (up, down) = checks.get(&group.id).copied().unwrap_or_default();
+        let status = MonitorStatus::of(logic, up, down);
+        if status.as_str() == group.status {
+            continue;
+        }
+
+        state
+            .db
+            .set_monitor_group_status(group.id, status.as_str())
+            .await?;
+        info!(
+            "Monitor {} changed from {} to {}",
+            group.name,
+            group.status,
+            status.as_str()
+        );
+
+        if group.status != MonitorStatus::Unknown.as_str()
+            && let Some(key) = status.message_key()
+        {
+            notify(state, &group, key, up, up + down).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Send a monitor status change to the providers routed for the group.
+async fn notify(state: &AppState, group: &MonitorGroup, key: MessageKey, up: i64, total: i64) {
+    let params = [
+        ("monitor", group.name.clone()),
+        ("up", up.to_string()),
+        ("total", total.to_string()),
+    ];
+    let group_targets: Vec<_> = group.notification_id.into_iter().collect();
+
+    if let Err(e) = routing::dispatch_monitor(
+        &state.db,
+        &group.name,
+        &group.task_ids,
+        &group_targets,
+        &state.runtime().locale,
+        key,
+        &params,
+    )
+    .await
+    {
+        error!("Failed to send monitor notification: {}", e);
+    }
+}
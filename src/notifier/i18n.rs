@@ -0,0 +1,1017 @@
+//! Message catalog for server-generated notification strings.
+//!
+//! Templates use `{name}` placeholders filled from the params passed to
+//! [`render`]. English covers every key; other locales fall back to English
+//! for keys they do not translate and for unknown locales. Regional variants
+//! (`de-AT`, `zh-CN`) use their language's catalog.
+//!
+//! The `metric`, `severity` and `date` params are localized as well: alert
+//! metric names and severities are replaced by their display names and
+//! `YYYY-MM-DD` dates are reformatted for the locale.
+
+use chrono::NaiveDate;
+
+/// Server-generated notification messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKey {
+    ClientOffline,
+    ClientOnline,
+    ThresholdFired,
+    ThresholdRecovered,
+    TrafficWarning,
+    CertExpiry,
+    DailyDigest,
+    WeeklyDigest,
+    ArchiveFailed,
+    IpChanged,
+    MonitorUp,
+    MonitorDegraded,
+    MonitorDown,
+    HeartbeatMissed,
+    HeartbeatRecovered,
+    AnomalyDetected,
+    AnomalyRecovered,
+    NewIpLogin,
+    FailedLogins,
+    SecurityEvent,
+}
+
+const DIGEST_BODY_EN: &str = "Servers online: {online}/{total}
+Offline: {offline}
+
+Top CPU (average):
+{top_cpu}
+
+Top traffic:
+{top_traffic}
+
+Alerts fired: {alert_count}
+{alerts}
+
+Disks above {disk_threshold}%:
+{disks}";
+
+const DIGEST_BODY_ZH_CN: &str = "在线服务器：{online}/{total}
+离线：{offline}
+
+CPU 占用前列（平均）：
+{top_cpu}
+
+流量前列：
+{top_traffic}
+
+触发告警：{alert_count}
+{alerts}
+
+磁盘占用超过 {disk_threshold}%：
+{disks}";
+
+const DIGEST_BODY_RU: &str = "Серверов в сети: {online}/{total}
+Не в сети: {offline}
+
+Наибольшая загрузка CPU (в среднем):
+{top_cpu}
+
+Наибольший трафик:
+{top_traffic}
+
+Сработавших оповещений: {alert_count}
+{alerts}
+
+Диски, заполненные более чем на {disk_threshold}%:
+{disks}";
+
+const DIGEST_BODY_DE: &str = "Server online: {online}/{total}
+Offline: {offline}
+
+Höchste CPU-Last (Durchschnitt):
+{top_cpu}
+
+Höchster Traffic:
+{top_traffic}
+
+Ausgelöste Alarme: {alert_count}
+{alerts}
+
+Festplatten über {disk_threshold} %:
+{disks}";
+
+const DIGEST_BODY_FR: &str = "Serveurs en ligne : {online}/{total}
+Hors ligne : {offline}
+
+CPU les plus sollicités (moyenne) :
+{top_cpu}
+
+Trafic le plus élevé :
+{top_traffic}
+
+Alertes déclenchées : {alert_count}
+{alerts}
+
+Disques au-dessus de {disk_threshold} % :
+{disks}";
+
+const DIGEST_BODY_JA: &str = "オンラインのサーバー：{online}/{total}
+オフライン：{offline}
+
+CPU 使用率上位（平均）：
+{top_cpu}
+
+トラフィック上位：
+{top_traffic}
+
+発生したアラート：{alert_count}
+{alerts}
+
+使用率 {disk_threshold}% 超のディスク：
+{disks}";
+
+/// English templates as `(title, body)`.
+fn en(key: MessageKey) -> (&'static str, &'static str) {
+    match key {
+        MessageKey::ClientOffline => ("[OFFLINE] {client}", "{client} has gone offline."),
+        MessageKey::ClientOnline => ("[ONLINE] {client}", "{client} is back online."),
+        MessageKey::ThresholdFired => (
+            "[{severity}] {metric} alert on {client}",
+            "{metric} is {value}, above threshold {threshold}.",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[RESOLVED] {metric} alert on {client}",
+            "{metric} is {value}, back below threshold {threshold}.",
+        ),
+        MessageKey::TrafficWarning => (
+            "[TRAFFIC] {client}",
+            "{client} has used {used} of its {limit} traffic limit ({percent}%).",
+        ),
+        MessageKey::CertExpiry => (
+            "[CERT] {target}",
+            "The certificate for {target} expires in {days} days.",
+        ),
+        MessageKey::DailyDigest => ("[DIGEST] Daily report {date}", DIGEST_BODY_EN),
+        MessageKey::WeeklyDigest => ("[DIGEST] Weekly report {date}", DIGEST_BODY_EN),
+        MessageKey::ArchiveFailed => (
+            "[ARCHIVE] Record archival failed",
+            "{count} client days could not be archived and were kept:\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[IP CHANGE] {client}",
+            "{client} is reporting from {ip}, which is not one of its recorded addresses ({known}).",
+        ),
+        MessageKey::MonitorUp => (
+            "[UP] {monitor}",
+            "{monitor} is up: {up} of {total} checks pass.",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[DEGRADED] {monitor}",
+            "{monitor} is degraded: {up} of {total} checks pass.",
+        ),
+        MessageKey::MonitorDown => (
+            "[DOWN] {monitor}",
+            "{monitor} is down: {up} of {total} checks pass.",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[MISSED] {heartbeat}",
+            "No heartbeat from {heartbeat} for {minutes} minutes.",
+        ),
+        MessageKey::HeartbeatRecovered => (
+            "[RECOVERED] {heartbeat}",
+            "{heartbeat} is sending heartbeats again.",
+        ),
+        MessageKey::AnomalyDetected => (
+            "[ANOMALY] {metric} on {client}",
+            "{metric} is {value}, {sigmas} standard deviations from its usual {mean} at this hour.",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[RESOLVED] {metric} anomaly on {client}",
+            "{metric} is {value}, back within its usual range.",
+        ),
+        MessageKey::NewIpLogin => (
+            "[SECURITY] New login IP for {user}",
+            "{user} signed in from {ip}, not used in the last {days} days.\nUser agent: {user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[SECURITY] Failed logins",
+            "{count} failed logins in the last {minutes} minutes, the latest as {user} from {ip}.",
+        ),
+        MessageKey::SecurityEvent => ("[SECURITY] {event}", "{event} for {user} from {ip}."),
+    }
+}
+
+/// Simplified Chinese templates as `(title, body)`.
+fn zh_cn(key: MessageKey) -> Option<(&'static str, &'static str)> {
+    let entry = match key {
+        MessageKey::ClientOffline => ("[离线] {client}", "{client} 已离线。"),
+        MessageKey::ClientOnline => ("[上线] {client}", "{client} 已恢复在线。"),
+        MessageKey::ThresholdFired => (
+            "[{severity}] {client} 的 {metric} 告警",
+            "{metric} 当前为 {value}，超过阈值 {threshold}。",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[已恢复] {client} 的 {metric} 告警",
+            "{metric} 当前为 {value}，已回落到阈值 {threshold} 以下。",
+        ),
+        MessageKey::TrafficWarning => (
+            "[流量] {client}",
+            "{client} 已使用 {used}，流量上限为 {limit}（{percent}%）。",
+        ),
+        MessageKey::CertExpiry => ("[证书] {target}", "{target} 的证书将在 {days} 天后过期。"),
+        MessageKey::DailyDigest => ("[摘要] 每日报告 {date}", DIGEST_BODY_ZH_CN),
+        MessageKey::WeeklyDigest => ("[摘要] 每周报告 {date}", DIGEST_BODY_ZH_CN),
+        MessageKey::ArchiveFailed => (
+            "[归档] 记录归档失败",
+            "{count} 个客户端日数据归档失败，已保留：\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[IP 变更] {client}",
+            "{client} 正在从 {ip} 上报，与记录的地址（{known}）不符。",
+        ),
+        MessageKey::MonitorUp => (
+            "[正常] {monitor}",
+            "{monitor} 已恢复正常：{total} 项检查中 {up} 项通过。",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[降级] {monitor}",
+            "{monitor} 部分异常：{total} 项检查中 {up} 项通过。",
+        ),
+        MessageKey::MonitorDown => (
+            "[故障] {monitor}",
+            "{monitor} 不可用：{total} 项检查中 {up} 项通过。",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[心跳丢失] {heartbeat}",
+            "{heartbeat} 已有 {minutes} 分钟未发送心跳。",
+        ),
+        MessageKey::HeartbeatRecovered => {
+            ("[心跳恢复] {heartbeat}", "{heartbeat} 已恢复发送心跳。")
+        }
+        MessageKey::AnomalyDetected => (
+            "[异常] {client} 的 {metric}",
+            "{metric} 当前为 {value}，偏离该时段通常值 {mean} 达 {sigmas} 个标准差。",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[已恢复] {client} 的 {metric} 异常",
+            "{metric} 当前为 {value}，已回到通常范围。",
+        ),
+        MessageKey::NewIpLogin => (
+            "[安全] {user} 的新登录 IP",
+            "{user} 从 {ip} 登录，该地址最近 {days} 天未使用过。\nUser-Agent：{user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[安全] 登录失败",
+            "最近 {minutes} 分钟内登录失败 {count} 次，最近一次以 {user} 从 {ip} 登录。",
+        ),
+        MessageKey::SecurityEvent => ("[安全] {event}", "{user} 从 {ip} 触发 {event}。"),
+    };
+    Some(entry)
+}
+
+/// Russian templates as `(title, body)`.
+fn ru(key: MessageKey) -> Option<(&'static str, &'static str)> {
+    let entry = match key {
+        MessageKey::ClientOffline => ("[НЕ В СЕТИ] {client}", "{client} не в сети."),
+        MessageKey::ClientOnline => ("[В СЕТИ] {client}", "{client} снова в сети."),
+        MessageKey::ThresholdFired => (
+            "[{severity}] {metric}: оповещение для {client}",
+            "{metric}: {value}, выше порога {threshold}.",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[РЕШЕНО] {metric}: оповещение для {client}",
+            "{metric}: {value}, снова ниже порога {threshold}.",
+        ),
+        MessageKey::TrafficWarning => (
+            "[ТРАФИК] {client}",
+            "{client} использовал {used} из лимита трафика {limit} ({percent}%).",
+        ),
+        MessageKey::CertExpiry => (
+            "[СЕРТИФИКАТ] {target}",
+            "Сертификат для {target} истекает через {days} дн.",
+        ),
+        MessageKey::DailyDigest => ("[СВОДКА] Ежедневный отчёт {date}", DIGEST_BODY_RU),
+        MessageKey::WeeklyDigest => ("[СВОДКА] Еженедельный отчёт {date}", DIGEST_BODY_RU),
+        MessageKey::ArchiveFailed => (
+            "[АРХИВ] Ошибка архивации записей",
+            "Не удалось заархивировать {count} клиенто-дней, данные сохранены:\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[СМЕНА IP] {client}",
+            "{client} отправляет данные с адреса {ip}, которого нет среди известных адресов ({known}).",
+        ),
+        MessageKey::MonitorUp => (
+            "[РАБОТАЕТ] {monitor}",
+            "{monitor} работает: пройдено {up} из {total} проверок.",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[ЧАСТИЧНЫЙ СБОЙ] {monitor}",
+            "{monitor} работает частично: пройдено {up} из {total} проверок.",
+        ),
+        MessageKey::MonitorDown => (
+            "[НЕДОСТУПЕН] {monitor}",
+            "{monitor} недоступен: пройдено {up} из {total} проверок.",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[НЕТ СИГНАЛА] {heartbeat}",
+            "От {heartbeat} нет сигнала уже {minutes} мин.",
+        ),
+        MessageKey::HeartbeatRecovered => (
+            "[СИГНАЛ ВОССТАНОВЛЕН] {heartbeat}",
+            "{heartbeat} снова отправляет сигналы.",
+        ),
+        MessageKey::AnomalyDetected => (
+            "[АНОМАЛИЯ] {metric} на {client}",
+            "{metric}: {value}, на {sigmas} стандартных отклонений от обычного {mean} в это время.",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[РЕШЕНО] {metric}: аномалия на {client}",
+            "{metric}: {value}, снова в обычных пределах.",
+        ),
+        MessageKey::NewIpLogin => (
+            "[БЕЗОПАСНОСТЬ] Новый IP входа для {user}",
+            "{user} вошёл с {ip}, который не использовался последние {days} дн.\nUser agent: {user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[БЕЗОПАСНОСТЬ] Неудачные входы",
+            "{count} неудачных входов за последние {minutes} мин., последний как {user} с {ip}.",
+        ),
+        MessageKey::SecurityEvent => ("[БЕЗОПАСНОСТЬ] {event}", "{event} для {user} с {ip}."),
+    };
+    Some(entry)
+}
+
+/// German templates as `(title, body)`.
+fn de(key: MessageKey) -> Option<(&'static str, &'static str)> {
+    let entry = match key {
+        MessageKey::ClientOffline => ("[OFFLINE] {client}", "{client} ist offline."),
+        MessageKey::ClientOnline => ("[ONLINE] {client}", "{client} ist wieder online."),
+        MessageKey::ThresholdFired => (
+            "[{severity}] Alarm {metric} auf {client}",
+            "{metric} liegt bei {value}, über dem Schwellenwert {threshold}.",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[BEHOBEN] Alarm {metric} auf {client}",
+            "{metric} liegt bei {value}, wieder unter dem Schwellenwert {threshold}.",
+        ),
+        MessageKey::TrafficWarning => (
+            "[TRAFFIC] {client}",
+            "{client} hat {used} von {limit} Traffic-Limit verbraucht ({percent} %).",
+        ),
+        MessageKey::CertExpiry => (
+            "[ZERTIFIKAT] {target}",
+            "Das Zertifikat für {target} läuft in {days} Tagen ab.",
+        ),
+        MessageKey::DailyDigest => ("[ÜBERSICHT] Tagesbericht {date}", DIGEST_BODY_DE),
+        MessageKey::WeeklyDigest => ("[ÜBERSICHT] Wochenbericht {date}", DIGEST_BODY_DE),
+        MessageKey::ArchiveFailed => (
+            "[ARCHIV] Archivierung fehlgeschlagen",
+            "{count} Client-Tage konnten nicht archiviert werden und wurden behalten:\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[IP-WECHSEL] {client}",
+            "{client} meldet sich von {ip}, keiner seiner bekannten Adressen ({known}).",
+        ),
+        MessageKey::MonitorUp => (
+            "[VERFÜGBAR] {monitor}",
+            "{monitor} ist verfügbar: {up} von {total} Prüfungen erfolgreich.",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[EINGESCHRÄNKT] {monitor}",
+            "{monitor} ist eingeschränkt: {up} von {total} Prüfungen erfolgreich.",
+        ),
+        MessageKey::MonitorDown => (
+            "[AUSGEFALLEN] {monitor}",
+            "{monitor} ist ausgefallen: {up} von {total} Prüfungen erfolgreich.",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[AUSGEBLIEBEN] {heartbeat}",
+            "Seit {minutes} Minuten kein Heartbeat von {heartbeat}.",
+        ),
+        MessageKey::HeartbeatRecovered => (
+            "[WIEDERHERGESTELLT] {heartbeat}",
+            "{heartbeat} sendet wieder Heartbeats.",
+        ),
+        MessageKey::AnomalyDetected => (
+            "[ANOMALIE] {metric} auf {client}",
+            "{metric} liegt bei {value}, {sigmas} Standardabweichungen vom üblichen Wert {mean} zu dieser Stunde.",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[BEHOBEN] Anomalie {metric} auf {client}",
+            "{metric} liegt bei {value}, wieder im üblichen Bereich.",
+        ),
+        MessageKey::NewIpLogin => (
+            "[SICHERHEIT] Neue Login-IP für {user}",
+            "{user} hat sich von {ip} angemeldet, die in den letzten {days} Tagen nicht verwendet wurde.\nUser-Agent: {user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[SICHERHEIT] Fehlgeschlagene Anmeldungen",
+            "{count} fehlgeschlagene Anmeldungen in den letzten {minutes} Minuten, zuletzt als {user} von {ip}.",
+        ),
+        MessageKey::SecurityEvent => ("[SICHERHEIT] {event}", "{event} für {user} von {ip}."),
+    };
+    Some(entry)
+}
+
+/// French templates as `(title, body)`.
+fn fr(key: MessageKey) -> Option<(&'static str, &'static str)> {
+    let entry = match key {
+        MessageKey::ClientOffline => ("[HORS LIGNE] {client}", "{client} est hors ligne."),
+        MessageKey::ClientOnline => ("[EN LIGNE] {client}", "{client} est de nouveau en ligne."),
+        MessageKey::ThresholdFired => (
+            "[{severity}] Alerte {metric} sur {client}",
+            "{metric} vaut {value}, au-dessus du seuil {threshold}.",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[RÉSOLU] Alerte {metric} sur {client}",
+            "{metric} vaut {value}, de nouveau sous le seuil {threshold}.",
+        ),
+        MessageKey::TrafficWarning => (
+            "[TRAFIC] {client}",
+            "{client} a consommé {used} sur sa limite de trafic de {limit} ({percent} %).",
+        ),
+        MessageKey::CertExpiry => (
+            "[CERTIFICAT] {target}",
+            "Le certificat de {target} expire dans {days} jours.",
+        ),
+        MessageKey::DailyDigest => ("[RÉSUMÉ] Rapport quotidien du {date}", DIGEST_BODY_FR),
+        MessageKey::WeeklyDigest => ("[RÉSUMÉ] Rapport hebdomadaire du {date}", DIGEST_BODY_FR),
+        MessageKey::ArchiveFailed => (
+            "[ARCHIVE] Échec de l'archivage des relevés",
+            "{count} jours-clients n'ont pas pu être archivés et ont été conservés :\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[CHANGEMENT D'IP] {client}",
+            "{client} envoie des rapports depuis {ip}, qui ne fait pas partie de ses adresses connues ({known}).",
+        ),
+        MessageKey::MonitorUp => (
+            "[DISPONIBLE] {monitor}",
+            "{monitor} est disponible : {up} vérifications sur {total} réussies.",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[DÉGRADÉ] {monitor}",
+            "{monitor} est dégradé : {up} vérifications sur {total} réussies.",
+        ),
+        MessageKey::MonitorDown => (
+            "[INDISPONIBLE] {monitor}",
+            "{monitor} est indisponible : {up} vérifications sur {total} réussies.",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[MANQUÉ] {heartbeat}",
+            "Aucun signal de vie de {heartbeat} depuis {minutes} minutes.",
+        ),
+        MessageKey::HeartbeatRecovered => (
+            "[RÉTABLI] {heartbeat}",
+            "{heartbeat} envoie de nouveau des signaux de vie.",
+        ),
+        MessageKey::AnomalyDetected => (
+            "[ANOMALIE] {metric} sur {client}",
+            "{metric} vaut {value}, à {sigmas} écarts-types de sa valeur habituelle {mean} à cette heure.",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[RÉSOLU] Anomalie {metric} sur {client}",
+            "{metric} vaut {value}, de retour dans sa plage habituelle.",
+        ),
+        MessageKey::NewIpLogin => (
+            "[SÉCURITÉ] Nouvelle IP de connexion pour {user}",
+            "{user} s'est connecté depuis {ip}, inutilisée ces {days} derniers jours.\nUser agent : {user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[SÉCURITÉ] Échecs de connexion",
+            "{count} échecs de connexion ces {minutes} dernières minutes, le dernier en tant que {user} depuis {ip}.",
+        ),
+        MessageKey::SecurityEvent => ("[SÉCURITÉ] {event}", "{event} pour {user} depuis {ip}."),
+    };
+    Some(entry)
+}
+
+/// Japanese templates as `(title, body)`.
+fn ja(key: MessageKey) -> Option<(&'static str, &'static str)> {
+    let entry = match key {
+        MessageKey::ClientOffline => (
+            "[オフライン] {client}",
+            "{client} がオフラインになりました。",
+        ),
+        MessageKey::ClientOnline => (
+            "[オンライン] {client}",
+            "{client} がオンラインに復帰しました。",
+        ),
+        MessageKey::ThresholdFired => (
+            "[{severity}] {client} の {metric} アラート",
+            "{metric} が {value} で、しきい値 {threshold} を超えています。",
+        ),
+        MessageKey::ThresholdRecovered => (
+            "[解決] {client} の {metric} アラート",
+            "{metric} が {value} で、しきい値 {threshold} を下回りました。",
+        ),
+        MessageKey::TrafficWarning => (
+            "[トラフィック] {client}",
+            "{client} はトラフィック上限 {limit} のうち {used} を使用しました（{percent}%）。",
+        ),
+        MessageKey::CertExpiry => (
+            "[証明書] {target}",
+            "{target} の証明書はあと {days} 日で期限切れになります。",
+        ),
+        MessageKey::DailyDigest => ("[サマリー] 日次レポート {date}", DIGEST_BODY_JA),
+        MessageKey::WeeklyDigest => ("[サマリー] 週次レポート {date}", DIGEST_BODY_JA),
+        MessageKey::ArchiveFailed => (
+            "[アーカイブ] レコードのアーカイブに失敗しました",
+            "{count} 件のクライアント日をアーカイブできなかったため、保持しています：\n{errors}",
+        ),
+        MessageKey::IpChanged => (
+            "[IP 変更] {client}",
+            "{client} が記録済みのアドレス（{known}）以外の {ip} から報告しています。",
+        ),
+        MessageKey::MonitorUp => (
+            "[正常] {monitor}",
+            "{monitor} は正常です：{total} 件中 {up} 件のチェックが成功しています。",
+        ),
+        MessageKey::MonitorDegraded => (
+            "[一部障害] {monitor}",
+            "{monitor} は一部障害中です：{total} 件中 {up} 件のチェックが成功しています。",
+        ),
+        MessageKey::MonitorDown => (
+            "[停止] {monitor}",
+            "{monitor} は停止しています：{total} 件中 {up} 件のチェックが成功しています。",
+        ),
+        MessageKey::HeartbeatMissed => (
+            "[ハートビート途絶] {heartbeat}",
+            "{heartbeat} から {minutes} 分間ハートビートがありません。",
+        ),
+        MessageKey::HeartbeatRecovered => (
+            "[ハートビート復旧] {heartbeat}",
+            "{heartbeat} からのハートビートが再開しました。",
+        ),
+        MessageKey::AnomalyDetected => (
+            "[異常] {client} の {metric}",
+            "{metric} が {value} で、この時間帯の通常値 {mean} から標準偏差 {sigmas} 個分外れています。",
+        ),
+        MessageKey::AnomalyRecovered => (
+            "[解決] {client} の {metric} 異常",
+            "{metric} が {value} で、通常の範囲に戻りました。",
+        ),
+        MessageKey::NewIpLogin => (
+            "[セキュリティ] {user} の新しいログイン IP",
+            "{user} が過去 {days} 日間使われていない {ip} からログインしました。\nユーザーエージェント：{user_agent}",
+        ),
+        MessageKey::FailedLogins => (
+            "[セキュリティ] ログイン失敗",
+            "過去 {minutes} 分間にログインが {count} 回失敗しました。最新は {user} として {ip} から。",
+        ),
+        MessageKey::SecurityEvent => ("[セキュリティ] {event}", "{ip} から {user} の {event}。"),
+    };
+    Some(entry)
+}
+
+/// Locales with a message catalog, in the column order of the label tables.
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "zh", "ru", "de", "fr", "ja"];
+
+/// Language part of a locale (`zh` for `zh-CN`), lowercased.
+fn language(locale: &str) -> String {
+    locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Whether `locale` has a message catalog.
+pub fn is_supported(locale: &str) -> bool {
+    SUPPORTED_LOCALES.contains(&language(locale).as_str())
+}
+
+/// Column of `locale` in the label tables; English for unknown locales.
+fn label_index(locale: &str) -> usize {
+    let language = language(locale);
+    SUPPORTED_LOCALES
+        .iter()
+        .position(|l| *l == language)
+        .unwrap_or(0)
+}
+
+/// Display names of alert metrics, in [`SUPPORTED_LOCALES`] order.
+fn metric_labels(metric: &str) -> Option<[&'static str; 6]> {
+    let labels = match metric {
+        "cpu" => [
+            "CPU usage",
+            "CPU 使用率",
+            "Загрузка CPU",
+            "CPU-Auslastung",
+            "Utilisation CPU",
+            "CPU 使用率",
+        ],
+        "gpu" => [
+            "GPU usage",
+            "GPU 使用率",
+            "Загрузка GPU",
+            "GPU-Auslastung",
+            "Utilisation GPU",
+            "GPU 使用率",
+        ],
+        "ram_pct" => [
+            "Memory usage",
+            "内存使用率",
+            "Использование памяти",
+            "Speicherauslastung",
+            "Utilisation mémoire",
+            "メモリ使用率",
+        ],
+        "swap_pct" => [
+            "Swap usage",
+            "交换分区使用率",
+            "Использование swap",
+            "Swap-Auslastung",
+            "Utilisation du swap",
+            "スワップ使用率",
+        ],
+        "disk_pct" => [
+            "Disk usage",
+            "磁盘使用率",
+            "Заполнение диска",
+            "Festplattenbelegung",
+            "Utilisation disque",
+            "ディスク使用率",
+        ],
+        "load" => [
+            "Load (1 min)",
+            "负载（1 分钟）",
+            "Нагрузка (1 мин)",
+            "Last (1 Min.)",
+            "Charge (1 min)",
+            "負荷（1 分）",
+        ],
+        "load5" => [
+            "Load (5 min)",
+            "负载（5 分钟）",
+            "Нагрузка (5 мин)",
+            "Last (5 Min.)",
+            "Charge (5 min)",
+            "負荷（5 分）",
+        ],
+        "load15" => [
+            "Load (15 min)",
+            "负载（15 分钟）",
+            "Нагрузка (15 мин)",
+            "Last (15 Min.)",
+            "Charge (15 min)",
+            "負荷（15 分）",
+        ],
+        "load15_per_core" => [
+            "Load per core (15 min)",
+            "每核负载（15 分钟）",
+            "Нагрузка на ядро (15 мин)",
+            "Last pro Kern (15 Min.)",
+            "Charge par cœur (15 min)",
+            "コアあたりの負荷（15 分）",
+        ],
+        "temp" => [
+            "Temperature",
+            "温度",
+            "Температура",
+            "Temperatur",
+            "Température",
+            "温度",
+        ],
+        "net_in" => [
+            "Inbound traffic",
+            "入站流量",
+            "Входящий трафик",
+            "Eingehender Traffic",
+            "Trafic entrant",
+            "受信トラフィック",
+        ],
+        "net_out" => [
+            "Outbound traffic",
+            "出站流量",
+            "Исходящий трафик",
+            "Ausgehender Traffic",
+            "Trafic sortant",
+            "送信トラフィック",
+        ],
+        "process" => [
+            "Processes",
+            "进程数",
+            "Процессы",
+            "Prozesse",
+            "Processus",
+            "プロセス数",
+        ],
+        "connections" => [
+            "Connections",
+            "连接数",
+            "Соединения",
+            "Verbindungen",
+            "Connexions",
+            "接続数",
+        ],
+        "fd_pct" => [
+            "File descriptor usage",
+            "文件描述符使用率",
+            "Использование дескрипторов",
+            "Dateideskriptor-Auslastung",
+            "Utilisation des descripteurs",
+            "ファイルディスクリプタ使用率",
+        ],
+        "inode_pct" => [
+            "Inode usage",
+            "inode 使用率",
+            "Использование inode",
+            "Inode-Auslastung",
+            "Utilisation des inodes",
+            "inode 使用率",
+        ],
+        "stale" => [
+            "Stale",
+            "长时间离线",
+            "Давно не в сети",
+            "Lange offline",
+            "Hors ligne prolongé",
+            "長時間オフライン",
+        ],
+        "clock_offset_ms" => [
+            "Clock offset (ms)",
+            "时钟偏移（毫秒）",
+            "Смещение часов (мс)",
+            "Uhrenabweichung (ms)",
+            "Décalage d'horloge (ms)",
+            "時刻のずれ（ms）",
+        ],
+        "tcp_established" => [
+            "TCP ESTABLISHED",
+            "TCP ESTABLISHED",
+            "TCP ESTABLISHED",
+            "TCP ESTABLISHED",
+            "TCP ESTABLISHED",
+            "TCP ESTABLISHED",
+        ],
+        "tcp_syn_recv" => [
+            "TCP SYN_RECV",
+            "TCP SYN_RECV",
+            "TCP SYN_RECV",
+            "TCP SYN_RECV",
+            "TCP SYN_RECV",
+            "TCP SYN_RECV",
+        ],
+        "tcp_time_wait" => [
+            "TCP TIME_WAIT",
+            "TCP TIME_WAIT",
+            "TCP TIME_WAIT",
+            "TCP TIME_WAIT",
+            "TCP TIME_WAIT",
+            "TCP TIME_WAIT",
+        ],
+        "tcp_close_wait" => [
+            "TCP CLOSE_WAIT",
+            "TCP CLOSE_WAIT",
+            "TCP CLOSE_WAIT",
+            "TCP CLOSE_WAIT",
+            "TCP CLOSE_WAIT",
+            "TCP CLOSE_WAIT",
+        ],
+        _ => return None,
+    };
+    Some(labels)
+}
+
+/// Alert severity labels, in [`SUPPORTED_LOCALES`] order.
+fn severity_labels(severity: &str) -> Option<[&'static str; 6]> {
+    let labels = match severity {
+        "warning" => [
+            "WARNING",
+            "警告",
+            "ПРЕДУПРЕЖДЕНИЕ",
+            "WARNUNG",
+            "AVERTISSEMENT",
+            "警告",
+        ],
+        "critical" => [
+            "CRITICAL",
+            "严重",
+            "КРИТИЧНО",
+            "KRITISCH",
+            "CRITIQUE",
+            "重大",
+        ],
+        _ => return None,
+    };
+    Some(labels)
+}
+
+/// Date formats, in [`SUPPORTED_LOCALES`] order.
+const DATE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d",
+    "%Y年%m月%d日",
+    "%d.%m.%Y",
+    "%d.%m.%Y",
+    "%d/%m/%Y",
+    "%Y年%m月%d日",
+];
+
+/// Localize the `metric`, `severity` and `date` params.
+fn localize_param(index: usize, name: &str, value: &str) -> String {
+    match name {
+        "metric" => {
+            metric_labels(value).map_or_else(|| value.to_string(), |l| l[index].to_string())
+        }
+        "severity" => {
+            severity_labels(value).map_or_else(|| value.to_uppercase(), |l| l[index].to_string())
+        }
+        "date" => NaiveDate::parse_from_str(value, "%Y-%m-%d").map_or_else(
+            |_| value.to_string(),
+            |date| date.format(DATE_FORMATS[index]).to_string(),
+        ),
+        _ => value.to_string(),
+    }
+}
+
+/// Look up templates for a locale, falling back to English.
+fn templates(locale: &str, key: MessageKey) -> (&'static str, &'static str) {
+    let localized = match language(locale).as_str() {
+        "zh" => zh_cn(key),
+        "ru" => ru(key),
+        "de" => de(key),
+        "fr" => fr(key),
+        "ja" => ja(key),
+        _ => None,
+    };
+    localized.unwrap_or_else(|| en(key))
+}
+
+/// Replace `{name}` placeholders with their parameter values.
+fn interpolate(template: &str, params: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in params {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// Render a message as `(title, body)` in the given locale.
+pub fn render(locale: &str, key: MessageKey, params: &[(&str, String)]) -> (String, String) {
+    let index = label_index(locale);
+    let params: Vec<(&str, String)> = params
+        .iter()
+        .map(|(name, value)| (*name, localize_param(index, name, value)))
+        .collect();
+    let (title, body) = templates(locale, key);
+    (interpolate(title, &params), interpolate(body, &params))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    const KEYS: &[MessageKey] = &[
+        MessageKey::ClientOffline,
+        MessageKey::ClientOnline,
+        MessageKey::ThresholdFired,
+        MessageKey::ThresholdRecovered,
+        MessageKey::TrafficWarning,
+        MessageKey::CertExpiry,
+        MessageKey::DailyDigest,
+        MessageKey::WeeklyDigest,
+        MessageKey::ArchiveFailed,
+        MessageKey::IpChanged,
+        MessageKey::MonitorUp,
+        MessageKey::MonitorDegraded,
+        MessageKey::MonitorDown,
+        MessageKey::HeartbeatMissed,
+        MessageKey::HeartbeatRecovered,
+        MessageKey::AnomalyDetected,
+        MessageKey::AnomalyRecovered,
+        MessageKey::NewIpLogin,
+        MessageKey::FailedLogins,
+        MessageKey::SecurityEvent,
+    ];
+
+    /// `{name}` placeholders of a template.
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn interpolates_params() {
+        let params = [
+            ("client", "web-1".to_string()),
+            ("metric", "cpu".to_string()),
+            ("severity", "critical".to_string()),
+            ("value", "97%".to_string()),
+            ("threshold", "90%".to_string()),
+        ];
+        let (title, body) = render("en", MessageKey::ThresholdFired, &params);
+        assert_eq!(title, "[CRITICAL] CPU usage alert on web-1");
+        assert_eq!(body, "CPU usage is 97%, above threshold 90%.");
+
+        let (title, _) = render("de", MessageKey::ThresholdFired, &params);
+        assert_eq!(title, "[KRITISCH] Alarm CPU-Auslastung auf web-1");
+
+        // Unknown values are passed through, missing params left in place
+        let (title, body) = render(
+            "en",
+            MessageKey::ThresholdFired,
+            &[("metric", "custom".into()), ("severity", "minor".into())],
+        );
+        assert_eq!(title, "[MINOR] custom alert on {client}");
+        assert!(body.contains("{value}"), "{}", body);
+    }
+
+    #[test]
+    fn interpolates_status_traffic_and_cert_params() {
+        let client = [("client", "web-1".to_string())];
+        assert_eq!(
+            render("en", MessageKey::ClientOffline, &client),
+            ("[OFFLINE] web-1".into(), "web-1 has gone offline.".into())
+        );
+        assert_eq!(
+            render("en", MessageKey::ClientOnline, &client),
+            ("[ONLINE] web-1".into(), "web-1 is back online.".into())
+        );
+        assert_eq!(
+            render("zh-CN", MessageKey::ClientOnline, &client).1,
+            "web-1 已恢复在线。"
+        );
+
+        let traffic = [
+            ("client", "web-1".to_string()),
+            ("used", "850.0 GiB".to_string()),
+            ("limit", "1000.0 GiB".to_string()),
+            ("percent", "85.0".to_string()),
+        ];
+        let (title, body) = render("en", MessageKey::TrafficWarning, &traffic);
+        assert_eq!(title, "[TRAFFIC] web-1");
+        assert_eq!(
+            body,
+            "web-1 has used 850.0 GiB of its 1000.0 GiB traffic limit (85.0%)."
+        );
+        assert_eq!(
+            render("ja", MessageKey::TrafficWarning, &traffic).1,
+            "web-1 はトラフィック上限 1000.0 GiB のうち 850.0 GiB を使用しました（85.0%）。"
+        );
+
+        let cert = [
+            ("target", "example.com".to_string()),
+            ("days", "7".to_string()),
+        ];
+        let (title, body) = render("en", MessageKey::CertExpiry, &cert);
+        assert_eq!(title, "[CERT] example.com");
+        assert_eq!(body, "The certificate for example.com expires in 7 days.");
+        assert_eq!(
+            render("de", MessageKey::CertExpiry, &cert).1,
+            "Das Zertifikat für example.com läuft in 7 Tagen ab."
+        );
+    }
+
+    #[test]
+    fn localizes_dates() {
+        let params = [("date", "2026-10-16".to_string())];
+        assert_eq!(interpolate("{date}", &params), "2026-10-16");
+        let index = label_index("zh-CN");
+        assert_eq!(
+            localize_param(index, "date", "2026-10-16"),
+            "2026年10月16日"
+        );
+        assert_eq!(localize_param(index, "date", "not a date"), "not a date");
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let params = [("client", "web-1".to_string())];
+        let english = render("en", MessageKey::ClientOffline, &params);
+        assert_eq!(english.0, "[OFFLINE] web-1");
+        for locale in ["", "xx", "pt-BR"] {
+            assert!(!is_supported(locale));
+            assert_eq!(render(locale, MessageKey::ClientOffline, &params), english);
+        }
+
+        // Regions and case are ignored
+        for locale in ["zh", "zh-CN", "ZH_cn"] {
+            assert!(is_supported(locale));
+            assert_eq!(
+                render(locale, MessageKey::ClientOffline, &params).0,
+                "[离线] web-1"
+            );
+        }
+    }
+
+    #[test]
+    fn catalogs_use_the_english_placeholders() {
+        for &key in KEYS {
+            let (en_title, en_body) = en(key);
+            let expected: BTreeSet<&str> = placeholders(en_title)
+                .union(&placeholders(en_body))
+                .copied()
+                .collect();
+            for locale in SUPPORTED_LOCALES {
+                let (title, body) = templates(locale, key);
+                assert!(!title.is_empty() && !body.is_empty());
+                let used: BTreeSet<&str> = placeholders(title)
+                    .union(&placeholders(body))
+                    .copied()
+                    .collect();
+                assert_eq!(used, expected, "{} {:?}", locale, key);
+            }
+        }
+    }
+}
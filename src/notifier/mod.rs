@@ -2,9 +2,15 @@
 //!
 //! Provides notification sending capabilities for various providers.
 
-use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+pub mod i18n;
+pub mod routing;
+pub mod validation;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::{error, info, warn};
+
+use crate::db::Notification;
 
 /// Notification provider types.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,10 +22,28 @@ pub enum NotificationProvider {
 }
 
 /// Telegram notification config.
+///
+/// `chat_ids` also accepts a single string, and the older `chat_id` key.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelegramConfig {
     pub bot_token: String,
-    pub chat_id: String,
+    #[serde(alias = "chat_id", deserialize_with = "one_or_many")]
+    pub chat_ids: Vec<String>,
+}
+
+/// Deserialize a string or an array of strings.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged, expecting = "a chat ID or an array of chat IDs")]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(id) => vec![id],
+        OneOrMany::Many(ids) => ids,
+    })
 }
 
 /// Email notification config.
@@ -41,6 +65,24 @@ pub struct WebhookConfig {
     pub headers: std::collections::HashMap<String, String>,
 }
 
+/// Deserialize the config of a provider, failing when it cannot be used
+/// to send. Nothing is sent.
+pub fn parse_config(provider: &str, config: &serde_json::Value) -> Result<()> {
+    match provider {
+        "telegram" => {
+            serde_json::from_value::<TelegramConfig>(config.clone())?;
+        }
+        "email" => {
+            serde_json::from_value::<EmailConfig>(config.clone())?;
+        }
+        "webhook" => {
+            serde_json::from_value::<WebhookConfig>(config.clone())?;
+        }
+        _ => bail!("unknown provider {}", provider),
+    }
+    Ok(())
+}
+
 /// Send a notification.
 pub async fn send_notification(
     provider: &str,
@@ -68,8 +110,41 @@ pub async fn send_notification(
     Ok(())
 }
 
-/// Send Telegram notification.
+/// Send a server-generated notification rendered in the notification's
+/// locale.
+///
+/// The locale is the notification's `notification_locale`, else a `locale`
+/// key in its config, else `default_locale`.
+pub async fn send_message(
+    notification: &Notification,
+    default_locale: &str,
+    key: i18n::MessageKey,
+    params: &[(&str, String)],
+) -> Result<()> {
+    let locale = notification
+        .notification_locale
+        .as_deref()
+        .or_else(|| notification.config.get("locale").and_then(|v| v.as_str()))
+        .unwrap_or(default_locale);
+    let (title, message) = i18n::render(locale, key, params);
+    send_notification(
+        &notification.provider,
+        &notification.config,
+        &title,
+        &message,
+    )
+    .await
+}
+
+/// Send Telegram notification to every configured chat.
+///
+/// A failed chat does not stop the others; an error is returned only if no
+/// chat received the message.
 async fn send_telegram(config: &TelegramConfig, title: &str, message: &str) -> Result<()> {
+    if config.chat_ids.is_empty() {
+        bail!("No Telegram chat IDs configured");
+    }
+
     let url = format!(
         "https://api.telegram.org/bot{}/sendMessage",
         config.bot_token
@@ -78,22 +153,37 @@ async fn send_telegram(config: &TelegramConfig, title: &str, message: &str) -> R
     let text = format!("*{}*\n\n{}", title, message);
 
     let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
-        .json(&serde_json::json!({
-            "chat_id": config.chat_id,
-            "text": text,
-            "parse_mode": "Markdown"
-        }))
-        .send()
-        .await?;
+    let mut failures = Vec::new();
+    for chat_id in &config.chat_ids {
+        let result = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "chat_id": chat_id,
+                "text": text,
+                "parse_mode": "Markdown"
+            }))
+            .send()
+            .await;
+
+        match result {
+            Ok(response) if response.status().is_success() => {
+                info!("Telegram notification sent successfully to {}", chat_id);
+            }
+            Ok(response) => failures.push(format!("{}: {}", chat_id, response.status())),
+            Err(e) => failures.push(format!("{}: {}", chat_id, e.without_url())),
+        }
+    }
 
-    if response.status().is_success() {
-        info!("Telegram notification sent successfully");
-    } else {
-        error!(
+    if failures.len() == config.chat_ids.len() {
+        bail!(
             "Failed to send Telegram notification: {}",
-            response.status()
+            failures.join("; ")
+        );
+    }
+    if !failures.is_empty() {
+        warn!(
+            "Failed to send Telegram notification to some chats: {}",
+            failures.join("; ")
         );
     }
 
@@ -0,0 +1,454 @@
+//! Notification routing.
+//!
+//! Resolves which notification providers receive an event for a client:
+//!
+//! 1. Every enabled route whose group/tag matcher and event types match.
+//! 2. If no route matches, the client-specific targets passed by the caller
+//!    (offline notifications for offline events, the rule's notification for
+//!    threshold events).
+//! 3. If there are none, the global `default_notification_id` setting.
+//!
+//! Monitor group and heartbeat events follow the same chain; routes match
+//! them by `group_name` equal to the monitor group or heartbeat name.
+//!
+//! Events for a client or monitor covered by an active silence are not sent.
+
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::i18n::MessageKey;
+use crate::db::{Client, Database, NotificationRoute};
+use crate::error::AppResult;
+use crate::silences;
+
+/// Event types that can be routed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    Offline,
+    Threshold,
+    Traffic,
+    IpChange,
+    Monitor,
+    Heartbeat,
+    Anomaly,
+}
+
+impl EventType {
+    /// All event types.
+    pub const ALL: &'static [EventType] = &[
+        EventType::Offline,
+        EventType::Threshold,
+        EventType::Traffic,
+        EventType::IpChange,
+        EventType::Monitor,
+        EventType::Heartbeat,
+        EventType::Anomaly,
+    ];
+
+    /// Event type name as stored in `notification_routes.event_types`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::Offline => "offline",
+            EventType::Threshold => "threshold",
+            EventType::Traffic => "traffic",
+            EventType::IpChange => "ip_change",
+            EventType::Monitor => "monitor",
+            EventType::Heartbeat => "heartbeat",
+            EventType::Anomaly => "anomaly",
+        }
+    }
+
+    /// Parse an event type from its stored name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|e| e.as_str() == name)
+    }
+}
+
+/// Check whether a route applies to a client and event.
+///
+/// A route with both a group and a tag requires both to match.
+pub fn route_matches(route: &NotificationRoute, client: &Client, event: EventType) -> bool {
+    if !route.event_types.is_empty() && !route.event_types.iter().any(|e| e == event.as_str()) {
+        return false;
+    }
+    if let Some(group) = &route.group_name
+        && client.group_name != *group
+    {
+        return false;
+    }
+    if let Some(tag) = &route.tag
+        && !client.tags.contains(tag)
+    {
+        return false;
+    }
+    route.group_name.is_some() || route.tag.is_some()
+}
+
+/// Check whether a route applies to an event of a monitor group or
+/// heartbeat called `name`.
+///
+/// Only routes with a `group_name` equal to the name and no tag match.
+pub fn route_matches_name(route: &NotificationRoute, event: EventType, name: &str) -> bool {
+    (route.event_types.is_empty() || route.event_types.iter().any(|e| e == event.as_str()))
+        && route.group_name.as_deref() == Some(name)
+        && route.tag.is_none()
+}
+
+/// Pick target notification IDs following the route/client/default chain.
+pub fn select_targets(
+    routes: &[NotificationRoute],
+    client: &Client,
+    event: EventType,
+    client_targets: &[Uuid],
+    default_target: Option<Uuid>,
+) -> Vec<Uuid> {
+    let routed = routes
+        .iter()
+        .filter(|r| r.enabled && route_matches(r, client, event))
+        .map(|r| r.notification_id)
+        .collect();
+    fall_back(routed, client_targets, default_target)
+}
+
+/// Use `fallback_targets`, then `default_target`, when no route matched, and
+/// drop duplicates.
+fn fall_back(
+    mut targets: Vec<Uuid>,
+    fallback_targets: &[Uuid],
+    default_target: Option<Uuid>,
+) -> Vec<Uuid> {
+    if targets.is_empty() {
+        targets = fallback_targets.to_vec();
+    }
+    if targets.is_empty() {
+        targets.extend(default_target);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    targets.retain(|id| seen.insert(*id));
+    targets
+}
+
+/// Send an event for a client to every resolved notification provider.
+pub async fn dispatch(
+    db: &Database,
+    client: &Client,
+    event: EventType,
+    client_targets: &[Uuid],
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) -> AppResult<()> {
+    if silences::client_silenced(&silences::active(db).await?, client) {
+        info!(
+            client_id = %client.id,
+            client_name = %client.name,
+            event = event.as_str(),
+            "Notification silenced"
+        );
+        return Ok(());
+    }
+
+    let routes = db.get_enabled_notification_routes().await?;
+    let default_target = db
+        .get_setting("default_notification_id")
+        .await?
+        .and_then(|v| serde_json::from_value::<Uuid>(v).ok());
+
+    let targets = select_targets(&routes, client, event, client_targets, default_target);
+    send(db, &targets, event, locale, key, params).await
+}
+
+/// Send a monitor group status change to every resolved notification
+/// provider, with the group's own notification as the fallback target.
+///
+/// The change is not sent while a silence covers one of `task_ids`.
+pub async fn dispatch_monitor(
+    db: &Database,
+    monitor_name: &str,
+    task_ids: &[Uuid],
+    group_targets: &[Uuid],
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) -> AppResult<()> {
+    if silences::tasks_silenced(&silences::active(db).await?, task_ids) {
+        info!(monitor = monitor_name, "Notification silenced");
+        return Ok(());
+    }
+
+    dispatch_named(
+        db,
+        EventType::Monitor,
+        monitor_name,
+        group_targets,
+        locale,
+        key,
+        params,
+    )
+    .await
+}
+
+/// Send a heartbeat state change to every resolved notification provider,
+/// with the heartbeat's own notification as the fallback target.
+pub async fn dispatch_heartbeat(
+    db: &Database,
+    heartbeat_name: &str,
+    heartbeat_targets: &[Uuid],
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) -> AppResult<()> {
+    dispatch_named(
+        db,
+        EventType::Heartbeat,
+        heartbeat_name,
+        heartbeat_targets,
+        locale,
+        key,
+        params,
+    )
+    .await
+}
+
+/// Send an event of a monitor group or heartbeat called `name`.
+async fn dispatch_named(
+    db: &Database,
+    event: EventType,
+    name: &str,
+    fallback_targets: &[Uuid],
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) -> AppResult<()> {
+    let routes = db.get_enabled_notification_routes().await?;
+    let default_target = db
+        .get_setting("default_notification_id")
+        .await?
+        .and_then(|v| serde_json::from_value::<Uuid>(v).ok());
+
+    let routed = routes
+        .iter()
+        .filter(|r| r.enabled && route_matches_name(r, event, name))
+        .map(|r| r.notification_id)
+        .collect();
+    let targets = fall_back(routed, fallback_targets, default_target);
+    send(db, &targets, event, locale, key, params).await
+}
+
+/// Send a message to each enabled notification among `targets`.
+async fn send(
+    db: &Database,
+    targets: &[Uuid],
+    event: EventType,
+    locale: &str,
+    key: MessageKey,
+    params: &[(&str, String)],
+) -> AppResult<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    for notification in db.get_enabled_notifications_by_ids(targets).await? {
+        if let Err(e) = super::send_message(&notification, locale, key, params).await {
+            error!(
+                "Failed to send {} notification via {}: {}",
+                event.as_str(),
+                notification.name,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn client(group: &str, tags: &[&str]) -> Client {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "token": "",
+            "name": "web-1",
+            "cpu_name": "",
+            "arch": "",
+            "cpu_cores": 0,
+            "os": "",
+            "kernel_version": "",
+            "gpu_name": "",
+            "virtualization": "",
+            "region": "",
+            "remark": "",
+            "public_remark": "",
+            "mem_total": 0,
+            "swap_total": 0,
+            "disk_total": 0,
+            "version": "",
+            "weight": 0,
+            "group_name": group,
+            "tags": tags,
+            "hidden": false,
+            "traffic_limit": 0,
+            "traffic_limit_type": "sum",
+            "online": true,
+            "display_color": "",
+            "display_icon": "",
+            "alert_on_ip_change": false,
+            "builtin": false,
+            "archived": false,
+            "manually_overridden": false,
+        }))
+        .unwrap()
+    }
+
+    fn route(group: Option<&str>, tag: Option<&str>, event_types: &[&str]) -> NotificationRoute {
+        NotificationRoute {
+            id: Uuid::new_v4(),
+            name: "route".into(),
+            group_name: group.map(String::from),
+            tag: tag.map(String::from),
+            event_types: event_types.iter().map(|e| e.to_string()).collect(),
+            notification_id: Uuid::new_v4(),
+            enabled: true,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn route_matchers() {
+        let web = client("web", &["prod", "eu"]);
+        assert!(route_matches(
+            &route(Some("web"), None, &[]),
+            &web,
+            EventType::Offline
+        ));
+        assert!(route_matches(
+            &route(None, Some("eu"), &[]),
+            &web,
+            EventType::Offline
+        ));
+        assert!(!route_matches(
+            &route(Some("db"), None, &[]),
+            &web,
+            EventType::Offline
+        ));
+        assert!(!route_matches(
+            &route(None, Some("us"), &[]),
+            &web,
+            EventType::Offline
+        ));
+        // Group and tag must both match
+        assert!(route_matches(
+            &route(Some("web"), Some("prod"), &[]),
+            &web,
+            EventType::Offline
+        ));
+        assert!(!route_matches(
+            &route(Some("web"), Some("us"), &[]),
+            &web,
+            EventType::Offline
+        ));
+        // Event types narrow the route; an empty list matches every event
+        let offline = route(Some("web"), None, &["offline", "traffic"]);
+        assert!(route_matches(&offline, &web, EventType::Traffic));
+        assert!(!route_matches(&offline, &web, EventType::Threshold));
+        // A route without a matcher matches nothing
+        assert!(!route_matches(
+            &route(None, None, &[]),
+            &web,
+            EventType::Offline
+        ));
+    }
+
+    #[test]
+    fn routes_take_precedence() {
+        let web = client("web", &["prod"]);
+        let by_group = route(Some("web"), None, &[]);
+        let by_tag = route(None, Some("prod"), &[]);
+        let other = route(Some("db"), None, &[]);
+        let mut disabled = route(Some("web"), None, &[]);
+        disabled.enabled = false;
+        let routes = [by_group.clone(), other, disabled, by_tag.clone()];
+        let client_target = Uuid::new_v4();
+        let default_target = Uuid::new_v4();
+
+        assert_eq!(
+            select_targets(
+                &routes,
+                &web,
+                EventType::Offline,
+                &[client_target],
+                Some(default_target)
+            ),
+            [by_group.notification_id, by_tag.notification_id]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_client_then_default_targets() {
+        let web = client("web", &[]);
+        let routes = [route(Some("db"), None, &[])];
+        let client_targets = [Uuid::new_v4(), Uuid::new_v4()];
+        let default_target = Uuid::new_v4();
+
+        assert_eq!(
+            select_targets(
+                &routes,
+                &web,
+                EventType::Threshold,
+                &client_targets,
+                Some(default_target)
+            ),
+            client_targets
+        );
+        assert_eq!(
+            select_targets(
+                &routes,
+                &web,
+                EventType::Threshold,
+                &[],
+                Some(default_target)
+            ),
+            [default_target]
+        );
+        assert!(select_targets(&routes, &web, EventType::Threshold, &[], None).is_empty());
+    }
+
+    #[test]
+    fn duplicate_targets_are_sent_once() {
+        let web = client("web", &["prod"]);
+        let mut by_tag = route(None, Some("prod"), &[]);
+        let by_group = route(Some("web"), None, &[]);
+        by_tag.notification_id = by_group.notification_id;
+        assert_eq!(
+            select_targets(
+                &[by_group.clone(), by_tag],
+                &web,
+                EventType::Offline,
+                &[],
+                None
+            ),
+            [by_group.notification_id]
+        );
+        let target = Uuid::new_v4();
+        assert_eq!(fall_back(Vec::new(), &[target, target], None), [target]);
+    }
+
+    #[test]
+    fn named_routes() {
+        let monitor = route(Some("api"), None, &["monitor"]);
+        assert!(route_matches_name(&monitor, EventType::Monitor, "api"));
+        assert!(!route_matches_name(&monitor, EventType::Heartbeat, "api"));
+        assert!(!route_matches_name(&monitor, EventType::Monitor, "web"));
+        // Tags never match monitor groups or heartbeats
+        assert!(!route_matches_name(
+            &route(Some("api"), Some("prod"), &[]),
+            EventType::Monitor,
+            "api"
+        ));
+    }
+}
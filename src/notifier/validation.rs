@@ -0,0 +1,311 @@
+//! Static checks of notification configs.
+//!
+//! A malformed config otherwise only shows up when the first alert that
+//! needs it fails to send. Configs are checked when they are saved and by
+//! `POST /api/admin/notifications/validate`; nothing is sent.
+
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{EmailConfig, TelegramConfig, WebhookConfig};
+
+/// A problem found in a notification config.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigProblem {
+    /// Config field the problem is about, e.g. `url` or `chat_ids[1]`.
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Check the config of a provider. Returns every problem found.
+pub fn validate_config(provider: &str, config: &Value) -> Vec<ConfigProblem> {
+    if !config.is_object() {
+        return vec![ConfigProblem::new("config", "must be a JSON object")];
+    }
+    let result = match provider {
+        "telegram" => {
+            parse(config, &["bot_token", "chat_ids|chat_id"]).map(|c| validate_telegram(&c))
+        }
+        "email" => parse(
+            config,
+            &[
+                "smtp_host",
+                "smtp_port",
+                "smtp_user",
+                "smtp_pass",
+                "from_addr",
+                "to_addr",
+            ],
+        )
+        .map(|c| validate_email(&c)),
+        "webhook" => parse(config, &["url"]).map(|c| validate_webhook(&c)),
+        _ => Err(vec![ConfigProblem::new(
+            "provider",
+            format!("unknown provider {}", provider),
+        )]),
+    };
+    result.unwrap_or_else(|problems| problems)
+}
+
+/// Check that the required fields are present, then deserialize the config.
+///
+/// Alternative names of a field are separated by `|`.
+fn parse<T: DeserializeOwned>(config: &Value, required: &[&str]) -> Result<T, Vec<ConfigProblem>> {
+    let missing: Vec<ConfigProblem> = required
+        .iter()
+        .filter(|field| field.split('|').all(|name| config.get(name).is_none()))
+        .map(|field| ConfigProblem::new(field.split('|').next().unwrap_or(field), "is required"))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    serde_json::from_value(config.clone())
+        .map_err(|e| vec![ConfigProblem::new("config", e.to_string())])
+}
+
+fn validate_telegram(config: &TelegramConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    if !is_bot_token(&config.bot_token) {
+        problems.push(ConfigProblem::new(
+            "bot_token",
+            "must look like 123456:ABC-DEF (from @BotFather)",
+        ));
+    }
+    if config.chat_ids.is_empty() {
+        problems.push(ConfigProblem::new("chat_ids", "needs at least one chat ID"));
+    }
+    for (i, chat_id) in config.chat_ids.iter().enumerate() {
+        if !is_chat_id(chat_id) {
+            problems.push(ConfigProblem::new(
+                format!("chat_ids[{}]", i),
+                format!(
+                    "{:?} is neither a numeric chat ID nor an @channel name",
+                    chat_id
+                ),
+            ));
+        }
+    }
+    problems
+}
+
+/// Bot tokens are the numeric bot ID, a colon and the secret.
+fn is_bot_token(token: &str) -> bool {
+    token.split_once(':').is_some_and(|(id, secret)| {
+        !id.is_empty()
+            && id.chars().all(|c| c.is_ascii_digit())
+            && !secret.is_empty()
+            && secret
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Chat IDs are numeric (negative for groups and channels) or `@username`.
+fn is_chat_id(chat_id: &str) -> bool {
+    if let Some(username) = chat_id.strip_prefix('@') {
+        return (5..=32).contains(&username.len())
+            && username.starts_with(|c: char| c.is_ascii_alphabetic())
+            && username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    }
+    let digits = chat_id.strip_prefix('-').unwrap_or(chat_id);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn validate_email(config: &EmailConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    if config.smtp_host.trim().is_empty() {
+        problems.push(ConfigProblem::new("smtp_host", "must not be empty"));
+    }
+    if config.smtp_port == 0 {
+        problems.push(ConfigProblem::new("smtp_port", "must be 1-65535"));
+    }
+    for (field, address) in [
+        ("from_addr", &config.from_addr),
+        ("to_addr", &config.to_addr),
+    ] {
+        if !is_email_address(address) {
+            problems.push(ConfigProblem::new(
+                field,
+                format!("{:?} is not an email address", address),
+            ));
+        }
+    }
+    problems
+}
+
+/// A plausible `local@domain.tld` address.
+fn is_email_address(address: &str) -> bool {
+    address.rsplit_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !address.chars().any(char::is_whitespace)
+    })
+}
+
+fn validate_webhook(config: &WebhookConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    match reqwest::Url::parse(&config.url) {
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => problems.push(ConfigProblem::new(
+            "url",
+            format!("scheme {} is not allowed (use http or https)", url.scheme()),
+        )),
+        Ok(url) if url.host_str().is_none() => {
+            problems.push(ConfigProblem::new("url", "has no host"))
+        }
+        Ok(_) => {}
+        Err(e) => problems.push(ConfigProblem::new(
+            "url",
+            format!("is not a valid URL: {}", e),
+        )),
+    }
+    for (name, value) in &config.headers {
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            problems.push(ConfigProblem::new(
+                format!("headers.{}", name),
+                "is not a valid header name",
+            ));
+        } else if HeaderValue::from_str(value).is_err() {
+            problems.push(ConfigProblem::new(
+                format!("headers.{}", name),
+                "is not a valid header value",
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Fields with problems, in order.
+    fn problem_fields(provider: &str, config: Value) -> Vec<String> {
+        validate_config(provider, &config)
+            .into_iter()
+            .map(|p| p.field)
+            .collect()
+    }
+
+    #[test]
+    fn telegram_configs() {
+        for config in [
+            json!({"bot_token": "123456:ABC-DEF_ghi", "chat_ids": ["-1001234", "@alerts_channel"]}),
+            json!({"bot_token": "123456:ABC", "chat_id": "42"}),
+        ] {
+            assert_eq!(problem_fields("telegram", config), Vec::<String>::new());
+        }
+
+        assert_eq!(
+            problem_fields("telegram", json!({})),
+            ["bot_token", "chat_ids"]
+        );
+        assert_eq!(
+            problem_fields(
+                "telegram",
+                json!({"bot_token": "ABC", "chat_ids": ["42", "chat", "@abc"]})
+            ),
+            ["bot_token", "chat_ids[1]", "chat_ids[2]"]
+        );
+        assert_eq!(
+            problem_fields("telegram", json!({"bot_token": "1:a", "chat_ids": []})),
+            ["chat_ids"]
+        );
+    }
+
+    #[test]
+    fn email_configs() {
+        let valid = json!({
+            "smtp_host": "smtp.example.com",
+            "smtp_port": 587,
+            "smtp_user": "alerts",
+            "smtp_pass": "secret",
+            "from_addr": "alerts@example.com",
+            "to_addr": "ops@example.com",
+        });
+        assert_eq!(problem_fields("email", valid.clone()), Vec::<String>::new());
+
+        let mut invalid = valid.clone();
+        invalid["smtp_host"] = json!(" ");
+        invalid["smtp_port"] = json!(0);
+        invalid["from_addr"] = json!("alerts");
+        invalid["to_addr"] = json!("ops@example.");
+        assert_eq!(
+            problem_fields("email", invalid),
+            ["smtp_host", "smtp_port", "from_addr", "to_addr"]
+        );
+
+        let mut wrong_type = valid;
+        wrong_type["smtp_port"] = json!(70000);
+        assert_eq!(problem_fields("email", wrong_type), ["config"]);
+        assert_eq!(
+            problem_fields("email", json!({"smtp_host": "smtp.example.com"})),
+            [
+                "smtp_port",
+                "smtp_user",
+                "smtp_pass",
+                "from_addr",
+                "to_addr"
+            ]
+        );
+    }
+
+    #[test]
+    fn webhook_configs() {
+        assert_eq!(
+            problem_fields(
+                "webhook",
+                json!({"url": "https://example.com/hook", "headers": {"X-Token": "abc"}})
+            ),
+            Vec::<String>::new()
+        );
+
+        for url in ["ftp://example.com", "not a url", "http://"] {
+            assert_eq!(problem_fields("webhook", json!({ "url": url })), ["url"]);
+        }
+        assert_eq!(
+            problem_fields(
+                "webhook",
+                json!({"url": "https://example.com", "headers": {"Bad Name": "x"}})
+            ),
+            ["headers.Bad Name"]
+        );
+        assert_eq!(
+            problem_fields(
+                "webhook",
+                json!({"url": "https://example.com", "headers": {"X-Token": "a\nb"}})
+            ),
+            ["headers.X-Token"]
+        );
+    }
+
+    #[test]
+    fn unknown_provider_and_non_object() {
+        assert_eq!(problem_fields("pager", json!({})), ["provider"]);
+        assert_eq!(
+            problem_fields("webhook", json!("https://example.com")),
+            ["config"]
+        );
+    }
+}
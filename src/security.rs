@@ -0,0 +1,269 @@
+//! Security events and their notifications.
+//!
+//! Logins, failed logins, password changes and session revocations are
+//! recorded in `security_events`. The `security_alerts` setting picks a
+//! notification and which events send to it:
+//!
+//! ```json
+//! {"notification_id": "...", "new_ip_login": true, "new_ip_days": 30,
+//!  "failed_logins": true, "failed_login_threshold": 10,
+//!  "failed_login_window_minutes": 10, "password_changed": false,
+//!  "session_revoked": false, "api_key_created": false}
+//! ```
+//!
+//! A login notifies only when the user has not signed in from its IP within
+//! `new_ip_days`. Failed logins notify once the count within the window
+//! reaches the threshold; the event that notified is marked, and no other
+//! failure notifies until the window has passed it, so a burst sends one
+//! message.
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::{SecurityEvent, User};
+use crate::error::{AppError, AppResult};
+use crate::notifier::i18n::MessageKey;
+
+/// Kind of security event, stored in `security_events.event_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventType {
+    LoginSuccess,
+    LoginFailed,
+    PasswordChanged,
+    SessionRevoked,
+    ApiKeyCreated,
+}
+
+impl SecurityEventType {
+    /// Type name as stored in `security_events.event_type`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEventType::LoginSuccess => "login_success",
+            SecurityEventType::LoginFailed => "login_failed",
+            SecurityEventType::PasswordChanged => "password_changed",
+            SecurityEventType::SessionRevoked => "session_revoked",
+            SecurityEventType::ApiKeyCreated => "api_key_created",
+        }
+    }
+}
+
+/// Security notification configuration stored in the `security_alerts`
+/// setting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityAlertSettings {
+    pub notification_id: Option<Uuid>,
+    /// Notify on a login from an IP the user has not used recently.
+    #[serde(default = "default_true")]
+    pub new_ip_login: bool,
+    /// Days an IP counts as seen after a login from it.
+    #[serde(default = "default_new_ip_days")]
+    pub new_ip_days: i64,
+    /// Notify on a burst of failed logins.
+    #[serde(default = "default_true")]
+    pub failed_logins: bool,
+    #[serde(default = "default_failed_login_threshold")]
+    pub failed_login_threshold: i64,
+    #[serde(default = "default_failed_login_window_minutes")]
+    pub failed_login_window_minutes: i64,
+    #[serde(default)]
+    pub password_changed: bool,
+    #[serde(default)]
+    pub session_revoked: bool,
+    #[serde(default)]
+    pub api_key_created: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_new_ip_days() -> i64 {
+    30
+}
+
+fn default_failed_login_threshold() -> i64 {
+    10
+}
+
+fn default_failed_login_window_minutes() -> i64 {
+    10
+}
+
+impl Default for SecurityAlertSettings {
+    fn default() -> Self {
+        Self {
+            notification_id: None,
+            new_ip_login: true,
+            new_ip_days: default_new_ip_days(),
+            failed_logins: true,
+            failed_login_threshold: default_failed_login_threshold(),
+            failed_login_window_minutes: default_failed_login_window_minutes(),
+            password_changed: false,
+            session_revoked: false,
+            api_key_created: false,
+        }
+    }
+}
+
+impl SecurityAlertSettings {
+    /// Check that the periods and the threshold are positive.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=365).contains(&self.new_ip_days) {
+            return Err("new_ip_days must be 1-365".into());
+        }
+        if !(1..=10_000).contains(&self.failed_login_threshold) {
+            return Err("failed_login_threshold must be 1-10000".into());
+        }
+        if !(1..=24 * 60).contains(&self.failed_login_window_minutes) {
+            return Err("failed_login_window_minutes must be 1-1440".into());
+        }
+        Ok(())
+    }
+}
+
+/// Load the security alert settings, falling back to defaults.
+pub async fn load_settings(state: &AppState) -> AppResult<SecurityAlertSettings> {
+    let settings = state
+        .db
+        .get_setting("security_alerts")
+        .await?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+/// Record a security event and send its notification in the background.
+///
+/// `user` is the account the event is about; `username` is the name given
+/// in a failed login, which may not exist. Failures are logged rather than
+/// returned so they never fail the request that caused the event.
+pub async fn record(
+    state: &AppState,
+    event_type: SecurityEventType,
+    user: Option<&User>,
+    username: Option<&str>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+) {
+    let event = match state
+        .db
+        .insert_security_event(
+            event_type.as_str(),
+            user.map(|u| u.id),
+            username.or(user.map(|u| u.username.as_str())),
+            ip_address,
+            user_agent,
+        )
+        .await
+    {
+        Ok(event) => event,
+        Err(e) => {
+            error!(
+                "Failed to record {} security event: {}",
+                event_type.as_str(),
+                e
+            );
+            return;
+        }
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = notify(&state, event_type, &event).await {
+            error!(
+                "Failed to send {} security notification: {}",
+                event_type.as_str(),
+                e
+            );
+        }
+    });
+}
+
+/// Send the notification for an event if the settings ask for one.
+async fn notify(
+    state: &AppState,
+    event_type: SecurityEventType,
+    event: &SecurityEvent,
+) -> AppResult<()> {
+    let settings = load_settings(state).await?;
+    let Some(notification_id) = settings.notification_id else {
+        return Ok(());
+    };
+
+    let mut params = vec![
+        ("user", event.username.clone().unwrap_or_else(|| "-".into())),
+        ("ip", event.ip_address.clone().unwrap_or_else(|| "-".into())),
+        (
+            "user_agent",
+            event.user_agent.clone().unwrap_or_else(|| "-".into()),
+        ),
+    ];
+    let key = match event_type {
+        SecurityEventType::LoginSuccess => {
+            let (Some(user_id), Some(ip)) = (event.user_id, event.ip_address.as_deref()) else {
+                return Ok(());
+            };
+            if !settings.new_ip_login
+                || state
+                    .db
+                    .has_recent_login_from_ip(user_id, ip, event.id, settings.new_ip_days)
+                    .await?
+            {
+                return Ok(());
+            }
+            params.push(("days", settings.new_ip_days.to_string()));
+            MessageKey::NewIpLogin
+        }
+        SecurityEventType::LoginFailed => {
+            if !settings.failed_logins {
+                return Ok(());
+            }
+            let window = settings.failed_login_window_minutes;
+            let count = state
+                .db
+                .count_recent_security_events(event_type.as_str(), window)
+                .await?;
+            if count < settings.failed_login_threshold
+                || !state
+                    .db
+                    .claim_security_notification(event.id, Some(window))
+                    .await?
+            {
+                return Ok(());
+            }
+            params.push(("count", count.to_string()));
+            params.push(("minutes", window.to_string()));
+            MessageKey::FailedLogins
+        }
+        SecurityEventType::PasswordChanged if settings.password_changed => {
+            MessageKey::SecurityEvent
+        }
+        SecurityEventType::SessionRevoked if settings.session_revoked => MessageKey::SecurityEvent,
+        SecurityEventType::ApiKeyCreated if settings.api_key_created => MessageKey::SecurityEvent,
+        _ => return Ok(()),
+    };
+    params.push(("event", event_type.as_str().to_string()));
+
+    let notification = state
+        .db
+        .find_notification_by_id(notification_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+    let locale = state.runtime().locale.clone();
+    crate::notifier::send_message(&notification, &locale, key, &params)
+        .await
+        .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))?;
+    if key != MessageKey::FailedLogins {
+        state.db.claim_security_notification(event.id, None).await?;
+    }
+    info!(
+        event_id = event.id,
+        "Sent {} security notification",
+        event_type.as_str()
+    );
+
+    Ok(())
+}
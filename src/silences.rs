@@ -0,0 +1,255 @@
+//! Silence windows.
+//!
+//! A silence suppresses notifications for the clients or ping tasks it
+//! matches while it is active. Events are still recorded (alert history,
+//! monitor status); only the notification is skipped. Silences are either
+//! one-off (`starts_at` to `ends_at`) or recur daily or weekly:
+//!
+//! ```json
+//! {"group_name": "db", "kind": "weekly", "time": "02:00", "weekdays": ["Sun"],
+//!  "timezone": "Europe/Berlin", "duration_minutes": 120, "comment": "Backups"}
+//! ```
+//!
+//! Recurring start times are local to the silence's timezone, so a window
+//! keeps its wall-clock time across DST changes. A start time that occurs
+//! twice uses the first occurrence; one that falls into a DST gap is read
+//! with the offset in effect before the gap.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use uuid::Uuid;
+
+use crate::db::{Client, Database, Silence, SilenceSchedule};
+use crate::error::AppResult;
+
+/// Longest recurring silence, one week.
+const MAX_DURATION_MINUTES: i32 = 7 * 24 * 60;
+
+/// How a silence recurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SilenceKind {
+    Once,
+    Daily,
+    Weekly,
+}
+
+impl SilenceKind {
+    /// Kind name as stored in `silences.kind`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SilenceKind::Once => "once",
+            SilenceKind::Daily => "daily",
+            SilenceKind::Weekly => "weekly",
+        }
+    }
+
+    /// Parse a kind from its stored name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [SilenceKind::Once, SilenceKind::Daily, SilenceKind::Weekly]
+            .into_iter()
+            .find(|k| k.as_str() == name)
+    }
+}
+
+impl SilenceSchedule {
+    /// Check that the schedule is complete and its fields parse.
+    pub fn validate(&self) -> Result<(), String> {
+        let kind = self.kind()?;
+        if kind == SilenceKind::Once {
+            return match (self.starts_at, self.ends_at) {
+                (Some(starts_at), Some(ends_at)) if starts_at < ends_at => Ok(()),
+                (Some(_), Some(_)) => Err("Silence must end after it starts".into()),
+                _ => Err("One-off silences need starts_at and ends_at".into()),
+            };
+        }
+
+        self.start_time()?;
+        self.tz()?;
+        if kind == SilenceKind::Weekly && self.days()?.is_empty() {
+            return Err("Weekly silences need at least one weekday".into());
+        }
+        if !(1..=MAX_DURATION_MINUTES).contains(&self.duration_minutes) {
+            return Err(format!(
+                "Silence duration must be 1-{} minutes",
+                MAX_DURATION_MINUTES
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the silence is active at `now`. Invalid schedules never are.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.active_at(now).unwrap_or(false)
+    }
+
+    fn active_at(&self, now: DateTime<Utc>) -> Result<bool, String> {
+        let kind = self.kind()?;
+        if kind == SilenceKind::Once {
+            return Ok(matches!(
+                (self.starts_at, self.ends_at),
+                (Some(starts_at), Some(ends_at)) if starts_at <= now && now < ends_at
+            ));
+        }
+
+        let time = self.start_time()?;
+        let tz = self.tz()?;
+        let days = self.days()?;
+        let duration = Duration::minutes(self.duration_minutes.into());
+
+        // A window that started on an earlier local day may still be running
+        let today = now.with_timezone(&tz).date_naive();
+        let days_back = duration.num_days() + 1;
+        Ok((0..=days_back).any(|back| {
+            let date = today - Duration::days(back);
+            if kind == SilenceKind::Weekly && !days.contains(&date.weekday()) {
+                return false;
+            }
+            let start = resolve_local(&tz, date.and_time(time));
+            start <= now && now < start + duration
+        }))
+    }
+
+    fn kind(&self) -> Result<SilenceKind, String> {
+        SilenceKind::from_name(&self.kind)
+            .ok_or_else(|| format!("Unknown silence kind: {}", self.kind))
+    }
+
+    fn start_time(&self) -> Result<NaiveTime, String> {
+        let time = self
+            .time
+            .as_deref()
+            .ok_or("Recurring silences need a start time")?;
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| format!("Invalid silence time: {} (use HH:MM)", time))
+    }
+
+    fn tz(&self) -> Result<Tz, String> {
+        self.timezone
+            .parse()
+            .map_err(|_| format!("Invalid timezone: {}", self.timezone))
+    }
+
+    fn days(&self) -> Result<Vec<Weekday>, String> {
+        self.weekdays
+            .iter()
+            .map(|day| day.parse().map_err(|_| format!("Invalid weekday: {}", day)))
+            .collect()
+    }
+}
+
+/// Resolve a local start time in `tz` to UTC.
+fn resolve_local(tz: &Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    if let Some(start) = tz.from_local_datetime(&local).earliest() {
+        return start.with_timezone(&Utc);
+    }
+    // The start time falls into a DST gap; read it with the offset in effect
+    // before the gap
+    match tz
+        .from_local_datetime(&(local - Duration::hours(1)))
+        .earliest()
+    {
+        Some(before) => before.with_timezone(&Utc) + Duration::hours(1),
+        None => Utc.from_utc_datetime(&local),
+    }
+}
+
+impl Silence {
+    /// Whether the silence matches a client. Task silences never do.
+    pub fn matches_client(&self, client: &Client) -> bool {
+        self.task_id.is_none()
+            && (self.client_id.is_some() || self.group_name.is_some())
+            && self.client_id.is_none_or(|id| id == client.id)
+            && self
+                .group_name
+                .as_ref()
+                .is_none_or(|group| *group == client.group_name)
+    }
+
+    /// Whether the silence matches a ping task.
+    pub fn matches_task(&self, task_id: Uuid) -> bool {
+        self.task_id == Some(task_id)
+    }
+}
+
+/// Silences active now.
+pub async fn active(db: &Database) -> AppResult<Vec<Silence>> {
+    let now = Utc::now();
+    let mut silences = db.get_all_silences().await?;
+    silences.retain(|s| s.schedule.is_active(now));
+    Ok(silences)
+}
+
+/// Whether any of `silences` matches the client.
+pub fn client_silenced(silences: &[Silence], client: &Client) -> bool {
+    silences.iter().any(|s| s.matches_client(client))
+}
+
+/// Whether any of `silences` matches one of the ping tasks.
+pub fn tasks_silenced(silences: &[Silence], task_ids: &[Uuid]) -> bool {
+    silences
+        .iter()
+        .any(|s| task_ids.iter().any(|&id| s.matches_task(id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recurring(kind: &str, time: &str, weekdays: &[&str], timezone: &str) -> SilenceSchedule {
+        SilenceSchedule {
+            kind: kind.into(),
+            starts_at: None,
+            ends_at: None,
+            time: Some(time.into()),
+            weekdays: weekdays.iter().map(|day| day.to_string()).collect(),
+            timezone: timezone.into(),
+            duration_minutes: 60,
+        }
+    }
+
+    fn at(utc: &str) -> DateTime<Utc> {
+        utc.parse().unwrap()
+    }
+
+    #[test]
+    fn start_in_spring_forward_gap() {
+        // 02:30 does not exist in Berlin on 2026-03-29 and is read as 02:30
+        // CET, i.e. 03:30 CEST
+        let schedule = recurring("daily", "02:30", &[], "Europe/Berlin");
+        schedule.validate().unwrap();
+        assert!(!schedule.is_active(at("2026-03-29T01:15:00Z")));
+        assert!(schedule.is_active(at("2026-03-29T01:30:00Z")));
+        assert!(schedule.is_active(at("2026-03-29T02:15:00Z")));
+        assert!(!schedule.is_active(at("2026-03-29T02:30:00Z")));
+    }
+
+    #[test]
+    fn start_in_fall_back_overlap() {
+        // 02:30 occurs twice in Berlin on 2026-10-25; the CEST one is used
+        let schedule = recurring("daily", "02:30", &[], "Europe/Berlin");
+        assert!(!schedule.is_active(at("2026-10-25T00:15:00Z")));
+        assert!(schedule.is_active(at("2026-10-25T00:30:00Z")));
+        assert!(schedule.is_active(at("2026-10-25T01:15:00Z")));
+        assert!(!schedule.is_active(at("2026-10-25T01:45:00Z")));
+        // The next day keeps the wall-clock time in CET
+        assert!(schedule.is_active(at("2026-10-26T01:45:00Z")));
+    }
+
+    #[test]
+    fn weekly_window_across_midnight() {
+        // Friday 23:00 in Shanghai is 15:00 UTC and runs into Saturday
+        let mut schedule = recurring("weekly", "23:00", &["Fri"], "Asia/Shanghai");
+        schedule.duration_minutes = 120;
+        schedule.validate().unwrap();
+        assert!(!schedule.is_active(at("2026-10-16T14:45:00Z")));
+        assert!(schedule.is_active(at("2026-10-16T15:00:00Z")));
+        assert!(schedule.is_active(at("2026-10-16T16:30:00Z")));
+        assert!(!schedule.is_active(at("2026-10-16T17:00:00Z")));
+        // Other days do not start a window
+        assert!(!schedule.is_active(at("2026-10-15T16:30:00Z")));
+        assert!(!schedule.is_active(at("2026-10-17T16:30:00Z")));
+
+        schedule.weekdays = vec!["Sat".into()];
+        assert!(!schedule.is_active(at("2026-10-16T16:30:00Z")));
+    }
+}
@@ -0,0 +1,401 @@
+//! S3-compatible object storage.
+//!
+//! A small client signing requests with AWS Signature Version 4, used to
+//! upload configuration backups and record archives. Configured through the
+//! `object_storage` setting:
+//!
+//! ```json
+//! {"enabled": true, "endpoint": "https://s3.example.com", "bucket": "vanmoi",
+//!  "region": "us-east-1", "access_key": "...", "secret_key": "...", "prefix": "vanmoi/"}
+//! ```
+//!
+//! Requests use path-style URLs (`<endpoint>/<bucket>/<key>`), which AWS and
+//! self-hosted stores such as MinIO both accept. The access and secret keys
+//! are write-only (see [`crate::api::secrets`]).
+
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api::{AppState, secrets};
+use crate::error::{AppError, AppResult};
+
+/// Timeout for a single object storage request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Object storage configuration stored in the `object_storage` setting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectStorageSettings {
+    pub enabled: bool,
+    /// Base URL of the S3 API, e.g. `https://s3.eu-west-1.amazonaws.com`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Prepended to every object key, e.g. `vanmoi/`.
+    pub prefix: String,
+}
+
+impl Default for ObjectStorageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            prefix: String::new(),
+        }
+    }
+}
+
+impl ObjectStorageSettings {
+    /// Check that an enabled configuration is complete.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let endpoint = reqwest::Url::parse(&self.endpoint)
+            .map_err(|_| format!("Invalid object storage endpoint: {}", self.endpoint))?;
+        if !matches!(endpoint.scheme(), "http" | "https") {
+            return Err(format!(
+                "Invalid object storage endpoint: {}",
+                self.endpoint
+            ));
+        }
+        if self.bucket.is_empty() || self.region.is_empty() {
+            return Err("Object storage bucket and region are required".to_string());
+        }
+        if self.access_key.is_empty() || self.secret_key.is_empty() {
+            return Err("Object storage access and secret keys are required".to_string());
+        }
+        Ok(())
+    }
+
+    /// Keep the stored keys when an update sends the sentinel back.
+    pub fn restore_secrets(&mut self, stored: &ObjectStorageSettings) -> AppResult<()> {
+        secrets::restore_str(&mut self.access_key, &stored.access_key, "access_key")?;
+        secrets::restore_str(&mut self.secret_key, &stored.secret_key, "secret_key")
+    }
+
+    /// Settings as returned by the API, with the keys masked.
+    pub fn masked(&self) -> Self {
+        Self {
+            access_key: secrets::mask_str(&self.access_key),
+            secret_key: secrets::mask_str(&self.secret_key),
+            ..self.clone()
+        }
+    }
+}
+
+/// Load the object storage settings, falling back to defaults.
+pub async fn load_settings(state: &AppState) -> AppResult<ObjectStorageSettings> {
+    let settings = state
+        .db
+        .get_setting("object_storage")
+        .await?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+/// Map a storage error into an [`AppError`].
+pub fn storage_error(e: anyhow::Error) -> AppError {
+    AppError::Internal(format!("Object storage request failed: {}", e))
+}
+
+/// An object in a bucket listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteObject {
+    /// Key relative to the configured prefix.
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// S3-compatible object storage client.
+pub struct ObjectStorage {
+    settings: ObjectStorageSettings,
+    http: reqwest::Client,
+}
+
+impl ObjectStorage {
+    /// Client for the configured storage, or `None` when it is disabled.
+    pub async fn from_state(state: &AppState) -> AppResult<Option<Self>> {
+        let settings = load_settings(state).await?;
+        Ok(settings.enabled.then(|| Self::new(settings)))
+    }
+
+    pub fn new(settings: ObjectStorageSettings) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent("vanmoi")
+            .build()
+            .unwrap_or_default();
+        Self { settings, http }
+    }
+
+    /// Upload an object, replacing any existing one with the same key.
+    pub async fn put_object(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<()> {
+        self.send(Method::PUT, key, &[], body, Some(content_type))
+            .await?;
+        Ok(())
+    }
+
+    /// Download an object.
+    pub async fn get_object(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self.send(Method::GET, key, &[], Vec::new(), None).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Check whether an object exists.
+    pub async fn object_exists(&self, key: &str) -> Result<bool> {
+        match self.send(Method::HEAD, key, &[], Vec::new(), None).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.downcast_ref::<NotFound>().is_some() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// List the objects whose key starts with `prefix`.
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<RemoteObject>> {
+        let full_prefix = format!("{}{}", self.settings.prefix, prefix);
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", full_prefix.as_str())];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let body = self
+                .send_raw(Method::GET, "", &query, Vec::new(), None)
+                .await?
+                .text()
+                .await?;
+
+            for contents in xml_elements(&body, "Contents") {
+                let Some(key) = xml_text(contents, "Key") else {
+                    continue;
+                };
+                objects.push(RemoteObject {
+                    key: key
+                        .strip_prefix(&self.settings.prefix)
+                        .unwrap_or(&key)
+                        .to_string(),
+                    size: xml_text(contents, "Size")
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0),
+                    last_modified: xml_text(contents, "LastModified").and_then(|s| s.parse().ok()),
+                });
+            }
+
+            continuation = xml_text(&body, "NextContinuationToken");
+            if xml_text(&body, "IsTruncated").as_deref() != Some("true") || continuation.is_none() {
+                break;
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// Send a request for an object key below the configured prefix.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let key = format!("{}{}", self.settings.prefix, key);
+        self.send_raw(method, &key, query, body, content_type).await
+    }
+
+    /// Send a signed request for a full object key (empty for the bucket).
+    async fn send_raw(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let endpoint = reqwest::Url::parse(&self.settings.endpoint)?;
+        let host = match endpoint.port() {
+            Some(port) => format!("{}:{}", endpoint.host_str().unwrap_or_default(), port),
+            None => endpoint.host_str().unwrap_or_default().to_string(),
+        };
+
+        let path = format!(
+            "{}/{}/{}",
+            endpoint.path().trim_end_matches('/'),
+            uri_encode(&self.settings.bucket, false),
+            uri_encode(key, true)
+        );
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(k, v)| (uri_encode(k, false), uri_encode(v, false)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = format!("{:x}", Sha256::digest(&body));
+        let authorization = self.authorization(
+            method.as_str(),
+            &path,
+            &query,
+            &host,
+            &payload_hash,
+            &amz_date,
+        );
+
+        let mut url = format!("{}://{}{}", endpoint.scheme(), host, path);
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query);
+        }
+        let mut request = self
+            .http
+            .request(method, url)
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body);
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(NotFound.into());
+        }
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            let message = xml_text(&text, "Message").unwrap_or(text);
+            bail!("Object storage returned {}: {}", status, message);
+        }
+        Ok(response)
+    }
+
+    /// Build the SigV4 `Authorization` header value.
+    ///
+    /// Signs `host`, `x-amz-content-sha256` and `x-amz-date`.
+    fn authorization(
+        &self,
+        method: &str,
+        path: &str,
+        query: &str,
+        host: &str,
+        payload_hash: &str,
+        amz_date: &str,
+    ) -> String {
+        const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.settings.region);
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, query, host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let key = hmac_sha256(
+            format!("AWS4{}", self.settings.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac_sha256(&key, self.settings.region.as_bytes());
+        let key = hmac_sha256(&key, b"s3");
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hmac_sha256(&key, string_to_sign.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.settings.access_key, scope, SIGNED_HEADERS, signature
+        )
+    }
+}
+
+/// The requested object does not exist.
+#[derive(Debug)]
+struct NotFound;
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Object not found")
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters, as SigV4 requires.
+fn uri_encode(input: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Inner text of each `<tag>...</tag>` element in `xml`.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        elements.push(&after[..end]);
+        rest = &after[end + close.len()..];
+    }
+    elements
+}
+
+/// Unescaped text of the first `<tag>` element in `xml`.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    xml_elements(xml, tag).first().map(|text| {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    })
+}
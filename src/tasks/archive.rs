@@ -0,0 +1,407 @@
+//! Record archival.
+//!
+//! When the `archive` setting is enabled, the retention cleanup exports
+//! expired records before deleting them, one zstd-compressed CSV file per
+//! client per UTC day:
+//!
+//! ```json
+//! {"enabled": true, "directory": "/var/lib/vanmoi/archive", "notification_id": "..."}
+//! ```
+//!
+//! Files are written to `<directory>/<client_id>/<YYYY-MM-DD>.csv.zst`, or
+//! uploaded as `archive/<client_id>/<YYYY-MM-DD>.csv.zst` when `target` is
+//! `object_storage` (see [`crate::storage`]), and read back before the day's
+//! records are deleted. A day that already has a file is merged into it by
+//! record ID, so archiving again after a failed delete or for late records
+//! never duplicates rows. Days that fail are kept for the next run and
+//! reported through `notification_id` (or the `default_notification_id`
+//! setting). The outcome of the last run is stored in the `archive_status`
+//! setting.
+//!
+//! Archives are CSV only; Parquet output is not supported.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::{Record, RecordDay};
+use crate::error::{AppError, AppResult};
+use crate::notifier::i18n::MessageKey;
+use crate::storage::{ObjectStorage, storage_error};
+
+/// zstd compression level for archive files.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Errors listed in a failure notification.
+const NOTIFY_MAX_ERRORS: usize = 10;
+
+/// Key prefix of archives in object storage.
+const OBJECT_PREFIX: &str = "archive/";
+
+/// Where archive files are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveTarget {
+    #[default]
+    Directory,
+    ObjectStorage,
+}
+
+/// Archival configuration stored in the `archive` setting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub target: ArchiveTarget,
+    /// Directory the archive files are written to.
+    #[serde(default)]
+    pub directory: String,
+    /// Notification used to report failures, instead of the default one.
+    pub notification_id: Option<Uuid>,
+}
+
+impl ArchiveSettings {
+    /// Check that an enabled directory archive has an absolute path.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled
+            && self.target == ArchiveTarget::Directory
+            && !Path::new(&self.directory).is_absolute()
+        {
+            return Err(format!(
+                "Archive directory must be an absolute path: {}",
+                self.directory
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of the last archival run, stored in the `archive_status` setting.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveStatus {
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub files_written: u64,
+    pub records_archived: u64,
+    pub bytes_written: u64,
+    pub errors: Vec<String>,
+}
+
+/// Load the archive settings, falling back to defaults.
+pub async fn load_settings(state: &AppState) -> AppResult<ArchiveSettings> {
+    let settings = state
+        .db
+        .get_setting("archive")
+        .await?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+/// Resolved archive destination.
+enum Destination {
+    Directory(PathBuf),
+    Storage(ObjectStorage),
+}
+
+impl Destination {
+    async fn resolve(state: &AppState, settings: &ArchiveSettings) -> AppResult<Self> {
+        match settings.target {
+            ArchiveTarget::Directory => Ok(Self::Directory(PathBuf::from(&settings.directory))),
+            ArchiveTarget::ObjectStorage => ObjectStorage::from_state(state)
+                .await?
+                .map(Self::Storage)
+                .ok_or_else(|| AppError::BadRequest("Object storage is not enabled".into())),
+        }
+    }
+
+    /// Path or object key of the archive file of a client day.
+    fn location(&self, client_id: Uuid, date: &str) -> String {
+        match self {
+            Self::Directory(directory) => directory
+                .join(client_id.to_string())
+                .join(format!("{}.csv.zst", date))
+                .display()
+                .to_string(),
+            Self::Storage(_) => format!("{}{}/{}.csv.zst", OBJECT_PREFIX, client_id, date),
+        }
+    }
+
+    /// Read the archive file of a client day, if it exists.
+    async fn read(&self, client_id: Uuid, date: &str) -> AppResult<Option<Vec<u8>>> {
+        let location = self.location(client_id, date);
+        match self {
+            Self::Directory(_) => match tokio::fs::read(&location).await {
+                Ok(contents) => Ok(Some(contents)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(io_error(e)),
+            },
+            Self::Storage(storage) => {
+                if !storage
+                    .object_exists(&location)
+                    .await
+                    .map_err(storage_error)?
+                {
+                    return Ok(None);
+                }
+                let contents = storage.get_object(&location).await.map_err(storage_error)?;
+                Ok(Some(contents))
+            }
+        }
+    }
+
+    /// Replace the archive file of a client day and read it back, returning
+    /// its contents.
+    async fn write(&self, client_id: Uuid, date: &str, compressed: Vec<u8>) -> AppResult<Vec<u8>> {
+        let location = self.location(client_id, date);
+        match self {
+            Self::Directory(directory) => {
+                tokio::fs::create_dir_all(directory.join(client_id.to_string()))
+                    .await
+                    .map_err(io_error)?;
+                // Write under a temporary name so a crash never leaves a
+                // partial archive
+                let path = PathBuf::from(location);
+                let tmp = path.with_extension("zst.tmp");
+                tokio::fs::write(&tmp, compressed).await.map_err(io_error)?;
+                tokio::fs::rename(&tmp, &path).await.map_err(io_error)?;
+                tokio::fs::read(&path).await.map_err(io_error)
+            }
+            Self::Storage(storage) => {
+                storage
+                    .put_object(&location, compressed, "application/zstd")
+                    .await
+                    .map_err(storage_error)?;
+                storage.get_object(&location).await.map_err(storage_error)
+            }
+        }
+    }
+}
+
+/// Load the status of the last archival run, if any.
+pub async fn load_status(state: &AppState) -> AppResult<Option<ArchiveStatus>> {
+    Ok(state
+        .db
+        .get_setting("archive_status")
+        .await?
+        .and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// Archive and delete the records of every client day older than `days`,
+/// leaving out archived clients with `keep_archived`.
+///
+/// Only whole UTC days are archived, so each file covers a complete day.
+/// Returns the number of records deleted.
+pub async fn archive_expired(
+    state: &AppState,
+    settings: &ArchiveSettings,
+    days: i32,
+    keep_archived: bool,
+) -> AppResult<u64> {
+    let started_at = Utc::now();
+    let cutoff = (started_at - chrono::Duration::days(days as i64))
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc();
+
+    let mut status = ArchiveStatus {
+        started_at: Some(started_at),
+        ..Default::default()
+    };
+    let mut deleted = 0;
+
+    match Destination::resolve(state, settings).await {
+        Ok(destination) => {
+            for day in state
+                .db
+                .get_record_days_before(cutoff, keep_archived)
+                .await?
+            {
+                deleted += archive_and_delete(state, &destination, &day, &mut status).await?;
+            }
+        }
+        Err(e) => {
+            warn!("Skipping record deletion, archive unavailable: {}", e);
+            status.errors.push(e.to_string());
+        }
+    }
+
+    status.finished_at = Some(Utc::now());
+    if status.files_written > 0 {
+        info!(
+            "Archived {} records into {} files ({} bytes)",
+            status.records_archived, status.files_written, status.bytes_written
+        );
+    }
+    if !status.errors.is_empty()
+        && let Err(e) = notify_failure(state, settings, &status.errors).await
+    {
+        error!("Failed to send archive failure notification: {}", e);
+    }
+    state
+        .db
+        .set_setting("archive_status", serde_json::json!(status))
+        .await?;
+
+    Ok(deleted)
+}
+
+/// Archive one client day, then delete its records if that succeeded.
+///
+/// Returns the number of records deleted.
+async fn archive_and_delete(
+    state: &AppState,
+    destination: &Destination,
+    day: &RecordDay,
+    status: &mut ArchiveStatus,
+) -> AppResult<u64> {
+    match archive_day(state, destination, day).await {
+        Ok((records, bytes, max_id)) => {
+            status.files_written += 1;
+            status.records_archived += records;
+            status.bytes_written += bytes;
+            state
+                .db
+                .delete_records_between(
+                    day.client_id,
+                    day.day,
+                    day.day + chrono::Duration::days(1),
+                    max_id,
+                )
+                .await
+        }
+        Err(e) => {
+            let message = format!("{} {}: {}", day.client_id, day.day.format("%Y-%m-%d"), e);
+            warn!("Skipping record deletion, archival failed for {}", message);
+            status.errors.push(message);
+            Ok(0)
+        }
+    }
+}
+
+/// Store one client day in its archive file and verify it.
+///
+/// Returns the number of records newly archived, the file size and the
+/// highest record ID archived, so records inserted afterwards are not
+/// deleted unarchived.
+async fn archive_day(
+    state: &AppState,
+    destination: &Destination,
+    day: &RecordDay,
+) -> AppResult<(u64, u64, i64)> {
+    let records = state
+        .db
+        .get_records_between(day.client_id, day.day, day.day + chrono::Duration::days(1))
+        .await?;
+    let max_id = records.iter().map(|r| r.id).max().unwrap_or(0);
+
+    let date = day.day.format("%Y-%m-%d").to_string();
+    let existing = match destination.read(day.client_id, &date).await? {
+        Some(compressed) => tokio::task::spawn_blocking(move || decode(&compressed))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??,
+        None => Vec::new(),
+    };
+    let (merged, count) = merge(existing, records);
+
+    let (csv, compressed) = tokio::task::spawn_blocking(move || encode(&merged))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    let written = destination.write(day.client_id, &date, compressed).await?;
+
+    let verified = zstd::decode_all(written.as_slice()).is_ok_and(|decoded| decoded == csv);
+    if !verified {
+        return Err(AppError::Internal(format!(
+            "Archive file failed verification: {}",
+            destination.location(day.client_id, &date)
+        )));
+    }
+
+    Ok((count, written.len() as u64, max_id))
+}
+
+/// Add `records` to the `archived` ones, leaving out IDs already archived.
+/// Returns the records ordered by ID and how many were added.
+fn merge(mut archived: Vec<Record>, records: Vec<Record>) -> (Vec<Record>, u64) {
+    let ids: HashSet<i64> = archived.iter().map(|r| r.id).collect();
+    let before = archived.len();
+    archived.extend(records.into_iter().filter(|r| !ids.contains(&r.id)));
+    let added = (archived.len() - before) as u64;
+    archived.sort_by_key(|r| r.id);
+    (archived, added)
+}
+
+/// Encode records as CSV and compress it, returning both.
+fn encode(records: &[Record]) -> AppResult<(Vec<u8>, Vec<u8>)> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in records {
+        writer
+            .serialize(record)
+            .map_err(|e| AppError::Internal(format!("CSV encoding failed: {}", e)))?;
+    }
+    let csv = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("CSV encoding failed: {}", e)))?;
+    let compressed = zstd::encode_all(csv.as_slice(), COMPRESSION_LEVEL).map_err(io_error)?;
+    Ok((csv, compressed))
+}
+
+/// Decompress and parse an archive file.
+fn decode(compressed: &[u8]) -> AppResult<Vec<Record>> {
+    let csv = zstd::decode_all(compressed).map_err(io_error)?;
+    csv::Reader::from_reader(csv.as_slice())
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(|e| AppError::Internal(format!("Existing archive is unreadable: {}", e)))
+}
+
+fn io_error(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("Archive I/O failed: {}", e))
+}
+
+/// Report failed client days through the archive or default notification.
+async fn notify_failure(
+    state: &AppState,
+    settings: &ArchiveSettings,
+    errors: &[String],
+) -> AppResult<()> {
+    let notification_id = match settings.notification_id {
+        Some(id) => Some(id),
+        None => state
+            .db
+            .get_setting("default_notification_id")
+            .await?
+            .and_then(|v| serde_json::from_value::<Uuid>(v).ok()),
+    };
+    let Some(notification_id) = notification_id else {
+        return Ok(());
+    };
+    let Some(notification) = state.db.find_notification_by_id(notification_id).await? else {
+        return Ok(());
+    };
+
+    let locale = state.runtime().locale.clone();
+    let mut lines: Vec<String> = errors
+        .iter()
+        .take(NOTIFY_MAX_ERRORS)
+        .map(|e| format!("- {}", e))
+        .collect();
+    if errors.len() > NOTIFY_MAX_ERRORS {
+        lines.push(format!("- ... ({} more)", errors.len() - NOTIFY_MAX_ERRORS));
+    }
+    let params = [
+        ("count", errors.len().to_string()),
+        ("errors", lines.join("\n")),
+    ];
+
+    crate::notifier::send_message(&notification, &locale, MessageKey::ArchiveFailed, &params)
+        .await
+        .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))
+}
@@ -0,0 +1,115 @@
+//! Configuration backups.
+//!
+//! Builds a JSON snapshot of the configuration (clients, notifications,
+//! notification routes, alert rules, ping tasks and settings) and uploads it
+//! to object storage once a day while object storage is enabled. Backups are
+//! stored as `backups/vanmoi-<timestamp>.json` below the storage prefix.
+//!
+//! The last upload time is stored in the `backup_last_uploaded` setting.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::api::AppState;
+use crate::build_info;
+use crate::error::{AppError, AppResult};
+use crate::storage::{ObjectStorage, storage_error};
+
+/// How often to check whether a backup is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Key prefix of backups in object storage.
+pub const BACKUP_PREFIX: &str = "backups/";
+
+/// Settings left out of backups: storage credentials and task state.
+const EXCLUDED_SETTINGS: &[&str] = &[
+    "object_storage",
+    "archive_status",
+    "backup_last_uploaded",
+    "digest_last_sent",
+];
+
+/// Build the configuration snapshot.
+pub async fn build_backup(state: &AppState) -> AppResult<serde_json::Value> {
+    let settings: serde_json::Map<String, serde_json::Value> = state
+        .db
+        .get_all_settings()
+        .await?
+        .into_iter()
+        .filter(|s| !EXCLUDED_SETTINGS.contains(&s.key.as_str()))
+        .map(|s| (s.key, s.value))
+        .collect();
+
+    Ok(serde_json::json!({
+        "version": build_info::VERSION,
+        "created_at": Utc::now(),
+        "clients": state.db.get_all_clients().await?,
+        "notifications": state.db.get_all_notifications().await?,
+        "notification_routes": state.db.get_all_notification_routes().await?,
+        "alert_rules": state.db.get_all_alert_rules().await?,
+        "ping_tasks": state.db.get_all_ping_tasks().await?,
+        "settings": settings,
+    }))
+}
+
+/// Build a backup and upload it, returning its key.
+pub async fn upload_backup(state: &AppState, storage: &ObjectStorage) -> AppResult<String> {
+    let backup = build_backup(state).await?;
+    let body = serde_json::to_vec_pretty(&backup)
+        .map_err(|e| AppError::Internal(format!("Backup encoding failed: {}", e)))?;
+    let key = format!(
+        "{}vanmoi-{}.json",
+        BACKUP_PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+
+    storage
+        .put_object(&key, body, "application/json")
+        .await
+        .map_err(storage_error)?;
+    state
+        .db
+        .set_setting("backup_last_uploaded", serde_json::json!(Utc::now()))
+        .await?;
+
+    Ok(key)
+}
+
+/// Upload daily backups until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = upload_if_due(&state).await {
+            error!("Configuration backup failed: {}", e);
+        }
+    }
+}
+
+/// Upload a backup if object storage is enabled and the last one is a day old.
+async fn upload_if_due(state: &AppState) -> AppResult<()> {
+    let Some(storage) = ObjectStorage::from_state(state).await? else {
+        return Ok(());
+    };
+
+    let last_uploaded = state
+        .db
+        .get_setting("backup_last_uploaded")
+        .await?
+        .and_then(|v| serde_json::from_value::<DateTime<Utc>>(v).ok());
+    if last_uploaded.is_some_and(|t| Utc::now() - t < chrono::Duration::days(1)) {
+        return Ok(());
+    }
+
+    let key = upload_backup(state, &storage).await?;
+    info!("Uploaded configuration backup {}", key);
+    Ok(())
+}
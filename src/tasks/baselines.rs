@@ -0,0 +1,72 @@
+//! Daily anomaly baseline update.
+//!
+//! While anomaly detection is enabled, the records since the last update are
+//! merged into the baselines once a day (see [`crate::anomaly`]). The end of
+//! the last merged range is stored in the `anomaly_baselines_until` setting,
+//! so restarts neither skip nor repeat records. The first update after
+//! enabling seeds the baselines from the last [`SEED_DAYS`] days.
+
+use std::time::Duration;
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::anomaly;
+use crate::api::AppState;
+use crate::error::AppResult;
+
+/// How often to check whether an update is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Days of records merged at most in one update.
+const SEED_DAYS: i64 = 7;
+
+/// Update the baselines when due until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = update_if_due(&state).await {
+            error!("Anomaly baseline update failed: {}", e);
+        }
+    }
+}
+
+/// Merge the completed hours since the last update once a day has passed,
+/// and load the baselines into the detector.
+async fn update_if_due(state: &AppState) -> AppResult<()> {
+    if !state.runtime().anomaly.enabled {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let until = now.duration_trunc(TimeDelta::hours(1)).unwrap_or(now);
+    let last = state
+        .db
+        .get_setting("anomaly_baselines_until")
+        .await?
+        .and_then(|v| serde_json::from_value::<DateTime<Utc>>(v).ok());
+
+    let due = last.is_none_or(|last| until - last >= TimeDelta::days(1));
+    if due {
+        let earliest = until - TimeDelta::days(SEED_DAYS);
+        let since = last.map_or(earliest, |last| last.max(earliest));
+        let updated = anomaly::update_baselines(state, since, until).await?;
+        state
+            .db
+            .set_setting("anomaly_baselines_until", serde_json::json!(until))
+            .await?;
+        info!("Updated {} anomaly baselines", updated);
+    }
+
+    if due || !state.anomalies.is_loaded() {
+        anomaly::load(state).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,330 @@
+//! Scheduled digest reports.
+//!
+//! Sends a daily or weekly summary through a notification provider when the
+//! `digest` setting is enabled:
+//!
+//! ```json
+//! {"enabled": true, "cadence": "daily", "time": "08:00", "weekday": "Mon",
+//!  "timezone": "Asia/Shanghai", "notification_id": "...", "skip_empty": true}
+//! ```
+//!
+//! The last send time is stored in the `digest_last_sent` setting so restarts
+//! do not send the same report twice.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::api::AppState;
+use crate::db::ClientAggregate;
+use crate::error::{AppError, AppResult};
+use crate::notifier::i18n::MessageKey;
+
+/// How often to check whether a digest is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of clients listed in each top list.
+const TOP_LIMIT: i32 = 5;
+
+/// Disk usage percentage above which a client is listed.
+const DISK_THRESHOLD: f64 = 80.0;
+
+/// Digest cadence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestCadence {
+    Daily,
+    Weekly,
+}
+
+/// Digest configuration stored in the `digest` setting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cadence")]
+    pub cadence: DigestCadence,
+    /// Local send time as `HH:MM`.
+    #[serde(default = "default_time")]
+    pub time: String,
+    /// Day of week for weekly digests.
+    #[serde(default = "default_weekday")]
+    pub weekday: Weekday,
+    /// IANA timezone name, e.g. `Asia/Shanghai`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub notification_id: Option<Uuid>,
+    /// Skip scheduled sends when nothing needs attention.
+    #[serde(default = "default_skip_empty")]
+    pub skip_empty: bool,
+}
+
+fn default_cadence() -> DigestCadence {
+    DigestCadence::Daily
+}
+
+fn default_time() -> String {
+    "08:00".to_string()
+}
+
+fn default_weekday() -> Weekday {
+    Weekday::Mon
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+fn default_skip_empty() -> bool {
+    true
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cadence: default_cadence(),
+            time: default_time(),
+            weekday: default_weekday(),
+            timezone: default_timezone(),
+            notification_id: None,
+            skip_empty: default_skip_empty(),
+        }
+    }
+}
+
+impl DigestSettings {
+    /// Check that the time and timezone parse.
+    pub fn validate(&self) -> Result<(), String> {
+        self.send_time()?;
+        self.tz()?;
+        Ok(())
+    }
+
+    fn send_time(&self) -> Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(&self.time, "%H:%M")
+            .map_err(|_| format!("Invalid digest time: {} (use HH:MM)", self.time))
+    }
+
+    fn tz(&self) -> Result<Tz, String> {
+        self.timezone
+            .parse()
+            .map_err(|_| format!("Invalid timezone: {}", self.timezone))
+    }
+
+    fn period(&self) -> chrono::Duration {
+        match self.cadence {
+            DigestCadence::Daily => chrono::Duration::days(1),
+            DigestCadence::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+
+    /// Most recent scheduled send time at or before `now`.
+    fn last_due(&self, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+        let time = self.send_time()?;
+        let tz = self.tz()?;
+
+        let mut date = now.with_timezone(&tz).date_naive();
+        if self.cadence == DigestCadence::Weekly {
+            let back = (7 + date.weekday().num_days_from_monday()
+                - self.weekday.num_days_from_monday())
+                % 7;
+            date -= chrono::Duration::days(back as i64);
+        }
+
+        let local = date.and_time(time);
+        let due = tz
+            .from_local_datetime(&local)
+            .earliest()
+            // The send time falls into a DST gap; use the UTC reading instead
+            .unwrap_or_else(|| Utc.from_utc_datetime(&local).with_timezone(&tz))
+            .with_timezone(&Utc);
+
+        if due > now {
+            Ok(due - self.period())
+        } else {
+            Ok(due)
+        }
+    }
+}
+
+/// Load the digest settings, falling back to defaults.
+pub async fn load_settings(state: &AppState) -> AppResult<DigestSettings> {
+    let settings = state
+        .db
+        .get_setting("digest")
+        .await?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+/// Send digests when they are due until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = send_if_due(&state).await {
+            error!("Digest report failed: {}", e);
+        }
+    }
+}
+
+/// Send the scheduled digest if one is due and has not been sent yet.
+async fn send_if_due(state: &AppState) -> AppResult<()> {
+    let settings = load_settings(state).await?;
+    if !settings.enabled || settings.notification_id.is_none() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let due = settings.last_due(now).map_err(AppError::BadRequest)?;
+
+    let last_sent = state
+        .db
+        .get_setting("digest_last_sent")
+        .await?
+        .and_then(|v| serde_json::from_value::<DateTime<Utc>>(v).ok());
+
+    match last_sent {
+        // First run after enabling: start counting from now
+        None => {}
+        Some(last_sent) if last_sent >= due => return Ok(()),
+        Some(_) => {
+            let sent = send_digest(state, &settings, due, settings.skip_empty).await?;
+            if sent {
+                info!("Sent {:?} digest report", settings.cadence);
+            } else {
+                info!(
+                    "Skipped {:?} digest report, nothing to report",
+                    settings.cadence
+                );
+            }
+        }
+    }
+
+    state
+        .db
+        .set_setting("digest_last_sent", serde_json::json!(now))
+        .await?;
+    Ok(())
+}
+
+/// Build and send a digest covering the period ending at `until`.
+///
+/// Returns `false` when `skip_empty` is set and nothing needs attention
+/// (no offline servers, fired alerts or full disks).
+pub async fn send_digest(
+    state: &AppState,
+    settings: &DigestSettings,
+    until: DateTime<Utc>,
+    skip_empty: bool,
+) -> AppResult<bool> {
+    let notification_id = settings
+        .notification_id
+        .ok_or_else(|| AppError::BadRequest("No digest notification configured".into()))?;
+    let notification = state
+        .db
+        .find_notification_by_id(notification_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Notification not found".into()))?;
+
+    let since = until - settings.period();
+    let clients = state.db.get_active_clients().await?;
+    let offline: Vec<&str> = clients
+        .iter()
+        .filter(|c| !c.online)
+        .map(|c| c.name.as_str())
+        .collect();
+    let top_cpu = state
+        .db
+        .get_top_cpu_clients(since, until, TOP_LIMIT)
+        .await?;
+    let top_traffic = state
+        .db
+        .get_top_traffic_clients(since, until, TOP_LIMIT)
+        .await?;
+    let alerts = state.db.count_alerts_by_client(since, until).await?;
+    let disks = state.db.get_high_disk_clients(DISK_THRESHOLD).await?;
+
+    if skip_empty && offline.is_empty() && alerts.is_empty() && disks.is_empty() {
+        return Ok(false);
+    }
+
+    let locale = state.runtime().locale.clone();
+    let tz: Tz = settings.tz().unwrap_or(Tz::UTC);
+
+    let key = match settings.cadence {
+        DigestCadence::Daily => MessageKey::DailyDigest,
+        DigestCadence::Weekly => MessageKey::WeeklyDigest,
+    };
+    let params = [
+        (
+            "date",
+            until.with_timezone(&tz).format("%Y-%m-%d").to_string(),
+        ),
+        ("online", (clients.len() - offline.len()).to_string()),
+        ("total", clients.len().to_string()),
+        (
+            "offline",
+            if offline.is_empty() {
+                "-".to_string()
+            } else {
+                offline.join(", ")
+            },
+        ),
+        ("top_cpu", list(&top_cpu, |v| format!("{:.1}%", v))),
+        ("top_traffic", list(&top_traffic, format_bytes)),
+        (
+            "alert_count",
+            alerts
+                .iter()
+                .map(|a| a.value as i64)
+                .sum::<i64>()
+                .to_string(),
+        ),
+        ("alerts", list(&alerts, |v| format!("{}", v as i64))),
+        ("disk_threshold", format!("{}", DISK_THRESHOLD)),
+        ("disks", list(&disks, |v| format!("{:.1}%", v))),
+    ];
+
+    crate::notifier::send_message(&notification, &locale, key, &params)
+        .await
+        .map_err(|e| AppError::Internal(format!("Notification failed: {}", e)))?;
+
+    Ok(true)
+}
+
+/// Format aggregates as `- name: value` lines, or `-` when empty.
+fn list(items: &[ClientAggregate], format_value: impl Fn(f64) -> String) -> String {
+    if items.is_empty() {
+        return "-".to_string();
+    }
+    items
+        .iter()
+        .map(|item| format!("- {}: {}", item.name, format_value(item.value)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format a byte count with binary units.
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,163 @@
+//! Background tasks.
+//!
+//! Periodic jobs spawned at startup that run until server shutdown.
+
+pub mod archive;
+pub mod backup;
+pub mod baselines;
+pub mod digest;
+pub mod ping;
+pub mod retention;
+pub mod self_monitor;
+mod telegram_bot;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::alerts;
+use crate::api::{AppState, DASHBOARD_CACHE_TTL, oidc, report_buffer};
+use crate::error::AppResult;
+use crate::heartbeats;
+use crate::middleware::IDEMPOTENCY_KEY_TTL_HOURS;
+use crate::monitors;
+
+/// Interval between alert rule evaluations.
+const ALERT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval between attempts to store buffered reports.
+const REPORT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between housekeeping runs.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawn all background tasks. They stop when `shutdown` is cancelled.
+pub fn spawn_all(state: AppState, shutdown: CancellationToken) {
+    tokio::spawn(alert_loop(state.clone(), shutdown.clone()));
+    tokio::spawn(maintenance_loop(state.clone(), shutdown.clone()));
+    tokio::spawn(report_flush_loop(state.clone(), shutdown.clone()));
+    tokio::spawn(digest::run(state.clone(), shutdown.clone()));
+    tokio::spawn(retention::run(state.clone(), shutdown.clone()));
+    tokio::spawn(backup::run(state.clone(), shutdown.clone()));
+    tokio::spawn(baselines::run(state.clone(), shutdown.clone()));
+    tokio::spawn(self_monitor::run(state.clone(), shutdown.clone()));
+    tokio::spawn(telegram_bot::run(state, shutdown));
+}
+
+/// Periodically evaluate alert rules, monitor groups and heartbeats.
+async fn alert_loop(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(ALERT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = alerts::evaluate_rules(&state).await {
+            error!("Alert evaluation failed: {}", e);
+        }
+        if let Err(e) = monitors::evaluate_groups(&state).await {
+            error!("Monitor evaluation failed: {}", e);
+        }
+        if let Err(e) = heartbeats::check(&state).await {
+            error!("Heartbeat check failed: {}", e);
+        }
+        if let Err(e) = alerts::status::check_report_timeouts(&state).await {
+            error!("Report timeout check failed: {}", e);
+        }
+    }
+}
+
+/// Store reports buffered while the database was down once it is back.
+async fn report_flush_loop(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(REPORT_FLUSH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = report_buffer::flush(&state).await {
+            error!("Failed to store buffered reports: {}", e);
+        }
+    }
+}
+
+/// Periodically run housekeeping jobs.
+async fn maintenance_loop(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = clear_expired_announcement(&state).await {
+            error!("Failed to clear expired announcement: {}", e);
+        }
+        if let Err(e) = state
+            .db
+            .delete_expired_idempotency_keys(IDEMPOTENCY_KEY_TTL_HOURS)
+            .await
+        {
+            error!("Failed to delete expired idempotency keys: {}", e);
+        }
+        // Pick up settings changed outside the admin API
+        if let Err(e) = state.reload_runtime_settings().await {
+            error!("Failed to reload runtime settings: {}", e);
+        }
+
+        // Drop rate limit windows that have already ended
+        state
+            .share_rate_limits
+            .retain(|_, (window_start, _)| window_start.elapsed() < Duration::from_secs(60));
+        state
+            .heartbeat_rate_limits
+            .retain(|_, (window_start, _)| window_start.elapsed() < Duration::from_secs(60));
+        state
+            .heartbeat_ip_rate_limits
+            .retain(|_, (window_start, _)| window_start.elapsed() < Duration::from_secs(60));
+        state
+            .report_rate_limits
+            .retain(|_, (window_start, _)| window_start.elapsed() < Duration::from_secs(60));
+        state
+            .oidc_pending
+            .retain(|_, pending| pending.created_at.elapsed() < oidc::PENDING_LOGIN_TTL);
+        state
+            .dashboard_cache
+            .retain(|_, (built_at, _)| built_at.elapsed() < DASHBOARD_CACHE_TTL);
+    }
+}
+
+/// Clear the announcement text once `announcement_until` has passed.
+async fn clear_expired_announcement(state: &AppState) -> AppResult<()> {
+    let until = state
+        .db
+        .get_setting("announcement_until")
+        .await?
+        .and_then(|v| serde_json::from_value::<DateTime<Utc>>(v).ok());
+
+    if let Some(until) = until
+        && until <= Utc::now()
+    {
+        let text = state.db.get_setting("announcement_text").await?;
+        if text
+            .as_ref()
+            .and_then(|v| v.as_str())
+            .is_some_and(|t| !t.is_empty())
+        {
+            state
+                .db
+                .set_setting("announcement_text", serde_json::json!(""))
+                .await?;
+            info!("Announcement expired and was cleared");
+        }
+    }
+
+    Ok(())
+}
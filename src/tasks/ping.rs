@@ -0,0 +1,161 @@
+//! Ping task execution.
+//!
+//! Targets starting with `http://` or `https://` are checked with a GET
+//! request: the check succeeds on a 2xx status and, when the task sets
+//! `expected_body_contains` or `expected_body_not_contains`, a matching
+//! response body (the first 1 MB). Other targets are probed with a TCP
+//! connect, since ICMP needs raw sockets: `host:port`, or a bare host or IP
+//! address on port 80. The latency is the time until the connection is
+//! established or the response headers arrive, DNS resolution included.
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::db::{Database, PingRecord, PingTask};
+use crate::error::AppResult;
+
+/// Port probed when a target has none.
+const DEFAULT_PORT: u16 = 80;
+
+/// Bytes of an HTTP response body searched for the expected strings.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Probe a ping task's target once and store the result.
+///
+/// An unreachable target is a failed record carrying the failure reason,
+/// not an error; errors are storage failures.
+pub async fn execute_ping(task: &PingTask, db: &Database) -> AppResult<PingRecord> {
+    let timeout = Duration::from_secs(task.timeout_seconds.max(1) as u64);
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, probe(task, started)).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {} seconds", timeout.as_secs())),
+    };
+
+    let (latency_ms, failure_reason) = match result {
+        Ok(latency_ms) => (Some(latency_ms), None),
+        Err(reason) => {
+            debug!(task = %task.name, target = %task.target, reason = %reason, "Ping failed");
+            (None, Some(reason))
+        }
+    };
+    db.insert_ping_record(
+        task.id,
+        None,
+        latency_ms,
+        failure_reason.is_none(),
+        failure_reason.as_deref(),
+    )
+    .await
+}
+
+/// Whether a target is checked over HTTP.
+pub fn is_http_target(target: &str) -> bool {
+    let target = target.trim();
+    target.starts_with("http://") || target.starts_with("https://")
+}
+
+/// Probe a target, returning the latency or why the check failed.
+async fn probe(task: &PingTask, started: Instant) -> Result<f32, String> {
+    if is_http_target(&task.target) {
+        http_probe(task, started).await
+    } else {
+        TcpStream::connect(probe_address(&task.target))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(elapsed_ms(started))
+    }
+}
+
+async fn http_probe(task: &PingTask, started: Instant) -> Result<f32, String> {
+    let mut request = reqwest::Client::new().get(task.target.trim());
+    if let Some(headers) = task.request_headers.as_object() {
+        for (name, value) in headers {
+            if let Some(value) = value.as_str() {
+                request = request.header(name, value);
+            }
+        }
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| error_chain(&e.without_url()))?;
+    let latency_ms = elapsed_ms(started);
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    if task.expected_body_contains.is_none() && task.expected_body_not_contains.is_none() {
+        return Ok(latency_ms);
+    }
+
+    let body = read_body(response).await.map_err(|e| {
+        format!(
+            "Failed to read response body: {}",
+            error_chain(&e.without_url())
+        )
+    })?;
+    let body = String::from_utf8_lossy(&body);
+    if let Some(expected) = &task.expected_body_contains
+        && !body.contains(expected.as_str())
+    {
+        return Err(format!("Response body does not contain {:?}", expected));
+    }
+    if let Some(unexpected) = &task.expected_body_not_contains
+        && body.contains(unexpected.as_str())
+    {
+        return Err(format!("Response body contains {:?}", unexpected));
+    }
+    Ok(latency_ms)
+}
+
+/// Read up to [`MAX_BODY_BYTES`] of a response body.
+async fn read_body(mut response: reqwest::Response) -> reqwest::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while body.len() < MAX_BODY_BYTES {
+        match response.chunk().await? {
+            Some(chunk) => body.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    body.truncate(MAX_BODY_BYTES);
+    Ok(body)
+}
+
+/// An error with its sources, e.g. `error sending request: tcp connect
+/// error: Connection refused`.
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn elapsed_ms(started: Instant) -> f32 {
+    started.elapsed().as_secs_f32() * 1000.0
+}
+
+/// The `host:port` to connect to for a target.
+fn probe_address(target: &str) -> String {
+    let target = target.trim();
+    if target.parse::<SocketAddr>().is_ok() {
+        return target.to_string();
+    }
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return SocketAddr::new(ip, DEFAULT_PORT).to_string();
+    }
+    match target.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => {
+            target.to_string()
+        }
+        _ => format!("{}:{}", target, DEFAULT_PORT),
+    }
+}
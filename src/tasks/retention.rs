@@ -0,0 +1,185 @@
+//! Record retention.
+//!
+//! Deletes records older than the `record_retention_days` setting, trims
+//! agent log lines to the latest [`MAX_LOG_LINES_PER_CLIENT`] per client, and
+//! vacuums the tables after large deletions so the freed space is reused.
+//! Retention is opt-in: while the setting is unset or 0, records are kept
+//! forever. Records are archived first when archival is enabled (see
+//! [`super::archive`]). Records of archived clients are kept unless
+//! `retain_archived_records` is off.
+//!
+//! Ping records grow much faster and can have a shorter `ping_retention_days`.
+//! While it is unset or 0 they follow the record retention, so enabling this
+//! cleanup never shortens how long pings are kept. Before they are deleted,
+//! completed hours are rolled up into `ping_records_hourly`, which is kept as
+//! long as the longer of the two retentions (forever while records are) so
+//! ping statistics still cover older windows.
+//!
+//! Admins can also start a cleanup through `DELETE /api/admin/records/old`.
+//! Runs are serialized by the cleanup lock in [`AppState`].
+
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use super::archive;
+use crate::api::AppState;
+use crate::error::{AppError, AppResult};
+
+/// How often the cleanup runs.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Log lines kept per client.
+pub const MAX_LOG_LINES_PER_CLIENT: i64 = 10_000;
+
+/// Deleted rows above which the cleaned tables are vacuumed.
+const VACUUM_AFTER_ROWS: u64 = 100_000;
+
+/// Rows removed by one cleanup run.
+#[derive(Debug, Default)]
+pub struct CleanupStats {
+    pub records_deleted: u64,
+    pub ping_records_deleted: u64,
+    pub ping_rollups_deleted: u64,
+    pub log_lines_deleted: u64,
+}
+
+/// Read the `record_retention_days` setting. `None`, when unset or 0, keeps
+/// records forever.
+pub async fn retention_days(state: &AppState) -> AppResult<Option<i32>> {
+    Ok(state
+        .db
+        .get_setting("record_retention_days")
+        .await?
+        .and_then(|v| v.as_i64())
+        .map(|v| v as i32)
+        .filter(|days| *days > 0))
+}
+
+/// Read the `ping_retention_days` setting, falling back to the record
+/// retention when it is unset or 0. `None` keeps ping records forever.
+pub async fn ping_retention_days(state: &AppState) -> AppResult<Option<i32>> {
+    let days = state
+        .db
+        .get_setting("ping_retention_days")
+        .await?
+        .and_then(|v| v.as_i64())
+        .map(|v| v as i32)
+        .filter(|days| *days > 0);
+    match days {
+        Some(days) => Ok(Some(days)),
+        None => retention_days(state).await,
+    }
+}
+
+/// Read the `retain_archived_records` setting: whether records of archived
+/// clients are exempt from retention. On by default.
+pub async fn retain_archived_records(state: &AppState) -> AppResult<bool> {
+    Ok(state
+        .db
+        .get_setting("retain_archived_records")
+        .await?
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true))
+}
+
+/// Run the cleanup periodically until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = cleanup(&state).await {
+            error!("Retention cleanup failed: {}", e);
+        }
+    }
+}
+
+/// Delete expired rows and vacuum after large deletions, waiting for a
+/// cleanup already running to finish.
+pub async fn cleanup(state: &AppState) -> AppResult<CleanupStats> {
+    let _guard = state.cleanup_lock.lock().await;
+    cleanup_locked(state).await
+}
+
+/// Like [`cleanup`], but fail with `Conflict` if a cleanup is running.
+pub async fn cleanup_now(state: &AppState) -> AppResult<CleanupStats> {
+    let Ok(_guard) = state.cleanup_lock.try_lock() else {
+        return Err(AppError::Conflict(
+            "Retention cleanup is already running".into(),
+        ));
+    };
+    cleanup_locked(state).await
+}
+
+async fn cleanup_locked(state: &AppState) -> AppResult<CleanupStats> {
+    let days = retention_days(state).await?;
+    let keep_archived = retain_archived_records(state).await?;
+    let archive_settings = archive::load_settings(state).await?;
+    let records_deleted = match days {
+        None => 0,
+        Some(days) if archive_settings.enabled => {
+            archive::archive_expired(state, &archive_settings, days, keep_archived).await?
+        }
+        Some(days) => state.db.delete_old_records(days, keep_archived).await?,
+    };
+
+    // Roll up before deleting so no raw ping hour is lost
+    let ping_days = ping_retention_days(state).await?;
+    state.db.rollup_ping_records().await?;
+    let stats = CleanupStats {
+        records_deleted,
+        ping_records_deleted: match ping_days {
+            Some(ping_days) => state.db.delete_old_ping_records(ping_days).await?,
+            None => 0,
+        },
+        // Rollups cover the record retention, so they are kept with records
+        ping_rollups_deleted: match (days, ping_days) {
+            (Some(days), Some(ping_days)) => {
+                state
+                    .db
+                    .delete_old_ping_rollups(days.max(ping_days))
+                    .await?
+            }
+            _ => 0,
+        },
+        log_lines_deleted: state.db.trim_log_lines(MAX_LOG_LINES_PER_CLIENT).await?,
+    };
+
+    if stats.records_deleted > 0 {
+        info!(
+            "Retention cleanup deleted {} records older than {} days",
+            stats.records_deleted,
+            days.unwrap_or_default()
+        );
+    }
+    if stats.ping_records_deleted > 0 {
+        info!(
+            "Retention cleanup deleted {} ping records older than {} days",
+            stats.ping_records_deleted,
+            ping_days.unwrap_or_default()
+        );
+    }
+    if stats.log_lines_deleted > 0 {
+        info!("Log rotation deleted {} log lines", stats.log_lines_deleted);
+    }
+
+    for (table, deleted) in [
+        ("records", stats.records_deleted),
+        ("ping_records", stats.ping_records_deleted),
+        ("ping_records_hourly", stats.ping_rollups_deleted),
+        ("client_logs", stats.log_lines_deleted),
+    ] {
+        if deleted > VACUUM_AFTER_ROWS {
+            info!("Vacuuming {} after deleting {} rows", table, deleted);
+            state.db.vacuum_table(table, true).await?;
+        }
+    }
+
+    Ok(stats)
+}
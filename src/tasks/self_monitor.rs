@@ -0,0 +1,319 @@
+//! Self-monitoring of the server's own host.
+//!
+//! While the `self_monitor` setting is on, the server samples its host the
+//! way an agent would and stores the records under the built-in client
+//! [`BUILTIN_CLIENT_NAME`], which is created on first use. Records take the
+//! same validation, storage and anomaly path as agent reports, so alerts,
+//! retention and dashboards treat the host like any other client.
+//!
+//! Metrics come from the `sysinfo` crate, with the disk being the one
+//! mounted at [`DISK_PATH`]. Socket, TCP state and file handle counts, which
+//! it does not cover, are read from `/proc`; inode counts are not collected.
+//! Metrics that are unavailable, e.g. in a restricted container or on a
+//! non-Linux host, are reported as 0.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use sysinfo::{
+    Components, CpuRefreshKind, Disks, Networks, ProcessRefreshKind, ProcessesToUpdate, System,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::alerts::status;
+use crate::anomaly;
+use crate::api::AppState;
+use crate::api::client::ReportTransport;
+use crate::build_info;
+use crate::db::{Client, RecordInput, TcpStates, validate_record};
+use crate::error::AppResult;
+
+/// Name of the built-in client.
+pub const BUILTIN_CLIENT_NAME: &str = "vanmoi-server";
+
+/// Seconds between reports when the `report_interval_seconds` setting is unset.
+const DEFAULT_INTERVAL_SECS: u32 = 10;
+
+/// Filesystem whose usage is reported.
+const DISK_PATH: &str = "/";
+
+/// Highest temperature reported, matching record validation.
+const MAX_TEMP: f32 = 200.0;
+
+/// Report the host every report interval until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let mut sampler = Sampler::new();
+    let mut client = None;
+
+    loop {
+        let secs = state
+            .runtime()
+            .report_interval_seconds
+            .unwrap_or(DEFAULT_INTERVAL_SECS)
+            .max(1);
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(Duration::from_secs(secs.into())) => {}
+        }
+
+        if !state.runtime().self_monitor {
+            continue;
+        }
+        if let Err(e) = report(&state, &mut client, &mut sampler).await {
+            error!("Self-monitoring report failed: {}", e);
+        }
+    }
+}
+
+/// Sample the host and store the record, creating the built-in client and
+/// uploading its basic info first if needed.
+async fn report(
+    state: &AppState,
+    client: &mut Option<Client>,
+    sampler: &mut Sampler,
+) -> AppResult<()> {
+    let record = sampler.sample();
+    let client = match client {
+        Some(client) => client,
+        None => {
+            let builtin = state.db.ensure_builtin_client(BUILTIN_CLIENT_NAME).await?;
+            upload_basic_info(state, &builtin, &record).await?;
+            info!("Self-monitoring as client {}", builtin.id);
+            client.insert(builtin)
+        }
+    };
+
+    if let Err(e) = validate_record(&record) {
+        warn!("Rejected self-monitoring record: {}", e);
+        return Ok(());
+    }
+    let came_online = state
+        .db
+        .mark_client_reported(client.id, ReportTransport::Builtin.as_str(), None)
+        .await?;
+    if came_online {
+        status::notify(state, client.id, true);
+    }
+    state.db.insert_record(client.id, &record).await?;
+    anomaly::observe(state, client, &record).await;
+    Ok(())
+}
+
+async fn upload_basic_info(
+    state: &AppState,
+    client: &Client,
+    record: &RecordInput,
+) -> AppResult<()> {
+    let cpu_cores = std::thread::available_parallelism().map_or(0, |n| n.get() as i32);
+    state
+        .db
+        .update_client_basic_info(
+            client.id,
+            &cpu_name(),
+            std::env::consts::ARCH,
+            cpu_cores,
+            &System::long_os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+            &System::kernel_version().unwrap_or_default(),
+            "",
+            "",
+            record.ram_total,
+            record.swap_total,
+            record.disk_total,
+            build_info::VERSION,
+            None,
+            None,
+        )
+        .await
+}
+
+/// Samples the host. sysinfo reports CPU usage and network traffic since
+/// the previous refresh, so the sampler keeps its handles between samples.
+struct Sampler {
+    at: Instant,
+    system: System,
+    networks: Networks,
+    disks: Disks,
+    components: Components,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu_usage();
+        Self {
+            at: Instant::now(),
+            system,
+            networks: Networks::new_with_refreshed_list(),
+            disks: Disks::new_with_refreshed_list(),
+            components: Components::new_with_refreshed_list(),
+        }
+    }
+
+    fn sample(&mut self) -> RecordInput {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.at).as_secs_f64();
+        self.at = now;
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing().without_tasks(),
+        );
+        self.networks.refresh(true);
+        self.disks.refresh(true);
+        self.components.refresh(true);
+
+        let rate = |bytes: u64| {
+            if elapsed > 0.0 {
+                (bytes as f64 / elapsed) as i64
+            } else {
+                0
+            }
+        };
+        let (mut net_in, mut net_out, mut total_down, mut total_up) = (0, 0, 0, 0);
+        for (_, data) in self
+            .networks
+            .list()
+            .iter()
+            .filter(|(interface, _)| interface.as_str() != "lo")
+        {
+            net_in += rate(data.received());
+            net_out += rate(data.transmitted());
+            total_down += data.total_received();
+            total_up += data.total_transmitted();
+        }
+
+        let bytes = |value: u64| value.min(i64::MAX as u64) as i64;
+        let ram_total = bytes(self.system.total_memory());
+        let swap_total = bytes(self.system.total_swap());
+        let load = System::load_average();
+        let (disk_used, disk_total) = self
+            .disks
+            .list()
+            .iter()
+            .find(|disk| disk.mount_point() == Path::new(DISK_PATH))
+            .map_or((0, 0), |disk| {
+                let total = bytes(disk.total_space());
+                (
+                    (total - bytes(disk.available_space())).clamp(0, total),
+                    total,
+                )
+            });
+        let (fd_used, fd_total) = file_handles();
+
+        RecordInput {
+            cpu: self.system.global_cpu_usage().clamp(0.0, 100.0),
+            gpu: 0.0,
+            ram: bytes(self.system.used_memory()).clamp(0, ram_total),
+            ram_total,
+            swap: bytes(self.system.used_swap()).clamp(0, swap_total),
+            swap_total,
+            load: load.one as f32,
+            load5: load.five as f32,
+            load15: load.fifteen as f32,
+            temp: self.temperature(),
+            disk: disk_used,
+            disk_total,
+            net_in,
+            net_out,
+            net_total_up: bytes(total_up),
+            net_total_down: bytes(total_down),
+            process: self.system.processes().len().min(i32::MAX as usize) as i32,
+            connections: socket_count(&["/proc/net/tcp", "/proc/net/tcp6"]),
+            connections_udp: socket_count(&["/proc/net/udp", "/proc/net/udp6"]),
+            uptime: bytes(System::uptime()),
+            fd_used,
+            fd_total,
+            inode_used: 0,
+            inode_total: 0,
+            recorded_at: None,
+            log_lines: None,
+            tcp_states: tcp_states(&["/proc/net/tcp", "/proc/net/tcp6"]),
+        }
+    }
+
+    /// Highest sensor temperature in °C.
+    fn temperature(&self) -> f32 {
+        self.components
+            .list()
+            .iter()
+            .filter_map(|component| component.temperature())
+            .filter(|temp| temp.is_finite())
+            .fold(0.0, f32::max)
+            .clamp(0.0, MAX_TEMP)
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Sockets listed in `/proc/net` socket tables, excluding their headers.
+fn socket_count(tables: &[&str]) -> i32 {
+    tables
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|table| table.lines().skip(1).count() as i32)
+        .sum()
+}
+
+/// TCP sockets by state from `/proc/net` socket tables, whose fourth column
+/// is the hex state code. `None` when no table could be read.
+fn tcp_states(tables: &[&str]) -> Option<TcpStates> {
+    let mut states = TcpStates::default();
+    let mut read = false;
+    for table in tables
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+    {
+        read = true;
+        for line in table.lines().skip(1) {
+            let count = match line.split_whitespace().nth(3) {
+                Some("01") => &mut states.established,
+                Some("02") => &mut states.syn_sent,
+                Some("03") => &mut states.syn_recv,
+                Some("04") => &mut states.fin_wait1,
+                Some("05") => &mut states.fin_wait2,
+                Some("06") => &mut states.time_wait,
+                Some("07") => &mut states.close,
+                Some("08") => &mut states.close_wait,
+                Some("09") => &mut states.last_ack,
+                Some("0A") => &mut states.listen,
+                Some("0B") => &mut states.closing,
+                _ => continue,
+            };
+            *count.get_or_insert(0) += 1;
+        }
+    }
+    read.then_some(states)
+}
+
+/// Allocated and maximum file handles from `/proc/sys/fs/file-nr`.
+fn file_handles() -> (i32, i32) {
+    let values: Vec<i64> = read_trimmed("/proc/sys/fs/file-nr")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    match values[..] {
+        [allocated, unused, max] => (
+            (allocated - unused).clamp(0, i32::MAX.into()) as i32,
+            max.clamp(0, i32::MAX.into()) as i32,
+        ),
+        _ => (0, 0),
+    }
+}
+
+/// CPU model of the first CPU.
+fn cpu_name() -> String {
+    let mut system = System::new();
+    system.refresh_cpu_list(CpuRefreshKind::nothing());
+    system
+        .cpus()
+        .first()
+        .map(|cpu| cpu.brand().trim().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,333 @@
+//! Telegram bot command interface.
+//!
+//! Long-polls the Telegram Bot API for commands from allow-listed chats when
+//! the `telegram_bot` setting is configured:
+//!
+//! ```json
+//! {"bot_token": "123:ABC", "allowed_chat_ids": ["123456789"]}
+//! ```
+//!
+//! Supported commands:
+//! - `/status` - online/offline summary
+//! - `/status <name>` - latest record for a client
+//! - `/mute <name> <duration>` - put a client into maintenance (e.g. `30m`, `2h`, `1d`)
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::api::AppState;
+
+/// Long-poll timeout passed to `getUpdates`.
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// How often to re-check the settings while the bot is not configured.
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum backoff after consecutive API errors.
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// Bot configuration stored in the `telegram_bot` setting.
+#[derive(Debug, Clone, Deserialize)]
+struct BotConfig {
+    bot_token: String,
+    #[serde(default)]
+    allowed_chat_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdatesResponse {
+    ok: bool,
+    #[serde(default)]
+    result: Vec<Update>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<IncomingMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncomingMessage {
+    chat: Chat,
+    from: Option<Chat>,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Run the bot polling loop until `shutdown` is cancelled.
+pub async fn run(state: AppState, shutdown: CancellationToken) {
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+        .build()
+        .unwrap_or_default();
+
+    let mut offset: i64 = 0;
+    let mut failures: u32 = 0;
+
+    loop {
+        let config = match load_config(&state).await {
+            Some(config) => config,
+            None => {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(IDLE_INTERVAL) => continue,
+                }
+            }
+        };
+
+        let result = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            result = poll_once(&state, &http, &config, &mut offset) => result,
+        };
+
+        match result {
+            Ok(()) => failures = 0,
+            Err(e) => {
+                failures += 1;
+                let backoff = 2u64.saturating_pow(failures).min(MAX_BACKOFF_SECS);
+                warn!(
+                    "Telegram bot polling failed ({}), retrying in {}s",
+                    e, backoff
+                );
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(backoff)) => {}
+                }
+            }
+        }
+    }
+
+    info!("Telegram bot stopped");
+}
+
+/// Load the bot config, returning `None` when it is absent or incomplete.
+async fn load_config(state: &AppState) -> Option<BotConfig> {
+    let value = state.db.get_setting("telegram_bot").await.ok()??;
+    let config: BotConfig = serde_json::from_value(value).ok()?;
+
+    if config.bot_token.is_empty() || config.allowed_chat_ids.is_empty() {
+        return None;
+    }
+    Some(config)
+}
+
+/// Fetch one batch of updates and handle the commands in it.
+async fn poll_once(
+    state: &AppState,
+    http: &reqwest::Client,
+    config: &BotConfig,
+    offset: &mut i64,
+) -> Result<()> {
+    let url = format!(
+        "https://api.telegram.org/bot{}/getUpdates",
+        config.bot_token
+    );
+    let response: UpdatesResponse = http
+        .get(&url)
+        .query(&[
+            ("offset", offset.to_string()),
+            ("timeout", POLL_TIMEOUT_SECS.to_string()),
+        ])
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    if !response.ok {
+        return Err(anyhow!(
+            "getUpdates error: {}",
+            response.description.unwrap_or_default()
+        ));
+    }
+
+    for update in response.result {
+        *offset = update.update_id + 1;
+
+        let Some(message) = update.message else {
+            continue;
+        };
+        let Some(text) = message.text else {
+            continue;
+        };
+
+        let chat_id = message.chat.id;
+        let allowed = config.allowed_chat_ids.iter().any(|id| {
+            *id == chat_id.to_string()
+                || message
+                    .from
+                    .as_ref()
+                    .is_some_and(|f| *id == f.id.to_string())
+        });
+        if !allowed {
+            warn!(
+                "Ignoring Telegram command from unauthorized chat {}",
+                chat_id
+            );
+            continue;
+        }
+
+        let reply = match handle_command(state, &text).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) => continue,
+            Err(e) => format!("Error: {}", e),
+        };
+
+        send_reply(http, config, chat_id, &reply).await?;
+    }
+
+    Ok(())
+}
+
+/// Handle a command, returning the reply text (`None` for non-commands).
+async fn handle_command(state: &AppState, text: &str) -> Result<Option<String>> {
+    let mut parts = text.split_whitespace();
+    let Some(command) = parts.next() else {
+        return Ok(None);
+    };
+    // Strip a "@botname" suffix used in group chats
+    let command = command.split('@').next().unwrap_or(command);
+    let args: Vec<&str> = parts.collect();
+
+    let reply = match (command, args.as_slice()) {
+        ("/status", []) => status_summary(state).await?,
+        ("/status", [name]) => client_status(state, name).await?,
+        ("/mute", [name, duration]) => mute_client(state, name, duration).await?,
+        ("/status", _) => "Usage: /status [name]".to_string(),
+        ("/mute", _) => "Usage: /mute <name> <duration>".to_string(),
+        _ if command.starts_with('/') => {
+            "Commands: /status, /status <name>, /mute <name> <duration>".to_string()
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(reply))
+}
+
+/// Build the `/status` summary.
+async fn status_summary(state: &AppState) -> Result<String> {
+    let clients = state.db.get_active_clients().await?;
+    let online = clients.iter().filter(|c| c.online).count();
+
+    let mut reply = format!(
+        "Online: {}/{}\nOffline: {}",
+        online,
+        clients.len(),
+        clients.len() - online
+    );
+    for client in clients.iter().filter(|c| !c.online) {
+        reply.push_str(&format!("\n- {}", client.name));
+    }
+    Ok(reply)
+}
+
+/// Build the `/status <name>` reply.
+async fn client_status(state: &AppState, name: &str) -> Result<String> {
+    let Some(client) = state.db.find_client_by_name(name).await? else {
+        return Ok(format!("Client not found: {}", name));
+    };
+
+    let state_word = if client.online { "online" } else { "offline" };
+    let Some(record) = state.db.get_latest_record(client.id).await? else {
+        return Ok(format!("{} ({})\nNo records yet", client.name, state_word));
+    };
+
+    Ok(format!(
+        "{} ({})\nCPU: {:.1}%\nRAM: {:.1}%\nDisk: {:.1}%\nLoad: {:.2}\nNet: {} B/s in, {} B/s out\nUptime: {}h",
+        client.name,
+        state_word,
+        record.cpu,
+        percent(record.ram, record.ram_total),
+        percent(record.disk, record.disk_total),
+        record.load,
+        record.net_in,
+        record.net_out,
+        record.uptime / 3600
+    ))
+}
+
+/// Handle `/mute <name> <duration>`.
+async fn mute_client(state: &AppState, name: &str, duration: &str) -> Result<String> {
+    let Some(seconds) = parse_duration(duration) else {
+        return Ok(format!(
+            "Invalid duration: {} (use e.g. 30m, 2h, 1d)",
+            duration
+        ));
+    };
+    let Some(client) = state.db.find_client_by_name(name).await? else {
+        return Ok(format!("Client not found: {}", name));
+    };
+
+    let until = chrono::Utc::now() + chrono::Duration::seconds(seconds);
+    state
+        .db
+        .set_client_maintenance(client.id, Some(until))
+        .await?;
+
+    info!("Client {} muted via Telegram until {}", client.name, until);
+    Ok(format!(
+        "{} muted until {}",
+        client.name,
+        until.format("%Y-%m-%d %H:%M UTC")
+    ))
+}
+
+/// Parse a duration like `90s`, `30m`, `2h` or `1d` into seconds.
+fn parse_duration(s: &str) -> Option<i64> {
+    let unit = s.chars().last()?;
+    let value: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|v| *v > 0)?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return None,
+    };
+    value.checked_mul(multiplier)
+}
+
+fn percent(used: i64, total: i64) -> f64 {
+    if total > 0 {
+        used as f64 * 100.0 / total as f64
+    } else {
+        0.0
+    }
+}
+
+/// Send a plain-text reply to a chat.
+async fn send_reply(
+    http: &reqwest::Client,
+    config: &BotConfig,
+    chat_id: i64,
+    text: &str,
+) -> Result<()> {
+    let url = format!(
+        "https://api.telegram.org/bot{}/sendMessage",
+        config.bot_token
+    );
+    let response = http
+        .post(&url)
+        .json(&serde_json::json!({
+            "chat_id": chat_id,
+            "text": text
+        }))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!("sendMessage failed: {}", response.status()));
+    }
+    Ok(())
+}
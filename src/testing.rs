@@ -0,0 +1,259 @@
+//! Helpers for integration tests, built with the `integration` feature.
+//!
+//! [`TestApp::spawn`] creates a throwaway database on the server named by
+//! `TEST_DATABASE_URL` (falling back to `DATABASE_URL`), initializes the
+//! schema and an admin user, and builds the full router without binding a
+//! port. Requests go through [`TestApp::request`], which drives the router
+//! with `tower::ServiceExt::oneshot`; [`TestApp::serve`] binds a local port
+//! for tests that need a real connection, such as WebSockets. Call
+//! [`TestApp::cleanup`] at the end of a test to drop the database.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Method, Request, StatusCode, header};
+use chrono::{DateTime, Utc};
+use sqlx::{Connection, PgConnection};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::api::{self, AppState};
+use crate::config::Config;
+use crate::db::{Client, Database, RecordInput};
+
+/// Admin credentials of a [`TestApp`].
+pub const ADMIN_USERNAME: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "test-password";
+
+/// Largest response body read by [`TestApp::request`].
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Router and state backed by a database of its own.
+pub struct TestApp {
+    pub state: AppState,
+    pub router: Router,
+    server_url: String,
+    db_name: String,
+}
+
+impl TestApp {
+    /// Create a fresh database and build the application on it.
+    pub async fn spawn() -> anyhow::Result<Self> {
+        Self::spawn_with(|_| {}).await
+    }
+
+    /// Like [`Self::spawn`], with `configure` adjusting the configuration
+    /// before the application is built.
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> anyhow::Result<Self> {
+        let server_url = std::env::var("TEST_DATABASE_URL")
+            .or_else(|_| std::env::var("DATABASE_URL"))
+            .map_err(|_| anyhow::anyhow!("TEST_DATABASE_URL or DATABASE_URL must be set"))?;
+        let db_name = format!("vanmoi_test_{}", Uuid::new_v4().simple());
+
+        let mut conn = PgConnection::connect(&server_url).await?;
+        sqlx::raw_sql(&format!("CREATE DATABASE {}", db_name))
+            .execute(&mut conn)
+            .await?;
+        conn.close().await?;
+
+        let mut config = Config::from_env()?;
+        config.database_url = database_url(&server_url, &db_name)?;
+        config.database_replica_urls = Vec::new();
+        config.admin_username = ADMIN_USERNAME.to_string();
+        config.admin_password = ADMIN_PASSWORD.to_string();
+        config.registration_token = None;
+        configure(&mut config);
+
+        let db = Database::connect(
+            &config.database_url,
+            &config.database_replica_urls,
+            Duration::from_secs(config.db_query_timeout_secs),
+        )
+        .await?
+        .with_secret_key(config.secret_key.as_deref());
+        db.init_schema().await?;
+        let password_hash = api::auth::hash_password(ADMIN_PASSWORD)?;
+        db.create_user(ADMIN_USERNAME, &password_hash).await?;
+
+        let state = AppState::new(db, config);
+        state.reload_runtime_settings().await?;
+        let router = api::create_router(state.clone());
+
+        Ok(Self {
+            state,
+            router,
+            server_url,
+            db_name,
+        })
+    }
+
+    /// Send a request to the router. `token` is sent as a bearer token and
+    /// `body` as JSON. Returns the status and the body parsed as JSON, or
+    /// `Null` when it is empty or not JSON.
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match body {
+            Some(json) => {
+                builder = builder.header(header::CONTENT_TYPE, "application/json");
+                Body::from(json.to_string())
+            }
+            None => Body::empty(),
+        };
+        let mut request = builder.body(body).expect("valid request");
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("router is infallible");
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), MAX_BODY_BYTES)
+            .await
+            .expect("readable body");
+        let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    /// Serve the router on a free local port until the test ends, returning
+    /// its address.
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("local port");
+        let addr = listener.local_addr().expect("bound address");
+        let router = self.router.clone();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+        });
+        addr
+    }
+
+    /// Log in as the admin and return the session token.
+    pub async fn login(&self) -> String {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/api/login",
+                None,
+                Some(serde_json::json!({
+                    "username": ADMIN_USERNAME,
+                    "password": ADMIN_PASSWORD,
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "login failed: {}", body);
+        body["token"]
+            .as_str()
+            .expect("login response has a token")
+            .to_string()
+    }
+
+    /// Add a client directly in the database. The returned client carries
+    /// its plaintext token.
+    pub async fn seed_client(&self, name: &str) -> Client {
+        self.state
+            .db
+            .create_client(name)
+            .await
+            .expect("client created")
+    }
+
+    /// Store `count` records for a client, one a minute up to now, and mark
+    /// the client as reporting.
+    pub async fn seed_records(&self, client_id: Uuid, count: usize) -> Vec<DateTime<Utc>> {
+        let now = Utc::now();
+        let mut times = Vec::with_capacity(count);
+        for i in 0..count {
+            let time = now - chrono::Duration::minutes((count - 1 - i) as i64);
+            self.state
+                .db
+                .insert_record_at(client_id, &sample_record(i as f32), Some(time))
+                .await
+                .expect("record stored");
+            times.push(time);
+        }
+        self.state
+            .db
+            .mark_client_reported(client_id, "http", None)
+            .await
+            .expect("client marked online");
+        times
+    }
+
+    /// Drop the test database.
+    pub async fn cleanup(self) -> anyhow::Result<()> {
+        let Self {
+            state,
+            router,
+            server_url,
+            db_name,
+        } = self;
+        drop(router);
+        drop(state);
+
+        let mut conn = PgConnection::connect(&server_url).await?;
+        sqlx::raw_sql(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", db_name))
+            .execute(&mut conn)
+            .await?;
+        conn.close().await?;
+        Ok(())
+    }
+}
+
+/// A valid report with `cpu` percent CPU usage.
+pub fn sample_record(cpu: f32) -> RecordInput {
+    serde_json::from_value(serde_json::json!({
+        "cpu": cpu,
+        "ram": 512 * 1024 * 1024,
+        "ram_total": 1024 * 1024 * 1024,
+        "disk": 10 * 1024 * 1024 * 1024_i64,
+        "disk_total": 100 * 1024 * 1024 * 1024_i64,
+        "net_in": 1024,
+        "net_out": 2048,
+        "net_total_up": 1_000_000,
+        "net_total_down": 2_000_000,
+        "uptime": 3600,
+    }))
+    .expect("valid record")
+}
+
+/// `server_url` with its database name replaced by `db_name`.
+fn database_url(server_url: &str, db_name: &str) -> anyhow::Result<String> {
+    let (base, query) = match server_url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (server_url, None),
+    };
+    let authority_end = base
+        .find("://")
+        .map(|i| i + 3)
+        .ok_or_else(|| anyhow::anyhow!("Invalid database URL"))?;
+    let path_start = base[authority_end..]
+        .find('/')
+        .map_or(base.len(), |i| authority_end + i);
+    let mut url = format!("{}/{}", &base[..path_start], db_name);
+    if let Some(query) = query {
+        url.push('?');
+        url.push_str(query);
+    }
+    Ok(url)
+}
@@ -0,0 +1,23 @@
+//! Country flag emoji.
+//!
+//! A flag is the pair of Unicode regional indicator symbols spelling the
+//! ISO 3166-1 alpha-2 code, e.g. `US` is U+1F1FA U+1F1F8.
+
+/// Regional indicator symbol letter A.
+const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+
+/// The ISO 3166-1 alpha-2 code in `region`, uppercased, when the region is
+/// one. Free-form regions such as city names have none.
+pub fn country_code(region: &str) -> Option<String> {
+    let region = region.trim();
+    (region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| region.to_ascii_uppercase())
+}
+
+/// Flag emoji of a two-letter country code, `None` for anything else.
+pub fn country_code_to_flag(code: &str) -> Option<String> {
+    let code = country_code(code)?;
+    code.chars()
+        .map(|c| char::from_u32(REGIONAL_INDICATOR_A + (c as u32 - 'A' as u32)))
+        .collect()
+}
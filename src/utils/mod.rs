@@ -0,0 +1,3 @@
+//! Small helpers shared across modules.
+
+pub mod flags;
@@ -2,6 +2,7 @@ import { createApp } from 'vue'
 import { createPinia } from 'pinia'
 import App from './App.vue'
 import router from './router'
+import { useBrandingStore } from './stores/branding'
 import './styles/main.css'
 
 const app = createApp(App)
@@ -9,4 +10,6 @@ const app = createApp(App)
 app.use(createPinia())
 app.use(router)
 
+useBrandingStore().load()
+
 app.mount('#app')
@@ -0,0 +1,61 @@
+import { defineStore } from 'pinia'
+import { ref } from 'vue'
+import api from '@/api'
+
+interface Announcement {
+    active: boolean
+    text: string
+    color: string
+}
+
+interface PublicSettings {
+    site_name: string
+    site_description: string
+    logo_url: string
+    custom_css: string
+    custom_js_url: string
+    announcement: Announcement
+}
+
+export const useBrandingStore = defineStore('branding', () => {
+    const settings = ref<PublicSettings | null>(null)
+
+    async function load() {
+        try {
+            const response = await api.get('/api/settings')
+            settings.value = response.data
+            apply(response.data)
+        } catch (e) {
+            console.error('Failed to load site settings:', e)
+        }
+    }
+
+    function apply(s: PublicSettings) {
+        document.title = s.site_name
+
+        if (s.site_description) {
+            document
+                .querySelector('meta[name="description"]')
+                ?.setAttribute('content', s.site_description)
+        }
+
+        if (s.custom_css) {
+            const style = document.createElement('style')
+            style.id = 'custom-css'
+            style.textContent = s.custom_css
+            document.head.appendChild(style)
+        }
+
+        if (s.custom_js_url) {
+            const script = document.createElement('script')
+            script.src = s.custom_js_url
+            script.async = true
+            document.head.appendChild(script)
+        }
+    }
+
+    return {
+        settings,
+        load
+    }
+})
@@ -26,6 +26,8 @@ interface Client {
     mem_total: number
     disk_total: number
     group_name: string
+    display_color: string
+    display_icon: string
     online: boolean
     last_seen_at: string | null
     status?: ClientStatus